@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::models::{AdminEvent, RateLimitViolation};
+use crate::AppState;
+
+/// Events buffered per admin socket before a slow one starts missing them.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// A user who keeps hitting a limit is reported once per this long, so a
+/// flood of refused messages doesn't become a flood of events.
+const RATE_LIMIT_REPORT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The moderators' live feed. One channel for the whole instance; the bus
+/// relays events published on other instances into it.
+pub struct AdminEvents {
+    tx: broadcast::Sender<AdminEvent>,
+    last_rate_limit: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl Default for AdminEvents {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            tx,
+            last_rate_limit: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl AdminEvents {
+    /// The local channel, for the bus to deliver into.
+    pub fn sender(&self) -> broadcast::Sender<AdminEvent> {
+        self.tx.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AdminEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Whether a violation by `user_id` is worth reporting now.
+    fn take_rate_limit_report(&self, user_id: Uuid) -> bool {
+        let now = Instant::now();
+        let mut last = self.last_rate_limit.lock().unwrap();
+        if last
+            .get(&user_id)
+            .is_some_and(|at| now.duration_since(*at) < RATE_LIMIT_REPORT_INTERVAL)
+        {
+            return false;
+        }
+        last.retain(|_, at| now.duration_since(*at) < RATE_LIMIT_REPORT_INTERVAL);
+        last.insert(user_id, now);
+        true
+    }
+}
+
+/// Send an event to every admin socket, on any instance.
+pub async fn publish(state: &AppState, event: AdminEvent) {
+    state
+        .bus
+        .publish_admin(&state.admin_events.sender(), event)
+        .await;
+}
+
+/// Tell moderators `user_id` was refused by `limiter`, at most once per
+/// `RATE_LIMIT_REPORT_INTERVAL` per user on this instance.
+pub async fn report_rate_limit(
+    state: &AppState,
+    user_id: Uuid,
+    limiter: &str,
+    conversation_id: Option<Uuid>,
+) {
+    if !state.admin_events.take_rate_limit_report(user_id) {
+        return;
+    }
+    publish(
+        state,
+        AdminEvent::RateLimited(RateLimitViolation {
+            user_id,
+            limiter: limiter.to_string(),
+            conversation_id,
+            at: chrono::Utc::now().to_rfc3339(),
+        }),
+    )
+    .await;
+}
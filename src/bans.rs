@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::sessions::SessionVersions;
+use crate::store::Storage;
+use crate::AppState;
+
+/// Accounts an admin has banned, suspended or shadow banned, held in memory
+/// so `get_session` and `deliver_message` can check them without a query
+/// per request. The source of truth is `profiles.banned_at`,
+/// `profiles.suspended_until` and `profiles.shadow_banned_at`. It is loaded
+/// before the server starts listening and `reload` swaps in a fresh copy
+/// (a failed reload keeps the old one); bans made on any instance reach
+/// every other one over the bus straight away.
+#[derive(Default)]
+pub struct BanList {
+    users: RwLock<HashSet<Uuid>>,
+    suspended: RwLock<HashMap<Uuid, DateTime<Utc>>>,
+    shadow: RwLock<HashSet<Uuid>>,
+}
+
+impl BanList {
+    /// Replace the in-memory sets with what's in the database. Returns the
+    /// number of accounts under any kind of ban. Nothing changes on error.
+    pub async fn reload(&self, store: &dyn Storage) -> Result<usize, ApiError> {
+        let profiles = store.restricted_profiles().await?;
+
+        let users: HashSet<Uuid> = profiles
+            .iter()
+            .filter(|p| p.is_banned())
+            .map(|p| p.id)
+            .collect();
+        let shadow: HashSet<Uuid> = profiles
+            .iter()
+            .filter(|p| p.shadow_banned_at.is_some())
+            .map(|p| p.id)
+            .collect();
+        let suspended: HashMap<Uuid, DateTime<Utc>> = profiles
+            .iter()
+            .filter_map(|p| Some((p.id, p.suspended_until()?)))
+            .collect();
+
+        let count = profiles.len();
+        *self.users.write().unwrap() = users;
+        *self.suspended.write().unwrap() = suspended;
+        *self.shadow.write().unwrap() = shadow;
+        Ok(count)
+    }
+
+    pub fn is_banned(&self, user_id: Uuid) -> bool {
+        self.users.read().unwrap().contains(&user_id)
+    }
+
+    pub fn ban(&self, user_id: Uuid) {
+        self.users.write().unwrap().insert(user_id);
+    }
+
+    pub fn unban(&self, user_id: Uuid) {
+        self.users.write().unwrap().remove(&user_id);
+    }
+
+    /// When `user_id`'s suspension ends, if they are suspended right now.
+    /// Suspensions that have run out are ignored, so they lift on their own.
+    pub fn suspended_until(&self, user_id: Uuid) -> Option<DateTime<Utc>> {
+        self.suspended
+            .read()
+            .unwrap()
+            .get(&user_id)
+            .copied()
+            .filter(|until| *until > Utc::now())
+    }
+
+    pub fn suspend(&self, user_id: Uuid, until: DateTime<Utc>) {
+        self.suspended.write().unwrap().insert(user_id, until);
+    }
+
+    pub fn lift_suspension(&self, user_id: Uuid) {
+        self.suspended.write().unwrap().remove(&user_id);
+    }
+
+    /// Whether `user_id`'s messages should only be shown to themselves.
+    pub fn is_shadow_banned(&self, user_id: Uuid) -> bool {
+        self.shadow.read().unwrap().contains(&user_id)
+    }
+
+    pub fn shadow_ban(&self, user_id: Uuid) {
+        self.shadow.write().unwrap().insert(user_id);
+    }
+
+    pub fn lift_shadow_ban(&self, user_id: Uuid) {
+        self.shadow.write().unwrap().remove(&user_id);
+    }
+}
+
+/// A change to who may use the service, shared over the bus so it takes
+/// effect on every instance at once instead of at their next reload.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AccessChange {
+    Ban { user_id: Uuid },
+    Unban { user_id: Uuid },
+    Suspend { user_id: Uuid, until: DateTime<Utc> },
+    LiftSuspension { user_id: Uuid },
+    ShadowBan { user_id: Uuid },
+    LiftShadowBan { user_id: Uuid },
+    SessionsRevoked { user_id: Uuid, version: i64 },
+}
+
+impl AccessChange {
+    /// Update this instance's copies. Applying a change twice is harmless.
+    pub fn apply_to(&self, bans: &BanList, sessions: &SessionVersions) {
+        match *self {
+            AccessChange::Ban { user_id } => bans.ban(user_id),
+            AccessChange::Unban { user_id } => bans.unban(user_id),
+            AccessChange::Suspend { user_id, until } => bans.suspend(user_id, until),
+            AccessChange::LiftSuspension { user_id } => bans.lift_suspension(user_id),
+            AccessChange::ShadowBan { user_id } => bans.shadow_ban(user_id),
+            AccessChange::LiftShadowBan { user_id } => bans.lift_shadow_ban(user_id),
+            AccessChange::SessionsRevoked { user_id, version } => sessions.raise(user_id, version),
+        }
+    }
+}
+
+/// Apply `change` here and pass it on to every other instance.
+pub async fn apply(state: &AppState, change: AccessChange) {
+    change.apply_to(&state.bans, &state.sessions);
+    state.bus.publish_access(change).await;
+}
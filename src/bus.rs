@@ -0,0 +1,284 @@
+use futures_util::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::bans::{AccessChange, BanList};
+use crate::config::{BusConfig, BusKind};
+use crate::handlers::chat::ConversationChannels;
+use crate::handlers::notifications::UserChannels;
+use crate::models::{AdminEvent, UserEvent, WsEvent};
+use crate::sessions::SessionVersions;
+
+/// Broadcast channels keyed by conversation or user id.
+type ChannelMap<E> = Arc<RwLock<HashMap<Uuid, broadcast::Sender<E>>>>;
+
+/// How conversation events reach every server instance.
+///
+/// `InProcess` hands events straight to the local broadcast channels, which is
+/// all a single instance needs. `Nats` publishes each event on a subject per
+/// conversation (`<prefix>.conversations.<id>`) or user
+/// (`<prefix>.users.<id>`), on `<prefix>.everyone` for events meant for
+/// every user, or on `<prefix>.admin` for the moderators' feed; every instance, including the sender, receives it back from
+/// NATS and forwards it to its own local sockets. Bans and session
+/// revocations go out on `<prefix>.access` and update every instance's
+/// in-memory copies.
+pub enum MessageBus {
+    InProcess,
+    Nats {
+        client: async_nats::Client,
+        prefix: String,
+    },
+}
+
+impl MessageBus {
+    /// Build the bus selected by `MESSAGE_BUS`. For NATS this also starts the
+    /// tasks that relay incoming events into `channels`, `user_channels` and
+    /// `admin_events`, and access changes into `bans` and `sessions`.
+    pub async fn connect(
+        config: &BusConfig,
+        channels: ConversationChannels,
+        user_channels: UserChannels,
+        admin_events: broadcast::Sender<AdminEvent>,
+        bans: Arc<BanList>,
+        sessions: Arc<SessionVersions>,
+    ) -> Self {
+        match config.kind {
+            BusKind::InProcess => MessageBus::InProcess,
+            BusKind::Nats => {
+                let client = async_nats::connect(&config.nats_url)
+                    .await
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to connect to NATS at '{}': {}", config.nats_url, e)
+                    });
+
+                let subject = format!("{}.conversations.*", config.nats_subject_prefix);
+                let subscriber = client
+                    .subscribe(subject.clone())
+                    .await
+                    .unwrap_or_else(|e| panic!("Failed to subscribe to '{}': {}", subject, e));
+                let user_subject = format!("{}.users.*", config.nats_subject_prefix);
+                let user_subscriber = client
+                    .subscribe(user_subject.clone())
+                    .await
+                    .unwrap_or_else(|e| panic!("Failed to subscribe to '{}': {}", user_subject, e));
+                let everyone_subject = format!("{}.everyone", config.nats_subject_prefix);
+                let everyone_subscriber = client
+                    .subscribe(everyone_subject.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to subscribe to '{}': {}", everyone_subject, e)
+                    });
+                let admin_subject = format!("{}.admin", config.nats_subject_prefix);
+                let admin_subscriber = client
+                    .subscribe(admin_subject.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to subscribe to '{}': {}", admin_subject, e)
+                    });
+
+                let access_subject = format!("{}.access", config.nats_subject_prefix);
+                let access_subscriber = client
+                    .subscribe(access_subject.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to subscribe to '{}': {}", access_subject, e)
+                    });
+
+                println!(
+                    "Message bus: NATS at {} ({}, {}, {}, {}, {})",
+                    config.nats_url,
+                    subject,
+                    user_subject,
+                    everyone_subject,
+                    admin_subject,
+                    access_subject
+                );
+                tokio::spawn(relay_from_nats(subscriber, channels));
+                tokio::spawn(relay_from_nats(user_subscriber, user_channels.clone()));
+                tokio::spawn(relay_everyone_from_nats(everyone_subscriber, user_channels));
+                tokio::spawn(relay_admin_from_nats(admin_subscriber, admin_events));
+                tokio::spawn(relay_access_from_nats(access_subscriber, bans, sessions));
+
+                MessageBus::Nats {
+                    client,
+                    prefix: config.nats_subject_prefix.clone(),
+                }
+            }
+        }
+    }
+
+    /// Fan an event out to everyone connected to the conversation, on any instance.
+    pub async fn publish(
+        &self,
+        channels: &ConversationChannels,
+        conversation_id: Uuid,
+        event: WsEvent,
+    ) {
+        self.publish_to(channels, "conversations", conversation_id, event)
+            .await;
+    }
+
+    /// Send an event to every notification socket `user_id` has open, on any
+    /// instance.
+    pub async fn publish_user(&self, channels: &UserChannels, user_id: Uuid, event: UserEvent) {
+        self.publish_to(channels, "users", user_id, event).await;
+    }
+
+    /// Send an event to every notification socket open on any instance.
+    pub async fn publish_everyone(&self, channels: &UserChannels, event: UserEvent) {
+        match self {
+            MessageBus::InProcess => deliver_everyone(channels, event).await,
+            MessageBus::Nats { client, prefix } => {
+                publish_nats(client, format!("{}.everyone", prefix), &event).await
+            }
+        }
+    }
+
+    /// Send an event to every admin socket open on any instance.
+    pub async fn publish_admin(&self, tx: &broadcast::Sender<AdminEvent>, event: AdminEvent) {
+        match self {
+            // Nobody watching is fine.
+            MessageBus::InProcess => {
+                let _ = tx.send(event);
+            }
+            MessageBus::Nats { client, prefix } => {
+                publish_nats(client, format!("{}.admin", prefix), &event).await
+            }
+        }
+    }
+
+    /// Pass a ban or session revocation on to the other instances. The
+    /// caller has already applied it locally.
+    pub async fn publish_access(&self, change: AccessChange) {
+        match self {
+            MessageBus::InProcess => {}
+            MessageBus::Nats { client, prefix } => {
+                publish_nats(client, format!("{}.access", prefix), &change).await
+            }
+        }
+    }
+
+    async fn publish_to<E: Serialize>(
+        &self,
+        channels: &ChannelMap<E>,
+        kind: &str,
+        id: Uuid,
+        event: E,
+    ) {
+        match self {
+            MessageBus::InProcess => deliver_local(channels, id, event).await,
+            MessageBus::Nats { client, prefix } => {
+                publish_nats(client, format!("{}.{}.{}", prefix, kind, id), &event).await
+            }
+        }
+    }
+}
+
+/// Send an event to the sockets connected to *this* instance, if any.
+async fn deliver_local<E>(channels: &ChannelMap<E>, id: Uuid, event: E) {
+    if let Some(tx) = channels.read().await.get(&id) {
+        // If nobody is listening the send will error, which is fine.
+        let _ = tx.send(event);
+    }
+}
+
+async fn publish_nats<E: Serialize>(client: &async_nats::Client, subject: String, event: &E) {
+    let payload = match serde_json::to_vec(event) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("[bus] Failed to encode event: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = client.publish(subject, payload.into()).await {
+        error!("[bus] NATS publish failed: {}", e);
+    }
+}
+
+/// Send an event to every user connected to *this* instance.
+async fn deliver_everyone<E: Clone>(channels: &ChannelMap<E>, event: E) {
+    for tx in channels.read().await.values() {
+        let _ = tx.send(event.clone());
+    }
+}
+
+/// Forward every event received from NATS to the local broadcast channels.
+async fn relay_from_nats<E: DeserializeOwned>(
+    mut subscriber: async_nats::Subscriber,
+    channels: ChannelMap<E>,
+) {
+    while let Some(msg) = subscriber.next().await {
+        let id = match msg
+            .subject
+            .rsplit('.')
+            .next()
+            .and_then(|id| Uuid::parse_str(id).ok())
+        {
+            Some(id) => id,
+            None => {
+                warn!(
+                    "[bus] Ignoring NATS message on unexpected subject {}",
+                    msg.subject
+                );
+                continue;
+            }
+        };
+
+        match serde_json::from_slice::<E>(&msg.payload) {
+            Ok(event) => deliver_local(&channels, id, event).await,
+            Err(e) => warn!("[bus] Ignoring undecodable NATS event: {}", e),
+        }
+    }
+
+    info!("[bus] NATS subscription closed");
+}
+
+/// Forward events published for everyone to all local user channels.
+async fn relay_everyone_from_nats(mut subscriber: async_nats::Subscriber, channels: UserChannels) {
+    while let Some(msg) = subscriber.next().await {
+        match serde_json::from_slice::<UserEvent>(&msg.payload) {
+            Ok(event) => deliver_everyone(&channels, event).await,
+            Err(e) => warn!("[bus] Ignoring undecodable NATS event: {}", e),
+        }
+    }
+
+    info!("[bus] NATS subscription closed");
+}
+
+/// Forward moderator events from NATS to this instance's admin sockets.
+async fn relay_admin_from_nats(
+    mut subscriber: async_nats::Subscriber,
+    tx: broadcast::Sender<AdminEvent>,
+) {
+    while let Some(msg) = subscriber.next().await {
+        match serde_json::from_slice::<AdminEvent>(&msg.payload) {
+            Ok(event) => {
+                let _ = tx.send(event);
+            }
+            Err(e) => warn!("[bus] Ignoring undecodable NATS event: {}", e),
+        }
+    }
+
+    info!("[bus] NATS subscription closed");
+}
+
+/// Apply bans and session revocations made on other instances.
+async fn relay_access_from_nats(
+    mut subscriber: async_nats::Subscriber,
+    bans: Arc<BanList>,
+    sessions: Arc<SessionVersions>,
+) {
+    while let Some(msg) = subscriber.next().await {
+        match serde_json::from_slice::<AccessChange>(&msg.payload) {
+            Ok(change) => change.apply_to(&bans, &sessions),
+            Err(e) => warn!("[bus] Ignoring undecodable NATS event: {}", e),
+        }
+    }
+
+    info!("[bus] NATS subscription closed");
+}
@@ -0,0 +1,566 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tower_cookies::Key;
+
+use crate::error::ApiError;
+
+/// Deployment-specific settings, read once from the environment at startup
+/// and shared with every handler through `AppState`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub ws: WsConfig,
+    pub username: UsernamePolicy,
+    pub messages: MessageConfig,
+    pub attachments: AttachmentConfig,
+    pub emoji: EmojiConfig,
+    pub avatars: AvatarConfig,
+    pub profile_fields: ProfileFieldsConfig,
+    pub exports: ExportConfig,
+    pub link_previews: LinkPreviewConfig,
+    pub calls: CallConfig,
+    pub jobs: JobsConfig,
+    pub bus: BusConfig,
+    pub discovery: DiscoveryConfig,
+    pub cookies: CookieConfig,
+    pub proxy: ProxyConfig,
+    pub quotas: QuotaConfig,
+}
+
+/// How to find the client's address behind reverse proxies.
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    /// Proxies in front of the server that append to `X-Forwarded-For`.
+    /// 0 (the default) ignores the header and uses the peer address, which
+    /// is the only safe choice when clients connect directly.
+    pub trusted_hops: usize,
+}
+
+/// Per-user consumption limits. 0 turns a quota off. Bots are exempt from
+/// the message quota.
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    /// Messages a user may send per UTC day, across every conversation.
+    pub messages_per_day: u64,
+    /// Total bytes of attachments a user may have stored.
+    pub attachment_bytes: u64,
+}
+
+/// Keys for the encrypted cookie jar.
+#[derive(Debug, Clone)]
+pub struct CookieConfig {
+    /// Encrypts and authenticates every cookie we set.
+    pub key: Key,
+    /// Retired keys still accepted when reading, so rotating `COOKIE_KEY`
+    /// doesn't log everyone out. Cookies opened with one are re-issued
+    /// under `key`.
+    pub previous_keys: Vec<Key>,
+}
+
+/// Rules every username must satisfy.
+#[derive(Debug, Clone)]
+pub struct UsernamePolicy {
+    /// Minimum length in characters (not bytes).
+    pub min_len: usize,
+    /// Maximum length in characters (not bytes).
+    pub max_len: usize,
+    /// Punctuation allowed in addition to letters and digits, e.g. "_.-".
+    pub allowed_symbols: String,
+    /// Allow letters/digits from any script instead of ASCII only.
+    pub allow_unicode: bool,
+    /// When false, usernames are folded to lowercase before storage and lookup,
+    /// so "Alice" and "alice" are the same account.
+    pub case_sensitive: bool,
+}
+
+/// Limits applied to every message, however it is sent.
+#[derive(Debug, Clone)]
+pub struct MessageConfig {
+    /// Maximum message content length in characters.
+    pub max_length: usize,
+    /// Maximum size of a single incoming WebSocket message in bytes.
+    /// Larger frames are refused by the WebSocket layer before parsing.
+    pub max_frame_bytes: usize,
+    /// How long a `client_id` is remembered for dropping retried sends.
+    pub dedupe_window: Duration,
+}
+
+/// File uploads stored in Supabase Storage.
+#[derive(Debug, Clone)]
+pub struct AttachmentConfig {
+    /// Storage bucket; must exist and should be private.
+    pub bucket: String,
+    /// Largest accepted upload in bytes.
+    pub max_bytes: usize,
+    /// MIME types accepted for upload, e.g. "image/png".
+    pub allowed_mime_types: Vec<String>,
+    /// Lifetime of the signed download URL stored with each message.
+    pub url_ttl: Duration,
+    /// Longest side, in pixels, of thumbnails generated for images.
+    pub thumbnail_max_dim: u32,
+}
+
+/// Custom emoji and sticker images.
+#[derive(Debug, Clone)]
+pub struct EmojiConfig {
+    /// Storage bucket; must exist and be public, since every client loads
+    /// these images.
+    pub bucket: String,
+    /// Largest accepted image in bytes.
+    pub max_bytes: usize,
+}
+
+/// Profile pictures and banners uploaded through `POST /profile/me/avatar`
+/// and `POST /profile/me/banner`.
+#[derive(Debug, Clone)]
+pub struct AvatarConfig {
+    /// Storage bucket; must exist and be public, since anyone who can see a
+    /// profile loads the image.
+    pub bucket: String,
+    /// Largest accepted upload in bytes.
+    pub max_bytes: usize,
+}
+
+/// Free-form profile fields stored in `profiles.extra_fields`, e.g.
+/// pronouns or a website. Adding a key here is all it takes to offer a
+/// new field.
+#[derive(Debug, Clone)]
+pub struct ProfileFieldsConfig {
+    /// Keys users may set, lowercase.
+    pub allowed: Vec<String>,
+    /// Longest value in characters (not bytes).
+    pub max_chars: usize,
+}
+
+/// Conversation exports and imports.
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    /// Storage bucket for background exports; should be private.
+    pub bucket: String,
+    /// Conversations with more messages than this are exported by a
+    /// background job instead of streamed in the response.
+    pub inline_max_messages: i64,
+    /// Lifetime of the download link for a finished export.
+    pub url_ttl: Duration,
+    /// Largest accepted `POST /import` body in bytes.
+    pub import_max_bytes: usize,
+}
+
+/// Server-side fetching of URLs posted in messages.
+#[derive(Debug, Clone)]
+pub struct LinkPreviewConfig {
+    pub enabled: bool,
+    /// Upper bound on one fetch, redirects included.
+    pub timeout: Duration,
+    /// Stop reading a page after this many bytes.
+    pub max_bytes: usize,
+    /// How long a fetched (or failed) preview is reused.
+    pub cache_ttl: Duration,
+    /// Most URLs kept in the cache.
+    pub cache_capacity: usize,
+}
+
+/// Voice/video call signaling.
+#[derive(Debug, Clone)]
+pub struct CallConfig {
+    /// An unanswered call stops ringing after this long.
+    pub ring_timeout: Duration,
+    /// TURN server URLs handed to clients, e.g. "turn:turn.example.com:3478".
+    pub turn_urls: Vec<String>,
+    /// Shared secret configured as `static-auth-secret` in coturn. TURN
+    /// credentials are only issued when this is set.
+    pub turn_secret: Option<String>,
+    /// How long minted TURN credentials stay valid.
+    pub turn_credential_ttl: Duration,
+}
+
+/// Background job runner settings.
+#[derive(Debug, Clone)]
+pub struct JobsConfig {
+    /// How often the runner looks for scheduled messages that are due.
+    pub scheduler_interval: Duration,
+    /// How often the runner picks up pending account erasures.
+    pub erasure_interval: Duration,
+    /// How often the runner expires messages in disappearing-message
+    /// conversations. Messages may outlive their TTL by up to this long.
+    pub expiry_interval: Duration,
+    /// How often the runner picks up pending conversation exports.
+    pub export_interval: Duration,
+    /// How often the word filter is reloaded from the database, so edits
+    /// made on another instance reach this one.
+    pub word_filter_interval: Duration,
+    /// How often the lists of banned accounts and IP addresses are reloaded
+    /// from the database, so bans made on another instance reach this one.
+    pub ban_list_interval: Duration,
+    /// How often per-user rate-limit overrides are reloaded from the
+    /// database, so changes made on another instance reach this one.
+    pub rate_limit_interval: Duration,
+}
+
+/// Which message bus fans events out across server instances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusKind {
+    /// Single instance only; events never leave the process.
+    InProcess,
+    Nats,
+}
+
+#[derive(Debug, Clone)]
+pub struct BusConfig {
+    pub kind: BusKind,
+    pub nats_url: String,
+    /// Subjects are `<prefix>.conversations.<conversation_id>`.
+    pub nats_subject_prefix: String,
+}
+
+/// Regional WebSocket endpoints advertised by `GET /ws/endpoints`.
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Region this instance runs in, e.g. "eu-west".
+    pub region: Option<String>,
+    /// From `WS_ENDPOINTS=eu-west=wss://eu.example.com,us-east=wss://us.example.com`.
+    pub endpoints: Vec<WsEndpoint>,
+    /// How often each endpoint's `/health` is probed.
+    pub probe_interval: Duration,
+    pub probe_timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct WsEndpoint {
+    pub region: String,
+    pub url: String,
+}
+
+/// WebSocket connection limits.
+#[derive(Debug, Clone)]
+pub struct WsConfig {
+    /// How often the server pings each connected client.
+    pub heartbeat_interval: Duration,
+    /// Close the socket once this many pings in a row go unanswered.
+    pub max_missed_heartbeats: u32,
+    /// Close the socket if the client sends no messages for this long.
+    pub max_idle: Duration,
+    /// Most messages replayed to a reconnecting client via `?since=`.
+    pub replay_limit: usize,
+    /// Sustained messages per second a single socket may send.
+    pub rate_per_sec: f64,
+    /// Messages a socket may send in a quick burst before being throttled.
+    pub rate_burst: u32,
+}
+
+impl Config {
+    /// Build the config from env vars, falling back to sensible defaults.
+    pub fn from_env() -> Self {
+        Self {
+            ws: WsConfig {
+                heartbeat_interval: Duration::from_secs(env_or("WS_HEARTBEAT_INTERVAL_SECS", 30)),
+                max_missed_heartbeats: env_or("WS_MAX_MISSED_HEARTBEATS", 3),
+                max_idle: Duration::from_secs(env_or("WS_MAX_IDLE_SECS", 30 * 60)),
+                replay_limit: env_or("WS_REPLAY_LIMIT", 500),
+                rate_per_sec: env_or("WS_RATE_PER_SEC", 5.0),
+                rate_burst: env_or("WS_RATE_BURST", 10),
+            },
+            username: UsernamePolicy {
+                min_len: env_or("USERNAME_MIN_LEN", 3),
+                max_len: env_or("USERNAME_MAX_LEN", 32),
+                allowed_symbols: env_or("USERNAME_ALLOWED_SYMBOLS", "_.-".to_string()),
+                allow_unicode: env_or("USERNAME_ALLOW_UNICODE", false),
+                case_sensitive: env_or("USERNAME_CASE_SENSITIVE", true),
+            },
+            messages: MessageConfig {
+                max_length: env_or("MESSAGE_MAX_LENGTH", 4000),
+                max_frame_bytes: env_or("MESSAGE_MAX_FRAME_BYTES", 64 * 1024),
+                dedupe_window: Duration::from_secs(env_or("MESSAGE_DEDUPE_WINDOW_SECS", 10 * 60)),
+            },
+            attachments: AttachmentConfig {
+                bucket: env_or("ATTACHMENT_BUCKET", "attachments".to_string()),
+                max_bytes: env_or("ATTACHMENT_MAX_BYTES", 10 * 1024 * 1024),
+                allowed_mime_types: env_or(
+                    "ATTACHMENT_MIME_TYPES",
+                    "image/png,image/jpeg,image/gif,image/webp,application/pdf,text/plain"
+                        .to_string(),
+                )
+                .split(',')
+                .map(|m| m.trim().to_lowercase())
+                .filter(|m| !m.is_empty())
+                .collect(),
+                url_ttl: Duration::from_secs(env_or("ATTACHMENT_URL_TTL_SECS", 365 * 24 * 60 * 60)),
+                thumbnail_max_dim: env_or("THUMBNAIL_MAX_DIM", 320),
+            },
+            emoji: EmojiConfig {
+                bucket: env_or("EMOJI_BUCKET", "emoji".to_string()),
+                max_bytes: env_or("EMOJI_MAX_BYTES", 512 * 1024),
+            },
+            avatars: AvatarConfig {
+                bucket: env_or("AVATAR_BUCKET", "avatars".to_string()),
+                max_bytes: env_or("AVATAR_MAX_BYTES", 5 * 1024 * 1024),
+            },
+            profile_fields: ProfileFieldsConfig {
+                allowed: env_or(
+                    "PROFILE_EXTRA_FIELDS",
+                    "pronouns,location,website".to_string(),
+                )
+                .split(',')
+                .map(|k| k.trim().to_lowercase())
+                .filter(|k| !k.is_empty())
+                .collect(),
+                max_chars: env_or("PROFILE_FIELD_MAX_CHARS", 100),
+            },
+            exports: ExportConfig {
+                bucket: env_or("EXPORT_BUCKET", "exports".to_string()),
+                inline_max_messages: env_or("EXPORT_INLINE_MAX_MESSAGES", 5000),
+                url_ttl: Duration::from_secs(env_or("EXPORT_URL_TTL_SECS", 24 * 60 * 60)),
+                import_max_bytes: env_or("IMPORT_MAX_BYTES", 50 * 1024 * 1024),
+            },
+            link_previews: LinkPreviewConfig {
+                enabled: env_or("LINK_PREVIEWS_ENABLED", true),
+                timeout: Duration::from_secs(env_or("LINK_PREVIEW_TIMEOUT_SECS", 5)),
+                max_bytes: env_or("LINK_PREVIEW_MAX_BYTES", 512 * 1024),
+                cache_ttl: Duration::from_secs(env_or("LINK_PREVIEW_CACHE_TTL_SECS", 60 * 60)),
+                cache_capacity: env_or("LINK_PREVIEW_CACHE_SIZE", 1000),
+            },
+            calls: CallConfig {
+                ring_timeout: Duration::from_secs(env_or("CALL_RING_TIMEOUT_SECS", 45)),
+                turn_urls: std::env::var("TURN_URLS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|u| !u.is_empty())
+                    .map(String::from)
+                    .collect(),
+                turn_secret: std::env::var("TURN_SECRET")
+                    .ok()
+                    .filter(|s| !s.trim().is_empty()),
+                turn_credential_ttl: Duration::from_secs(env_or(
+                    "TURN_CREDENTIAL_TTL_SECS",
+                    12 * 60 * 60,
+                )),
+            },
+            jobs: JobsConfig {
+                scheduler_interval: Duration::from_secs(env_or("SCHEDULER_INTERVAL_SECS", 30)),
+                erasure_interval: Duration::from_secs(env_or("ERASURE_INTERVAL_SECS", 60)),
+                expiry_interval: Duration::from_secs(env_or("MESSAGE_EXPIRY_INTERVAL_SECS", 60)),
+                export_interval: Duration::from_secs(env_or("EXPORT_INTERVAL_SECS", 15)),
+                word_filter_interval: Duration::from_secs(env_or("WORD_FILTER_RELOAD_SECS", 60)),
+                ban_list_interval: Duration::from_secs(env_or("BAN_LIST_RELOAD_SECS", 30)),
+                rate_limit_interval: Duration::from_secs(env_or(
+                    "RATE_LIMIT_OVERRIDES_RELOAD_SECS",
+                    60,
+                )),
+            },
+            bus: BusConfig {
+                kind: bus_kind_from_env(),
+                nats_url: env_or("NATS_URL", "nats://127.0.0.1:4222".to_string()),
+                nats_subject_prefix: env_or("NATS_SUBJECT_PREFIX", "gigachat".to_string()),
+            },
+            discovery: DiscoveryConfig {
+                region: std::env::var("REGION")
+                    .ok()
+                    .filter(|r| !r.trim().is_empty()),
+                endpoints: ws_endpoints_from_env(),
+                probe_interval: Duration::from_secs(env_or("WS_ENDPOINT_PROBE_SECS", 30)),
+                probe_timeout: Duration::from_secs(env_or("WS_ENDPOINT_PROBE_TIMEOUT_SECS", 3)),
+            },
+            cookies: cookie_config_from_env(),
+            proxy: ProxyConfig {
+                trusted_hops: env_or("TRUSTED_PROXY_HOPS", 0),
+            },
+            quotas: QuotaConfig {
+                messages_per_day: env_or("QUOTA_MESSAGES_PER_DAY", 5000),
+                attachment_bytes: env_or("QUOTA_ATTACHMENT_BYTES", 1024 * 1024 * 1024),
+            },
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Username policy
+// ---------------------------------------------------------------------------
+
+impl UsernamePolicy {
+    /// Canonical form used for storage and lookups: trimmed, and lowercased
+    /// unless the deployment is case-sensitive. Never fails, so it is safe
+    /// for login and search where we must not reveal the policy.
+    pub fn normalize(&self, raw: &str) -> String {
+        let trimmed = raw.trim();
+        if self.case_sensitive {
+            trimmed.to_string()
+        } else {
+            trimmed.to_lowercase()
+        }
+    }
+
+    /// Normalize `raw` and check it against every rule.
+    /// This is the single validation point for anything that sets a username.
+    pub fn validate(&self, raw: &str) -> Result<String, ApiError> {
+        let username = self.normalize(raw);
+        let len = username.chars().count();
+
+        if len == 0 {
+            return Err(ApiError::BadRequest("Username cannot be empty".into()));
+        }
+        if len < self.min_len || len > self.max_len {
+            return Err(ApiError::BadRequest(format!(
+                "Username must be between {} and {} characters",
+                self.min_len, self.max_len
+            )));
+        }
+
+        let allowed = |c: char| {
+            let is_word = if self.allow_unicode {
+                c.is_alphanumeric()
+            } else {
+                c.is_ascii_alphanumeric()
+            };
+            is_word || self.allowed_symbols.contains(c)
+        };
+
+        if let Some(bad) = username.chars().find(|c| !allowed(*c)) {
+            return Err(ApiError::BadRequest(format!(
+                "Username contains a character that is not allowed: '{}'",
+                bad
+            )));
+        }
+
+        Ok(username)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Profile fields
+// ---------------------------------------------------------------------------
+
+impl ProfileFieldsConfig {
+    /// Check a set of extra fields from an edit, returning what to store.
+    /// Values are trimmed and empty ones dropped, so sending `""` clears a
+    /// field.
+    pub fn validate(
+        &self,
+        fields: BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>, ApiError> {
+        let mut out = BTreeMap::new();
+        for (key, value) in fields {
+            let key = key.trim().to_lowercase();
+            if !self.allowed.contains(&key) {
+                return Err(ApiError::BadRequest(format!(
+                    "Unknown profile field '{}'. Allowed: {}",
+                    key,
+                    self.allowed.join(", ")
+                )));
+            }
+            let value = value.trim();
+            if value.chars().count() > self.max_chars {
+                return Err(ApiError::BadRequest(format!(
+                    "Profile field '{}' must be at most {} characters",
+                    key, self.max_chars
+                )));
+            }
+            if value.chars().any(char::is_control) {
+                return Err(ApiError::BadRequest(format!(
+                    "Profile field '{}' contains control characters",
+                    key
+                )));
+            }
+            if !value.is_empty() {
+                out.insert(key, value.to_string());
+            }
+        }
+        Ok(out)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Read `MESSAGE_BUS`. A typo here would silently split a multi-instance
+/// deployment, so unknown values are fatal.
+fn bus_kind_from_env() -> BusKind {
+    let raw = std::env::var("MESSAGE_BUS").unwrap_or_default();
+    match raw.trim().to_lowercase().as_str() {
+        "" | "inprocess" | "in-process" | "local" => BusKind::InProcess,
+        "nats" => BusKind::Nats,
+        other => panic!(
+            "MESSAGE_BUS '{}' is not supported — use 'inprocess' or 'nats'",
+            other
+        ),
+    }
+}
+
+/// Read `COOKIE_KEY` (base64, at least 64 bytes) and the comma-separated
+/// `COOKIE_PREVIOUS_KEYS`. A malformed key is fatal; a missing one falls back
+/// to a random key, which only works for a single instance and logs
+/// everyone out on restart.
+fn cookie_config_from_env() -> CookieConfig {
+    let key = match std::env::var("COOKIE_KEY") {
+        Ok(raw) if !raw.trim().is_empty() => parse_cookie_key("COOKIE_KEY", &raw),
+        _ => {
+            eprintln!(
+                "WARNING: COOKIE_KEY not set, using a random key; sessions won't survive a restart"
+            );
+            Key::generate()
+        }
+    };
+
+    let previous_keys = std::env::var("COOKIE_PREVIOUS_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|raw| !raw.is_empty())
+        .map(|raw| parse_cookie_key("COOKIE_PREVIOUS_KEYS", raw))
+        .collect();
+
+    CookieConfig { key, previous_keys }
+}
+
+fn parse_cookie_key(var: &str, raw: &str) -> Key {
+    let bytes = BASE64
+        .decode(raw.trim())
+        .unwrap_or_else(|e| panic!("{} is not valid base64: {}", var, e));
+    Key::try_from(bytes.as_slice()).unwrap_or_else(|_| {
+        panic!(
+            "{} must decode to at least 64 bytes (got {}); generate one with `openssl rand -base64 64`",
+            var,
+            bytes.len()
+        )
+    })
+}
+
+/// Parse `WS_ENDPOINTS` as comma-separated `region=url` pairs.
+fn ws_endpoints_from_env() -> Vec<WsEndpoint> {
+    let raw = std::env::var("WS_ENDPOINTS").unwrap_or_default();
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((region, url)) if !region.trim().is_empty() && !url.trim().is_empty() => {
+                Some(WsEndpoint {
+                    region: region.trim().to_string(),
+                    url: url.trim().trim_end_matches('/').to_string(),
+                })
+            }
+            _ => {
+                eprintln!(
+                    "WARNING: ignoring WS_ENDPOINTS entry '{}' (expected region=url)",
+                    entry
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse an env var, using `default` when it is missing or malformed.
+pub fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    match std::env::var(key) {
+        Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "WARNING: {} has an invalid value '{}', using default",
+                key, raw
+            );
+            default
+        }),
+        Err(_) => default,
+    }
+}
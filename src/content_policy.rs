@@ -0,0 +1,155 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::env_or;
+use crate::error::ApiError;
+
+/// Sanitization applied to message text before it is persisted or
+/// broadcast. Every send path runs content through `sanitize`, right before
+/// the length check, so stored and live copies always match.
+#[derive(Debug, Clone)]
+pub struct ContentPolicy {
+    /// Fold to Unicode NFC so visually identical text compares equal.
+    pub normalize_unicode: bool,
+    /// Also render a safe Markdown subset to HTML (`content_html`).
+    pub render_markdown: bool,
+    /// Reject text stacking more than this many combining marks on one
+    /// character ("zalgo" text that draws over neighbouring messages).
+    pub max_combining_marks: usize,
+    /// Case-insensitive substrings that get a message rejected outright.
+    pub blocked_patterns: Vec<String>,
+}
+
+/// Message text after the policy has been applied.
+#[derive(Debug)]
+pub struct Sanitized {
+    pub content: String,
+    /// Rendered Markdown, when the policy asks for it.
+    pub html: Option<String>,
+}
+
+impl ContentPolicy {
+    /// Build the policy from env vars, falling back to sensible defaults.
+    pub fn from_env() -> Self {
+        Self {
+            normalize_unicode: env_or("CONTENT_NORMALIZE_UNICODE", true),
+            render_markdown: env_or("CONTENT_RENDER_MARKDOWN", false),
+            max_combining_marks: env_or("CONTENT_MAX_COMBINING_MARKS", 8),
+            blocked_patterns: env_or(
+                "CONTENT_BLOCKED_PATTERNS",
+                "<script,javascript:,vbscript:,data:text/html".to_string(),
+            )
+            .split(',')
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty())
+            .collect(),
+        }
+    }
+
+    /// Strip control and bidi-override characters, normalize, reject
+    /// dangerous payloads and optionally render Markdown.
+    pub fn sanitize(&self, raw: &str) -> Result<Sanitized, ApiError> {
+        let stripped: String = raw.chars().filter(|c| !is_stripped(*c)).collect();
+        let content = if self.normalize_unicode {
+            stripped.nfc().collect()
+        } else {
+            stripped
+        };
+
+        let lower = content.to_lowercase();
+        if let Some(pattern) = self
+            .blocked_patterns
+            .iter()
+            .find(|p| lower.contains(p.as_str()))
+        {
+            return Err(ApiError::BadRequest(format!(
+                "Message contains disallowed content: '{}'",
+                pattern
+            )));
+        }
+
+        if self.max_combining_marks > 0 && max_combining_run(&content) > self.max_combining_marks {
+            return Err(ApiError::BadRequest(
+                "Message contains too many stacked accents".into(),
+            ));
+        }
+
+        let html = self.render_html(&content);
+        Ok(Sanitized { content, html })
+    }
+
+    /// The `content_html` for already-sanitized text, when Markdown
+    /// rendering is on. Used again after the word filter masks a message.
+    pub fn render_html(&self, content: &str) -> Option<String> {
+        self.render_markdown.then(|| render_markdown(content))
+    }
+}
+
+/// Strip the same characters as `ContentPolicy::sanitize` and normalize to
+/// NFC, for short user-supplied text outside messages.
+pub fn normalize_text(raw: &str) -> String {
+    raw.chars().filter(|c| !is_stripped(*c)).nfc().collect()
+}
+
+/// Control characters other than newline and tab, plus the bidi overrides
+/// used to make text render differently from how it reads.
+fn is_stripped(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t')
+        || matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Longest run of combining marks following a single character.
+fn max_combining_run(text: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        if is_combining_mark(c) {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest
+}
+
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+/// Render Markdown to HTML, keeping only what is safe to inject into the
+/// page: raw HTML is shown as text, images are reduced to their alt text,
+/// and links that aren't http(s) or mailto lose their href.
+fn render_markdown(text: &str) -> String {
+    let mut in_unsafe_link = false;
+    let events =
+        Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH).filter_map(|event| match event {
+            Event::Html(raw) => Some(Event::Text(raw)),
+            Event::Start(Tag::Image(..)) | Event::End(Tag::Image(..)) => None,
+            Event::Start(Tag::Link(_, ref dest, _)) if !is_safe_link(dest) => {
+                in_unsafe_link = true;
+                None
+            }
+            Event::End(Tag::Link(..)) if in_unsafe_link => {
+                in_unsafe_link = false;
+                None
+            }
+            other => Some(other),
+        });
+
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+fn is_safe_link(dest: &str) -> bool {
+    let lower = dest.trim().to_ascii_lowercase();
+    lower.starts_with("https://") || lower.starts_with("http://") || lower.starts_with("mailto:")
+}
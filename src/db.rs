@@ -0,0 +1,513 @@
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::fmt::Display;
+use tracing::error;
+
+use crate::error::ApiError;
+
+// ---------------------------------------------------------------------------
+// Typed query layer
+// ---------------------------------------------------------------------------
+
+/// Every table the backend touches. Naming tables through this enum turns a
+/// misspelt table into a compile error instead of a runtime 404.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Profiles,
+    Friends,
+    Conversations,
+    ConversationMembers,
+    Messages,
+    ScheduledMessages,
+    ModerationSnapshots,
+    ErasureRequests,
+    ErasureBackupExclusions,
+    AdminAuditLog,
+    Polls,
+    PollVotes,
+    EmojiPacks,
+    Emoji,
+    Drafts,
+    ConversationExports,
+    MessageRevisions,
+    WordFilterTerms,
+    UserBlocks,
+    Activity,
+    UserSettings,
+    NotificationSettings,
+    Reports,
+    HeldMessages,
+    IpBans,
+    RateLimitOverrides,
+    Appeals,
+}
+
+impl Table {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Table::Profiles => "profiles",
+            Table::Friends => "friends",
+            Table::Conversations => "conversations",
+            Table::ConversationMembers => "conversation_members",
+            Table::Messages => "messages",
+            Table::ScheduledMessages => "scheduled_messages",
+            Table::ModerationSnapshots => "moderation_snapshots",
+            Table::ErasureRequests => "erasure_requests",
+            Table::ErasureBackupExclusions => "erasure_backup_exclusions",
+            Table::AdminAuditLog => "admin_audit_log",
+            Table::Polls => "polls",
+            Table::PollVotes => "poll_votes",
+            Table::EmojiPacks => "emoji_packs",
+            Table::Emoji => "emoji",
+            Table::Drafts => "drafts",
+            Table::ConversationExports => "conversation_exports",
+            Table::MessageRevisions => "message_revisions",
+            Table::WordFilterTerms => "word_filter_terms",
+            Table::UserBlocks => "user_blocks",
+            Table::Activity => "activity",
+            Table::UserSettings => "user_settings",
+            Table::NotificationSettings => "notification_settings",
+            Table::Reports => "reports",
+            Table::HeldMessages => "held_messages",
+            Table::IpBans => "ip_bans",
+            Table::RateLimitOverrides => "rate_limit_overrides",
+            Table::Appeals => "appeals",
+        }
+    }
+}
+
+/// A column name. Only the constants in [`col`] can be constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column(&'static str);
+
+impl Column {
+    pub const fn name(self) -> &'static str {
+        self.0
+    }
+}
+
+/// Column names used in filters, ordering and projections.
+pub mod col {
+    use super::Column;
+
+    pub const ID: Column = Column("id");
+    pub const USERNAME: Column = Column("username");
+    pub const USER_ID: Column = Column("user_id");
+    pub const USER_A: Column = Column("user_a");
+    pub const USER_B: Column = Column("user_b");
+    pub const STATUS: Column = Column("status");
+    pub const CONVERSATION_ID: Column = Column("conversation_id");
+    pub const SENDER_ID: Column = Column("sender_id");
+    pub const CREATED_AT: Column = Column("created_at");
+    pub const CREATED_BY: Column = Column("created_by");
+    pub const NOTE: Column = Column("note");
+    pub const NEXT_RUN_AT: Column = Column("next_run_at");
+    pub const PAUSED: Column = Column("paused");
+    pub const REQUESTED_AT: Column = Column("requested_at");
+    pub const IS_DELETED: Column = Column("is_deleted");
+    pub const MESSAGE_TTL_SECONDS: Column = Column("message_ttl_seconds");
+    pub const POLL_ID: Column = Column("poll_id");
+    pub const PACK_ID: Column = Column("pack_id");
+    pub const NAME: Column = Column("name");
+    pub const UPDATED_AT: Column = Column("updated_at");
+    pub const CLIENT_ID: Column = Column("client_id");
+    pub const ATTACHMENT: Column = Column("attachment");
+    pub const MESSAGE_ID: Column = Column("message_id");
+    pub const FRIEND_CODE: Column = Column("friend_code");
+    pub const FAVORITE_A: Column = Column("favorite_a");
+    pub const FAVORITE_B: Column = Column("favorite_b");
+    pub const BLOCKER_ID: Column = Column("blocker_id");
+    pub const BLOCKED_ID: Column = Column("blocked_id");
+    pub const DEACTIVATED_AT: Column = Column("deactivated_at");
+    pub const REPORTER_ID: Column = Column("reporter_id");
+    pub const TARGET_USER_ID: Column = Column("target_user_id");
+    pub const BANNED_AT: Column = Column("banned_at");
+    pub const TARGET_ID: Column = Column("target_id");
+    pub const SHADOW_BANNED_AT: Column = Column("shadow_banned_at");
+    pub const SUSPENDED_UNTIL: Column = Column("suspended_until");
+    pub const SESSION_VERSION: Column = Column("session_version");
+    pub const ADMIN_ID: Column = Column("admin_id");
+    pub const ACTION: Column = Column("action");
+    /// Generated `tsvector` over `messages.content`, GIN-indexed for search.
+    pub const CONTENT_TSV: Column = Column("content_tsv");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// Start a query against `table`, e.g.
+/// `db::from(Table::Friends).eq(col::USER_A, me).fetch().await?`.
+pub fn from(table: Table) -> Query {
+    Query {
+        table,
+        params: Vec::new(),
+    }
+}
+
+/// A PostgREST read, update or delete built from typed filters.
+///
+/// Values are encoded in one place: every filter goes through reqwest's
+/// query-string encoding, so a username containing `&` or `#` can't
+/// truncate or extend the URL the way a hand-built string could, and
+/// `in.(...)` list items are double-quoted so commas or parentheses inside
+/// a value can't change the filter.
+#[derive(Debug, Clone)]
+pub struct Query {
+    table: Table,
+    params: Vec<(&'static str, String)>,
+}
+
+impl Query {
+    pub fn eq(self, column: Column, value: impl Display) -> Self {
+        self.filter(column, "eq", value)
+    }
+
+    pub fn gt(self, column: Column, value: impl Display) -> Self {
+        self.filter(column, "gt", value)
+    }
+
+    pub fn lt(self, column: Column, value: impl Display) -> Self {
+        self.filter(column, "lt", value)
+    }
+
+    pub fn gte(self, column: Column, value: impl Display) -> Self {
+        self.filter(column, "gte", value)
+    }
+
+    pub fn lte(self, column: Column, value: impl Display) -> Self {
+        self.filter(column, "lte", value)
+    }
+
+    /// Matches `false` and `null`, which `eq.false` would miss.
+    pub fn not_true(mut self, column: Column) -> Self {
+        self.params.push((column.name(), "not.is.true".into()));
+        self
+    }
+
+    pub fn null(mut self, column: Column) -> Self {
+        self.params.push((column.name(), "is.null".into()));
+        self
+    }
+
+    pub fn not_null(mut self, column: Column) -> Self {
+        self.params.push((column.name(), "not.is.null".into()));
+        self
+    }
+
+    pub fn in_list<I, V>(mut self, column: Column, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Display,
+    {
+        let items: Vec<String> = values
+            .into_iter()
+            .map(|v| quote_list_item(&v.to_string()))
+            .collect();
+        self.params
+            .push((column.name(), format!("in.({})", items.join(","))));
+        self
+    }
+
+    pub fn not_in<I, V>(mut self, column: Column, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Display,
+    {
+        let items: Vec<String> = values
+            .into_iter()
+            .map(|v| quote_list_item(&v.to_string()))
+            .collect();
+        self.params
+            .push((column.name(), format!("not.in.({})", items.join(","))));
+        self
+    }
+
+    /// Match rows satisfying any of `conditions`, each in PostgREST's
+    /// `column.op.value` form (`and(...)` groups allowed). Values are not
+    /// quoted, so only use this with ids and literals.
+    pub fn or(mut self, conditions: &[String]) -> Self {
+        self.params
+            .push(("or", format!("({})", conditions.join(","))));
+        self
+    }
+
+    /// Case-insensitive prefix match. `%`, `_` and `\` in `prefix` match
+    /// literally rather than acting as wildcards.
+    pub fn starts_with_ci(mut self, column: Column, prefix: &str) -> Self {
+        self.params
+            .push((column.name(), format!("ilike.{}*", escape_like(prefix))));
+        self
+    }
+
+    /// Case-insensitive equality, escaped the same way as `starts_with_ci`.
+    pub fn eq_ci(mut self, column: Column, value: &str) -> Self {
+        self.params
+            .push((column.name(), format!("ilike.{}", escape_like(value))));
+        self
+    }
+
+    /// Full-text match using web-search syntax (`"exact phrase"`, `or`,
+    /// `-exclude`) against a `tsvector` column.
+    pub fn full_text(mut self, column: Column, config: &str, query: impl Display) -> Self {
+        self.params
+            .push((column.name(), format!("wfts({}).{}", config, query)));
+        self
+    }
+
+    /// Only return these columns instead of `*`.
+    pub fn columns(mut self, columns: &[Column]) -> Self {
+        let names: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+        self.params.push(("select", names.join(",")));
+        self
+    }
+
+    /// Add a sort key; call repeatedly for tie-breakers.
+    pub fn order(mut self, column: Column, order: Order) -> Self {
+        let key = format!(
+            "{}.{}",
+            column.name(),
+            match order {
+                Order::Asc => "asc",
+                Order::Desc => "desc",
+            }
+        );
+        match self.params.iter_mut().find(|(k, _)| *k == "order") {
+            Some((_, existing)) => {
+                existing.push(',');
+                existing.push_str(&key);
+            }
+            None => self.params.push(("order", key)),
+        }
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.params.push(("limit", limit.to_string()));
+        self
+    }
+
+    /// Skip this many rows; pair with `order` so pages are stable.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.params.push(("offset", offset.to_string()));
+        self
+    }
+
+    /// Fetch every matching row.
+    pub async fn fetch(self) -> Result<Vec<Value>, ApiError> {
+        let req = request(Method::GET, self.table.name())?.query(&self.params);
+        execute(req, "select", self.table.name()).await
+    }
+
+    /// PATCH every matching row and return the updated rows.
+    /// supabase_rs can only update a single row by `id`.
+    pub async fn update(self, body: Value) -> Result<Vec<Value>, ApiError> {
+        let req = request(Method::PATCH, self.table.name())?
+            .query(&self.params)
+            .header("Prefer", "return=representation")
+            .json(&body);
+        execute(req, "update", self.table.name()).await
+    }
+
+    /// DELETE every matching row and return the deleted rows.
+    pub async fn delete(self) -> Result<Vec<Value>, ApiError> {
+        let req = request(Method::DELETE, self.table.name())?
+            .query(&self.params)
+            .header("Prefer", "return=representation");
+        execute(req, "delete", self.table.name()).await
+    }
+
+    fn filter(mut self, column: Column, op: &str, value: impl Display) -> Self {
+        self.params
+            .push((column.name(), format!("{}.{}", op, value)));
+        self
+    }
+}
+
+/// Quote a value for a PostgREST `in.(...)` list, escaping `\\` and `"`.
+fn quote_list_item(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// ---------------------------------------------------------------------------
+// Raw requests
+// ---------------------------------------------------------------------------
+
+/// Call a Postgres function exposed by PostgREST (`POST /rest/v1/rpc/{function}`).
+pub async fn rpc(function: &str, args: Value) -> Result<Value, ApiError> {
+    let req = request(Method::POST, &format!("rpc/{}", function))?.json(&args);
+    execute(req, "rpc", function).await
+}
+
+/// Start an authenticated request against `/rest/v1/{table}`.
+fn request(method: Method, table: &str) -> Result<RequestBuilder, ApiError> {
+    let supabase_url = std::env::var("SUPABASE_URL")
+        .map_err(|_| ApiError::Internal("SUPABASE_URL not set".into()))?;
+    let supabase_key = std::env::var("SUPABASE_KEY")
+        .map_err(|_| ApiError::Internal("SUPABASE_KEY not set".into()))?;
+
+    let url = format!("{}/rest/v1/{}", supabase_url.trim_end_matches('/'), table);
+
+    Ok(reqwest::Client::new()
+        .request(method, url)
+        .header("apikey", &supabase_key)
+        .header("Authorization", format!("Bearer {}", supabase_key)))
+}
+
+/// Send a PostgREST request and parse the JSON it returns.
+async fn execute<T: DeserializeOwned>(
+    req: RequestBuilder,
+    op: &str,
+    table: &str,
+) -> Result<T, ApiError> {
+    let res = req.send().await.map_err(network_error)?;
+
+    let status = res.status();
+    let body = res
+        .text()
+        .await
+        .unwrap_or_else(|_| "(could not read body)".into());
+
+    if !status.is_success() {
+        error!("[db::{}] {} on '{}'", op, status, table);
+        return Err(classify_error(status, &body, table));
+    }
+
+    serde_json::from_str(&body)
+        .map_err(|e| ApiError::Database(format!("Unexpected Supabase response: {}", e)))
+}
+
+/// Insert a row, or update the existing one that has the same values in
+/// `on_conflict` (which must be a unique constraint). Returns the stored row.
+pub async fn upsert(table: Table, on_conflict: &[Column], body: Value) -> Result<Value, ApiError> {
+    let columns: Vec<&str> = on_conflict.iter().map(|c| c.name()).collect();
+    let req = request(Method::POST, table.name())?
+        .query(&[("on_conflict", columns.join(","))])
+        .header(
+            "Prefer",
+            "resolution=merge-duplicates,return=representation",
+        )
+        .json(&body);
+    let rows: Vec<Value> = execute(req, "upsert", table.name()).await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| ApiError::Database("Supabase returned no row for upsert".into()))
+}
+
+/// Insert many rows in one request without reading them back.
+pub async fn insert_many(table: Table, rows: &[Value]) -> Result<(), ApiError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let res = request(Method::POST, table.name())?
+        .header("Prefer", "return=minimal")
+        .json(rows)
+        .send()
+        .await
+        .map_err(network_error)?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().await.unwrap_or_default();
+        error!("[db::insert_many] {} on '{}'", status, table.name());
+        return Err(classify_error(status, &body, table.name()));
+    }
+    Ok(())
+}
+
+/// Insert a row and return its `id` as a string (for tables with UUID keys).
+pub async fn insert(table: Table, body: Value) -> Result<String, ApiError> {
+    let row = insert_returning(table, body).await?;
+
+    let id = row.get("id").and_then(|v| v.as_str()).ok_or_else(|| {
+        ApiError::Internal(format!("Supabase response missing 'id' field: {}", row))
+    })?;
+
+    Ok(id.to_string())
+}
+
+/// Insert a row and return it, including server-generated columns.
+/// supabase_rs's insert() only gives "400 Bad Request" with no details.
+///
+/// Rows hold message text and password hashes, so only the table and the
+/// status are logged, never the bodies.
+pub async fn insert_returning(table: Table, body: Value) -> Result<Value, ApiError> {
+    let req = request(Method::POST, table.name())?
+        // Ask Supabase to return the inserted row so we can read the generated id
+        .header("Prefer", "return=representation")
+        .json(&body);
+    let rows: Vec<Value> = execute(req, "insert", table.name()).await?;
+    rows.into_iter()
+        .next()
+        .ok_or_else(|| ApiError::Internal("Supabase returned empty array after insert".into()))
+}
+
+// ---------------------------------------------------------------------------
+// Error mapping
+// ---------------------------------------------------------------------------
+
+/// Turn a failed PostgREST response into the matching `ApiError`, using the
+/// Postgres SQLSTATE in the body's `code` field where there is one.
+fn classify_error(status: StatusCode, body: &str, table: &str) -> ApiError {
+    let json = serde_json::from_str::<Value>(body).ok();
+    let field = |name: &str| {
+        json.as_ref()
+            .and_then(|j| j.get(name))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+    };
+
+    let code = field("code").unwrap_or_default();
+    // Parse the error body for a friendlier message
+    let detail = field("message")
+        .or_else(|| field("msg"))
+        .or_else(|| field("details"))
+        .or_else(|| field("hint"))
+        .unwrap_or_else(|| body.to_string());
+
+    match code.as_str() {
+        "23505" => ApiError::UniqueViolation(detail),
+        "23503" => ApiError::ForeignKeyViolation(detail),
+        "57014" => ApiError::DatabaseTimeout,
+        "42501" => ApiError::PermissionDenied(format!(
+            "'{}' refused the query. Disable Row Level Security (RLS) or use the service-role key. ({})",
+            table, detail
+        )),
+        // Undefined table, as reported by Postgres or by PostgREST's schema cache.
+        "42P01" | "PGRST205" => ApiError::Database(format!(
+            "Table '{}' not found. Did you run the SQL from SCHEMA.md in your Supabase SQL Editor? ({})",
+            table, detail
+        )),
+        _ if status == StatusCode::GATEWAY_TIMEOUT => ApiError::DatabaseTimeout,
+        _ => ApiError::Database(format!(
+            "Supabase error {} on '{}': {}",
+            status.as_u16(),
+            table,
+            detail
+        )),
+    }
+}
+
+fn network_error(e: reqwest::Error) -> ApiError {
+    if e.is_timeout() {
+        ApiError::DatabaseTimeout
+    } else {
+        ApiError::Database(format!("Network error talking to Supabase: {}", e))
+    }
+}
+
+/// Escape `value` for an `ilike` pattern so it only matches literally.
+/// PostgREST's `*` wildcard can't be escaped, so it is dropped.
+fn escape_like(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| *c != '*')
+        .flat_map(|c| match c {
+            '%' | '_' | '\\' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
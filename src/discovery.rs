@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+use crate::config::{DiscoveryConfig, WsEndpoint};
+
+/// Last probe result for one regional endpoint.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    pub healthy: bool,
+    /// Round-trip time of the health probe as seen from this instance.
+    pub latency_ms: Option<u64>,
+    pub last_checked: String,
+}
+
+/// The configured regional WebSocket endpoints and their latest health.
+pub struct EndpointRegistry {
+    pub endpoints: Vec<WsEndpoint>,
+    status: RwLock<HashMap<String, EndpointStatus>>,
+}
+
+impl EndpointRegistry {
+    pub fn new(config: &DiscoveryConfig) -> Self {
+        Self {
+            endpoints: config.endpoints.clone(),
+            status: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn status(&self, region: &str) -> Option<EndpointStatus> {
+        self.status.read().await.get(region).cloned()
+    }
+
+    /// Probe every endpoint's `/health` once and record the results.
+    pub async fn probe_all(&self, timeout: Duration) {
+        let client = reqwest::Client::new();
+
+        for endpoint in &self.endpoints {
+            let started = Instant::now();
+            let healthy = client
+                .get(health_url(&endpoint.url))
+                .timeout(timeout)
+                .send()
+                .await
+                .is_ok_and(|res| res.status().is_success());
+
+            let status = EndpointStatus {
+                healthy,
+                latency_ms: healthy.then(|| started.elapsed().as_millis() as u64),
+                last_checked: chrono::Utc::now().to_rfc3339(),
+            };
+            self.status
+                .write()
+                .await
+                .insert(endpoint.region.clone(), status);
+        }
+    }
+}
+
+/// `wss://eu.example.com` → `https://eu.example.com/health`.
+fn health_url(ws_url: &str) -> String {
+    let base = if let Some(rest) = ws_url.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else if let Some(rest) = ws_url.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else {
+        ws_url.to_string()
+    };
+    format!("{}/health", base.trim_end_matches('/'))
+}
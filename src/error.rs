@@ -1,29 +1,92 @@
 use axum::{
-    http::StatusCode,
+    body::Body,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Locales the message catalog covers. English is the `Display` text.
+pub const SUPPORTED_LOCALES: [&str; 4] = ["en", "de", "es", "fr"];
 
 /// Every handler returns `Result<T, ApiError>`.
 /// This enum covers all the error cases the API can produce.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ApiError {
     /// Something went wrong talking to Supabase.
     Database(String),
 
+    /// An insert or update hit a unique constraint (Postgres 23505).
+    /// Handlers may treat this as success when the write is idempotent.
+    UniqueViolation(String),
+
+    /// A row references another row that doesn't exist (Postgres 23503).
+    ForeignKeyViolation(String),
+
+    /// Row Level Security or a missing grant refused the query (Postgres 42501).
+    PermissionDenied(String),
+
+    /// The query was cancelled by the statement timeout, or Supabase didn't
+    /// answer in time.
+    DatabaseTimeout,
+
     /// The username or password was wrong.
     InvalidCredentials,
 
     /// No session cookie or the cookie is invalid.
     Unauthorized,
 
+    /// Logged in, but not allowed to perform this action.
+    Forbidden(String),
+
+    /// An admin banned this account; it can't log in or use a session.
+    AccountBanned,
+
+    /// An admin suspended this account until `until` (RFC 3339), which is
+    /// `remaining_secs` away. It lifts on its own.
+    AccountSuspended { until: String, remaining_secs: u64 },
+
     /// The request body or parameters are invalid.
     BadRequest(String),
 
+    /// One or more fields of the request body are invalid, keyed by field.
+    Validation(BTreeMap<String, String>),
+
     /// A resource (profile, conversation, etc.) was not found.
     NotFound(String),
 
+    /// Message content exceeds the configured maximum length (in characters).
+    MessageTooLong { max: usize },
+
+    /// An uploaded file exceeds the configured maximum size (in bytes).
+    AttachmentTooLarge { max_bytes: usize },
+
+    /// An uploaded file's type is not on the allow-list.
+    UnsupportedMediaType(String),
+
+    /// Automated moderation flagged the message; it was kept for a
+    /// moderator instead of being delivered.
+    MessageHeld,
+
+    /// The conversation is locked; nobody can post until it is unlocked.
+    ConversationLocked,
+
+    /// The user has used up one of their quotas (`messages_per_day` or
+    /// `attachment_bytes`).
+    QuotaExceeded { quota: &'static str, limit: u64 },
+
+    /// The conversation is in slow mode and the sender posted less than
+    /// `interval_secs` ago.
+    SlowMode {
+        interval_secs: u64,
+        retry_after_secs: u64,
+    },
+
+    /// The sender is going too fast (or looks like spam) and must wait.
+    RateLimited { retry_after_secs: u64 },
+
     /// Catch-all for unexpected internal errors.
     Internal(String),
 }
@@ -32,28 +95,391 @@ impl std::fmt::Display for ApiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ApiError::Database(msg) => write!(f, "Database error: {}", msg),
+            ApiError::UniqueViolation(msg) => write!(f, "Already exists: {}", msg),
+            ApiError::ForeignKeyViolation(msg) => {
+                write!(f, "Referenced record does not exist: {}", msg)
+            }
+            ApiError::PermissionDenied(msg) => write!(f, "Database permission denied: {}", msg),
+            ApiError::DatabaseTimeout => write!(f, "The database took too long to respond"),
             ApiError::InvalidCredentials => write!(f, "Invalid username or password"),
             ApiError::Unauthorized => write!(f, "You must be logged in to do that"),
+            ApiError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ApiError::AccountBanned => write!(f, "This account has been banned"),
+            ApiError::AccountSuspended { until, .. } => {
+                write!(f, "This account is suspended until {}", until)
+            }
             ApiError::BadRequest(msg) => write!(f, "Error: {}", msg),
+            ApiError::Validation(fields) => write!(f, "Invalid fields: {}", field_list(fields)),
             ApiError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ApiError::MessageTooLong { max } => {
+                write!(f, "Message is too long (max {} characters)", max)
+            }
+            ApiError::AttachmentTooLarge { max_bytes } => {
+                write!(f, "Attachment is too large (max {} bytes)", max_bytes)
+            }
+            ApiError::UnsupportedMediaType(msg) => write!(f, "Unsupported file type: {}", msg),
+            ApiError::MessageHeld => write!(f, "Your message is waiting for moderator review"),
+            ApiError::ConversationLocked => {
+                write!(f, "This conversation is locked; new messages can't be sent")
+            }
+            ApiError::QuotaExceeded { quota, limit } => match *quota {
+                "messages_per_day" => {
+                    write!(f, "You have reached your limit of {} messages a day", limit)
+                }
+                _ => write!(
+                    f,
+                    "You have used all {} bytes of your attachment storage",
+                    limit
+                ),
+            },
+            ApiError::SlowMode {
+                retry_after_secs, ..
+            } => write!(
+                f,
+                "Slow mode is on; you can send again in {}s",
+                retry_after_secs
+            ),
+            ApiError::RateLimited { retry_after_secs } => write!(
+                f,
+                "You are sending messages too fast; try again in {}s",
+                retry_after_secs
+            ),
             ApiError::Internal(msg) => write!(f, "Internal error: {}", msg),
         }
     }
 }
 
+impl ApiError {
+    /// A `RateLimited` error telling the caller to wait `wait`, rounded up to
+    /// whole seconds and never less than one.
+    pub fn rate_limited(wait: Duration) -> Self {
+        ApiError::RateLimited {
+            retry_after_secs: wait.as_secs_f64().ceil().max(1.0) as u64,
+        }
+    }
+
+    /// An `AccountSuspended` error for a suspension ending at `until`.
+    pub fn suspended(until: chrono::DateTime<chrono::Utc>) -> Self {
+        let remaining = (until - chrono::Utc::now()).num_seconds().max(1);
+        ApiError::AccountSuspended {
+            until: until.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            remaining_secs: remaining as u64,
+        }
+    }
+
+    /// Whether the same request may well succeed if tried again later.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ApiError::Database(_)
+                | ApiError::DatabaseTimeout
+                | ApiError::Internal(_)
+                | ApiError::SlowMode { .. }
+                | ApiError::RateLimited { .. }
+        )
+    }
+
+    /// How long the caller must wait before trying again, for the errors
+    /// that say.
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            ApiError::RateLimited { retry_after_secs }
+            | ApiError::SlowMode {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
+        }
+    }
+
+    /// Stable, machine-readable identifier for the error, sent alongside the
+    /// human-readable message so clients can branch on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Database(_) => "database_error",
+            ApiError::UniqueViolation(_) => "unique_violation",
+            ApiError::ForeignKeyViolation(_) => "foreign_key_violation",
+            ApiError::PermissionDenied(_) => "permission_denied",
+            ApiError::DatabaseTimeout => "database_timeout",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::AccountBanned => "account_banned",
+            ApiError::AccountSuspended { .. } => "account_suspended",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::MessageTooLong { .. } => "message_too_long",
+            ApiError::AttachmentTooLarge { .. } => "attachment_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::MessageHeld => "message_held",
+            ApiError::ConversationLocked => "conversation_locked",
+            ApiError::QuotaExceeded { .. } => "quota_exceeded",
+            ApiError::SlowMode { .. } => "slow_mode",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Message catalog
+// ---------------------------------------------------------------------------
+
+impl ApiError {
+    /// The message in `locale`, for the errors whose text doesn't come from
+    /// the handler. `None` means the English `Display` text is used.
+    pub fn localized(&self, locale: &str) -> Option<String> {
+        let text = match (locale, self) {
+            ("de", ApiError::DatabaseTimeout) => "Die Datenbank hat zu lange gebraucht".into(),
+            ("de", ApiError::InvalidCredentials) => "Benutzername oder Passwort ist falsch".into(),
+            ("de", ApiError::Unauthorized) => "Dafür musst du angemeldet sein".into(),
+            ("de", ApiError::AccountBanned) => "Dieses Konto wurde gesperrt".into(),
+            ("de", ApiError::AccountSuspended { until, .. }) => {
+                format!("Dieses Konto ist bis {} gesperrt", until)
+            }
+            ("de", ApiError::MessageHeld) => {
+                "Deine Nachricht wird von der Moderation geprüft".into()
+            }
+            ("de", ApiError::ConversationLocked) => {
+                "Diese Unterhaltung ist gesperrt; es können keine Nachrichten gesendet werden"
+                    .into()
+            }
+            ("de", ApiError::QuotaExceeded { quota, limit }) => match *quota {
+                "messages_per_day" => {
+                    format!(
+                        "Du hast dein Limit von {} Nachrichten pro Tag erreicht",
+                        limit
+                    )
+                }
+                _ => format!(
+                    "Du hast deinen Speicher für Anhänge ({} Bytes) aufgebraucht",
+                    limit
+                ),
+            },
+            ("de", ApiError::MessageTooLong { max }) => {
+                format!("Die Nachricht ist zu lang (höchstens {} Zeichen)", max)
+            }
+            ("de", ApiError::AttachmentTooLarge { max_bytes }) => {
+                format!("Der Anhang ist zu groß (höchstens {} Bytes)", max_bytes)
+            }
+            ("de", ApiError::RateLimited { retry_after_secs }) => format!(
+                "Du sendest zu schnell; versuche es in {}s erneut",
+                retry_after_secs
+            ),
+            (
+                "de",
+                ApiError::SlowMode {
+                    retry_after_secs, ..
+                },
+            ) => format!(
+                "Langsamer Modus ist aktiv; du kannst in {}s wieder senden",
+                retry_after_secs
+            ),
+            ("de", ApiError::Validation(fields)) => {
+                format!("Ungültige Felder: {}", field_list(fields))
+            }
+
+            ("es", ApiError::DatabaseTimeout) => {
+                "La base de datos tardó demasiado en responder".into()
+            }
+            ("es", ApiError::InvalidCredentials) => {
+                "Nombre de usuario o contraseña incorrectos".into()
+            }
+            ("es", ApiError::Unauthorized) => "Debes iniciar sesión para hacer eso".into(),
+            ("es", ApiError::AccountBanned) => "Esta cuenta ha sido suspendida".into(),
+            ("es", ApiError::AccountSuspended { until, .. }) => {
+                format!("Esta cuenta está suspendida hasta {}", until)
+            }
+            ("es", ApiError::MessageHeld) => "Tu mensaje está pendiente de moderación".into(),
+            ("es", ApiError::ConversationLocked) => {
+                "Esta conversación está bloqueada; no se pueden enviar mensajes".into()
+            }
+            ("es", ApiError::QuotaExceeded { quota, limit }) => match *quota {
+                "messages_per_day" => {
+                    format!("Has alcanzado tu límite de {} mensajes al día", limit)
+                }
+                _ => format!(
+                    "Has agotado tu almacenamiento de archivos adjuntos ({} bytes)",
+                    limit
+                ),
+            },
+            ("es", ApiError::MessageTooLong { max }) => {
+                format!("El mensaje es demasiado largo (máximo {} caracteres)", max)
+            }
+            ("es", ApiError::AttachmentTooLarge { max_bytes }) => format!(
+                "El archivo adjunto es demasiado grande (máximo {} bytes)",
+                max_bytes
+            ),
+            ("es", ApiError::RateLimited { retry_after_secs }) => format!(
+                "Estás enviando demasiado rápido; inténtalo de nuevo en {}s",
+                retry_after_secs
+            ),
+            (
+                "es",
+                ApiError::SlowMode {
+                    retry_after_secs, ..
+                },
+            ) => format!(
+                "El modo lento está activado; puedes volver a enviar en {}s",
+                retry_after_secs
+            ),
+            ("es", ApiError::Validation(fields)) => {
+                format!("Campos no válidos: {}", field_list(fields))
+            }
+
+            ("fr", ApiError::DatabaseTimeout) => {
+                "La base de données a mis trop de temps à répondre".into()
+            }
+            ("fr", ApiError::InvalidCredentials) => {
+                "Nom d'utilisateur ou mot de passe incorrect".into()
+            }
+            ("fr", ApiError::Unauthorized) => "Vous devez être connecté pour faire cela".into(),
+            ("fr", ApiError::AccountBanned) => "Ce compte a été banni".into(),
+            ("fr", ApiError::AccountSuspended { until, .. }) => {
+                format!("Ce compte est suspendu jusqu'au {}", until)
+            }
+            ("fr", ApiError::MessageHeld) => "Votre message est en attente de modération".into(),
+            ("fr", ApiError::ConversationLocked) => {
+                "Cette conversation est verrouillée ; impossible d'envoyer des messages".into()
+            }
+            ("fr", ApiError::QuotaExceeded { quota, limit }) => match *quota {
+                "messages_per_day" => {
+                    format!(
+                        "Vous avez atteint votre limite de {} messages par jour",
+                        limit
+                    )
+                }
+                _ => format!(
+                    "Vous avez utilisé tout votre espace pour les pièces jointes ({} octets)",
+                    limit
+                ),
+            },
+            ("fr", ApiError::MessageTooLong { max }) => {
+                format!("Le message est trop long ({} caractères maximum)", max)
+            }
+            ("fr", ApiError::AttachmentTooLarge { max_bytes }) => format!(
+                "La pièce jointe est trop volumineuse ({} octets maximum)",
+                max_bytes
+            ),
+            ("fr", ApiError::RateLimited { retry_after_secs }) => format!(
+                "Vous envoyez trop vite ; réessayez dans {} s",
+                retry_after_secs
+            ),
+            (
+                "fr",
+                ApiError::SlowMode {
+                    retry_after_secs, ..
+                },
+            ) => format!(
+                "Le mode lent est activé ; vous pourrez renvoyer un message dans {} s",
+                retry_after_secs
+            ),
+            ("fr", ApiError::Validation(fields)) => {
+                format!("Champs invalides : {}", field_list(fields))
+            }
+
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    /// The JSON body sent for this error with `message` as its text.
+    fn body(&self, message: String) -> serde_json::Value {
+        match self {
+            ApiError::RateLimited { retry_after_secs } => json!({
+                "error": message,
+                "code": self.code(),
+                "retry_after_secs": retry_after_secs,
+            }),
+            ApiError::Validation(fields) => {
+                json!({ "error": message, "code": self.code(), "fields": fields })
+            }
+            ApiError::AccountSuspended {
+                until,
+                remaining_secs,
+            } => json!({
+                "error": message,
+                "code": self.code(),
+                "suspended_until": until,
+                "remaining_secs": remaining_secs,
+            }),
+            ApiError::QuotaExceeded { quota, limit } => json!({
+                "error": message,
+                "code": self.code(),
+                "quota": quota,
+                "limit": limit,
+            }),
+            ApiError::SlowMode {
+                interval_secs,
+                retry_after_secs,
+            } => json!({
+                "error": message,
+                "code": self.code(),
+                "slow_mode_seconds": interval_secs,
+                "retry_after_secs": retry_after_secs,
+            }),
+            _ => json!({ "error": message, "code": self.code() }),
+        }
+    }
+
+    /// Rewrite an error response built by `into_response` in `locale`, if
+    /// the catalog has the message.
+    pub fn localize_response(&self, response: &mut Response, locale: &str) {
+        if let Some(message) = self.localized(locale) {
+            if let Ok(bytes) = serde_json::to_vec(&self.body(message)) {
+                response.headers_mut().remove(header::CONTENT_LENGTH);
+                *response.body_mut() = Body::from(bytes);
+            }
+        }
+    }
+}
+
+fn field_list(fields: &BTreeMap<String, String>) -> String {
+    fields.keys().cloned().collect::<Vec<_>>().join(", ")
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, message) = match &self {
             ApiError::Database(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
+            ApiError::UniqueViolation(_) => (StatusCode::CONFLICT, self.to_string()),
+            ApiError::ForeignKeyViolation(_) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, self.to_string())
+            }
+            ApiError::PermissionDenied(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::DatabaseTimeout => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
             ApiError::InvalidCredentials => (StatusCode::UNAUTHORIZED, self.to_string()),
             ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
+            ApiError::Forbidden(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::AccountBanned => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::AccountSuspended { .. } => (StatusCode::FORBIDDEN, self.to_string()),
             ApiError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            ApiError::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
             ApiError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            ApiError::MessageTooLong { .. } => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            ApiError::AttachmentTooLarge { .. } => {
+                (StatusCode::PAYLOAD_TOO_LARGE, self.to_string())
+            }
+            ApiError::UnsupportedMediaType(_) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, self.to_string())
+            }
+            ApiError::MessageHeld => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::ConversationLocked => (StatusCode::LOCKED, self.to_string()),
+            ApiError::QuotaExceeded { .. } => (StatusCode::FORBIDDEN, self.to_string()),
+            ApiError::SlowMode { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            ApiError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
             ApiError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
 
-        let body = json!({ "error": message });
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(self.body(message))).into_response();
+        if let Some(retry_after_secs) = self.retry_after_secs() {
+            if let Ok(value) = retry_after_secs.to_string().parse() {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+        }
+        // Picked up by `locale::localize_errors` to translate the message.
+        response.extensions_mut().insert(self);
+        response
     }
 }
 
@@ -0,0 +1,107 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::env_or;
+use crate::error::ApiError;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Senders tracked before idle ones are swept out.
+const SWEEP_THRESHOLD: usize = 4096;
+
+/// Caps on how many friend requests one user may send. Requests that are
+/// rejected or withdrawn still count, which is the point: it stops someone
+/// asking the same person over and over.
+#[derive(Debug, Clone)]
+pub struct FriendRequestLimits {
+    pub per_hour: usize,
+    pub per_day: usize,
+    /// How long before the same sender may ask the same user again.
+    pub repeat_after: Duration,
+}
+
+impl FriendRequestLimits {
+    /// Build the limits from env vars, falling back to sensible defaults.
+    pub fn from_env() -> Self {
+        Self {
+            per_hour: env_or("FRIEND_REQUESTS_PER_HOUR", 20),
+            per_day: env_or("FRIEND_REQUESTS_PER_DAY", 100),
+            repeat_after: Duration::from_secs(env_or("FRIEND_REQUEST_REPEAT_SECS", 86_400)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SenderHistory {
+    /// When each request in the last day was sent, oldest first.
+    sent: VecDeque<Instant>,
+    /// Last request to each target.
+    targets: HashMap<Uuid, Instant>,
+}
+
+/// Applies [`FriendRequestLimits`]. In-memory and per instance, like the
+/// spam guard.
+pub struct FriendRequestLimiter {
+    limits: FriendRequestLimits,
+    senders: Mutex<HashMap<Uuid, SenderHistory>>,
+}
+
+impl FriendRequestLimiter {
+    pub fn new(limits: FriendRequestLimits) -> Self {
+        Self {
+            limits,
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a new request from `sender` to `target` and count it if allowed.
+    /// Refusals come back as `ApiError::RateLimited`.
+    pub fn check(&self, sender: Uuid, target: Uuid) -> Result<(), ApiError> {
+        let limits = &self.limits;
+        let now = Instant::now();
+        let mut senders = self.senders.lock().unwrap();
+        if senders.len() > SWEEP_THRESHOLD {
+            senders.retain(|_, h| h.sent.back().is_some_and(|t| now.duration_since(*t) < DAY));
+        }
+        let history = senders.entry(sender).or_default();
+
+        while history
+            .sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= DAY)
+        {
+            history.sent.pop_front();
+        }
+        history
+            .targets
+            .retain(|_, t| now.duration_since(*t) < limits.repeat_after);
+
+        if let Some(last) = history.targets.get(&target) {
+            return Err(ApiError::rate_limited(
+                limits.repeat_after - now.duration_since(*last),
+            ));
+        }
+        if limits.per_day > 0 && history.sent.len() >= limits.per_day {
+            return Err(ApiError::rate_limited(
+                DAY - now.duration_since(history.sent[0]),
+            ));
+        }
+        let in_last_hour: Vec<&Instant> = history
+            .sent
+            .iter()
+            .filter(|t| now.duration_since(**t) < HOUR)
+            .collect();
+        if limits.per_hour > 0 && in_last_hour.len() >= limits.per_hour {
+            return Err(ApiError::rate_limited(
+                HOUR - now.duration_since(*in_last_hour[0]),
+            ));
+        }
+
+        history.sent.push_back(now);
+        history.targets.insert(target, now);
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tower_cookies::Cookies;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::models::{ActivityKind, ActivityRow, FeedEntry, FeedQuery, ProfileRow};
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /feed?limit=30&before={activity_id}  –  what friends have been up to
+// ---------------------------------------------------------------------------
+
+/// Newest first. Friends who have turned off `share_activity` are left out,
+/// including anything they did before opting out.
+pub async fn feed_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let limit = query.limit.unwrap_or(30).clamp(1, 100);
+
+    let friend_ids: Vec<Uuid> = state
+        .store
+        .accepted_friends_of(&[me])
+        .await?
+        .remove(&me)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let profiles = state.store.profiles(&friend_ids).await?;
+    let sharing: Vec<Uuid> = profiles
+        .values()
+        .filter(|p| shares_activity(p))
+        .map(|p| p.id)
+        .collect();
+    if sharing.is_empty() {
+        return Ok(Json(json!({ "entries": [], "next_before": null })));
+    }
+
+    let rows = state
+        .store
+        .activity_page(&sharing, query.before, limit)
+        .await?;
+
+    let next_before = if rows.len() == limit {
+        rows.last().and_then(|r| r.id)
+    } else {
+        None
+    };
+    let entries: Vec<FeedEntry> = rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(FeedEntry {
+                id: row.id?,
+                user: profiles.get(&row.user_id)?.clone().into(),
+                kind: row.kind,
+                data: row.data,
+                created_at: row.created_at,
+            })
+        })
+        .collect();
+
+    Ok(Json(
+        json!({ "entries": entries, "next_before": next_before }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Record something `user` did for their friends' feeds, unless they have
+/// opted out. Best effort: a failure is logged and never fails the action
+/// that triggered it.
+pub async fn record_activity(
+    state: &AppState,
+    user: &ProfileRow,
+    kind: ActivityKind,
+    data: serde_json::Value,
+) {
+    if !shares_activity(user) || user.is_bot() {
+        return;
+    }
+    let row = ActivityRow {
+        id: None,
+        user_id: user.id,
+        kind,
+        data,
+        created_at: None,
+    };
+    if let Err(e) = state.store.insert_activity(&row).await {
+        error!(
+            "[activity] Failed to record {:?} for {}: {}",
+            kind, user.id, e
+        );
+    }
+}
+
+fn shares_activity(profile: &ProfileRow) -> bool {
+    profile.share_activity.unwrap_or(true)
+}
@@ -0,0 +1,1176 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    async_trait,
+    extract::{
+        ws::{Message, WebSocket},
+        FromRequestParts, Path, Query, State, WebSocketUpgrade,
+    },
+    http::request::Parts,
+    response::IntoResponse,
+    Json,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::fmt::Display;
+use std::time::Instant;
+use tokio::sync::broadcast;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::bans::{self, AccessChange};
+use crate::db::Order;
+use crate::error::ApiError;
+use crate::handlers::auth::{ensure_username_available, erase_account, get_session, hash_password};
+use crate::handlers::chat::{error_frame, publish, release_channel, subscribe};
+use crate::handlers::edits::fetch_live_message;
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{
+    AdminStatsQuery, AdminUserQuery, AdminUserView, AuditLogQuery, AuditLogRow, BanUserRequest,
+    CreateBotRequest, CreateBotResponse, CreateSnapshotRequest, MessageDeleted,
+    PasswordResetResponse, ProfileRow, SetAnnouncementRequest, SnapshotRow, SuspendUserRequest,
+    WsEvent, BOT_CAPABILITIES,
+};
+use crate::sessions;
+use crate::storage;
+use crate::store::{AuditLogFilter, MessageFilter};
+use crate::wire::WireFormat;
+use crate::AppState;
+
+const MAX_BAN_REASON_CHARS: usize = 500;
+/// A year; anything longer should be a ban.
+const MAX_SUSPENSION_SECS: u64 = 365 * 24 * 60 * 60;
+
+// ---------------------------------------------------------------------------
+// POST /admin/bots  –  create a bot account
+// ---------------------------------------------------------------------------
+
+pub async fn create_bot_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateBotRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let username = state.config.username.validate(&body.username)?;
+
+    if let Some(unknown) = body
+        .capabilities
+        .iter()
+        .find(|c| !BOT_CAPABILITIES.contains(&c.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown bot capability '{}'. Allowed: {}",
+            unknown,
+            BOT_CAPABILITIES.join(", ")
+        )));
+    }
+
+    ensure_username_available(&state, &username).await?;
+
+    // Bots authenticate through the normal /login flow, with a random
+    // token standing in for the password.
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let password_hash = hash_password(&token)?;
+
+    let bot_id = Uuid::new_v4();
+    let display_name = body.display_name.unwrap_or_else(|| username.clone());
+
+    state
+        .store
+        .insert_profile(json!({
+            "id": bot_id.to_string(),
+            "username": username,
+            "password_hash": password_hash,
+            "display_name": display_name,
+            "is_bot": true,
+            "bot_capabilities": body.capabilities,
+        }))
+        .await?;
+
+    info!(
+        "[create_bot] admin={} created bot id={} username={}",
+        admin.id, bot_id, username
+    );
+
+    Ok(Json(CreateBotResponse {
+        user_id: bot_id,
+        username,
+        token,
+        capabilities: body.capabilities,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/conversations/{id}/snapshots  –  freeze history for review
+// ---------------------------------------------------------------------------
+
+pub async fn create_snapshot_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<CreateSnapshotRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    const DEFAULT_LIMIT: usize = 500;
+    const MAX_LIMIT: usize = 5000;
+
+    let admin = require_admin(&state, &cookies).await?;
+    let limit = body.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    // Newest first so the limit keeps the most recent messages, then flip.
+    // Raw rows are kept as-is so every column survives as evidence.
+    let mut messages = state
+        .store
+        .raw_messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                ..Default::default()
+            },
+            Order::Desc,
+            Some(limit),
+        )
+        .await?;
+    messages.reverse();
+
+    let members = state.store.members(conversation_id).await?;
+
+    if messages.is_empty() && members.is_empty() {
+        return Err(ApiError::NotFound("Conversation not found".into()));
+    }
+
+    // The captured messages are the newest ones, so everything from the
+    // oldest captured id on covers exactly their edits.
+    let revisions = match messages.first().and_then(|m| m.get("id")?.as_i64()) {
+        Some(oldest) => {
+            state
+                .store
+                .conversation_revisions(conversation_id, oldest)
+                .await?
+        }
+        None => Vec::new(),
+    };
+
+    let member_ids: Vec<Uuid> = members.iter().map(|m| m.user_id).collect();
+    let reports = state
+        .store
+        .conversation_reports(conversation_id, &member_ids)
+        .await?;
+
+    let snapshot = SnapshotRow {
+        id: Uuid::new_v4(),
+        conversation_id,
+        created_by: admin.id,
+        note: body.note,
+        messages: json!(messages),
+        members: json!(members),
+        revisions: json!(revisions),
+        reports: json!(reports),
+        created_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+
+    state.store.insert_snapshot(&snapshot).await?;
+
+    info!(
+        "[snapshot] admin={} captured {} messages from conversation {} as snapshot {}",
+        admin.id,
+        messages.len(),
+        conversation_id,
+        snapshot.id
+    );
+
+    Ok(Json(snapshot))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/conversations/{id}/snapshots
+// ---------------------------------------------------------------------------
+
+pub async fn list_snapshots_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&state, &cookies).await?;
+
+    // Only metadata here; fetch a single snapshot for its contents.
+    let rows = state.store.snapshots(conversation_id).await?;
+
+    Ok(Json(json!({ "snapshots": rows })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/snapshots/{id}
+// ---------------------------------------------------------------------------
+
+pub async fn get_snapshot_handler(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&state, &cookies).await?;
+
+    Ok(Json(state.store.snapshot(id).await?))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/erasures  –  account erasure reports
+// ---------------------------------------------------------------------------
+
+pub async fn list_erasures_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&state, &cookies).await?;
+
+    let erasures = state.store.erasure_requests(200).await?;
+
+    Ok(Json(json!({ "erasures": erasures })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/erasures/{id}
+// ---------------------------------------------------------------------------
+
+pub async fn get_erasure_handler(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&state, &cookies).await?;
+
+    Ok(Json(state.store.erasure_request(id).await?))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /admin/conversations/{id}/announcement  –  make a conversation read-only
+// ---------------------------------------------------------------------------
+
+/// While set, only admins can post and their messages go out as
+/// `announcement` events.
+pub async fn set_announcement_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<SetAnnouncementRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    state
+        .store
+        .update_conversation(
+            conversation_id,
+            json!({ "is_announcement": body.is_announcement }),
+        )
+        .await?;
+
+    info!(
+        "[announcement] admin={} set is_announcement={} on {}",
+        admin.id, body.is_announcement, conversation_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "conversation.announcement",
+        conversation_id,
+        json!({ "is_announcement": body.is_announcement }),
+    )
+    .await;
+
+    Ok(Json(json!({
+        "conversation_id": conversation_id,
+        "is_announcement": body.is_announcement,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/users/{id}/verify  –  give a user the verified badge
+// DELETE /admin/users/{id}/verify  –  take it away
+// ---------------------------------------------------------------------------
+
+pub async fn verify_user_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    set_verified(&state, &cookies, user_id, true).await
+}
+
+pub async fn unverify_user_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    set_verified(&state, &cookies, user_id, false).await
+}
+
+async fn set_verified(
+    state: &AppState,
+    cookies: &Cookies,
+    user_id: Uuid,
+    verified: bool,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let admin = require_admin(state, cookies).await?;
+
+    state
+        .store
+        .update_profile(user_id, json!({ "is_verified": verified }))
+        .await?;
+
+    info!(
+        "[admin] admin={} set is_verified={} on {}",
+        admin.id, verified, user_id
+    );
+    audit(
+        state,
+        admin.id,
+        if verified {
+            "user.verify"
+        } else {
+            "user.unverify"
+        },
+        user_id,
+        json!({ "is_verified": verified }),
+    )
+    .await;
+
+    Ok(Json(json!({ "user_id": user_id, "is_verified": verified })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/users?q=gi&banned=true&limit=50&offset=0  –  find accounts
+// ---------------------------------------------------------------------------
+
+/// Unlike `/users/search` nothing is hidden: deactivated, deleted and
+/// banned accounts are all listed.
+pub async fn list_users_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<AdminUserQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0);
+
+    let prefix = query
+        .q
+        .as_deref()
+        .map(|q| state.config.username.normalize(q))
+        .filter(|q| !q.is_empty());
+
+    let users: Vec<AdminUserView> = state
+        .store
+        .profiles_page(prefix.as_deref(), query.banned, limit, offset)
+        .await?
+        .into_iter()
+        .map(AdminUserView::from)
+        .collect();
+
+    Ok(Json(json!({
+        "next_offset": if users.len() == limit { Some(offset + limit) } else { None },
+        "users": users,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/users/{id}  –  one account with its reports and audit trail
+// ---------------------------------------------------------------------------
+
+pub async fn get_user_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let profile = state.store.profile(user_id).await?;
+
+    let reports = state.store.reports_about(user_id, 20).await?;
+
+    let about_user = AuditLogFilter {
+        target_id: Some(user_id.to_string()),
+        ..AuditLogFilter::default()
+    };
+    let audit_log = state.store.audit_log(&about_user, 50, 0).await?;
+
+    Ok(Json(json!({
+        "user": AdminUserView::from(profile),
+        "reports": reports,
+        "audit_log": audit_log,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/users/{id}/ban  –  lock an account out
+// DELETE /admin/users/{id}/ban  –  let it back in
+// ---------------------------------------------------------------------------
+
+/// Takes effect on every instance at once: the user's next request is
+/// refused even with a valid session cookie. Open sockets stay up until
+/// they close.
+pub async fn ban_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+    Json(body): Json<BanUserRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(
+        ban_user(&state, admin.id, user_id, body.reason).await?,
+    ))
+}
+
+/// Ban `user_id` as `admin_id` and audit it.
+pub async fn ban_user(
+    state: &AppState,
+    admin_id: Uuid,
+    user_id: Uuid,
+    reason: Option<String>,
+) -> Result<AdminUserView, ApiError> {
+    if user_id == admin_id {
+        return Err(ApiError::BadRequest("You cannot ban yourself".into()));
+    }
+    let reason = reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_BAN_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_BAN_REASON_CHARS
+        )));
+    }
+
+    let user = update_user(
+        state,
+        user_id,
+        json!({
+            "banned_at": chrono::Utc::now().to_rfc3339(),
+            "ban_reason": reason,
+        }),
+    )
+    .await?;
+    bans::apply(state, AccessChange::Ban { user_id }).await;
+
+    info!("[admin] admin={} banned {}", admin_id, user_id);
+    audit(
+        state,
+        admin_id,
+        "user.ban",
+        user_id,
+        json!({ "reason": reason }),
+    )
+    .await;
+
+    Ok(user)
+}
+
+pub async fn unban_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = update_user(
+        &state,
+        user_id,
+        json!({ "banned_at": null, "ban_reason": null }),
+    )
+    .await?;
+    bans::apply(&state, AccessChange::Unban { user_id }).await;
+
+    info!("[admin] admin={} unbanned {}", admin.id, user_id);
+    audit(&state, admin.id, "user.unban", user_id, json!({})).await;
+
+    Ok(Json(user))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/users/{id}/suspend  –  lock an account out for a while
+// DELETE /admin/users/{id}/suspend  –  end the suspension early
+// ---------------------------------------------------------------------------
+
+/// Like a ban, but it lifts on its own after `duration_secs`. Until then
+/// login and every session request are refused with the time remaining.
+/// Suspending again replaces the earlier end time.
+pub async fn suspend_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+    Json(body): Json<SuspendUserRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if user_id == admin.id {
+        return Err(ApiError::BadRequest("You cannot suspend yourself".into()));
+    }
+    if body.duration_secs == 0 || body.duration_secs > MAX_SUSPENSION_SECS {
+        return Err(ApiError::BadRequest(format!(
+            "duration_secs must be 1-{}",
+            MAX_SUSPENSION_SECS
+        )));
+    }
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_BAN_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_BAN_REASON_CHARS
+        )));
+    }
+
+    let until = chrono::Utc::now() + chrono::Duration::seconds(body.duration_secs as i64);
+    let user = update_user(
+        &state,
+        user_id,
+        json!({
+            "suspended_until": to_timestamp(until),
+            "suspension_reason": reason,
+        }),
+    )
+    .await?;
+    bans::apply(&state, AccessChange::Suspend { user_id, until }).await;
+
+    info!(
+        "[admin] admin={} suspended {} until {}",
+        admin.id, user_id, until
+    );
+    audit(
+        &state,
+        admin.id,
+        "user.suspend",
+        user_id,
+        json!({ "until": to_timestamp(until), "reason": reason }),
+    )
+    .await;
+
+    Ok(Json(user))
+}
+
+pub async fn unsuspend_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = update_user(
+        &state,
+        user_id,
+        json!({ "suspended_until": null, "suspension_reason": null }),
+    )
+    .await?;
+    bans::apply(&state, AccessChange::LiftSuspension { user_id }).await;
+
+    info!(
+        "[admin] admin={} lifted suspension of {}",
+        admin.id, user_id
+    );
+    audit(&state, admin.id, "user.unsuspend", user_id, json!({})).await;
+
+    Ok(Json(user))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/users/{id}/shadow-ban  –  hide everything a user posts
+// DELETE /admin/users/{id}/shadow-ban  –  stop hiding it
+// ---------------------------------------------------------------------------
+
+/// The user carries on as normal and sees their own messages delivered,
+/// but nobody else is shown them, live or in history. Lifting it doesn't
+/// reveal what was sent in the meantime.
+pub async fn shadow_ban_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    if user_id == admin.id {
+        return Err(ApiError::BadRequest("You cannot ban yourself".into()));
+    }
+
+    let user = update_user(
+        &state,
+        user_id,
+        json!({ "shadow_banned_at": chrono::Utc::now().to_rfc3339() }),
+    )
+    .await?;
+    bans::apply(&state, AccessChange::ShadowBan { user_id }).await;
+
+    info!("[admin] admin={} shadow banned {}", admin.id, user_id);
+    audit(&state, admin.id, "user.shadow_ban", user_id, json!({})).await;
+
+    Ok(Json(user))
+}
+
+pub async fn lift_shadow_ban_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = update_user(&state, user_id, json!({ "shadow_banned_at": null })).await?;
+    bans::apply(&state, AccessChange::LiftShadowBan { user_id }).await;
+
+    info!(
+        "[admin] admin={} lifted shadow ban on {}",
+        admin.id, user_id
+    );
+    audit(&state, admin.id, "user.shadow_unban", user_id, json!({})).await;
+
+    Ok(Json(user))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/users/{id}/password-reset  –  replace a user's password
+// ---------------------------------------------------------------------------
+
+/// The old password and every existing session stop working at once, so
+/// whoever took over the account is logged out. The user logs in with the
+/// temporary one and is told to pick their own through `POST /me/password`.
+pub async fn reset_password_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    if state.store.profile(user_id).await?.deleted_at.is_some() {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+
+    let temporary_password = temporary_password();
+    update_user(
+        &state,
+        user_id,
+        json!({
+            "password_hash": hash_password(&temporary_password)?,
+            "must_change_password": true,
+        }),
+    )
+    .await?;
+    sessions::revoke(&state, user_id).await?;
+
+    info!(
+        "[admin] admin={} reset the password of {}",
+        admin.id, user_id
+    );
+    audit(&state, admin.id, "user.password_reset", user_id, json!({})).await;
+
+    Ok(Json(PasswordResetResponse {
+        user_id,
+        temporary_password,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/users/{id}  –  delete an account
+// ---------------------------------------------------------------------------
+
+/// Same erasure as `DELETE /me`, without the owner's password.
+pub async fn delete_user_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    if user_id == admin.id {
+        return Err(ApiError::BadRequest(
+            "Use DELETE /me to delete your own account".into(),
+        ));
+    }
+    if state.store.profile(user_id).await?.deleted_at.is_some() {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+
+    let erasure_id = erase_account(&state, user_id).await?;
+
+    info!(
+        "[admin] admin={} deleted {}, erasure {}",
+        admin.id, user_id, erasure_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "user.delete",
+        user_id,
+        json!({ "erasure_id": erasure_id }),
+    )
+    .await;
+
+    Ok(Json(
+        json!({ "status": "deleted", "erasure_id": erasure_id }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/messages/{id}  –  remove any message
+// ---------------------------------------------------------------------------
+
+/// Tombstones the message the way conversation deletion does, in any
+/// conversation and whoever sent it. The removed content is kept in the
+/// audit entry so the decision can be reviewed later.
+pub async fn delete_message_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(message_id): Path<i64>,
+) -> Result<impl IntoResponse, ApiError> {
+    remove_message(&state, admin.id, message_id).await?;
+    Ok(Json(json!({ "deleted": message_id })))
+}
+
+/// Tombstone a message as `admin_id`, audit it and tell the conversation.
+pub async fn remove_message(
+    state: &AppState,
+    admin_id: Uuid,
+    message_id: i64,
+) -> Result<(), ApiError> {
+    let message = fetch_live_message(state, message_id).await?;
+
+    state
+        .store
+        .tombstone_messages(&MessageFilter {
+            ids: Some(vec![message_id]),
+            ..Default::default()
+        })
+        .await?;
+
+    if let Some(attachment) = &message.attachment {
+        let paths: Vec<String> = std::iter::once(attachment.path.clone())
+            .chain(attachment.thumbnail.as_ref().map(|t| t.path.clone()))
+            .collect();
+        if let Err(e) = storage::remove(&state.config.attachments.bucket, &paths).await {
+            error!(
+                "[admin] Failed to delete attachment of message {}: {}",
+                message_id, e
+            );
+        }
+    }
+
+    info!(
+        "[admin] admin={} deleted message {} in {}",
+        admin_id, message_id, message.conversation_id
+    );
+    audit(
+        state,
+        admin_id,
+        "message.delete",
+        message_id,
+        json!({
+            "conversation_id": message.conversation_id,
+            "sender_id": message.sender_id,
+            "content": message.content,
+            "attachment": message.attachment.as_ref().map(|a| &a.path),
+        }),
+    )
+    .await;
+
+    publish(
+        state,
+        message.conversation_id,
+        WsEvent::MessageDeleted(MessageDeleted { message_id }),
+    )
+    .await;
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/conversations/{id}/watch  –  read-only audit stream (WebSocket)
+// ---------------------------------------------------------------------------
+
+/// Attach to a conversation's live events without joining it, for abuse
+/// investigations. No membership row is created, members aren't notified,
+/// and the start and end of every session go to the audit log.
+pub async fn watch_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    state.store.conversation(conversation_id).await?;
+
+    Ok(ws.on_upgrade(move |socket| handle_watch_socket(socket, state, conversation_id, admin.id)))
+}
+
+/// Forward every event, including ephemerals and bot interactions meant
+/// for a single member, and refuse anything the admin tries to send.
+async fn handle_watch_socket(
+    socket: WebSocket,
+    state: AppState,
+    conversation_id: Uuid,
+    admin_id: Uuid,
+) {
+    let started = Instant::now();
+    audit(
+        &state,
+        admin_id,
+        "conversation.watch_start",
+        conversation_id,
+        json!({}),
+    )
+    .await;
+    info!(
+        "[watch] admin={} watching conversation {}",
+        admin_id, conversation_id
+    );
+
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let mut rx = subscribe(&state.channels, conversation_id).await;
+
+    let hello = json!({
+        "type": "watching",
+        "conversation_id": conversation_id,
+        "read_only": true,
+    });
+    if ws_sender
+        .send(Message::Text(hello.to_string()))
+        .await
+        .is_ok()
+    {
+        loop {
+            tokio::select! {
+                received = rx.recv() => {
+                    let event = match received {
+                        Ok(e) => e,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    let json_text = match serde_json::to_string(&event) {
+                        Ok(s) => s,
+                        Err(_) => continue,
+                    };
+                    if ws_sender.send(Message::Text(json_text)).await.is_err() {
+                        break;
+                    }
+                }
+                incoming = ws_receiver.next() => match incoming {
+                    Some(Ok(Message::Text(_))) | Some(Ok(Message::Binary(_))) => {
+                        // Admin tooling always speaks JSON.
+                        let frame =
+                            error_frame(WireFormat::Json, "read_only", "This is a watch-only connection");
+                        if let Some(frame) = frame {
+                            if ws_sender.send(frame).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {} // Pings are auto-answered.
+                },
+            }
+        }
+    }
+
+    drop(rx);
+    release_channel(&state.channels, conversation_id).await;
+
+    audit(
+        &state,
+        admin_id,
+        "conversation.watch_end",
+        conversation_id,
+        json!({ "duration_secs": started.elapsed().as_secs() }),
+    )
+    .await;
+    info!(
+        "[watch] admin={} stopped watching conversation {}",
+        admin_id, conversation_id
+    );
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/spam/metrics  –  how spam scoring is doing
+// ---------------------------------------------------------------------------
+
+/// Counts and the score distribution since this instance started; other
+/// instances keep their own.
+pub async fn spam_metrics_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(state.spam_scores.metrics()))
+}
+
+// ---------------------------------------------------------------------------
+// GET /ws/admin  –  live moderation feed (WebSocket)
+// ---------------------------------------------------------------------------
+
+/// New reports, flagged and held messages, and rate-limit hits from every
+/// instance as they happen. Events sent while nobody is connected are not
+/// replayed; the report and moderation queues still have them.
+pub async fn admin_events_ws_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+    Ok(ws.on_upgrade(move |socket| handle_admin_events_socket(socket, state, admin.id)))
+}
+
+async fn handle_admin_events_socket(socket: WebSocket, state: AppState, admin_id: Uuid) {
+    let started = Instant::now();
+    audit(&state, admin_id, "admin_events.start", admin_id, json!({})).await;
+    info!("[admin_events] admin={} connected", admin_id);
+
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let mut rx = state.admin_events.subscribe();
+
+    let hello = json!({ "type": "connected", "read_only": true });
+    if ws_sender
+        .send(Message::Text(hello.to_string()))
+        .await
+        .is_ok()
+    {
+        loop {
+            tokio::select! {
+                received = rx.recv() => {
+                    let event = match received {
+                        Ok(e) => e,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            let notice = json!({ "type": "lagged", "skipped": skipped });
+                            if ws_sender.send(Message::Text(notice.to_string())).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    let json_text = match serde_json::to_string(&event) {
+                        Ok(s) => s,
+                        Err(_) => continue,
+                    };
+                    if ws_sender.send(Message::Text(json_text)).await.is_err() {
+                        break;
+                    }
+                }
+                incoming = ws_receiver.next() => match incoming {
+                    Some(Ok(Message::Text(_))) | Some(Ok(Message::Binary(_))) => {
+                        let frame =
+                            error_frame(WireFormat::Json, "read_only", "This is a read-only feed");
+                        if let Some(frame) = frame {
+                            if ws_sender.send(frame).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {} // Pings are auto-answered.
+                },
+            }
+        }
+    }
+
+    audit(
+        &state,
+        admin_id,
+        "admin_events.end",
+        admin_id,
+        json!({ "duration_secs": started.elapsed().as_secs() }),
+    )
+    .await;
+    info!("[admin_events] admin={} disconnected", admin_id);
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/stats?days=14  –  dashboard numbers
+// ---------------------------------------------------------------------------
+
+/// Totals and per-day series come from the database; `connections` counts
+/// the sockets and event streams open on this instance only.
+///
+/// ```sql
+/// create function admin_daily_stats(days int)
+/// returns table(day date, messages bigint, active_users bigint,
+///               new_users bigint, new_conversations bigint)
+/// language sql stable as $$
+///   select d::date,
+///          (select count(*) from messages m
+///           where m.created_at >= d and m.created_at < d + interval '1 day'),
+///          (select count(distinct m.sender_id) from messages m
+///           where m.created_at >= d and m.created_at < d + interval '1 day'
+///             and not coalesce(m.is_bot, false)),
+///          (select count(*) from profiles p
+///           where p.created_at >= d and p.created_at < d + interval '1 day'),
+///          (select count(*) from conversations c
+///           where c.created_at >= d and c.created_at < d + interval '1 day')
+///   from generate_series(
+///     date_trunc('day', now() at time zone 'utc') - (days - 1) * interval '1 day',
+///     date_trunc('day', now() at time zone 'utc'),
+///     interval '1 day') d
+///   order by 1;
+/// $$;
+/// create function admin_user_totals()
+/// returns table(registered bigint, bots bigint, banned bigint, seen_last_day bigint)
+/// language sql stable as $$
+///   select count(*) filter (where deleted_at is null),
+///          count(*) filter (where deleted_at is null and is_bot),
+///          count(*) filter (where banned_at is not null),
+///          count(*) filter (where last_seen_at > now() - interval '1 day')
+///   from profiles;
+/// $$;
+/// ```
+pub async fn stats_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<AdminStatsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let days = query.days.unwrap_or(14).clamp(1, 90);
+
+    let daily = state.store.daily_stats(days).await?;
+    let users = state.store.user_totals().await?;
+
+    let conversation_streams: usize = state
+        .channels
+        .read()
+        .await
+        .values()
+        .map(|tx| tx.receiver_count())
+        .sum();
+    let (connected_users, notification_sockets) = {
+        let channels = state.user_channels.read().await;
+        (
+            channels.len(),
+            channels
+                .values()
+                .map(|tx| tx.receiver_count())
+                .sum::<usize>(),
+        )
+    };
+
+    Ok(Json(json!({
+        "users": users,
+        "daily": daily,
+        "connections": {
+            "conversation_streams": conversation_streams,
+            "notification_sockets": notification_sockets,
+            "connected_users": connected_users,
+        },
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/audit?actor=&action=user.*&from=&to=&limit=50&offset=0
+//   –  search the audit log
+// ---------------------------------------------------------------------------
+
+/// Newest first. Every filter is optional and they combine.
+pub async fn list_audit_log_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0);
+
+    let mut filter = AuditLogFilter {
+        admin_id: query.actor,
+        target_id: query.target.as_deref().map(|t| t.trim().to_string()),
+        ..AuditLogFilter::default()
+    };
+    if let Some(action) = query.action.as_deref().map(str::trim) {
+        match action.strip_suffix('*') {
+            Some(prefix) => filter.action_prefix = Some(prefix.to_string()),
+            None => filter.action = Some(action.to_string()),
+        }
+    }
+    if let Some(from) = query.from.as_deref() {
+        filter.created_from = Some(parse_time("from", from)?);
+    }
+    if let Some(to) = query.to.as_deref() {
+        filter.created_before = Some(parse_time("to", to)?);
+    }
+
+    let entries = state.store.audit_log(&filter, limit, offset).await?;
+
+    Ok(Json(json!({
+        "next_offset": if entries.len() == limit { Some(offset + limit) } else { None },
+        "entries": entries,
+    })))
+}
+
+/// Parse an RFC 3339 query parameter, normalized to UTC.
+pub fn parse_time(name: &str, raw: &str) -> Result<String, ApiError> {
+    raw.trim()
+        .parse::<chrono::DateTime<chrono::Utc>>()
+        .map(to_timestamp)
+        .map_err(|_| ApiError::BadRequest(format!("{} must be an RFC 3339 timestamp", name)))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Temporary passwords draw from 64 symbols, so `next_u32() % len` is
+/// unbiased.
+const TEMP_PASSWORD_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const TEMP_PASSWORD_LEN: usize = 20;
+
+fn temporary_password() -> String {
+    (0..TEMP_PASSWORD_LEN)
+        .map(|_| {
+            let i = OsRng.next_u32() as usize % TEMP_PASSWORD_ALPHABET.len();
+            TEMP_PASSWORD_ALPHABET[i] as char
+        })
+        .collect()
+}
+
+/// Apply `changes` to a profile and return it as admins see it.
+pub async fn update_user(
+    state: &AppState,
+    user_id: Uuid,
+    changes: serde_json::Value,
+) -> Result<AdminUserView, ApiError> {
+    Ok(AdminUserView::from(
+        state.store.update_profile(user_id, changes).await?,
+    ))
+}
+
+/// The signed-in admin. Taking this as a handler argument does the same
+/// check as `require_admin` before the handler runs.
+pub struct Admin(pub ProfileRow);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let cookies = Cookies::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, msg)| ApiError::Internal(msg.into()))?;
+        require_admin(state, &cookies).await.map(Admin)
+    }
+}
+
+/// Append an entry to the admin audit log. Best-effort: a failed write is
+/// logged but never blocks the action being audited.
+pub async fn audit(
+    state: &AppState,
+    admin_id: Uuid,
+    action: &str,
+    target_id: impl Display,
+    details: serde_json::Value,
+) {
+    let entry = AuditLogRow {
+        id: Uuid::new_v4(),
+        admin_id,
+        action: action.to_string(),
+        target_id: target_id.to_string(),
+        details,
+        created_at: None,
+    };
+
+    if let Err(e) = state.store.record_audit(&entry).await {
+        error!(
+            "[audit] Failed to record {} by admin {}: {}",
+            action, admin_id, e
+        );
+    }
+}
+
+/// Resolve the session user and make sure they are an admin.
+pub async fn require_admin(state: &AppState, cookies: &Cookies) -> Result<ProfileRow, ApiError> {
+    let user_id = get_session(state, cookies)?;
+    let profile = state.store.profile(user_id).await?;
+
+    if !profile.is_admin() {
+        return Err(ApiError::Forbidden("Admin access required".into()));
+    }
+
+    Ok(profile)
+}
@@ -0,0 +1,119 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, Admin};
+use crate::handlers::chat::{deliver_message, validate_content};
+use crate::models::{CreateAnnouncementRequest, SystemAnnouncement, UserEvent, WsIncoming};
+use crate::AppState;
+
+const MAX_TITLE_CHARS: usize = 120;
+
+// ---------------------------------------------------------------------------
+// POST /admin/announcements  –  tell everyone connected
+// ---------------------------------------------------------------------------
+
+/// Pushed as a `system_announcement` event to every open notification
+/// socket on every instance. Nobody who is offline gets it unless it is
+/// also posted in an announcement conversation with `conversation_id`.
+pub async fn create_announcement_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Json(body): Json<CreateAnnouncementRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let content = body.content.trim().to_string();
+    if content.is_empty() {
+        return Err(ApiError::BadRequest("Announcement cannot be empty".into()));
+    }
+    validate_content(&state.config.messages, &content)?;
+    let title = body
+        .title
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+    if title
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > MAX_TITLE_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Title must be at most {} characters",
+            MAX_TITLE_CHARS
+        )));
+    }
+
+    let message_id = match body.conversation_id {
+        Some(conversation_id) => {
+            if !state
+                .store
+                .conversation(conversation_id)
+                .await?
+                .is_announcement
+                .unwrap_or(false)
+            {
+                return Err(ApiError::BadRequest(
+                    "Announcements can only be posted in announcement conversations".into(),
+                ));
+            }
+            let text = match &title {
+                Some(title) => format!("**{}**\n\n{}", title, content),
+                None => content.clone(),
+            };
+            deliver_message(
+                &state,
+                conversation_id,
+                admin.id,
+                false,
+                WsIncoming {
+                    content: text,
+                    components: None,
+                    attachment: None,
+                    poll: None,
+                    sticker_id: None,
+                    client_id: None,
+                    approved: true,
+                },
+            )
+            .await?
+            .id
+        }
+        None => None,
+    };
+
+    let announcement = SystemAnnouncement {
+        id: Uuid::new_v4(),
+        kind: body.kind,
+        title,
+        content,
+        conversation_id: body.conversation_id,
+        message_id,
+        sent_at: chrono::Utc::now().to_rfc3339(),
+    };
+    state
+        .bus
+        .publish_everyone(
+            &state.user_channels,
+            UserEvent::SystemAnnouncement(announcement.clone()),
+        )
+        .await;
+
+    info!(
+        "[announcements] admin={} sent announcement {}",
+        admin.id, announcement.id
+    );
+    audit(
+        &state,
+        admin.id,
+        "announcement.send",
+        announcement.id,
+        json!({
+            "kind": announcement.kind,
+            "title": announcement.title,
+            "content": announcement.content,
+            "conversation_id": announcement.conversation_id,
+        }),
+    )
+    .await;
+
+    Ok(Json(announcement))
+}
@@ -0,0 +1,288 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::bans::{self, AccessChange};
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, update_user, Admin};
+use crate::handlers::auth::get_restricted_session;
+use crate::handlers::notifications::notify_user;
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{
+    AppealKind, AppealQueueQuery, AppealResolved, AppealRow, CreateAppealRequest,
+    ResolveAppealRequest, UserEvent, APPEAL_STATUSES,
+};
+use crate::AppState;
+
+const MAX_MESSAGE_CHARS: usize = 2000;
+const MAX_RESPONSE_CHARS: usize = 1000;
+
+// ---------------------------------------------------------------------------
+// POST /appeals  –  ask for a ban or suspension to be lifted
+// GET /appeals  –  the caller's appeals and how they were decided
+// ---------------------------------------------------------------------------
+
+/// Works with the session of a banned or suspended account, which every
+/// other route refuses. Each ban or suspension can be appealed once.
+pub async fn create_appeal_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateAppealRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_restricted_session(&state, &cookies)?;
+
+    let message = body.message.trim().to_string();
+    if message.is_empty() {
+        return Err(ApiError::BadRequest("Appeal cannot be empty".into()));
+    }
+    if message.chars().count() > MAX_MESSAGE_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Appeal must be at most {} characters",
+            MAX_MESSAGE_CHARS
+        )));
+    }
+
+    let profile = state.store.profile(me).await?;
+    let (kind, restriction_at) = match (&profile.banned_at, profile.suspended_until()) {
+        (Some(banned_at), _) => (AppealKind::Ban, banned_at.clone()),
+        (None, Some(until)) => (AppealKind::Suspension, to_timestamp(until)),
+        (None, None) => {
+            return Err(ApiError::BadRequest(
+                "Your account is not banned or suspended".into(),
+            ))
+        }
+    };
+
+    // Compared as times: Postgres doesn't hand back the text we stored.
+    let restriction = parse(&restriction_at);
+    let already_appealed = state
+        .store
+        .appeals_of(me)
+        .await?
+        .iter()
+        .any(|a| a.kind == kind && parse(&a.restriction_at) == restriction);
+    if already_appealed {
+        return Err(ApiError::BadRequest(format!(
+            "You have already appealed this {}",
+            match kind {
+                AppealKind::Ban => "ban",
+                AppealKind::Suspension => "suspension",
+            }
+        )));
+    }
+
+    let appeal = AppealRow {
+        id: Uuid::new_v4(),
+        user_id: me,
+        kind,
+        restriction_at,
+        message,
+        status: "open".into(),
+        created_at: None,
+        reviewed_by: None,
+        reviewed_at: None,
+        response: None,
+    };
+    state.store.insert_appeal(&appeal).await?;
+
+    info!(
+        "[appeals] {} appealed their {:?}, appeal {}",
+        me, kind, appeal.id
+    );
+
+    Ok(Json(json!({
+        "appeal_id": appeal.id,
+        "status": "received",
+        "message": "Your appeal has been received. A moderator will review it.",
+    })))
+}
+
+/// Newest first. Who reviewed an appeal is not shared.
+pub async fn my_appeals_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_restricted_session(&state, &cookies)?;
+
+    let appeals: Vec<serde_json::Value> = state
+        .store
+        .appeals_of(me)
+        .await?
+        .into_iter()
+        .map(|a| {
+            json!({
+                "id": a.id,
+                "kind": a.kind,
+                "message": a.message,
+                "status": a.status,
+                "created_at": a.created_at,
+                "reviewed_at": a.reviewed_at,
+                "response": a.response,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "appeals": appeals })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/appeals?status=open&limit=50&offset=0  –  the review queue
+// ---------------------------------------------------------------------------
+
+/// Oldest first, so appeals are worked through in the order they came in.
+pub async fn list_appeals_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<AppealQueueQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let status = query.status.as_deref().unwrap_or("open");
+    if !APPEAL_STATUSES.contains(&status) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown status '{}'. Allowed: {}",
+            status,
+            APPEAL_STATUSES.join(", ")
+        )));
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0);
+
+    let appeals = state.store.appeals_page(status, limit, offset).await?;
+
+    Ok(Json(json!({
+        "next_offset": if appeals.len() == limit { Some(offset + limit) } else { None },
+        "appeals": appeals,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/appeals/{id}/resolve  –  approve or reject an appeal
+// ---------------------------------------------------------------------------
+
+/// Approving lifts the ban or suspension at once on this instance. The
+/// user is told on any notification socket they still have open; locked
+/// out users usually have none, so they see the outcome in `GET /appeals`.
+pub async fn resolve_appeal_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(appeal_id): Path<Uuid>,
+    Json(body): Json<ResolveAppealRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let appeal = state.store.appeal(appeal_id).await?;
+    if appeal.status != "open" {
+        return Err(ApiError::BadRequest(
+            "This appeal has already been decided".into(),
+        ));
+    }
+
+    let response = body
+        .response
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if response
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_RESPONSE_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Response must be at most {} characters",
+            MAX_RESPONSE_CHARS
+        )));
+    }
+
+    if body.approve {
+        match appeal.kind {
+            AppealKind::Ban => {
+                update_user(
+                    &state,
+                    appeal.user_id,
+                    json!({ "banned_at": null, "ban_reason": null }),
+                )
+                .await?;
+                bans::apply(
+                    &state,
+                    AccessChange::Unban {
+                        user_id: appeal.user_id,
+                    },
+                )
+                .await;
+            }
+            AppealKind::Suspension => {
+                update_user(
+                    &state,
+                    appeal.user_id,
+                    json!({ "suspended_until": null, "suspension_reason": null }),
+                )
+                .await?;
+                bans::apply(
+                    &state,
+                    AccessChange::LiftSuspension {
+                        user_id: appeal.user_id,
+                    },
+                )
+                .await;
+            }
+        }
+    }
+
+    let status = if body.approve { "approved" } else { "rejected" };
+    let updated = state
+        .store
+        .update_appeal(
+            appeal_id,
+            json!({
+                "status": status,
+                "reviewed_by": admin.id,
+                "reviewed_at": to_timestamp(Utc::now()),
+                "response": response,
+            }),
+        )
+        .await?;
+
+    info!(
+        "[appeals] admin={} {} appeal {} by {}",
+        admin.id, status, appeal_id, appeal.user_id
+    );
+    audit(
+        &state,
+        admin.id,
+        if body.approve {
+            "appeal.approve"
+        } else {
+            "appeal.reject"
+        },
+        appeal_id,
+        json!({
+            "user_id": appeal.user_id,
+            "kind": appeal.kind,
+            "response": response,
+        }),
+    )
+    .await;
+
+    notify_user(
+        &state,
+        appeal.user_id,
+        UserEvent::AppealResolved(AppealResolved {
+            appeal_id,
+            approved: body.approve,
+            response,
+        }),
+    )
+    .await;
+
+    Ok(Json(updated))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    timestamp.parse().ok()
+}
@@ -0,0 +1,305 @@
+use axum::extract::multipart::{Multipart, MultipartError};
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use std::io::Cursor;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::config::AttachmentConfig;
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{
+    deliver_message, find_sent_message, validate_client_id, verify_membership,
+};
+use crate::handlers::usage::{check_attachment_quota, check_message_quota};
+use crate::models::{Attachment, Thumbnail, WsIncoming};
+use crate::storage;
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/attachments  –  upload a file as a message
+// ---------------------------------------------------------------------------
+
+/// Multipart form with a `file` part and optional `caption` and `client_id`
+/// text parts.
+/// The file goes to Supabase Storage and is then sent through the normal
+/// message pipeline, so it is stored and broadcast like any text message.
+pub async fn upload_attachment_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    let config = &state.config.attachments;
+    let mut caption = String::new();
+    let mut client_id: Option<String> = None;
+    let mut upload: Option<(String, String, Vec<u8>)> = None;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(bad_multipart)? {
+        match field.name() {
+            Some("caption") => {
+                caption = field.text().await.map_err(bad_multipart)?;
+            }
+            Some("client_id") => {
+                client_id = Some(field.text().await.map_err(bad_multipart)?);
+            }
+            Some("file") => {
+                let file_name = sanitize_file_name(field.file_name().unwrap_or("file"));
+                let mime_type = field
+                    .content_type()
+                    .unwrap_or("application/octet-stream")
+                    .to_lowercase();
+                check_mime_type(config, &mime_type)?;
+
+                // Read chunk by chunk so an oversized upload is cut off early.
+                let mut data = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(bad_multipart)? {
+                    if data.len() + chunk.len() > config.max_bytes {
+                        return Err(ApiError::AttachmentTooLarge {
+                            max_bytes: config.max_bytes,
+                        });
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                upload = Some((file_name, mime_type, data));
+            }
+            _ => {} // Ignore unknown parts.
+        }
+    }
+
+    let (file_name, mime_type, data) = match upload {
+        Some(u) => u,
+        None => return Err(ApiError::BadRequest("Missing 'file' part".into())),
+    };
+    if data.is_empty() {
+        return Err(ApiError::BadRequest("Attachment is empty".into()));
+    }
+
+    // A retried upload shouldn't store the file a second time.
+    validate_client_id(client_id.as_deref())?;
+    if let Some(original) =
+        find_sent_message(&state, conversation_id, me, client_id.as_deref()).await?
+    {
+        return Ok(Json(original));
+    }
+
+    // Browsers render images inline, so don't take the declared type on trust.
+    let kind = if mime_type.starts_with("image/") {
+        if !looks_like_image(&mime_type, &data) {
+            return Err(ApiError::UnsupportedMediaType(format!(
+                "content does not match '{}'",
+                mime_type
+            )));
+        }
+        "image"
+    } else {
+        "file"
+    };
+
+    // Decode and downscale off the async runtime; a corrupt image is refused.
+    let thumbnail_data = if kind == "image" {
+        let max_dim = config.thumbnail_max_dim;
+        let data = data.clone();
+        tokio::task::spawn_blocking(move || make_thumbnail(&data, max_dim))
+            .await
+            .map_err(|e| ApiError::Internal(format!("Thumbnail task failed: {}", e)))??
+    } else {
+        None
+    };
+
+    let size_bytes = data.len();
+    let is_bot = state.store.profile(me).await?.is_bot();
+    if !is_bot {
+        check_message_quota(&state, me).await?;
+    }
+    check_attachment_quota(&state, me, size_bytes).await?;
+
+    let object_id = Uuid::new_v4();
+    let path = format!("{}/{}-{}", conversation_id, object_id, file_name);
+    storage::upload(&config.bucket, &path, &mime_type, data.into()).await?;
+
+    let url = storage::signed_url(&config.bucket, &path, config.url_ttl).await?;
+
+    // The original is already stored, so a failed thumbnail only costs the preview.
+    let thumbnail = match thumbnail_data {
+        Some(thumb) => {
+            let thumb_path = format!(
+                "{}/{}-thumb.{}",
+                conversation_id, object_id, thumb.extension
+            );
+            match upload_thumbnail(config, &thumb_path, thumb).await {
+                Ok(t) => Some(t),
+                Err(e) => {
+                    error!(
+                        "[attachments] Failed to store thumbnail {}: {}",
+                        thumb_path, e
+                    );
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    let url_expires_at = (chrono::Utc::now()
+        + chrono::Duration::from_std(config.url_ttl).unwrap_or_default())
+    .to_rfc3339();
+
+    info!(
+        "[attachments] user={} uploaded {} ({} bytes) to conversation {}",
+        me, path, size_bytes, conversation_id
+    );
+
+    let message = deliver_message(
+        &state,
+        conversation_id,
+        me,
+        is_bot,
+        WsIncoming {
+            content: caption,
+            components: None,
+            attachment: Some(Attachment {
+                kind: kind.to_string(),
+                path,
+                file_name,
+                mime_type,
+                size_bytes,
+                url,
+                url_expires_at,
+                thumbnail,
+            }),
+            poll: None,
+            sticker_id: None,
+            client_id,
+            approved: false,
+        },
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// An encoded thumbnail waiting to be uploaded.
+struct ThumbnailData {
+    bytes: Vec<u8>,
+    mime_type: &'static str,
+    extension: &'static str,
+    width: u32,
+    height: u32,
+}
+
+/// Downscale an image so its longest side is at most `max_dim`, keeping the
+/// aspect ratio. Returns `None` when it is already small enough to serve
+/// as its own preview. Transparent images stay PNG; the rest become JPEG.
+fn make_thumbnail(data: &[u8], max_dim: u32) -> Result<Option<ThumbnailData>, ApiError> {
+    let img = image::load_from_memory(data)
+        .map_err(|e| ApiError::UnsupportedMediaType(format!("could not decode image: {}", e)))?;
+
+    if img.width() <= max_dim && img.height() <= max_dim {
+        return Ok(None);
+    }
+
+    let thumb = img.thumbnail(max_dim, max_dim);
+    let (thumb, format, mime_type, extension) = if img.color().has_alpha() {
+        (thumb, image::ImageFormat::Png, "image/png", "png")
+    } else {
+        // JPEG has no alpha channel, so flatten to RGB first.
+        (
+            image::DynamicImage::from(thumb.to_rgb8()),
+            image::ImageFormat::Jpeg,
+            "image/jpeg",
+            "jpg",
+        )
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    thumb
+        .write_to(&mut bytes, format)
+        .map_err(|e| ApiError::Internal(format!("Failed to encode thumbnail: {}", e)))?;
+
+    Ok(Some(ThumbnailData {
+        bytes: bytes.into_inner(),
+        mime_type,
+        extension,
+        width: thumb.width(),
+        height: thumb.height(),
+    }))
+}
+
+async fn upload_thumbnail(
+    config: &AttachmentConfig,
+    path: &str,
+    thumb: ThumbnailData,
+) -> Result<Thumbnail, ApiError> {
+    storage::upload(&config.bucket, path, thumb.mime_type, thumb.bytes.into()).await?;
+    let url = storage::signed_url(&config.bucket, path, config.url_ttl).await?;
+
+    Ok(Thumbnail {
+        path: path.to_string(),
+        url,
+        width: thumb.width,
+        height: thumb.height,
+    })
+}
+
+pub fn bad_multipart(e: MultipartError) -> ApiError {
+    ApiError::BadRequest(format!("Invalid multipart body: {}", e))
+}
+
+fn check_mime_type(config: &AttachmentConfig, mime_type: &str) -> Result<(), ApiError> {
+    if config.allowed_mime_types.iter().any(|m| m == mime_type) {
+        Ok(())
+    } else {
+        Err(ApiError::UnsupportedMediaType(format!(
+            "'{}' is not allowed. Allowed: {}",
+            mime_type,
+            config.allowed_mime_types.join(", ")
+        )))
+    }
+}
+
+/// Keep ASCII letters, digits, `.`, `-` and `_` so the name is safe as a
+/// Storage path segment; anything else becomes `_`.
+fn sanitize_file_name(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or(raw);
+    let cleaned: String = base
+        .chars()
+        .take(100)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Check the file's magic bytes against its declared image type.
+pub fn looks_like_image(mime_type: &str, data: &[u8]) -> bool {
+    match mime_type {
+        "image/png" => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => data.starts_with(b"\xFF\xD8\xFF"),
+        "image/gif" => data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
+        "image/webp" => data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP",
+        // Other image types can't be verified; treat them as plain files.
+        _ => false,
+    }
+}
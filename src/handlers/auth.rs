@@ -9,37 +9,96 @@ use argon2::{
 };
 
 use crate::error::ApiError;
-use crate::models::{AuthResponse, LoginRequest, ProfileRow, RegisterRequest};
+use crate::handlers::profile::touch_last_seen;
+use crate::models::{
+    AuthResponse, ChangePasswordRequest, DeactivateAccountRequest, DeleteAccountRequest,
+    LoginRequest, RegisterRequest,
+};
+use crate::sessions;
 use crate::AppState;
 
 /// Name of the session cookie.
 const SESSION_COOKIE: &str = "gigachat_session";
 
+/// Shortest password accepted at signup or when changing it.
+const MIN_PASSWORD_LEN: usize = 6;
+
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Write the user-id into a cookie so subsequent requests are authenticated.
-/// Secure + SameSite=None are required for cross-origin deployments
-/// (e.g. frontend on Vercel, backend on Render).
-pub fn set_session(cookies: &Cookies, user_id: Uuid) {
-    let mut cookie = Cookie::new(SESSION_COOKIE, user_id.to_string());
+/// Write the user-id and the account's session version into an encrypted
+/// cookie so subsequent requests are authenticated. Secure + SameSite=None
+/// are required for cross-origin deployments (e.g. frontend on Vercel,
+/// backend on Render).
+pub fn set_session(state: &AppState, cookies: &Cookies, user_id: Uuid, version: i64) {
+    let mut cookie = Cookie::new(SESSION_COOKIE, format!("{}:{}", user_id, version));
     cookie.set_path("/");
     cookie.set_http_only(true);
     cookie.set_secure(true);
     cookie.set_same_site(tower_cookies::cookie::SameSite::None);
-    cookies.add(cookie);
+    write_private(state, cookies, cookie);
 }
 
 /// Read and parse the user-id from the session cookie.
-pub fn get_session(cookies: &Cookies) -> Result<Uuid, ApiError> {
-    let cookie = cookies.get(SESSION_COOKIE).ok_or(ApiError::Unauthorized)?;
-    let value = cookie.value().to_string();
-    Uuid::parse_str(&value).map_err(|_| ApiError::Unauthorized)
+/// A session sealed with a retired key is re-issued under the current one.
+/// Sessions of banned and suspended accounts are refused.
+pub fn get_session(state: &AppState, cookies: &Cookies) -> Result<Uuid, ApiError> {
+    let user_id = get_restricted_session(state, cookies)?;
+    if state.bans.is_banned(user_id) {
+        return Err(ApiError::AccountBanned);
+    }
+    if let Some(until) = state.bans.suspended_until(user_id) {
+        return Err(ApiError::suspended(until));
+    }
+    Ok(user_id)
+}
+
+/// `get_session` without the ban and suspension checks, for the few routes
+/// a locked-out user may still use, such as appealing. Sessions issued
+/// before the account's sessions were revoked are refused here too.
+pub fn get_restricted_session(state: &AppState, cookies: &Cookies) -> Result<Uuid, ApiError> {
+    let (value, stale) =
+        read_private(state, cookies, SESSION_COOKIE).ok_or(ApiError::Unauthorized)?;
+    // Cookies from before session versions carry only the user-id.
+    let (user_id, version) = match value.split_once(':') {
+        Some((id, version)) => (id, version.parse().map_err(|_| ApiError::Unauthorized)?),
+        None => (value.as_str(), 0),
+    };
+    let user_id = Uuid::parse_str(user_id).map_err(|_| ApiError::Unauthorized)?;
+    if version < state.sessions.current(user_id) {
+        return Err(ApiError::Unauthorized);
+    }
+    if stale {
+        set_session(state, cookies, user_id, version);
+    }
+    Ok(user_id)
+}
+
+/// Encrypt `cookie` with the current cookie key and queue it on the response.
+/// Use this for every cookie we set so clients can neither read nor forge them.
+pub fn write_private(state: &AppState, cookies: &Cookies, cookie: Cookie<'static>) {
+    cookies.private(&state.config.cookies.key).add(cookie);
+}
+
+/// Decrypt the cookie `name`, trying the current key and then each retired
+/// key. Returns the value and whether a retired key was needed, in which case
+/// the caller should re-issue the cookie. Tampered cookies read as missing.
+pub fn read_private(state: &AppState, cookies: &Cookies, name: &str) -> Option<(String, bool)> {
+    let keys = &state.config.cookies;
+    if let Some(cookie) = cookies.private(&keys.key).get(name) {
+        return Some((cookie.value().to_string(), false));
+    }
+    keys.previous_keys.iter().find_map(|key| {
+        cookies
+            .private(key)
+            .get(name)
+            .map(|cookie| (cookie.value().to_string(), true))
+    })
 }
 
 /// Hash a plaintext password with Argon2.
-fn hash_password(password: &str) -> Result<String, ApiError> {
+pub fn hash_password(password: &str) -> Result<String, ApiError> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     let hash = argon2
@@ -48,114 +107,41 @@ fn hash_password(password: &str) -> Result<String, ApiError> {
     Ok(hash.to_string())
 }
 
-/// Verify a plaintext password against an Argon2 hash string.
-fn verify_password(password: &str, hash: &str) -> Result<bool, ApiError> {
-    let parsed = PasswordHash::new(hash)
-        .map_err(|e| ApiError::Internal(format!("Bad stored hash: {}", e)))?;
-    Ok(Argon2::default()
-        .verify_password(password.as_bytes(), &parsed)
-        .is_ok())
-}
-
-/// Direct insert into Supabase via reqwest so we can read the full error body.
-/// supabase_rs's insert() only gives "400 Bad Request" with no details.
-async fn supabase_insert(table: &str, body: serde_json::Value) -> Result<String, ApiError> {
-    let supabase_url = std::env::var("SUPABASE_URL")
-        .map_err(|_| ApiError::Internal("SUPABASE_URL not set".into()))?;
-    let supabase_key = std::env::var("SUPABASE_KEY")
-        .map_err(|_| ApiError::Internal("SUPABASE_KEY not set".into()))?;
-
-    let url = format!("{}/rest/v1/{}", supabase_url.trim_end_matches('/'), table);
-
-    eprintln!("[supabase_insert] POST {} body={}", url, body);
-
-    let client = reqwest::Client::new();
-    let res = client
-        .post(&url)
-        .header("apikey", &supabase_key)
-        .header("Authorization", format!("Bearer {}", supabase_key))
-        .header("Content-Type", "application/json")
-        // Ask Supabase to return the inserted row so we can read the generated id
-        .header("Prefer", "return=representation")
-        .json(&body)
-        .send()
+/// Fail with a BadRequest if a profile with this username already exists.
+pub async fn ensure_username_available(state: &AppState, username: &str) -> Result<(), ApiError> {
+    let existing = state
+        .store
+        .profile_by_username(username)
         .await
         .map_err(|e| {
-            eprintln!("[supabase_insert] Network error: {}", e);
-            ApiError::Database(format!("Network error talking to Supabase: {}", e))
+            eprintln!("[register] Failed to query profiles table: {}", e);
+            e
         })?;
 
-    let status = res.status();
-    let response_text = res
-        .text()
-        .await
-        .unwrap_or_else(|_| "(could not read body)".into());
-
-    eprintln!(
-        "[supabase_insert] Response status={} body={}",
-        status, response_text
-    );
-
-    if !status.is_success() {
-        // Parse the error body for a friendlier message
-        let detail = if let Ok(json) = serde_json::from_str::<serde_json::Value>(&response_text) {
-            json.get("message")
-                .or_else(|| json.get("msg"))
-                .or_else(|| json.get("details"))
-                .or_else(|| json.get("hint"))
-                .and_then(|v| v.as_str())
-                .unwrap_or(&response_text)
-                .to_string()
-        } else {
-            response_text.clone()
-        };
-
-        if status.as_u16() == 404 {
-            return Err(ApiError::Database(format!(
-                "Table '{}' not found. Did you run the SQL from SCHEMA.md in your Supabase SQL Editor? ({})",
-                table, detail
-            )));
-        } else if status.as_u16() == 403 {
-            return Err(ApiError::Database(format!(
-                "Permission denied on '{}'. Disable Row Level Security (RLS) or use the service-role key. ({})",
-                table, detail
-            )));
-        } else if status.as_u16() == 409 {
-            return Err(ApiError::BadRequest(format!("Duplicate entry: {}", detail)));
-        } else {
-            return Err(ApiError::Database(format!(
-                "Supabase error {} on '{}': {}",
-                status.as_u16(),
-                table,
-                detail
-            )));
-        }
+    if existing.is_some() {
+        return Err(ApiError::BadRequest("Username is already taken".into()));
     }
+    Ok(())
+}
 
-    // Parse the response to extract the id of the inserted row.
-    // Supabase returns an array like [{ "id": "...", ... }]
-    let rows: Vec<serde_json::Value> = serde_json::from_str(&response_text).map_err(|e| {
-        eprintln!(
-            "[supabase_insert] Failed to parse response as JSON array: {} body={}",
-            e, response_text
-        );
-        ApiError::Internal(format!("Unexpected Supabase response: {}", response_text))
-    })?;
-
-    if rows.is_empty() {
-        return Err(ApiError::Internal(
-            "Supabase returned empty array after insert".into(),
-        ));
+/// Fail with a BadRequest if `password` is too weak to accept.
+fn validate_password(password: &str) -> Result<(), ApiError> {
+    if password.len() < MIN_PASSWORD_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        )));
     }
+    Ok(())
+}
 
-    let id = rows[0].get("id").and_then(|v| v.as_str()).ok_or_else(|| {
-        ApiError::Internal(format!(
-            "Supabase response missing 'id' field: {}",
-            response_text
-        ))
-    })?;
-
-    Ok(id.to_string())
+/// Verify a plaintext password against an Argon2 hash string.
+fn verify_password(password: &str, hash: &str) -> Result<bool, ApiError> {
+    let parsed = PasswordHash::new(hash)
+        .map_err(|e| ApiError::Internal(format!("Bad stored hash: {}", e)))?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok())
 }
 
 // ---------------------------------------------------------------------------
@@ -168,34 +154,13 @@ pub async fn register_handler(
     Json(body): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
     // --- validate input ---
-    let username = body.username.trim().to_string();
+    let username = state.config.username.validate(&body.username)?;
     let password = body.password.clone();
 
-    if username.is_empty() {
-        return Err(ApiError::BadRequest("Username cannot be empty".into()));
-    }
-    if password.len() < 6 {
-        return Err(ApiError::BadRequest(
-            "Password must be at least 6 characters".into(),
-        ));
-    }
+    validate_password(&password)?;
 
     // --- check if username already taken ---
-    let rows = state
-        .supabase
-        .select("profiles")
-        .eq("username", &username)
-        .execute()
-        .await
-        .map_err(|e| {
-            let msg = e.to_string();
-            eprintln!("[register] Failed to query profiles table: {}", msg);
-            ApiError::Database(format!("Failed to check username: {}", msg))
-        })?;
-
-    if !rows.is_empty() {
-        return Err(ApiError::BadRequest("Username is already taken".into()));
-    }
+    ensure_username_available(&state, &username).await?;
 
     // --- hash the password ---
     let password_hash = hash_password(&password)?;
@@ -206,10 +171,7 @@ pub async fn register_handler(
 
     let display_name = body.display_name.unwrap_or_else(|| username.clone());
 
-    // --- insert into Supabase using direct HTTP ---
-    // We bypass supabase_rs for insert because its error messages are opaque
-    // ("400 Bad Request" with no details). Direct reqwest lets us read
-    // the full Supabase error body for debugging.
+    // --- insert the profile ---
     let insert_body = json!({
         "id": user_id.to_string(),
         "username": username,
@@ -222,19 +184,31 @@ pub async fn register_handler(
         username, user_id
     );
 
-    let _returned_id = supabase_insert("profiles", insert_body).await?;
+    // The availability check above can race with a concurrent signup.
+    match state.store.insert_profile(insert_body).await {
+        Ok(_) => {}
+        Err(ApiError::UniqueViolation(_)) => {
+            return Err(ApiError::BadRequest("Username is already taken".into()));
+        }
+        Err(e) => return Err(e),
+    }
 
     eprintln!("[register] Insert succeeded for user_id={}", user_id);
 
     // --- set session cookie so the user is logged in immediately ---
-    set_session(&cookies, user_id);
+    set_session(&state, &cookies, user_id, 0);
 
     eprintln!(
         "[register] Success! user_id={} username={}",
         user_id, username
     );
 
-    Ok(Json(AuthResponse { user_id, username }))
+    Ok(Json(AuthResponse {
+        user_id,
+        username,
+        reactivated: false,
+        must_change_password: false,
+    }))
 }
 
 // ---------------------------------------------------------------------------
@@ -246,7 +220,7 @@ pub async fn login_handler(
     cookies: Cookies,
     Json(body): Json<LoginRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let username = body.username.trim().to_string();
+    let username = state.config.username.normalize(&body.username);
     let password = body.password.clone();
 
     if username.is_empty() || password.is_empty() {
@@ -256,27 +230,17 @@ pub async fn login_handler(
     }
 
     // --- fetch user by username ---
-    let rows = state
-        .supabase
-        .select("profiles")
-        .eq("username", &username)
-        .execute()
+    let profile = match state
+        .store
+        .profile_by_username(&username)
         .await
         .map_err(|e| {
-            let msg = e.to_string();
-            eprintln!("[login] Failed to query profiles table: {}", msg);
-            ApiError::Database(format!("Failed to look up user: {}", msg))
-        })?;
-
-    if rows.is_empty() {
-        return Err(ApiError::InvalidCredentials);
-    }
-
-    let profile: ProfileRow = serde_json::from_value(rows[0].clone()).map_err(|e| {
-        eprintln!("[login] Failed to parse profile row: {}", e);
-        eprintln!("[login] Raw row data: {}", rows[0]);
-        ApiError::Database(format!("Failed to parse profile data: {}", e))
-    })?;
+            eprintln!("[login] Failed to query profiles table: {}", e);
+            e
+        })? {
+        Some(profile) => profile,
+        None => return Err(ApiError::InvalidCredentials),
+    };
 
     // --- verify password ---
     let stored_hash = profile
@@ -288,8 +252,33 @@ pub async fn login_handler(
         return Err(ApiError::InvalidCredentials);
     }
 
+    // --- banned accounts stay out ---
+    if profile.is_banned() {
+        eprintln!("[login] Refused banned account {}", profile.id);
+        return Err(ApiError::AccountBanned);
+    }
+    if let Some(until) = profile.suspended_until() {
+        eprintln!("[login] Refused suspended account {}", profile.id);
+        return Err(ApiError::suspended(until));
+    }
+
+    // --- logging in undoes a deactivation ---
+    let reactivated = profile.is_deactivated();
+    if reactivated {
+        state
+            .store
+            .update_profile(profile.id, json!({ "deactivated_at": null }))
+            .await?;
+        eprintln!("[login] Reactivated account {}", profile.id);
+    }
+
     // --- set session ---
-    set_session(&cookies, profile.id);
+    set_session(
+        &state,
+        &cookies,
+        profile.id,
+        profile.session_version.unwrap_or(0),
+    );
 
     eprintln!(
         "[login] Success! user_id={} username={}",
@@ -299,6 +288,8 @@ pub async fn login_handler(
     Ok(Json(AuthResponse {
         user_id: profile.id,
         username: profile.username,
+        reactivated,
+        must_change_password: profile.must_change_password.unwrap_or(false),
     }))
 }
 
@@ -306,8 +297,16 @@ pub async fn login_handler(
 // POST /logout
 // ---------------------------------------------------------------------------
 
-pub async fn logout_handler(cookies: Cookies) -> impl IntoResponse {
-    // Remove the session cookie by setting it to empty with max-age 0.
+pub async fn logout_handler(State(state): State<AppState>, cookies: Cookies) -> impl IntoResponse {
+    if let Ok(user_id) = get_session(&state, &cookies) {
+        touch_last_seen(&state, user_id).await;
+    }
+    clear_session(&cookies);
+    Json(json!({ "status": "logged out" }))
+}
+
+/// Remove the session cookie by setting it to empty with max-age 0.
+fn clear_session(cookies: &Cookies) {
     let mut cookie = Cookie::new(SESSION_COOKIE, "");
     cookie.set_path("/");
     cookie.set_http_only(true);
@@ -315,15 +314,162 @@ pub async fn logout_handler(cookies: Cookies) -> impl IntoResponse {
     cookie.set_same_site(tower_cookies::cookie::SameSite::None);
     cookie.set_max_age(Some(tower_cookies::cookie::time::Duration::ZERO));
     cookies.add(cookie);
+}
 
-    Json(json!({ "status": "logged out" }))
+// ---------------------------------------------------------------------------
+// POST /me/deactivate  –  hide my account until I log in again
+// ---------------------------------------------------------------------------
+
+/// Unlike `DELETE /me` nothing is erased: the profile just disappears from
+/// search, profile pages and friends' lists, and can't receive friend
+/// requests. Every session is logged out; logging in again reactivates it.
+pub async fn deactivate_account_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<DeactivateAccountRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
+    let profile = state.store.profile(user_id).await?;
+
+    let stored_hash = profile
+        .password_hash
+        .as_deref()
+        .ok_or(ApiError::InvalidCredentials)?;
+    if !verify_password(&body.password, stored_hash)? {
+        return Err(ApiError::InvalidCredentials);
+    }
+
+    state
+        .store
+        .update_profile(
+            user_id,
+            json!({ "deactivated_at": chrono::Utc::now().to_rfc3339() }),
+        )
+        .await?;
+    touch_last_seen(&state, user_id).await;
+    // Every device is logged out, not just this one.
+    sessions::revoke(&state, user_id).await?;
+    clear_session(&cookies);
+    eprintln!("[deactivate] {} deactivated their account", user_id);
+
+    Ok(Json(json!({ "status": "deactivated" })))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /me  –  delete my account (right to erasure)
+// ---------------------------------------------------------------------------
+
+pub async fn delete_account_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<DeleteAccountRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
+    let profile = state.store.profile(user_id).await?;
+
+    let stored_hash = profile
+        .password_hash
+        .as_deref()
+        .ok_or(ApiError::InvalidCredentials)?;
+    if !verify_password(&body.password, stored_hash)? {
+        return Err(ApiError::InvalidCredentials);
+    }
+
+    let erasure_id = erase_account(&state, user_id).await?;
+    clear_session(&cookies);
+
+    eprintln!(
+        "[delete_account] user_id={} queued erasure {}",
+        user_id, erasure_id
+    );
+
+    Ok(Json(
+        json!({ "status": "deleted", "erasure_id": erasure_id }),
+    ))
+}
+
+/// Make the account unusable right away: no password, no recognisable
+/// name, no sessions on any device. The erasure job scrubs everything else
+/// in the background.
+/// Returns the id of the queued erasure request.
+pub async fn erase_account(state: &AppState, user_id: Uuid) -> Result<Uuid, ApiError> {
+    state
+        .store
+        .update_profile(
+            user_id,
+            json!({
+                "username": format!("deleted-{}", user_id.simple()),
+                "display_name": "Deleted user",
+                "password_hash": null,
+                "avatar_url": null,
+                "banner_url": null,
+                "bio": null,
+                "deleted_at": chrono::Utc::now().to_rfc3339(),
+            }),
+        )
+        .await?;
+    sessions::revoke(state, user_id).await?;
+
+    let erasure_id = Uuid::new_v4();
+    state.store.queue_erasure(erasure_id, user_id).await?;
+
+    Ok(erasure_id)
+}
+
+// ---------------------------------------------------------------------------
+// POST /me/password  –  change my password
+// ---------------------------------------------------------------------------
+
+/// Also how a user finishes an admin-forced reset: the temporary password
+/// is the current one, and changing it clears `must_change_password`.
+pub async fn change_password_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
+    let profile = state.store.profile(user_id).await?;
+
+    let stored_hash = profile
+        .password_hash
+        .as_deref()
+        .ok_or(ApiError::InvalidCredentials)?;
+    if !verify_password(&body.current_password, stored_hash)? {
+        return Err(ApiError::InvalidCredentials);
+    }
+    validate_password(&body.new_password)?;
+    if body.new_password == body.current_password {
+        return Err(ApiError::BadRequest(
+            "New password must be different from the current one".into(),
+        ));
+    }
+
+    state
+        .store
+        .update_profile(
+            user_id,
+            json!({
+                "password_hash": hash_password(&body.new_password)?,
+                "must_change_password": false,
+            }),
+        )
+        .await?;
+    // Log out every other device; this one keeps going on a fresh cookie.
+    let version = sessions::revoke(&state, user_id).await?;
+    set_session(&state, &cookies, user_id, version);
+    eprintln!("[change_password] {} changed their password", user_id);
+
+    Ok(Json(json!({ "status": "password changed" })))
 }
 
 // ---------------------------------------------------------------------------
 // GET /me
 // ---------------------------------------------------------------------------
 
-pub async fn me_handler(cookies: Cookies) -> Result<impl IntoResponse, ApiError> {
-    let user_id = get_session(&cookies)?;
+pub async fn me_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
     Ok(Json(json!({ "user_id": user_id })))
 }
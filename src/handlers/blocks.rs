@@ -0,0 +1,189 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::models::{BlockUserRequest, BlockedUser, PageQuery, ProfileResponse, WsEvent};
+use crate::AppState;
+
+/// How long a cached block list is trusted. Changes made on this instance
+/// apply at once; ones made elsewhere within this long.
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Who each user has blocked, for filtering live events without a query
+/// per event.
+pub type BlockCache = Arc<RwLock<HashMap<Uuid, (Instant, Arc<HashSet<Uuid>>)>>>;
+
+/// Create a new empty cache. Called once at startup.
+pub fn new_block_cache() -> BlockCache {
+    Arc::new(RwLock::new(HashMap::new()))
+}
+
+// ---------------------------------------------------------------------------
+// GET /blocks?limit=50&offset=0  –  everyone the caller has blocked
+// ---------------------------------------------------------------------------
+
+/// Most recently blocked first.
+pub async fn list_blocks_handler(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0);
+
+    let blocks = state.store.blocks_page(me, limit, offset).await?;
+
+    let ids: Vec<Uuid> = blocks.iter().map(|b| b.blocked_id).collect();
+    let mut profiles = state.store.profiles(&ids).await?;
+
+    let blocked: Vec<BlockedUser> = blocks
+        .iter()
+        .filter_map(|b| {
+            Some(BlockedUser {
+                profile: ProfileResponse::from(profiles.remove(&b.blocked_id)?),
+                blocked_at: b.created_at.clone(),
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "blocked": blocked,
+        "next_offset": if blocks.len() == limit { Some(offset + limit) } else { None },
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /blocks  –  block a user
+// ---------------------------------------------------------------------------
+
+/// Also ends any friendship or pending request between the two. The
+/// blocked user isn't told.
+pub async fn block_user_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<BlockUserRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    if me == body.user_id {
+        return Err(ApiError::BadRequest("You cannot block yourself".into()));
+    }
+    state.store.profile(body.user_id).await?;
+
+    state.store.add_block(me, body.user_id).await?;
+
+    state
+        .store
+        .delete_friendship_between(me, body.user_id)
+        .await?;
+
+    invalidate(&state, me).await;
+    info!("[blocks] {} blocked {}", me, body.user_id);
+
+    Ok(Json(json!({ "blocked": body.user_id })))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /blocks/{user_id}  –  unblock
+// ---------------------------------------------------------------------------
+
+pub async fn unblock_user_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    if !state.store.remove_block(me, user_id).await? {
+        return Err(ApiError::NotFound("User is not blocked".into()));
+    }
+
+    invalidate(&state, me).await;
+    info!("[blocks] {} unblocked {}", me, user_id);
+
+    Ok(Json(json!({ "unblocked": user_id })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Refuse an interaction between two users if either has blocked the other.
+pub async fn ensure_not_blocked(state: &AppState, a: Uuid, b: Uuid) -> Result<(), ApiError> {
+    if state.store.blocked_between(a, b).await? {
+        Err(ApiError::Forbidden(
+            "You can't interact with this user".into(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Everyone `user_id` has blocked, from the cache when fresh. A failed
+/// lookup hides nothing rather than dropping the user's events.
+pub async fn blocked_by(state: &AppState, user_id: Uuid) -> Arc<HashSet<Uuid>> {
+    if let Some((at, set)) = state.blocks.read().await.get(&user_id) {
+        if at.elapsed() < CACHE_TTL {
+            return set.clone();
+        }
+    }
+
+    let set = match state.store.blocked_ids(user_id).await {
+        Ok(set) => set,
+        Err(e) => {
+            error!("[blocks] Failed to load blocks for {}: {}", user_id, e);
+            return Arc::new(HashSet::new());
+        }
+    };
+
+    let set = Arc::new(set);
+    let mut cache = state.blocks.write().await;
+    cache.retain(|_, (at, _)| at.elapsed() < CACHE_TTL);
+    cache.insert(user_id, (Instant::now(), set.clone()));
+    set
+}
+
+/// Everyone `user_id` has blocked plus everyone who has blocked them,
+/// i.e. the users they shouldn't be shown.
+pub async fn blocked_either_way(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<HashSet<Uuid>, ApiError> {
+    let mut set: HashSet<Uuid> = blocked_by(state, user_id).await.as_ref().clone();
+    set.extend(state.store.blocker_ids(user_id, None).await?);
+    Ok(set)
+}
+
+/// Whether `viewer` has blocked whoever caused `event`.
+pub async fn is_hidden_from(state: &AppState, viewer: Uuid, event: &WsEvent) -> bool {
+    match event.sender_id() {
+        Some(sender) => blocked_by(state, viewer).await.contains(&sender),
+        None => false,
+    }
+}
+
+/// Of `members`, the ones who have blocked `sender`.
+pub async fn members_blocking(
+    state: &AppState,
+    sender: Uuid,
+    members: &[Uuid],
+) -> Result<HashSet<Uuid>, ApiError> {
+    state.store.blocker_ids(sender, Some(members)).await
+}
+
+async fn invalidate(state: &AppState, user_id: Uuid) {
+    state.blocks.write().await.remove(&user_id);
+}
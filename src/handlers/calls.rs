@@ -0,0 +1,362 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha1::Sha1;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{publish, verify_membership};
+use crate::handlers::notifications::notify_user;
+use crate::handlers::settings::fetch_notification_settings_many;
+use crate::models::{
+    CallInfo, CallSignal, CallSignalRequest, CallState, IncomingCall, UserEvent, WsEvent,
+};
+use crate::AppState;
+
+/// The call running in each conversation, keyed by conversation id.
+///
+/// Calls are 1-on-1, so a conversation has at most one. The tracker is held
+/// in memory by the instance the caller's socket is on; with the NATS bus,
+/// signals still reach every instance but `GET .../call` only knows about
+/// calls signalled through this one.
+pub type CallTracker = Arc<RwLock<HashMap<Uuid, TrackedCall>>>;
+
+/// Create a new empty call tracker. Called once at startup.
+pub fn new_call_tracker() -> CallTracker {
+    Arc::new(RwLock::new(HashMap::new()))
+}
+
+pub struct TrackedCall {
+    info: CallInfo,
+    /// When the call last changed state; used to expire unanswered offers.
+    since: Instant,
+}
+
+/// The four signaling frame types a client may send.
+#[derive(Debug, Clone, Copy)]
+pub enum CallSignalKind {
+    Offer,
+    Answer,
+    IceCandidate,
+    End,
+}
+
+impl CallSignalKind {
+    /// Match a client frame's `"type"`.
+    pub fn from_type(frame_type: &str) -> Option<Self> {
+        match frame_type {
+            "call_offer" => Some(Self::Offer),
+            "call_answer" => Some(Self::Answer),
+            "ice_candidate" => Some(Self::IceCandidate),
+            "call_end" => Some(Self::End),
+            _ => None,
+        }
+    }
+
+    fn into_event(self, signal: CallSignal) -> WsEvent {
+        match self {
+            Self::Offer => WsEvent::CallOffer(signal),
+            Self::Answer => WsEvent::CallAnswer(signal),
+            Self::IceCandidate => WsEvent::IceCandidate(signal),
+            Self::End => WsEvent::CallEnd(signal),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GET /conversations/{id}/call  –  current call, for incoming-call UI
+// ---------------------------------------------------------------------------
+
+pub async fn get_call_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    let ring_timeout = state.config.calls.ring_timeout;
+    let mut calls = state.calls.write().await;
+    let call = match calls.get(&conversation_id) {
+        Some(c) if is_expired(c, ring_timeout) => {
+            calls.remove(&conversation_id);
+            None
+        }
+        Some(c) => Some(c.info.clone()),
+        None => None,
+    };
+
+    Ok(Json(json!({ "call": call })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /calls/turn-credentials  –  short-lived TURN credentials
+// ---------------------------------------------------------------------------
+
+/// Mint credentials for coturn's shared-secret ("TURN REST API") auth:
+/// the username is `<expiry unix time>:<user id>` and the password is
+/// base64(HMAC-SHA1(secret, username)). coturn recomputes the HMAC, so the
+/// secret never leaves the server. The response is shaped like an
+/// `RTCIceServer` so clients can pass it straight to `RTCPeerConnection`.
+pub async fn turn_credentials_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let calls = &state.config.calls;
+    let secret = match &calls.turn_secret {
+        Some(s) if !calls.turn_urls.is_empty() => s,
+        _ => return Err(ApiError::NotFound("TURN is not configured".into())),
+    };
+
+    let expires_at = chrono::Utc::now()
+        + chrono::Duration::from_std(calls.turn_credential_ttl).unwrap_or_default();
+    let username = format!("{}:{}", expires_at.timestamp(), me);
+
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes())
+        .map_err(|e| ApiError::Internal(format!("Invalid TURN secret: {}", e)))?;
+    mac.update(username.as_bytes());
+    let credential = BASE64.encode(mac.finalize().into_bytes());
+
+    Ok(Json(json!({
+        "urls": calls.turn_urls,
+        "username": username,
+        "credential": credential,
+        "ttl": calls.turn_credential_ttl.as_secs(),
+        "expires_at": expires_at.to_rfc3339(),
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// WebSocket signaling
+// ---------------------------------------------------------------------------
+
+/// Validate a signaling frame against the conversation's call state, update
+/// the tracker and relay the frame to the other side. Nothing is persisted.
+pub async fn handle_call_signal(
+    state: &AppState,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    kind: CallSignalKind,
+    req: CallSignalRequest,
+) -> Result<(), ApiError> {
+    if let Some(to) = req.to {
+        if to == user_id {
+            return Err(ApiError::BadRequest("You cannot call yourself".into()));
+        }
+        if verify_membership(state, conversation_id, to).await.is_err() {
+            return Err(ApiError::BadRequest(
+                "Recipient is not a member of this conversation".into(),
+            ));
+        }
+    }
+
+    let ring_timeout = state.config.calls.ring_timeout;
+    let mut new_call = false;
+    {
+        let mut calls = state.calls.write().await;
+        if calls
+            .get(&conversation_id)
+            .is_some_and(|c| is_expired(c, ring_timeout))
+        {
+            calls.remove(&conversation_id);
+        }
+
+        match kind {
+            CallSignalKind::Offer => {
+                if let Some(existing) = calls.get(&conversation_id) {
+                    if existing.info.call_id != req.call_id {
+                        return Err(ApiError::BadRequest(
+                            "A call is already in progress in this conversation".into(),
+                        ));
+                    }
+                    // Re-offers (ICE restarts) on the same call are relayed as-is.
+                } else {
+                    calls.insert(
+                        conversation_id,
+                        TrackedCall {
+                            info: CallInfo {
+                                call_id: req.call_id,
+                                conversation_id,
+                                caller_id: user_id,
+                                callee_id: req.to,
+                                state: CallState::Ringing,
+                                started_at: chrono::Utc::now().to_rfc3339(),
+                            },
+                            since: Instant::now(),
+                        },
+                    );
+                    new_call = true;
+                }
+            }
+            CallSignalKind::Answer => {
+                let call = find_call(&mut calls, conversation_id, req.call_id)?;
+                if call.info.caller_id == user_id {
+                    return Err(ApiError::BadRequest(
+                        "You cannot answer your own call".into(),
+                    ));
+                }
+                if call.info.callee_id.is_some_and(|c| c != user_id) {
+                    return Err(ApiError::Forbidden("This call is not for you".into()));
+                }
+                if call.info.state == CallState::Ringing {
+                    call.info.state = CallState::Active;
+                    call.info.callee_id = Some(user_id);
+                    call.since = Instant::now();
+                }
+            }
+            CallSignalKind::IceCandidate => {
+                let call = find_call(&mut calls, conversation_id, req.call_id)?;
+                ensure_participant(&call.info, user_id)?;
+            }
+            CallSignalKind::End => {
+                // Ending an unknown call is harmless; the other side may
+                // already have hung up.
+                if let Ok(call) = find_call(&mut calls, conversation_id, req.call_id) {
+                    ensure_participant(&call.info, user_id)?;
+                    calls.remove(&conversation_id);
+                }
+            }
+        }
+    }
+
+    publish(
+        state,
+        conversation_id,
+        kind.into_event(CallSignal {
+            call_id: req.call_id,
+            from: user_id,
+            to: req.to,
+            data: req.data,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }),
+    )
+    .await;
+
+    if new_call {
+        tokio::spawn(ring_members(
+            state.clone(),
+            IncomingCall {
+                call_id: req.call_id,
+                conversation_id,
+                caller_id: user_id,
+            },
+            req.to,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Tell the callee, or every other member when the offer has no `to`, on
+/// their notification sockets, so devices not in the conversation ring too.
+async fn ring_members(state: AppState, call: IncomingCall, to: Option<Uuid>) {
+    let members = match to {
+        Some(to) => vec![to],
+        None => match state.store.members(call.conversation_id).await {
+            Ok(members) => members
+                .into_iter()
+                .map(|m| m.user_id)
+                .filter(|id| *id != call.caller_id)
+                .collect(),
+            Err(e) => {
+                error!(
+                    "[calls] Failed to load members of {}: {}",
+                    call.conversation_id, e
+                );
+                return;
+            }
+        },
+    };
+    let settings = match fetch_notification_settings_many(&state, &members).await {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("[calls] Failed to load notification settings: {}", e);
+            return;
+        }
+    };
+    for member in members {
+        if settings.get(&member).cloned().unwrap_or_default().calls.ws {
+            notify_user(&state, member, UserEvent::IncomingCall(call.clone())).await;
+        }
+    }
+}
+
+/// End the conversation's call if `user_id` is in it. Called when one of
+/// their sockets closes so the other side isn't left waiting.
+pub async fn end_calls_for(state: &AppState, conversation_id: Uuid, user_id: Uuid) {
+    let call_id = {
+        let mut calls = state.calls.write().await;
+        match calls.get(&conversation_id) {
+            Some(c) if ensure_participant(&c.info, user_id).is_ok() => {
+                let call_id = c.info.call_id;
+                calls.remove(&conversation_id);
+                call_id
+            }
+            _ => return,
+        }
+    };
+
+    info!(
+        "[calls] Ending call {} in conversation {}: user {} disconnected",
+        call_id, conversation_id, user_id
+    );
+    publish(
+        state,
+        conversation_id,
+        WsEvent::CallEnd(CallSignal {
+            call_id,
+            from: user_id,
+            to: None,
+            data: json!({ "reason": "disconnected" }),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }),
+    )
+    .await;
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+fn find_call(
+    calls: &mut HashMap<Uuid, TrackedCall>,
+    conversation_id: Uuid,
+    call_id: Uuid,
+) -> Result<&mut TrackedCall, ApiError> {
+    match calls.get_mut(&conversation_id) {
+        Some(c) if c.info.call_id == call_id => Ok(c),
+        _ => Err(ApiError::NotFound("Call not found".into())),
+    }
+}
+
+/// While ringing, anyone the offer could reach may take part in ICE.
+fn ensure_participant(call: &CallInfo, user_id: Uuid) -> Result<(), ApiError> {
+    let is_participant = call.caller_id == user_id
+        || match call.state {
+            CallState::Ringing => call.callee_id.is_none_or(|c| c == user_id),
+            CallState::Active => call.callee_id == Some(user_id),
+        };
+    if is_participant {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden("You are not in this call".into()))
+    }
+}
+
+fn is_expired(call: &TrackedCall, ring_timeout: std::time::Duration) -> bool {
+    call.info.state == CallState::Ringing && call.since.elapsed() > ring_timeout
+}
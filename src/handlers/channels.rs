@@ -0,0 +1,157 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use std::collections::HashSet;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::activity::record_activity;
+use crate::handlers::auth::get_session;
+use crate::models::{ActivityKind, ChannelListQuery, CreateChannelRequest, MemberRow};
+use crate::AppState;
+
+const MAX_NAME_CHARS: usize = 100;
+
+// ---------------------------------------------------------------------------
+// POST /channels  –  create a public channel owned by the caller
+// ---------------------------------------------------------------------------
+
+pub async fn create_channel_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateChannelRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let name = body.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Channel name must be 1-{} characters",
+            MAX_NAME_CHARS
+        )));
+    }
+
+    let channel_id = Uuid::new_v4();
+    state
+        .store
+        .insert_conversation(json!({
+            "id": channel_id,
+            "is_group": true,
+            "name": name,
+            "avatar_url": body.avatar_url,
+            "visibility": "public",
+        }))
+        .await?;
+    state
+        .store
+        .add_members(&[MemberRow::new(channel_id, me, "owner")])
+        .await?;
+
+    info!(
+        "[channels] {} created channel {} ({})",
+        me, channel_id, name
+    );
+
+    Ok(Json(json!({ "conversation_id": channel_id, "name": name })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /channels  –  discover public channels, biggest first
+// ---------------------------------------------------------------------------
+
+pub async fn list_channels_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ChannelListQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0);
+    let search = query
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(escape_like);
+
+    let mut channels = state
+        .store
+        .public_channels(search.as_deref(), limit, offset)
+        .await?;
+
+    if !channels.is_empty() {
+        let joined: HashSet<Uuid> = state
+            .store
+            .memberships(me)
+            .await?
+            .iter()
+            .map(|m| m.conversation_id)
+            .collect();
+        for channel in &mut channels {
+            channel.is_member = joined.contains(&channel.id);
+        }
+    }
+
+    Ok(Json(json!({
+        "channels": channels,
+        "next_offset": if channels.len() == limit { Some(offset + limit) } else { None },
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /channels/{id}/join  –  become a member of a public channel
+// ---------------------------------------------------------------------------
+
+/// Joining twice is harmless. Private conversations look the same as
+/// missing ones so their ids can't be probed.
+pub async fn join_channel_handler(
+    State(state): State<AppState>,
+    Path(channel_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let channel = match state.store.conversation(channel_id).await {
+        Ok(c) if c.is_public() && c.deleted_at.is_none() => c,
+        Ok(_) | Err(ApiError::NotFound(_)) => {
+            return Err(ApiError::NotFound("Channel not found".into()))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let already_member = state.store.member(channel_id, me).await?.is_some();
+    if !already_member {
+        state
+            .store
+            .add_members(&[MemberRow::new(channel_id, me, "member")])
+            .await?;
+        info!("[channels] {} joined channel {}", me, channel_id);
+
+        record_activity(
+            &state,
+            &state.store.profile(me).await?,
+            ActivityKind::JoinedChannel,
+            json!({ "conversation_id": channel.id, "name": channel.name }),
+        )
+        .await;
+    }
+
+    Ok(Json(json!({
+        "conversation_id": channel.id,
+        "name": channel.name,
+        "joined": true,
+    })))
+}
+
+/// Treat `%` and `_` in a search as literal characters.
+fn escape_like(raw: &str) -> String {
+    raw.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -1,33 +1,77 @@
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
-        Path, State, WebSocketUpgrade,
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        Path, Query, State, WebSocketUpgrade,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
     },
-    response::IntoResponse,
     Json,
 };
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{stream, SinkExt, Stream, StreamExt};
 use serde_json::json;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{broadcast, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tower_cookies::Cookies;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+use crate::admin_events;
+use crate::config::MessageConfig;
+use crate::db::Order;
 use crate::error::ApiError;
+use crate::handlers::admin::audit;
 use crate::handlers::auth::get_session;
-use crate::models::{ConversationResponse, MessageRow, StartConversationRequest, WsBroadcast};
+use crate::handlers::blocks::{blocked_by, ensure_not_blocked, is_hidden_from};
+use crate::handlers::calls::{end_calls_for, handle_call_signal, CallSignalKind};
+use crate::handlers::emoji::fetch_sticker;
+use crate::handlers::moderation::hold_message;
+use crate::handlers::notifications::{notify_new_message, notify_user};
+use crate::handlers::polls::attach_polls;
+use crate::handlers::profile::touch_last_seen;
+use crate::handlers::scheduled::to_timestamp;
+use crate::handlers::settings::fetch_settings;
+use crate::handlers::usage::check_message_quota;
+use crate::link_preview;
+use crate::models::{
+    AdminEvent, CallSignalRequest, ComponentInteraction, ComponentInteractionRequest,
+    ConversationDeleted, ConversationLockChanged, ConversationResponse, ConversationSummary,
+    ConversationUpdate, EphemeralMessage, FlaggedMessage, LastMessagePreview, LinkPreviewUpdate,
+    MemberRow, MessageComponent, MessageContextQuery, MessagePageQuery, MessageRow,
+    NewMessageNotice, SendEphemeralRequest, SetAdultContentRequest, SetSlowModeRequest,
+    ShadowMessage, SlowModeChanged, StartConversationRequest, UpdateConversationRequest, UserEvent,
+    WsBroadcast, WsConnectQuery, WsEvent, WsIncoming, CAP_START_CONVERSATIONS,
+};
+use crate::moderation::ModerationVerdict;
+use crate::spam::{self, ScoreAction};
+use crate::spam_policy::SpamVerdict;
+use crate::storage;
+use crate::store::MessageFilter;
+use crate::wire::{self, WireFormat};
 use crate::AppState;
 
 /// Type alias for the shared map of conversation broadcast channels.
-pub type ConversationChannels = Arc<RwLock<HashMap<Uuid, broadcast::Sender<WsBroadcast>>>>;
+pub type ConversationChannels = Arc<RwLock<HashMap<Uuid, broadcast::Sender<WsEvent>>>>;
 
 /// Create a new empty channel map. Called once at startup.
 pub fn new_channel_map() -> ConversationChannels {
     Arc::new(RwLock::new(HashMap::new()))
 }
 
+/// When each (conversation, sender) pair last took a slow mode slot, and
+/// the interval it was taken under.
+pub type SlowModeSlots = Arc<Mutex<HashMap<(Uuid, Uuid), (Instant, std::time::Duration)>>>;
+
+/// Create a new empty slot map. Called once at startup.
+pub fn new_slow_mode_slots() -> SlowModeSlots {
+    Arc::new(Mutex::new(HashMap::new()))
+}
+
 // ---------------------------------------------------------------------------
 // POST /conversations  –  start (or retrieve) a 1-on-1 conversation
 // ---------------------------------------------------------------------------
@@ -37,7 +81,7 @@ pub async fn start_conversation_handler(
     cookies: Cookies,
     Json(body): Json<StartConversationRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let me = get_session(&cookies)?;
+    let me = get_session(&state, &cookies)?;
     info!(
         "[start_conversation] me={}, friend_id={}",
         me, body.friend_id
@@ -49,26 +93,18 @@ pub async fn start_conversation_handler(
         ));
     }
 
-    // Check if a conversation already exists between these two users.
-    // We query conversation_members for both users and find a shared conversation_id.
-    let my_convos = state
-        .supabase
-        .select("conversation_members")
-        .eq("user_id", &me.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    let friend_convos = state
-        .supabase
-        .select("conversation_members")
-        .eq("user_id", &body.friend_id.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+    // Bots may only create conversations when explicitly allowed.
+    if !state.store.profile(me).await?.can(CAP_START_CONVERSATIONS) {
+        return Err(ApiError::Forbidden(
+            "This bot is not allowed to start conversations".into(),
+        ));
+    }
+    ensure_not_blocked(&state, me, body.friend_id).await?;
 
-    let my_ids = extract_conversation_ids(&my_convos);
-    let friend_ids = extract_conversation_ids(&friend_convos);
+    // Check if a conversation already exists between these two users.
+    // We look up both users' memberships and find a shared conversation_id.
+    let my_ids = conversation_ids(&state.store.memberships(me).await?);
+    let friend_ids = conversation_ids(&state.store.memberships(body.friend_id).await?);
 
     // Find any conversation_id that appears in both sets.
     for cid in &my_ids {
@@ -79,7 +115,14 @@ pub async fn start_conversation_handler(
         }
     }
 
-    // No existing conversation – create one.
+    // No existing conversation – create one, if the other user takes DMs
+    // from the caller.
+    let dm_policy = fetch_settings(&state, body.friend_id).await?.dm_policy;
+    if !dm_policy.allows(state.store.are_friends(me, body.friend_id).await?) {
+        return Err(ApiError::Forbidden(
+            "This user doesn't accept direct messages from you".into(),
+        ));
+    }
     let conv_id = Uuid::new_v4();
     info!(
         "[start_conversation] Creating new conversation: {}",
@@ -87,46 +130,22 @@ pub async fn start_conversation_handler(
     );
 
     state
-        .supabase
-        .insert(
-            "conversations",
-            json!({
-                "id": conv_id.to_string(),
-                "is_group": false,
-            }),
-        )
+        .store
+        .insert_conversation(json!({ "id": conv_id, "is_group": false }))
         .await
         .map_err(|e| {
             error!("[start_conversation] Failed to insert conversation: {}", e);
-            ApiError::Database(e.to_string())
+            e
         })?;
 
-    // Add both users as members (include "role" column from actual schema).
+    // Add both users as members.
     state
-        .supabase
-        .insert(
-            "conversation_members",
-            json!({
-                "conversation_id": conv_id.to_string(),
-                "user_id": me.to_string(),
-                "role": "member",
-            }),
-        )
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    state
-        .supabase
-        .insert(
-            "conversation_members",
-            json!({
-                "conversation_id": conv_id.to_string(),
-                "user_id": body.friend_id.to_string(),
-                "role": "member",
-            }),
-        )
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+        .store
+        .add_members(&[
+            MemberRow::new(conv_id, me, "member"),
+            MemberRow::new(conv_id, body.friend_id, "member"),
+        ])
+        .await?;
 
     info!(
         "[start_conversation] Success, returning conversation_id: {}",
@@ -141,22 +160,492 @@ pub async fn start_conversation_handler(
 // GET /conversations  –  list my conversations
 // ---------------------------------------------------------------------------
 
+/// Everything a conversation list needs, in a fixed number of queries
+/// however many conversations there are: previews and unread counts, the
+/// conversations, their members, those members' profiles and the caller's
+/// favorite friends. Direct conversations with favorites come first, then
+/// the rest, most recently active first.
 pub async fn list_conversations_handler(
     State(state): State<AppState>,
     cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
-    let me = get_session(&cookies)?;
+    const PREVIEW_CHARS: usize = 100;
 
-    let result = state
-        .supabase
-        .select("conversation_members")
-        .eq("user_id", &me.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+    let me = get_session(&state, &cookies)?;
+
+    let previews = state.store.conversation_previews(me).await?;
+    if previews.is_empty() {
+        return Ok(Json(json!({ "conversations": [] })));
+    }
+    let ids: Vec<Uuid> = previews.iter().map(|p| p.conversation_id).collect();
+
+    let conversations = state.store.conversations(&ids).await?;
+
+    let mut members: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for member in state.store.members_of(&ids).await? {
+        if member.user_id != me {
+            members
+                .entry(member.conversation_id)
+                .or_default()
+                .push(member.user_id);
+        }
+    }
+
+    let other_ids: Vec<Uuid> = members
+        .values()
+        .flatten()
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let profiles = state.store.profiles(&other_ids).await?;
+
+    let favorites = state.store.favorite_friends(me).await?;
+
+    let mut summaries: Vec<ConversationSummary> = previews
+        .into_iter()
+        .map(|preview| {
+            let conversation = conversations.get(&preview.conversation_id);
+            let member_ids = members.remove(&preview.conversation_id).unwrap_or_default();
+            let is_group = conversation.and_then(|c| c.is_group).unwrap_or(false);
+            let is_favorite =
+                !is_group && member_ids.first().is_some_and(|id| favorites.contains(id));
+
+            let (name, avatar_url) = if is_group {
+                let name = conversation
+                    .and_then(|c| c.name.clone())
+                    .unwrap_or_else(|| {
+                        member_ids
+                            .iter()
+                            .filter_map(|id| profiles.get(id))
+                            .map(|p| p.display_name.clone().unwrap_or_else(|| p.username.clone()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    });
+                (name, conversation.and_then(|c| c.avatar_url.clone()))
+            } else {
+                match member_ids.first().and_then(|id| profiles.get(id)) {
+                    Some(p) => (
+                        p.display_name.clone().unwrap_or_else(|| p.username.clone()),
+                        p.avatar_url.clone(),
+                    ),
+                    None => (String::new(), None),
+                }
+            };
+
+            let last_message = match (
+                preview.last_message_id,
+                preview.last_sender_id,
+                preview.last_message_at.clone(),
+            ) {
+                (Some(id), Some(sender_id), Some(created_at)) => Some(LastMessagePreview {
+                    id,
+                    sender_id,
+                    content: preview
+                        .last_content
+                        .unwrap_or_default()
+                        .chars()
+                        .take(PREVIEW_CHARS)
+                        .collect(),
+                    message_type: preview.last_message_type.unwrap_or_else(|| "text".into()),
+                    created_at,
+                }),
+                _ => None,
+            };
+
+            ConversationSummary {
+                conversation_id: preview.conversation_id,
+                is_group,
+                is_announcement: conversation
+                    .and_then(|c| c.is_announcement)
+                    .unwrap_or(false),
+                is_locked: conversation.is_some_and(|c| c.is_locked()),
+                slow_mode_seconds: conversation.and_then(|c| c.slow_mode_seconds),
+                name,
+                avatar_url,
+                member_ids,
+                last_activity_at: preview
+                    .last_message_at
+                    .or_else(|| conversation.and_then(|c| c.created_at.clone())),
+                last_message,
+                unread_count: preview.unread_count,
+                is_favorite,
+            }
+        })
+        .collect();
+
+    // RFC 3339 strings from Postgres sort chronologically.
+    summaries.sort_by(|a, b| {
+        b.is_favorite
+            .cmp(&a.is_favorite)
+            .then_with(|| b.last_activity_at.cmp(&a.last_activity_at))
+    });
+
+    Ok(Json(json!({ "conversations": summaries })))
+}
+
+// ---------------------------------------------------------------------------
+// PATCH /conversations/{id}  –  change conversation settings
+// ---------------------------------------------------------------------------
+
+/// Either side of a direct conversation may change its settings; in a group
+/// only the owner or an admin can.
+pub async fn update_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<UpdateConversationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    const MIN_TTL_SECS: u64 = 30;
+    const MAX_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+    let me = get_session(&state, &cookies)?;
+    let conversation = state.store.conversation(conversation_id).await?;
+    if conversation.is_group.unwrap_or(false) {
+        require_owner_or_admin(&state, conversation_id, me).await?;
+    } else {
+        verify_membership(&state, conversation_id, me).await?;
+    }
+
+    let ttl = match body.message_ttl_seconds {
+        Some(ttl) => ttl.filter(|t| *t > 0),
+        None => {
+            return Err(ApiError::BadRequest(
+                "Provide at least one field to update".into(),
+            ))
+        }
+    };
+    if let Some(t) = ttl {
+        if !(MIN_TTL_SECS..=MAX_TTL_SECS).contains(&t) {
+            return Err(ApiError::BadRequest(format!(
+                "message_ttl_seconds must be between {} and {}",
+                MIN_TTL_SECS, MAX_TTL_SECS
+            )));
+        }
+    }
+
+    state
+        .store
+        .update_conversation(conversation_id, json!({ "message_ttl_seconds": ttl }))
+        .await?;
+
+    info!(
+        "[update_conversation] {} set message_ttl_seconds={:?} on {}",
+        me, ttl, conversation_id
+    );
+
+    publish(
+        &state,
+        conversation_id,
+        WsEvent::ConversationUpdated(ConversationUpdate {
+            conversation_id,
+            message_ttl_seconds: ttl,
+            updated_by: me,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({
+        "conversation_id": conversation_id,
+        "message_ttl_seconds": ttl,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /conversations/{id}/adult-content  –  relax the word filter for a group
+// ---------------------------------------------------------------------------
+
+/// Only group owners (or admins) can opt a group in. Terms marked
+/// `enforce_in_adult` still apply.
+pub async fn set_adult_content_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<SetAdultContentRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let conversation = state.store.conversation(conversation_id).await?;
+    if !conversation.is_group.unwrap_or(false) {
+        return Err(ApiError::BadRequest(
+            "Only group conversations can allow adult content".into(),
+        ));
+    }
+
+    require_owner_or_admin(&state, conversation_id, me).await?;
+
+    state
+        .store
+        .update_conversation(
+            conversation_id,
+            json!({ "allow_adult_content": body.enabled }),
+        )
+        .await?;
+
+    info!(
+        "[adult_content] {} set allow_adult_content={} on {}",
+        me, body.enabled, conversation_id
+    );
+
+    Ok(Json(json!({
+        "conversation_id": conversation_id,
+        "allow_adult_content": body.enabled,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/lock  –  make a conversation read-only
+// DELETE /conversations/{id}/lock  –  let members post again
+// ---------------------------------------------------------------------------
+
+/// For the group owner or an admin. While locked every send is refused
+/// with `conversation_locked`, admins' included; history, reactions to
+/// existing messages and calls are unaffected.
+pub async fn lock_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    set_locked(&state, conversation_id, &cookies, true).await
+}
+
+pub async fn unlock_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    set_locked(&state, conversation_id, &cookies, false).await
+}
+
+async fn set_locked(
+    state: &AppState,
+    conversation_id: Uuid,
+    cookies: &Cookies,
+    locked: bool,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let me = get_session(state, cookies)?;
+    let conversation = state.store.conversation(conversation_id).await?;
+    if conversation.deleted_at.is_some() {
+        return Err(ApiError::NotFound("Conversation not found".into()));
+    }
+    let admin_override = require_owner_or_admin(state, conversation_id, me).await?;
+
+    let update = if locked {
+        json!({ "locked_at": to_timestamp(chrono::Utc::now()), "locked_by": me })
+    } else {
+        json!({ "locked_at": null, "locked_by": null })
+    };
+    state
+        .store
+        .update_conversation(conversation_id, update)
+        .await?;
+
+    info!(
+        "[lock] {} {} conversation {}",
+        me,
+        if locked { "locked" } else { "unlocked" },
+        conversation_id
+    );
+    if admin_override {
+        audit(
+            state,
+            me,
+            if locked {
+                "conversation.lock"
+            } else {
+                "conversation.unlock"
+            },
+            conversation_id,
+            json!({}),
+        )
+        .await;
+    }
+
+    publish(
+        state,
+        conversation_id,
+        WsEvent::ConversationLockChanged(ConversationLockChanged {
+            conversation_id,
+            locked,
+            changed_by: me,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({
+        "conversation_id": conversation_id,
+        "locked": locked,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /conversations/{id}/slow-mode  –  space out each member's messages
+// ---------------------------------------------------------------------------
+
+/// For the group owner or an admin. Bots are exempt, and so are held
+/// messages a moderator releases.
+pub async fn set_slow_mode_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<SetSlowModeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    const MAX_SLOW_MODE_SECS: u64 = 6 * 60 * 60;
 
-    let ids = extract_conversation_ids(&result);
-    Ok(Json(json!({ "conversations": ids })))
+    let me = get_session(&state, &cookies)?;
+    let conversation = state.store.conversation(conversation_id).await?;
+    if !conversation.is_group.unwrap_or(false) {
+        return Err(ApiError::BadRequest(
+            "Slow mode is only available in group conversations".into(),
+        ));
+    }
+    let seconds = body.seconds.filter(|s| *s > 0);
+    if seconds.is_some_and(|s| s > MAX_SLOW_MODE_SECS) {
+        return Err(ApiError::BadRequest(format!(
+            "seconds must be at most {}",
+            MAX_SLOW_MODE_SECS
+        )));
+    }
+    require_owner_or_admin(&state, conversation_id, me).await?;
+
+    state
+        .store
+        .update_conversation(conversation_id, json!({ "slow_mode_seconds": seconds }))
+        .await?;
+
+    info!(
+        "[slow_mode] {} set slow_mode_seconds={:?} on {}",
+        me, seconds, conversation_id
+    );
+
+    publish(
+        &state,
+        conversation_id,
+        WsEvent::SlowModeChanged(SlowModeChanged {
+            conversation_id,
+            slow_mode_seconds: seconds,
+            changed_by: me,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({
+        "conversation_id": conversation_id,
+        "slow_mode_seconds": seconds,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /conversations/{id}  –  delete a conversation for everyone
+// ---------------------------------------------------------------------------
+
+/// Either side of a DM may delete it; groups only by their owner. Messages
+/// are tombstoned and their files removed, memberships go, and the
+/// conversation row is kept (marked deleted) for moderation snapshots.
+pub async fn delete_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let members = state.store.members(conversation_id).await?;
+    // Logged in but not a member: 401 would send the client back to login.
+    let me_member = match members.iter().find(|m| m.user_id == me) {
+        Some(member) => member,
+        None => {
+            return Err(ApiError::Forbidden(
+                "You are not a member of this conversation".into(),
+            ))
+        }
+    };
+
+    let conversation = state.store.conversation(conversation_id).await?;
+    if conversation.is_group.unwrap_or(false) && !me_member.is_owner() {
+        return Err(ApiError::Forbidden(
+            "Only the group owner can delete this conversation".into(),
+        ));
+    }
+
+    // Files first: once the rows are tombstoned nothing points at them.
+    let attachments = state
+        .store
+        .messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                with_attachment: true,
+                ..Default::default()
+            },
+            Order::Asc,
+            None,
+        )
+        .await?;
+    let paths: Vec<String> = attachments
+        .iter()
+        .filter_map(|m| m.attachment.as_ref())
+        .flat_map(|a| {
+            std::iter::once(a.path.clone()).chain(a.thumbnail.as_ref().map(|t| t.path.clone()))
+        })
+        .collect();
+    if let Err(e) = storage::remove(&state.config.attachments.bucket, &paths).await {
+        error!(
+            "[delete_conversation] Failed to delete attachments of {}: {}",
+            conversation_id, e
+        );
+    }
+
+    state
+        .store
+        .tombstone_messages(&MessageFilter {
+            conversation_id: Some(conversation_id),
+            live_only: true,
+            ..Default::default()
+        })
+        .await?;
+    state
+        .store
+        .delete_conversation_schedules(conversation_id)
+        .await?;
+    state
+        .store
+        .delete_conversation_drafts(conversation_id)
+        .await?;
+    state.store.remove_members(conversation_id).await?;
+    state
+        .store
+        .update_conversation(
+            conversation_id,
+            json!({ "deleted_at": to_timestamp(chrono::Utc::now()) }),
+        )
+        .await?;
+
+    info!(
+        "[delete_conversation] {} deleted conversation {} ({} members)",
+        me,
+        conversation_id,
+        members.len()
+    );
+
+    let deleted = ConversationDeleted {
+        conversation_id,
+        deleted_by: me,
+    };
+    // Open sockets close themselves on this event, which tears the channel down.
+    publish(
+        &state,
+        conversation_id,
+        WsEvent::ConversationDeleted(deleted.clone()),
+    )
+    .await;
+    for member in &members {
+        notify_user(
+            &state,
+            member.user_id,
+            UserEvent::ConversationDeleted(deleted.clone()),
+        )
+        .await;
+    }
+
+    Ok(Json(json!({ "status": "deleted" })))
 }
 
 // ---------------------------------------------------------------------------
@@ -166,11 +655,15 @@ pub async fn list_conversations_handler(
 pub async fn get_messages_handler(
     State(state): State<AppState>,
     Path(conversation_id): Path<Uuid>,
+    Query(page): Query<MessagePageQuery>,
     cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
+    const DEFAULT_PAGE_SIZE: usize = 50;
+    const MAX_PAGE_SIZE: usize = 200;
+
     info!("[get_messages] conversation_id={}", conversation_id);
 
-    let me = get_session(&cookies)?;
+    let me = get_session(&state, &cookies)?;
     info!("[get_messages] user_id={}", me);
 
     // Verify the user is a member of this conversation.
@@ -179,101 +672,491 @@ pub async fn get_messages_handler(
         return Err(e);
     }
 
-    let rows = state
-        .supabase
-        .select("messages")
-        .eq("conversation_id", &conversation_id.to_string())
-        .execute()
+    let limit = page
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    // Newest first so the database only hands back one page. Ordered by
+    // `id` alone, the same key the `before` cursor filters on: concurrent
+    // sends can commit with timestamps and ids in different orders, and a
+    // cursor on one key over pages sorted by another skips or repeats rows.
+    let mut messages = state
+        .store
+        .messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                before_id: page.before,
+                visible_to: Some(me),
+                ..Default::default()
+            },
+            Order::Desc,
+            Some(limit),
+        )
+        .await?;
+    attach_polls(&state, &mut messages, me).await?;
+
+    // A full page means there may be older messages behind it.
+    let next_before = if messages.len() == limit {
+        messages.last().and_then(|m| m.id)
+    } else {
+        None
+    };
+
+    // The client expects chronological order within a page.
+    messages.reverse();
+
+    let message_count = fetch_message_count(&state, conversation_id).await?;
+
+    Ok(Json(json!({
+        "messages": messages,
+        "message_count": message_count,
+        "next_before": next_before,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /conversations/{id}/messages/{message_id}/context  –  jump to a message
+// ---------------------------------------------------------------------------
+
+/// A message plus its neighbours, for permalinks from search results or pins.
+pub async fn get_message_context_handler(
+    State(state): State<AppState>,
+    Path((conversation_id, message_id)): Path<(Uuid, i64)>,
+    Query(params): Query<MessageContextQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    const DEFAULT_AROUND: usize = 25;
+    const MAX_AROUND: usize = 100;
+
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    let around = params.around.unwrap_or(DEFAULT_AROUND).min(MAX_AROUND);
+
+    let in_conversation = MessageFilter {
+        conversation_id: Some(conversation_id),
+        visible_to: Some(me),
+        ..Default::default()
+    };
+    let target = match state
+        .store
+        .messages(
+            &MessageFilter {
+                ids: Some(vec![message_id]),
+                ..in_conversation.clone()
+            },
+            Order::Asc,
+            Some(1),
+        )
+        .await?
+        .pop()
+    {
+        Some(m) => m,
+        None => return Err(ApiError::NotFound("Message not found".into())),
+    };
+
+    // Ask for one extra on each side to learn whether more history exists.
+    let mut before = state
+        .store
+        .messages(
+            &MessageFilter {
+                before_id: Some(message_id),
+                ..in_conversation.clone()
+            },
+            Order::Desc,
+            Some(around + 1),
+        )
+        .await?;
+    let has_more_before = before.len() > around;
+    before.truncate(around);
+    before.reverse();
+
+    let mut after = state
+        .store
+        .messages(
+            &MessageFilter {
+                after_id: Some(message_id),
+                ..in_conversation
+            },
+            Order::Asc,
+            Some(around + 1),
+        )
+        .await?;
+    let has_more_after = after.len() > around;
+    after.truncate(around);
+
+    let mut messages = before;
+    messages.push(target);
+    messages.extend(after);
+    attach_polls(&state, &mut messages, me).await?;
+
+    Ok(Json(json!({
+        "target_id": message_id,
+        "messages": messages,
+        "has_more_before": has_more_before,
+        "has_more_after": has_more_after,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/ephemeral  –  notice visible to one member only
+// ---------------------------------------------------------------------------
+
+pub async fn send_ephemeral_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<SendEphemeralRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    const DEFAULT_TTL_SECS: u32 = 60;
+    const MAX_TTL_SECS: u32 = 60 * 60;
+
+    let me = get_session(&state, &cookies)?;
+    let sender = state.store.profile(me).await?;
+
+    // Bots answer commands in their own conversations; admins can post
+    // moderation notices anywhere.
+    if sender.is_bot() {
+        verify_membership(&state, conversation_id, me).await?;
+    } else if !sender.is_admin() {
+        return Err(ApiError::Forbidden(
+            "Only bots and admins can send ephemeral messages".into(),
+        ));
+    }
+
+    if verify_membership(&state, conversation_id, body.recipient_id)
         .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+        .is_err()
+    {
+        return Err(ApiError::BadRequest(
+            "Recipient is not a member of this conversation".into(),
+        ));
+    }
 
-    let mut messages: Vec<MessageRow> = Vec::new();
-    for val in rows {
-        if let Ok(msg) = serde_json::from_value::<MessageRow>(val) {
-            messages.push(msg);
-        }
+    let content = state.content_policy.sanitize(&body.content)?.content;
+    if content.trim().is_empty() {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
     }
+    validate_content(&state.config.messages, &content)?;
+
+    let expires_in_seconds = body
+        .expires_in_seconds
+        .unwrap_or(DEFAULT_TTL_SECS)
+        .clamp(1, MAX_TTL_SECS);
+
+    publish(
+        &state,
+        conversation_id,
+        WsEvent::Ephemeral(EphemeralMessage {
+            sender_id: me,
+            recipient_id: body.recipient_id,
+            content,
+            is_bot: sender.is_bot(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            expires_in_seconds,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({ "status": "sent" })))
+}
 
-    // Sort by created_at ascending so the client gets chronological order.
-    messages.sort_by(|a, b| {
-        let a_time = a.created_at.as_deref().unwrap_or("");
-        let b_time = b.created_at.as_deref().unwrap_or("");
-        a_time.cmp(b_time)
+// ---------------------------------------------------------------------------
+// GET /conversations/{id}/events  –  Server-Sent Events fallback
+// ---------------------------------------------------------------------------
+
+/// Per-stream state threaded through `stream::unfold`.
+/// Field order matters: the receiver must drop before the guard releases the channel.
+struct SseSubscription {
+    rx: broadcast::Receiver<WsEvent>,
+    _guard: ChannelGuard,
+    user_id: Uuid,
+    state: AppState,
+}
+
+/// Receive-only live stream for clients whose proxies break WebSockets.
+/// Fed from the same broadcast channel as `/ws/{id}`. Reconnecting browsers
+/// send `Last-Event-ID` automatically, which replays missed messages.
+pub async fn sse_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    Query(params): Query<WsConnectQuery>,
+    headers: HeaderMap,
+    cookies: Cookies,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, user_id).await?;
+
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+
+    // Subscribe *before* replaying so nothing sent during the catch-up is lost.
+    let rx = subscribe(&state.channels, conversation_id).await;
+
+    let mut missed = match last_event_id.or(params.since) {
+        Some(since) => {
+            fetch_messages_since(
+                &state,
+                conversation_id,
+                user_id,
+                since,
+                state.config.ws.replay_limit,
+            )
+            .await?
+        }
+        None => Vec::new(),
+    };
+    let blocked = blocked_by(&state, user_id).await;
+    missed.retain(|event| !event.sender_id().is_some_and(|s| blocked.contains(&s)));
+    let replay = stream::iter(missed.into_iter().map(|event| Ok(sse_event(&event))));
+
+    let subscription = SseSubscription {
+        rx,
+        _guard: ChannelGuard {
+            channels: state.channels.clone(),
+            conversation_id,
+        },
+        user_id,
+        state: state.clone(),
+    };
+
+    let live = stream::unfold(subscription, |mut sub| async move {
+        loop {
+            match sub.rx.recv().await {
+                Ok(event)
+                    if event.is_visible_to(sub.user_id)
+                        && !is_hidden_from(&sub.state, sub.user_id, &event).await =>
+                {
+                    return Some((Ok(sse_event(&event.for_client())), sub));
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
     });
 
-    Ok(Json(json!({ "messages": messages })))
+    Ok(Sse::new(replay.chain(live)).keep_alive(KeepAlive::default()))
+}
+
+/// Encode a conversation event as an SSE event named after its type.
+/// Messages carry their id so `Last-Event-ID` works on reconnect.
+fn sse_event(event: &WsEvent) -> Event {
+    let payload = serde_json::to_value(event).unwrap_or_default();
+    let kind = payload
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("message")
+        .to_string();
+
+    let sse = Event::default().event(kind).data(payload.to_string());
+    match event {
+        WsEvent::Message(WsBroadcast { id: Some(id), .. })
+        | WsEvent::Announcement(WsBroadcast { id: Some(id), .. }) => sse.id(id.to_string()),
+        _ => sse,
+    }
 }
 
 // ---------------------------------------------------------------------------
-// GET /ws/{conversation_id}  –  WebSocket upgrade
+// GET /ws/{conversation_id}?since={message_id}  –  WebSocket upgrade
 // ---------------------------------------------------------------------------
 
 pub async fn ws_handler(
     State(state): State<AppState>,
     Path(conversation_id): Path<Uuid>,
+    Query(params): Query<WsConnectQuery>,
     cookies: Cookies,
     ws: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user_id = get_session(&cookies)?;
+    let user_id = get_session(&state, &cookies)?;
 
     // Verify membership before upgrading.
     verify_membership(&state, conversation_id, user_id).await?;
 
-    let channels = state.channels.clone();
-    let supabase = state.supabase.clone();
+    // Looked up once so every message from this socket can be labelled.
+    let is_bot = state.store.profile(user_id).await?.is_bot();
 
-    Ok(ws.on_upgrade(move |socket| {
-        handle_socket(socket, conversation_id, user_id, channels, supabase)
-    }))
+    // Refuse oversized frames at the protocol level, before we ever parse them.
+    let max_frame = state.config.messages.max_frame_bytes;
+
+    Ok(ws
+        .max_message_size(max_frame)
+        .max_frame_size(max_frame)
+        .protocols(wire::PROTOCOLS)
+        .on_upgrade(move |socket| {
+            let format = WireFormat::from_protocol(socket.protocol().and_then(|p| p.to_str().ok()));
+            handle_socket(
+                socket,
+                state,
+                conversation_id,
+                user_id,
+                is_bot,
+                format,
+                params.since,
+            )
+        }))
 }
 
 // ---------------------------------------------------------------------------
 // WebSocket connection handler
 // ---------------------------------------------------------------------------
 
+/// Liveness timestamps shared between the send and receive halves of a socket.
+struct Liveness {
+    /// Last time the client answered one of our pings.
+    last_pong: Instant,
+    /// Last time the client sent an actual chat message.
+    last_activity: Instant,
+}
+
 async fn handle_socket(
     socket: WebSocket,
+    state: AppState,
     conversation_id: Uuid,
     user_id: Uuid,
-    channels: ConversationChannels,
-    supabase: Arc<supabase_rs::SupabaseClient>,
+    is_bot: bool,
+    format: WireFormat,
+    since: Option<i64>,
 ) {
+    let ws_config = state.config.ws.clone();
+
     // Split the socket into sender and receiver halves using futures-util.
     let (mut ws_sender, mut ws_receiver) = socket.split();
 
-    // Get or create the broadcast channel for this conversation.
-    let tx = {
-        let mut map = channels.write().await;
-        map.entry(conversation_id)
-            .or_insert_with(|| {
-                let (tx, _) = broadcast::channel(256);
-                tx
-            })
-            .clone()
-    };
+    // Subscribe *before* replaying so nothing sent during the catch-up is lost.
+    let channels = state.channels.clone();
+    let mut rx = subscribe(&channels, conversation_id).await;
+
+    // Replay anything the client missed while it was disconnected.
+    if let Some(since) = since {
+        match fetch_messages_since(
+            &state,
+            conversation_id,
+            user_id,
+            since,
+            ws_config.replay_limit,
+        )
+        .await
+        {
+            Ok(mut missed) => {
+                let blocked = blocked_by(&state, user_id).await;
+                missed.retain(|event| !event.sender_id().is_some_and(|s| blocked.contains(&s)));
+                info!(
+                    "[ws] Replaying {} missed messages to user {} in conversation {}",
+                    missed.len(),
+                    user_id,
+                    conversation_id
+                );
+                for event in missed {
+                    let frame = match format.encode(&event) {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    if ws_sender.send(frame).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => error!("[ws] Failed to load messages since {}: {}", since, e),
+        }
+    }
 
-    let mut rx = tx.subscribe();
+    // Frames meant for this client only (e.g. errors), bypassing the broadcast.
+    let (direct_tx, mut direct_rx) = mpsc::channel::<Message>(16);
 
-    // Spawn a task that forwards broadcast messages → WebSocket sender.
+    let liveness = Arc::new(Mutex::new(Liveness {
+        last_pong: Instant::now(),
+        last_activity: Instant::now(),
+    }));
+
+    // Spawn a task that forwards broadcast events → WebSocket sender,
+    // and pings the client on every heartbeat tick.
+    let liveness_for_send = liveness.clone();
+    let state_for_send = state.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(broadcast_msg) = rx.recv().await {
-            let json_text = match serde_json::to_string(&broadcast_msg) {
-                Ok(s) => s,
-                Err(_) => continue,
-            };
-            if ws_sender
-                .send(Message::Text(json_text.into()))
-                .await
-                .is_err()
-            {
-                // Client disconnected.
-                break;
+        let mut heartbeat = tokio::time::interval(ws_config.heartbeat_interval);
+        // The first tick completes immediately; skip it so we don't ping on connect.
+        heartbeat.tick().await;
+
+        loop {
+            tokio::select! {
+                received = rx.recv() => {
+                    let event = match received {
+                        Ok(e) => e,
+                        Err(_) => break,
+                    };
+                    if !event.is_visible_to(user_id)
+                        || is_hidden_from(&state_for_send, user_id, &event).await
+                    {
+                        continue;
+                    }
+                    let event = event.for_client();
+                    let frame = match format.encode(&event) {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    if ws_sender.send(frame).await.is_err() {
+                        // Client disconnected.
+                        break;
+                    }
+                    if matches!(event, WsEvent::ConversationDeleted(_)) {
+                        let _ = ws_sender
+                            .send(Message::Close(Some(CloseFrame {
+                                code: close_code::NORMAL,
+                                reason: "conversation deleted".into(),
+                            })))
+                            .await;
+                        break;
+                    }
+                }
+                Some(frame) = direct_rx.recv() => {
+                    if ws_sender.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    let (since_pong, since_activity) = {
+                        let l = liveness_for_send.lock().unwrap();
+                        (l.last_pong.elapsed(), l.last_activity.elapsed())
+                    };
+
+                    let pong_deadline =
+                        ws_config.heartbeat_interval * ws_config.max_missed_heartbeats;
+                    let reason = if since_pong > pong_deadline {
+                        Some("heartbeat timeout")
+                    } else if since_activity > ws_config.max_idle {
+                        Some("idle timeout")
+                    } else {
+                        None
+                    };
+
+                    if let Some(reason) = reason {
+                        info!(
+                            "[ws] Closing socket for user {} in conversation {}: {}",
+                            user_id, conversation_id, reason
+                        );
+                        let _ = ws_sender
+                            .send(Message::Close(Some(CloseFrame {
+                                code: close_code::AWAY,
+                                reason: reason.into(),
+                            })))
+                            .await;
+                        break;
+                    }
+
+                    if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
     });
 
     // Main loop: read messages from the WebSocket client using StreamExt::next().
-    let tx_for_recv = tx.clone();
+    let mut bucket = state.rate_limits.socket_bucket(user_id, &state.config.ws);
+    let call_state = state.clone();
     let mut recv_task = tokio::spawn(async move {
         while let Some(result) = ws_receiver.next().await {
             let msg = match result {
@@ -281,83 +1164,888 @@ async fn handle_socket(
                 Err(_) => break, // Connection error → stop.
             };
 
-            let text = match msg {
-                Message::Text(t) => t.to_string(),
-                Message::Close(_) => break,
-                _ => continue, // Ignore binary, ping, pong.
-            };
-
-            // Parse the incoming message. Expect: { "content": "..." }
-            let content = match serde_json::from_str::<serde_json::Value>(&text) {
-                Ok(val) => match val.get("content").and_then(|c| c.as_str()) {
-                    Some(c) => c.to_string(),
-                    None => continue, // Ignore malformed messages.
+            let payload = match msg {
+                // If it's not JSON, treat the raw text as the message content.
+                Message::Text(t) => serde_json::from_str::<serde_json::Value>(&t)
+                    .unwrap_or_else(|_| json!({ "content": t })),
+                Message::Binary(bytes) => match format.decode_binary(&bytes) {
+                    Some(val) => val,
+                    None => continue, // Ignore binary on JSON sockets and malformed msgpack.
                 },
-                Err(_) => {
-                    // If it's not JSON, treat the raw text as the message content.
-                    text.clone()
+                Message::Pong(_) => {
+                    liveness.lock().unwrap().last_pong = Instant::now();
+                    continue;
                 }
+                Message::Close(_) => break,
+                _ => continue, // Client pings are auto-answered.
             };
 
-            if content.trim().is_empty() {
+            liveness.lock().unwrap().last_activity = Instant::now();
+
+            // Drop anything over the per-socket rate limit and tell the sender.
+            if !bucket.try_take() {
+                admin_events::report_rate_limit(&state, user_id, "socket", Some(conversation_id))
+                    .await;
+                if let Some(frame) =
+                    error_frame(format, "rate_limited", "You are sending messages too fast")
+                {
+                    let _ = direct_tx.try_send(frame);
+                }
                 continue;
             }
 
-            let now = chrono::Utc::now().to_rfc3339();
-
-            // Persist the message to Supabase (best-effort; don't kill the socket on failure).
-            // Don't send "id" — it's auto-increment int8 in the actual schema.
-            let insert_body = serde_json::json!({
-                "conversation_id": conversation_id.to_string(),
-                "sender_id": user_id.to_string(),
-                "content": content,
-                "message_type": "text",
-            });
-
-            let _ = supabase.insert("messages", insert_body).await;
-
-            // Broadcast to all connected clients in this conversation.
-            let broadcast_msg = WsBroadcast {
-                sender_id: user_id,
-                content,
-                created_at: now,
-            };
-
-            // If nobody is listening the send will error, which is fine.
-            let _ = tx_for_recv.send(broadcast_msg);
+            if let Err(e) =
+                handle_client_frame(&state, conversation_id, user_id, is_bot, payload).await
+            {
+                if let Some(frame) = api_error_frame(format, &e) {
+                    let _ = direct_tx.try_send(frame);
+                }
+            }
         }
     });
 
-    // Wait for either task to finish, then abort the other.
+    // Wait for either task to finish, then abort the other and wait for it to
+    // be dropped, so our receiver is gone before we check the subscriber count.
     tokio::select! {
         _ = &mut send_task => {
             recv_task.abort();
+            let _ = recv_task.await;
         }
         _ = &mut recv_task => {
             send_task.abort();
+            let _ = send_task.await;
+        }
+    }
+
+    // Don't leave the other side of a call ringing or connected to nobody.
+    end_calls_for(&call_state, conversation_id, user_id).await;
+
+    release_channel(&channels, conversation_id).await;
+    touch_last_seen(&call_state, user_id).await;
+}
+
+/// Dispatch one decoded frame from a client. Errors are reported back to
+/// that client only; the socket stays open.
+async fn handle_client_frame(
+    state: &AppState,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    is_bot: bool,
+    payload: serde_json::Value,
+) -> Result<(), ApiError> {
+    // Expect { "content": "..." }, { "type": "component_interaction", ... }
+    // or one of the call signaling types.
+    let frame_type = payload.get("type").and_then(|t| t.as_str());
+    if frame_type == Some("component_interaction") {
+        let req = serde_json::from_value::<ComponentInteractionRequest>(payload)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid interaction: {}", e)))?;
+        return handle_component_interaction(state, conversation_id, user_id, req).await;
+    }
+    if let Some(kind) = frame_type.and_then(CallSignalKind::from_type) {
+        let req = serde_json::from_value::<CallSignalRequest>(payload)
+            .map_err(|e| ApiError::BadRequest(format!("Invalid call signal: {}", e)))?;
+        return handle_call_signal(state, conversation_id, user_id, kind, req).await;
+    }
+    let incoming = match serde_json::from_value::<WsIncoming>(payload) {
+        Ok(m) => m,
+        Err(_) => return Ok(()), // Ignore malformed messages.
+    };
+
+    if incoming.content.trim().is_empty() {
+        return Ok(());
+    }
+
+    deliver_message(state, conversation_id, user_id, is_bot, incoming).await?;
+    Ok(())
+}
+
+/// Validate, persist and broadcast a chat message. Every send path goes
+/// through here so the same limits apply everywhere.
+pub async fn deliver_message(
+    state: &AppState,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    is_bot: bool,
+    incoming: WsIncoming,
+) -> Result<WsBroadcast, ApiError> {
+    validate_client_id(incoming.client_id.as_deref())?;
+    if let Some(original) = find_sent_message(
+        state,
+        conversation_id,
+        sender_id,
+        incoming.client_id.as_deref(),
+    )
+    .await?
+    {
+        return Ok(original);
+    }
+
+    if !is_bot && !incoming.approved {
+        check_message_quota(state, sender_id).await?;
+    }
+
+    // Announcement conversations are read-only for everyone but admins,
+    // locked ones for everyone.
+    let conversation = state.store.conversation(conversation_id).await?;
+    if conversation.is_locked() {
+        return Err(ApiError::ConversationLocked);
+    }
+    let slow_mode = conversation
+        .slow_mode_seconds
+        .filter(|s| *s > 0 && !is_bot && !incoming.approved);
+    if let Some(interval) = slow_mode {
+        check_slow_mode(state, conversation_id, sender_id, interval).await?;
+    }
+    let announcement = conversation.is_announcement.unwrap_or(false);
+    if announcement && !state.store.profile(sender_id).await?.is_admin() {
+        return Err(ApiError::Forbidden(
+            "Only admins can post in announcement conversations".into(),
+        ));
+    }
+
+    let sanitized = state.content_policy.sanitize(&incoming.content)?;
+    let filtered = state.word_filter.apply(
+        &sanitized.content,
+        conversation.allow_adult_content.unwrap_or(false),
+    )?;
+    let content = filtered.content;
+    let content_html = if filtered.masked {
+        state.content_policy.render_html(&content)
+    } else {
+        sanitized.html
+    };
+
+    let sticker = match incoming.sticker_id {
+        Some(id) => Some(fetch_sticker(state, id).await?),
+        None => None,
+    };
+
+    // Attachments and stickers may go out without a caption.
+    if content.trim().is_empty() && incoming.attachment.is_none() && sticker.is_none() {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
+    }
+    validate_content(&state.config.messages, &content)?;
+
+    let spam = if is_bot || incoming.approved {
+        SpamVerdict::Allow
+    } else {
+        match state
+            .spam
+            .check(sender_id, &content, state.rate_limits.send_limit(sender_id))
+        {
+            Err(e @ ApiError::RateLimited { .. }) => {
+                admin_events::report_rate_limit(state, sender_id, "spam", Some(conversation_id))
+                    .await;
+                return Err(e);
+            }
+            verdict => verdict?,
+        }
+    };
+
+    if let Some(components) = &incoming.components {
+        if !is_bot {
+            return Err(ApiError::Forbidden(
+                "Only bots can attach interactive components".into(),
+            ));
+        }
+        validate_components(components)?;
+    }
+
+    if !is_bot && !incoming.approved && state.spam_scores.enabled() {
+        state.spam_scores.check_slowed(sender_id)?;
+        let (score, action) = spam::score_message(state, sender_id, &content).await?;
+        match action {
+            ScoreAction::Allow => {}
+            ScoreAction::Hold => {
+                warn!(
+                    "[deliver_message] Holding message from {} in {} ({})",
+                    sender_id,
+                    conversation_id,
+                    score.describe()
+                );
+                hold_message(
+                    state,
+                    conversation_id,
+                    sender_id,
+                    incoming,
+                    score.describe(),
+                )
+                .await?;
+                return Err(ApiError::MessageHeld);
+            }
+            ScoreAction::SlowDown(wait) => {
+                warn!(
+                    "[deliver_message] Slowing down {} in {} ({})",
+                    sender_id,
+                    conversation_id,
+                    score.describe()
+                );
+                admin_events::report_rate_limit(
+                    state,
+                    sender_id,
+                    "spam_score",
+                    Some(conversation_id),
+                )
+                .await;
+                return Err(ApiError::rate_limited(wait));
+            }
         }
     }
+
+    let moderation = if is_bot || incoming.approved {
+        ModerationVerdict::Allow
+    } else {
+        state.moderation.check(&content).await?
+    };
+    if let ModerationVerdict::Hold(categories) = moderation {
+        hold_message(state, conversation_id, sender_id, incoming, categories).await?;
+        return Err(ApiError::MessageHeld);
+    }
+
+    let message_type = if incoming.poll.is_some() {
+        "poll"
+    } else if sticker.is_some() {
+        "sticker"
+    } else if let Some(a) = &incoming.attachment {
+        a.kind.as_str()
+    } else {
+        "text"
+    };
+
+    // Persist the message to Supabase (best-effort; a failed insert still
+    // reaches live clients, it just won't show up in history).
+    // Don't send "id" — it's auto-increment int8 in the actual schema.
+    let mut insert_body = json!({
+        "conversation_id": conversation_id.to_string(),
+        "sender_id": sender_id.to_string(),
+        "content": content,
+        "content_html": content_html,
+        "message_type": message_type,
+        "poll_id": incoming.poll.as_ref().map(|p| p.id),
+        "sticker": sticker,
+        "is_bot": is_bot,
+        "components": incoming.components,
+        "attachment": incoming.attachment,
+        "client_id": incoming.client_id,
+    });
+
+    // (source, reason) for the moderators' live feed.
+    let mut flags: Vec<(&str, String)> = Vec::new();
+    if let SpamVerdict::Flag(reason) = spam {
+        warn!(
+            "[deliver_message] Flagging message from {} in {} as spam ({})",
+            sender_id, conversation_id, reason
+        );
+        insert_body["spam_flag"] = json!(reason);
+        flags.push(("spam", reason.to_string()));
+    }
+    if let Some(terms) = &filtered.flag {
+        warn!(
+            "[deliver_message] Flagging message from {} in {} for review (word filter)",
+            sender_id, conversation_id
+        );
+        insert_body["filter_flag"] = json!(terms);
+        flags.push(("word_filter", terms.clone()));
+    }
+    if let ModerationVerdict::Flag(categories) = &moderation {
+        warn!(
+            "[deliver_message] Flagging message from {} in {} for review ({})",
+            sender_id, conversation_id, categories
+        );
+        insert_body["moderation_flag"] = json!(categories);
+        flags.push(("moderation", categories.clone()));
+    }
+
+    // A shadow banned sender sees their message go through as usual;
+    // nobody else ever does.
+    let shadow = !is_bot && state.bans.is_shadow_banned(sender_id);
+    if shadow {
+        info!(
+            "[deliver_message] Hiding message from shadow banned {} in {}",
+            sender_id, conversation_id
+        );
+        insert_body["shadow_hidden"] = json!(true);
+    }
+
+    // Use a cached preview straight away; anything else is fetched after
+    // the message has gone out so a slow site never delays delivery.
+    let preview_url = if state.link_previews.enabled() {
+        link_preview::first_url(&content)
+    } else {
+        None
+    };
+    let cached_preview = preview_url
+        .as_deref()
+        .and_then(|url| state.link_previews.cached(url));
+    if let Some(Some(preview)) = &cached_preview {
+        insert_body["link_preview"] = json!(preview);
+    }
+
+    if let Some(interval) = slow_mode {
+        reserve_slow_mode_slot(state, conversation_id, sender_id, interval)?;
+    }
+    let stored = match state.store.insert_message(insert_body).await {
+        Ok(row) => Some(row),
+        Err(e) => {
+            error!("[deliver_message] Failed to persist message: {}", e);
+            None
+        }
+    };
+
+    if stored.is_some() && !shadow {
+        increment_message_count(state, conversation_id).await;
+    }
+
+    let broadcast_msg = WsBroadcast {
+        id: stored.as_ref().and_then(|m| m.id),
+        sender_id,
+        content,
+        content_html,
+        created_at: stored
+            .and_then(|m| m.created_at)
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        is_bot,
+        components: incoming.components,
+        attachment: incoming.attachment.map(Box::new),
+        link_preview: cached_preview.clone().flatten().map(Box::new),
+        poll: incoming.poll.map(Box::new),
+        sticker: sticker.map(Box::new),
+        client_id: incoming.client_id,
+        edited_at: None,
+    };
+
+    for (source, reason) in flags {
+        admin_events::publish(
+            state,
+            AdminEvent::MessageFlagged(FlaggedMessage {
+                message_id: broadcast_msg.id,
+                conversation_id,
+                sender_id,
+                content: broadcast_msg.content.clone(),
+                source: source.to_string(),
+                reason,
+            }),
+        )
+        .await;
+    }
+
+    // Only the sender's own sockets get it: no badges, no preview fetch.
+    if shadow {
+        publish(
+            state,
+            conversation_id,
+            WsEvent::ShadowMessage(ShadowMessage {
+                message: broadcast_msg.clone(),
+            }),
+        )
+        .await;
+        return Ok(broadcast_msg);
+    }
+
+    // Broadcast to all connected clients in this conversation.
+    publish(
+        state,
+        conversation_id,
+        message_event(announcement, broadcast_msg.clone()),
+    )
+    .await;
+
+    // Badge updates for members who aren't looking at this conversation.
+    if let Some(message_id) = broadcast_msg.id {
+        tokio::spawn(notify_new_message(
+            state.clone(),
+            NewMessageNotice {
+                conversation_id,
+                message_id,
+                sender_id,
+                created_at: broadcast_msg.created_at.clone(),
+                mentioned: false,
+                silent: false,
+            },
+            broadcast_msg.content.clone(),
+        ));
+    }
+
+    if let (Some(url), None, Some(message_id)) = (preview_url, cached_preview, broadcast_msg.id) {
+        tokio::spawn(attach_link_preview(
+            state.clone(),
+            conversation_id,
+            message_id,
+            url,
+        ));
+    }
+
+    Ok(broadcast_msg)
+}
+
+/// The message `sender_id` already sent with this `client_id`, if it was
+/// within the dedupe window. A retry after a dropped connection gets the
+/// original back (and nothing is broadcast twice); the sender can pick up
+/// anything it missed through `?since=`.
+pub async fn find_sent_message(
+    state: &AppState,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    client_id: Option<&str>,
+) -> Result<Option<WsBroadcast>, ApiError> {
+    let client_id = match client_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let window =
+        chrono::Duration::from_std(state.config.messages.dedupe_window).unwrap_or_default();
+    let since = to_timestamp(chrono::Utc::now() - window);
+
+    let mut rows = state
+        .store
+        .messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                sender_id: Some(sender_id),
+                client_id: Some(client_id.to_string()),
+                created_from: Some(since),
+                ..Default::default()
+            },
+            Order::Asc,
+            Some(1),
+        )
+        .await?;
+    attach_polls(state, &mut rows, sender_id).await?;
+
+    let row = match rows.pop() {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    info!(
+        "[deliver_message] Dropped repeated send {} from {} (message {:?})",
+        client_id, sender_id, row.id
+    );
+    Ok(Some(broadcast_from_row(row)))
+}
+
+/// Client ids are opaque, but keep them short enough to store and index.
+pub fn validate_client_id(client_id: Option<&str>) -> Result<(), ApiError> {
+    match client_id {
+        Some(id) if id.is_empty() || id.len() > 64 => Err(ApiError::BadRequest(
+            "client_id must be between 1 and 64 bytes".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fetch the preview for a just-sent message, store it on the row and tell
+/// the conversation. Runs in the background; failures only mean no preview.
+async fn attach_link_preview(state: AppState, conversation_id: Uuid, message_id: i64, url: String) {
+    let preview = match state.link_previews.preview(&url).await {
+        Some(p) => p,
+        None => return,
+    };
+
+    if let Err(e) = state
+        .store
+        .update_message(message_id, json!({ "link_preview": preview }))
+        .await
+    {
+        error!(
+            "[link_preview] Failed to store preview for message {}: {}",
+            message_id, e
+        );
+    }
+
+    publish(
+        &state,
+        conversation_id,
+        WsEvent::LinkPreview(LinkPreviewUpdate {
+            message_id,
+            link_preview: preview,
+        }),
+    )
+    .await;
+}
+
+/// Forward a user's click on a bot message to the bot that sent it.
+async fn handle_component_interaction(
+    state: &AppState,
+    conversation_id: Uuid,
+    user_id: Uuid,
+    req: ComponentInteractionRequest,
+) -> Result<(), ApiError> {
+    let message = match state.store.message(req.message_id).await? {
+        Some(m) if m.conversation_id == conversation_id => m,
+        _ => return Err(ApiError::NotFound("Message not found".into())),
+    };
+
+    let component = message
+        .components
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .find(|c| c.id() == req.component_id)
+        .ok_or_else(|| ApiError::BadRequest("Unknown component".into()))?;
+
+    // Menus must report one of their own options.
+    if let MessageComponent::Select { options, .. } = component {
+        let valid = req
+            .value
+            .as_deref()
+            .is_some_and(|v| options.iter().any(|o| o.value == v));
+        if !valid {
+            return Err(ApiError::BadRequest("Invalid option for this menu".into()));
+        }
+    }
+
+    publish(
+        state,
+        conversation_id,
+        WsEvent::ComponentInteraction(ComponentInteraction {
+            message_id: req.message_id,
+            component_id: req.component_id,
+            value: req.value,
+            user_id,
+            bot_id: message.sender_id,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }),
+    )
+    .await;
+
+    Ok(())
 }
 
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Extract conversation_id UUIDs from a Vec of conversation_members rows.
-fn extract_conversation_ids(rows: &[serde_json::Value]) -> Vec<Uuid> {
-    let mut ids = Vec::new();
-    for row in rows {
-        if let Some(cid_str) = row.get("conversation_id").and_then(|v| v.as_str()) {
-            if let Ok(cid) = Uuid::parse_str(cid_str) {
-                ids.push(cid);
+/// The conversation ids of membership rows.
+fn conversation_ids(members: &[MemberRow]) -> Vec<Uuid> {
+    members.iter().map(|m| m.conversation_id).collect()
+}
+
+/// Check message content against the configured limits.
+/// Every send path (WebSocket, REST) must go through this before persisting.
+pub fn validate_content(config: &MessageConfig, content: &str) -> Result<(), ApiError> {
+    if content.chars().count() > config.max_length {
+        return Err(ApiError::MessageTooLong {
+            max: config.max_length,
+        });
+    }
+    Ok(())
+}
+
+/// Get or create the broadcast channel for a conversation and subscribe to it.
+/// Subscribing while still holding the lock means `release_channel` can't
+/// remove the channel in between.
+pub async fn subscribe(
+    channels: &ConversationChannels,
+    conversation_id: Uuid,
+) -> broadcast::Receiver<WsEvent> {
+    let mut map = channels.write().await;
+    map.entry(conversation_id)
+        .or_insert_with(|| {
+            let (tx, _) = broadcast::channel(256);
+            tx
+        })
+        .subscribe()
+}
+
+/// Releases a conversation's channel when dropped, for subscribers (like SSE
+/// streams) that end by being dropped rather than by returning.
+struct ChannelGuard {
+    channels: ConversationChannels,
+    conversation_id: Uuid,
+}
+
+impl Drop for ChannelGuard {
+    fn drop(&mut self) {
+        let channels = self.channels.clone();
+        let conversation_id = self.conversation_id;
+        tokio::spawn(async move { release_channel(&channels, conversation_id).await });
+    }
+}
+
+/// Remove a conversation's broadcast channel once its last subscriber has
+/// disconnected, so the map doesn't grow forever on long-running servers.
+pub async fn release_channel(channels: &ConversationChannels, conversation_id: Uuid) {
+    let mut map = channels.write().await;
+    if map
+        .get(&conversation_id)
+        .is_some_and(|tx| tx.receiver_count() == 0)
+    {
+        map.remove(&conversation_id);
+    }
+}
+
+/// Send an event to everyone connected to the conversation, on any instance.
+pub async fn publish(state: &AppState, conversation_id: Uuid, event: WsEvent) {
+    state
+        .bus
+        .publish(&state.channels, conversation_id, event)
+        .await;
+}
+
+/// Schema checks for bot-supplied message components.
+fn validate_components(components: &[MessageComponent]) -> Result<(), ApiError> {
+    const MAX_COMPONENTS: usize = 5;
+    const MAX_OPTIONS: usize = 25;
+    const MAX_ID_LEN: usize = 64;
+    const MAX_LABEL_LEN: usize = 80;
+
+    if components.len() > MAX_COMPONENTS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} components per message",
+            MAX_COMPONENTS
+        )));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for component in components {
+        let id = component.id();
+        if id.is_empty() || id.len() > MAX_ID_LEN {
+            return Err(ApiError::BadRequest(format!(
+                "Component id must be 1-{} characters",
+                MAX_ID_LEN
+            )));
+        }
+        if !seen.insert(id) {
+            return Err(ApiError::BadRequest(format!(
+                "Duplicate component id '{}'",
+                id
+            )));
+        }
+
+        let label_ok = |l: &str| !l.trim().is_empty() && l.chars().count() <= MAX_LABEL_LEN;
+        match component {
+            MessageComponent::Button { label, style, .. } => {
+                if !label_ok(label) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Button label must be 1-{} characters",
+                        MAX_LABEL_LEN
+                    )));
+                }
+                if let Some(style) = style {
+                    if !["primary", "secondary", "danger"].contains(&style.as_str()) {
+                        return Err(ApiError::BadRequest(format!(
+                            "Unknown button style '{}'",
+                            style
+                        )));
+                    }
+                }
+            }
+            MessageComponent::Select { options, .. } => {
+                if options.is_empty() || options.len() > MAX_OPTIONS {
+                    return Err(ApiError::BadRequest(format!(
+                        "Menus need 1-{} options",
+                        MAX_OPTIONS
+                    )));
+                }
+                if options
+                    .iter()
+                    .any(|o| !label_ok(&o.label) || o.value.is_empty())
+                {
+                    return Err(ApiError::BadRequest(
+                        "Every menu option needs a label and a value".into(),
+                    ));
+                }
             }
         }
     }
-    ids
+
+    Ok(())
+}
+
+/// Build a `{"type":"error","code":...,"message":...}` frame for a single
+/// client, in that client's wire format.
+pub fn error_frame(format: WireFormat, code: &str, message: &str) -> Option<Message> {
+    format.encode(&json!({ "type": "error", "code": code, "message": message }))
+}
+
+/// `error_frame` for an error a client frame ran into, with
+/// `retry_after_secs` added when the error says how long to wait.
+fn api_error_frame(format: WireFormat, e: &ApiError) -> Option<Message> {
+    let mut frame = json!({ "type": "error", "code": e.code(), "message": e.to_string() });
+    if let Some(secs) = e.retry_after_secs() {
+        frame["retry_after_secs"] = json!(secs);
+    }
+    format.encode(&frame)
+}
+
+/// Refuse a message from `sender_id` if their previous one in the
+/// conversation was less than `interval_secs` ago. Deleted messages still
+/// count, so deleting one doesn't reset the wait.
+async fn check_slow_mode(
+    state: &AppState,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    interval_secs: u64,
+) -> Result<(), ApiError> {
+    let last_sent = state
+        .store
+        .messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                sender_id: Some(sender_id),
+                ..Default::default()
+            },
+            Order::Desc,
+            Some(1),
+        )
+        .await?
+        .pop()
+        .and_then(|m| m.created_at?.parse::<chrono::DateTime<chrono::Utc>>().ok());
+    let last_sent = match last_sent {
+        Some(t) => t,
+        None => return Ok(()),
+    };
+
+    let elapsed = (chrono::Utc::now() - last_sent).num_seconds().max(0) as u64;
+    if elapsed < interval_secs {
+        return Err(ApiError::SlowMode {
+            interval_secs,
+            retry_after_secs: interval_secs - elapsed,
+        });
+    }
+    Ok(())
+}
+
+/// Claim `sender_id`'s next slow mode slot in the conversation, just before
+/// their message is stored. `check_slow_mode` reads the last stored message,
+/// so two sends racing each other would both pass it; only one of them gets
+/// the slot here.
+fn reserve_slow_mode_slot(
+    state: &AppState,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    interval_secs: u64,
+) -> Result<(), ApiError> {
+    // Past this many entries, slots that have run out are dropped.
+    const PRUNE_ABOVE: usize = 10_000;
+
+    let interval = std::time::Duration::from_secs(interval_secs);
+    let mut slots = state.slow_mode.lock().unwrap();
+    if let Some((sent_at, _)) = slots.get(&(conversation_id, sender_id)) {
+        let elapsed = sent_at.elapsed();
+        if elapsed < interval {
+            return Err(ApiError::SlowMode {
+                interval_secs,
+                retry_after_secs: (interval - elapsed).as_secs().max(1),
+            });
+        }
+    }
+    if slots.len() > PRUNE_ABOVE {
+        slots.retain(|_, (sent_at, interval)| sent_at.elapsed() < *interval);
+    }
+    slots.insert((conversation_id, sender_id), (Instant::now(), interval));
+    Ok(())
+}
+
+/// Bump the conversation's stored message counter by one.
+async fn increment_message_count(state: &AppState, conversation_id: Uuid) {
+    if let Err(e) = state.store.increment_message_count(conversation_id).await {
+        error!(
+            "[increment_message_count] conversation {}: {}",
+            conversation_id, e
+        );
+    }
+}
+
+/// Read the stored message counter, avoiding a `count(*)` over `messages`.
+pub async fn fetch_message_count(state: &AppState, conversation_id: Uuid) -> Result<i64, ApiError> {
+    match state.store.conversation(conversation_id).await {
+        Ok(c) => Ok(c.message_count.unwrap_or(0)),
+        Err(ApiError::NotFound(_)) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Load up to `limit` messages newer than `since` (by id), oldest first,
+/// shaped like live broadcasts so the client handles them identically.
+async fn fetch_messages_since(
+    state: &AppState,
+    conversation_id: Uuid,
+    viewer: Uuid,
+    since: i64,
+    limit: usize,
+) -> Result<Vec<WsEvent>, ApiError> {
+    let announcement = state
+        .store
+        .conversation(conversation_id)
+        .await?
+        .is_announcement
+        .unwrap_or(false);
+    let mut rows = state
+        .store
+        .messages(
+            &MessageFilter {
+                conversation_id: Some(conversation_id),
+                after_id: Some(since),
+                visible_to: Some(viewer),
+                ..Default::default()
+            },
+            Order::Asc,
+            Some(limit),
+        )
+        .await?;
+    attach_polls(state, &mut rows, viewer).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| message_event(announcement, broadcast_from_row(row)))
+        .collect())
+}
+
+/// Wrap a message in the event its conversation sends it as.
+fn message_event(announcement: bool, msg: WsBroadcast) -> WsEvent {
+    if announcement {
+        WsEvent::Announcement(msg)
+    } else {
+        WsEvent::Message(msg)
+    }
+}
+
+/// Allow the conversation's owner or an admin. Returns true when it is an
+/// admin acting on a conversation they don't own.
+pub async fn require_owner_or_admin(
+    state: &AppState,
+    conversation_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, ApiError> {
+    let member = state.store.member(conversation_id, user_id).await?;
+    if member.as_ref().is_some_and(|m| m.is_owner()) {
+        return Ok(false);
+    }
+    if state.store.profile(user_id).await?.is_admin() {
+        return Ok(true);
+    }
+    match member {
+        Some(_) => Err(ApiError::Forbidden(
+            "Only the group owner can change this".into(),
+        )),
+        None => Err(ApiError::Forbidden(
+            "You are not a member of this conversation".into(),
+        )),
+    }
+}
+
+/// Shape a stored message like a live broadcast.
+fn broadcast_from_row(msg: MessageRow) -> WsBroadcast {
+    WsBroadcast {
+        id: msg.id,
+        sender_id: msg.sender_id,
+        content: msg.content,
+        content_html: msg.content_html,
+        created_at: msg.created_at.unwrap_or_default(),
+        is_bot: msg.is_bot.unwrap_or(false),
+        components: msg.components,
+        attachment: msg.attachment.map(Box::new),
+        link_preview: msg.link_preview.map(Box::new),
+        poll: msg.poll.map(Box::new),
+        sticker: msg.sticker.map(Box::new),
+        client_id: msg.client_id,
+        edited_at: msg.edited_at,
+    }
 }
 
 /// Check that the given user is a member of the conversation. Returns an error if not.
-async fn verify_membership(
+pub async fn verify_membership(
     state: &AppState,
     conversation_id: Uuid,
     user_id: Uuid,
@@ -367,21 +2055,17 @@ async fn verify_membership(
         conversation_id, user_id
     );
 
-    let rows = state
-        .supabase
-        .select("conversation_members")
-        .eq("conversation_id", &conversation_id.to_string())
-        .eq("user_id", &user_id.to_string())
-        .execute()
+    let is_member = state
+        .store
+        .member(conversation_id, user_id)
         .await
+        .map(|m| m.is_some())
         .map_err(|e| {
             error!("[verify_membership] Database error: {}", e);
-            ApiError::Database(e.to_string())
+            e
         })?;
 
-    info!("[verify_membership] Found {} membership rows", rows.len());
-
-    if rows.is_empty() {
+    if !is_member {
         error!(
             "[verify_membership] User {} is not a member of conversation {}",
             user_id, conversation_id
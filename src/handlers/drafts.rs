@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::verify_membership;
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{DraftRow, SaveDraftRequest};
+use crate::AppState;
+
+/// Drafts can be a bit longer than a message; they're trimmed on send.
+const MAX_DRAFT_CHARS: usize = 8000;
+
+// ---------------------------------------------------------------------------
+// PUT /conversations/{id}/draft  –  save (or clear) the caller's draft
+// ---------------------------------------------------------------------------
+
+/// Last write wins by the client's edit time, not arrival order. A save
+/// that lost returns `"applied": false` with the draft that beat it.
+pub async fn save_draft_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<SaveDraftRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    if body.content.chars().count() > MAX_DRAFT_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Draft must be at most {} characters",
+            MAX_DRAFT_CHARS
+        )));
+    }
+
+    // Clamp to now so a device with a fast clock can't pin its draft.
+    let now = Utc::now();
+    let edited_at = match body.updated_at.as_deref() {
+        Some(raw) => match DateTime::parse_from_rfc3339(raw) {
+            Ok(t) => t.with_timezone(&Utc).min(now),
+            Err(_) => {
+                return Err(ApiError::BadRequest(
+                    "updated_at must be an RFC 3339 timestamp".into(),
+                ))
+            }
+        },
+        None => now,
+    };
+
+    let current = state.store.draft(me, conversation_id).await?;
+    if let Some(current) = current {
+        let newer = DateTime::parse_from_rfc3339(&current.updated_at)
+            .is_ok_and(|t| t.with_timezone(&Utc) > edited_at);
+        if newer {
+            return Ok(Json(json!({ "draft": current, "applied": false })));
+        }
+    }
+
+    if body.content.trim().is_empty() {
+        state.store.delete_draft(me, conversation_id).await?;
+        return Ok(Json(json!({ "draft": null, "applied": true })));
+    }
+
+    let draft = DraftRow {
+        user_id: me,
+        conversation_id,
+        content: body.content,
+        updated_at: to_timestamp(edited_at),
+    };
+    let stored = state.store.save_draft(&draft).await?;
+
+    Ok(Json(json!({ "draft": stored, "applied": true })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /drafts  –  every draft the caller has, newest first
+// ---------------------------------------------------------------------------
+
+pub async fn list_drafts_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let drafts = state.store.drafts_of(me).await?;
+
+    Ok(Json(json!({ "drafts": drafts })))
+}
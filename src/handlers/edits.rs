@@ -0,0 +1,235 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use tower_cookies::Cookies;
+use tracing::{info, warn};
+
+use crate::admin_events;
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{publish, validate_content, verify_membership};
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{
+    AdminEvent, EditMessageRequest, FlaggedMessage, MessageEdited, MessageRevisionRow, MessageRow,
+    ShadowEdit, WsEvent,
+};
+use crate::moderation::ModerationVerdict;
+use crate::spam::{self, ScoreAction};
+use crate::spam_policy::SpamVerdict;
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// PATCH /messages/{id}  –  edit one of the caller's messages
+// ---------------------------------------------------------------------------
+
+/// The replaced content is kept in `message_revisions` before the message
+/// is updated, so every version stays visible through the history endpoint.
+/// The new content goes through the same spam and moderation checks as a
+/// new message; an edit that would be held is refused instead, since a
+/// held edit has nowhere to go once approved.
+pub async fn edit_message_handler(
+    State(state): State<AppState>,
+    Path(message_id): Path<i64>,
+    cookies: Cookies,
+    Json(body): Json<EditMessageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let message = fetch_live_message(&state, message_id).await?;
+    verify_membership(&state, message.conversation_id, me).await?;
+
+    if message.sender_id != me {
+        return Err(ApiError::Forbidden(
+            "You can only edit your own messages".into(),
+        ));
+    }
+
+    let sanitized = state.content_policy.sanitize(&body.content)?;
+    let conversation = state.store.conversation(message.conversation_id).await?;
+    let filtered = state.word_filter.apply(
+        &sanitized.content,
+        conversation.allow_adult_content.unwrap_or(false),
+    )?;
+    let content_html = if filtered.masked {
+        state.content_policy.render_html(&filtered.content)
+    } else {
+        sanitized.html
+    };
+    let has_media = message.attachment.is_some() || message.sticker.is_some();
+    if filtered.content.trim().is_empty() && !has_media {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
+    }
+    validate_content(&state.config.messages, &filtered.content)?;
+
+    if filtered.content == message.content {
+        return Ok(Json(message));
+    }
+
+    let conversation_id = message.conversation_id;
+    let is_bot = message.is_bot.unwrap_or(false);
+
+    let spam = if is_bot {
+        SpamVerdict::Allow
+    } else {
+        match state
+            .spam
+            .check(me, &filtered.content, state.rate_limits.send_limit(me))
+        {
+            Err(e @ ApiError::RateLimited { .. }) => {
+                admin_events::report_rate_limit(&state, me, "spam", Some(conversation_id)).await;
+                return Err(e);
+            }
+            verdict => verdict?,
+        }
+    };
+
+    if !is_bot && state.spam_scores.enabled() {
+        state.spam_scores.check_slowed(me)?;
+        let (score, action) = spam::score_message(&state, me, &filtered.content).await?;
+        match action {
+            ScoreAction::Allow => {}
+            ScoreAction::Hold => {
+                warn!(
+                    "[edit_message] Refusing edit of {} by {} ({})",
+                    message_id,
+                    me,
+                    score.describe()
+                );
+                return Err(edit_held());
+            }
+            ScoreAction::SlowDown(wait) => {
+                admin_events::report_rate_limit(&state, me, "spam_score", Some(conversation_id))
+                    .await;
+                return Err(ApiError::rate_limited(wait));
+            }
+        }
+    }
+
+    let moderation = if is_bot {
+        ModerationVerdict::Allow
+    } else {
+        state.moderation.check(&filtered.content).await?
+    };
+    if let ModerationVerdict::Hold(categories) = &moderation {
+        warn!(
+            "[edit_message] Refusing edit of {} by {} ({})",
+            message_id, me, categories
+        );
+        return Err(edit_held());
+    }
+
+    let revision = MessageRevisionRow {
+        id: None,
+        message_id,
+        conversation_id,
+        content: message.content.clone(),
+        content_html: message.content_html.clone(),
+        edited_by: me,
+        created_at: None,
+    };
+    state.store.add_revision(&revision).await?;
+
+    let edited_at = to_timestamp(Utc::now());
+    // (source, reason) for the moderators' live feed.
+    let mut flags: Vec<(&str, String)> = Vec::new();
+    let mut update = json!({
+        "content": filtered.content,
+        "content_html": content_html,
+        "filter_flag": filtered.flag,
+        "edited_at": edited_at,
+    });
+    if let SpamVerdict::Flag(reason) = spam {
+        update["spam_flag"] = json!(reason);
+        flags.push(("spam", reason.to_string()));
+    }
+    if let Some(terms) = &filtered.flag {
+        flags.push(("word_filter", terms.clone()));
+    }
+    if let ModerationVerdict::Flag(categories) = moderation {
+        update["moderation_flag"] = json!(categories);
+        flags.push(("moderation", categories));
+    }
+
+    let updated = match state.store.update_message(message_id, update).await? {
+        Some(m) => m,
+        None => return Err(ApiError::NotFound("Message not found".into())),
+    };
+
+    info!(
+        "[edit_message] {} edited message {} in {}",
+        me, message_id, conversation_id
+    );
+
+    for (source, reason) in flags {
+        admin_events::publish(
+            &state,
+            AdminEvent::MessageFlagged(FlaggedMessage {
+                message_id: Some(message_id),
+                conversation_id,
+                sender_id: me,
+                content: updated.content.clone(),
+                source: source.to_string(),
+                reason,
+            }),
+        )
+        .await;
+    }
+
+    let edit = MessageEdited {
+        message_id,
+        content: updated.content.clone(),
+        content_html: updated.content_html.clone(),
+        edited_at,
+    };
+    // A hidden message's edit goes to its sender alone, like the message.
+    let event = if message.shadow_hidden.unwrap_or(false) {
+        WsEvent::ShadowEdit(ShadowEdit {
+            sender_id: me,
+            edit,
+        })
+    } else {
+        WsEvent::MessageEdited(edit)
+    };
+    publish(&state, conversation_id, event).await;
+
+    Ok(Json(updated))
+}
+
+// ---------------------------------------------------------------------------
+// GET /messages/{id}/history  –  every earlier version of a message
+// ---------------------------------------------------------------------------
+
+/// Any member can read the history, oldest version first. Deleted
+/// messages have none: their revisions are removed with them.
+pub async fn message_history_handler(
+    State(state): State<AppState>,
+    Path(message_id): Path<i64>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let message = fetch_live_message(&state, message_id).await?;
+    verify_membership(&state, message.conversation_id, me).await?;
+
+    let revisions = state.store.revisions(message_id).await?;
+
+    Ok(Json(json!({
+        "message_id": message_id,
+        "content": message.content,
+        "edited_at": message.edited_at,
+        "revisions": revisions,
+    })))
+}
+
+fn edit_held() -> ApiError {
+    ApiError::BadRequest("This edit needs moderator review, so it was not applied".into())
+}
+
+pub async fn fetch_live_message(state: &AppState, message_id: i64) -> Result<MessageRow, ApiError> {
+    match state.store.message(message_id).await? {
+        Some(m) if !m.is_deleted.unwrap_or(false) => Ok(m),
+        _ => Err(ApiError::NotFound("Message not found".into())),
+    }
+}
@@ -0,0 +1,305 @@
+use axum::extract::multipart::Multipart;
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, require_admin};
+use crate::handlers::attachments::{bad_multipart, looks_like_image};
+use crate::handlers::auth::get_session;
+use crate::models::{CreateEmojiPackRequest, EmojiPackRow, EmojiRow, Sticker};
+use crate::storage;
+use crate::AppState;
+
+/// Image types accepted for emoji and stickers; all can be animated or
+/// transparent, and all are checked by magic bytes.
+const ALLOWED_MIME_TYPES: [&str; 3] = ["image/png", "image/gif", "image/webp"];
+
+const PACK_KINDS: [&str; 2] = ["emoji", "sticker"];
+
+// ---------------------------------------------------------------------------
+// GET /emoji  –  every pack with its emoji/stickers
+// ---------------------------------------------------------------------------
+
+pub async fn list_emoji_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    get_session(&state, &cookies)?;
+
+    let packs = state.store.emoji_packs().await?;
+    let items = state.store.all_emoji().await?;
+
+    let packs: Vec<serde_json::Value> = packs
+        .into_iter()
+        .map(|pack| {
+            let pack_items: Vec<serde_json::Value> = items
+                .iter()
+                .filter(|e| e.pack_id == pack.id)
+                .map(|e| json!({ "id": e.id, "name": e.name, "url": e.url }))
+                .collect();
+            json!({
+                "id": pack.id,
+                "name": pack.name,
+                "kind": pack.kind,
+                "items": pack_items,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "packs": packs })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/emoji-packs  –  create a pack (admin only)
+// ---------------------------------------------------------------------------
+
+pub async fn create_emoji_pack_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateEmojiPackRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let name = body.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > 64 {
+        return Err(ApiError::BadRequest(
+            "Pack name must be between 1 and 64 characters".into(),
+        ));
+    }
+    let kind = body.kind.unwrap_or_else(|| "emoji".into());
+    if !PACK_KINDS.contains(&kind.as_str()) {
+        return Err(ApiError::BadRequest(
+            "kind must be 'emoji' or 'sticker'".into(),
+        ));
+    }
+
+    let pack = EmojiPackRow {
+        id: Uuid::new_v4(),
+        name,
+        kind,
+        created_by: admin.id,
+        created_at: None,
+    };
+    state.store.insert_emoji_pack(&pack).await?;
+
+    audit(
+        &state,
+        admin.id,
+        "emoji_pack.create",
+        pack.id,
+        json!({ "name": pack.name, "kind": pack.kind }),
+    )
+    .await;
+    Ok(Json(pack))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/emoji-packs/{id}  –  remove a pack and its images
+// ---------------------------------------------------------------------------
+
+pub async fn delete_emoji_pack_handler(
+    State(state): State<AppState>,
+    Path(pack_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let paths: Vec<String> = state
+        .store
+        .delete_emoji_pack(pack_id)
+        .await?
+        .into_iter()
+        .map(|e| e.path)
+        .collect();
+    remove_images(&state, &paths).await;
+
+    audit(
+        &state,
+        admin.id,
+        "emoji_pack.delete",
+        pack_id,
+        json!({ "items": paths.len() }),
+    )
+    .await;
+    Ok(Json(json!({ "status": "deleted" })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/emoji-packs/{id}/items  –  upload one emoji or sticker
+// ---------------------------------------------------------------------------
+
+/// Multipart form with a `file` image part and a `name` text part.
+pub async fn upload_emoji_handler(
+    State(state): State<AppState>,
+    Path(pack_id): Path<Uuid>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+    let config = &state.config.emoji;
+
+    let pack = state.store.emoji_pack(pack_id).await?;
+
+    let mut name: Option<String> = None;
+    let mut upload: Option<(String, Vec<u8>)> = None;
+    while let Some(mut field) = multipart.next_field().await.map_err(bad_multipart)? {
+        match field.name() {
+            Some("name") => {
+                name = Some(field.text().await.map_err(bad_multipart)?);
+            }
+            Some("file") => {
+                let mime_type = field.content_type().unwrap_or_default().to_lowercase();
+                if !ALLOWED_MIME_TYPES.contains(&mime_type.as_str()) {
+                    return Err(ApiError::UnsupportedMediaType(format!(
+                        "'{}' is not allowed. Allowed: {}",
+                        mime_type,
+                        ALLOWED_MIME_TYPES.join(", ")
+                    )));
+                }
+                let mut data = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(bad_multipart)? {
+                    if data.len() + chunk.len() > config.max_bytes {
+                        return Err(ApiError::AttachmentTooLarge {
+                            max_bytes: config.max_bytes,
+                        });
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                upload = Some((mime_type, data));
+            }
+            _ => {} // Ignore unknown parts.
+        }
+    }
+
+    let name = validate_name(name.as_deref().unwrap_or_default())?;
+    let (mime_type, data) = match upload {
+        Some(u) => u,
+        None => return Err(ApiError::BadRequest("Missing 'file' part".into())),
+    };
+    if !looks_like_image(&mime_type, &data) {
+        return Err(ApiError::UnsupportedMediaType(format!(
+            "content does not match '{}'",
+            mime_type
+        )));
+    }
+
+    let id = Uuid::new_v4();
+    let extension = mime_type.trim_start_matches("image/");
+    let path = format!("{}/{}.{}", pack.id, id, extension);
+    storage::upload(&config.bucket, &path, &mime_type, data.into()).await?;
+
+    let emoji = EmojiRow {
+        id,
+        pack_id: pack.id,
+        name,
+        url: storage::public_url(&config.bucket, &path)?,
+        path,
+        mime_type,
+        created_at: None,
+    };
+    if let Err(e) = state.store.insert_emoji(&emoji).await {
+        // Don't leave an orphaned image behind.
+        remove_images(&state, std::slice::from_ref(&emoji.path)).await;
+        return Err(match e {
+            ApiError::UniqueViolation(_) => {
+                ApiError::UniqueViolation(format!("':{}:' is already taken", emoji.name))
+            }
+            other => other,
+        });
+    }
+
+    info!(
+        "[emoji] {} added {} '{}' to pack {}",
+        admin.id, pack.kind, emoji.name, pack.id
+    );
+    audit(
+        &state,
+        admin.id,
+        "emoji.create",
+        emoji.id,
+        json!({ "name": emoji.name, "pack_id": pack.id }),
+    )
+    .await;
+    Ok(Json(emoji))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/emoji/{id}  –  remove one emoji or sticker
+// ---------------------------------------------------------------------------
+
+pub async fn delete_emoji_handler(
+    State(state): State<AppState>,
+    Path(emoji_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let emoji = match state.store.delete_emoji(emoji_id).await? {
+        Some(emoji) => emoji,
+        None => return Err(ApiError::NotFound("Emoji not found".into())),
+    };
+    remove_images(&state, std::slice::from_ref(&emoji.path)).await;
+
+    audit(
+        &state,
+        admin.id,
+        "emoji.delete",
+        emoji_id,
+        json!({ "name": emoji.name }),
+    )
+    .await;
+    Ok(Json(json!({ "status": "deleted" })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Look up a sticker for a `message_type` "sticker" message. Items from
+/// emoji packs can't be sent on their own.
+pub async fn fetch_sticker(state: &AppState, id: Uuid) -> Result<Sticker, ApiError> {
+    let emoji = match state.store.emoji(id).await? {
+        Some(emoji) => emoji,
+        None => return Err(ApiError::NotFound("Sticker not found".into())),
+    };
+    if state.store.emoji_pack(emoji.pack_id).await?.kind != "sticker" {
+        return Err(ApiError::BadRequest("That emoji is not a sticker".into()));
+    }
+
+    Ok(Sticker {
+        id: emoji.id,
+        pack_id: emoji.pack_id,
+        name: emoji.name,
+        url: emoji.url,
+    })
+}
+
+/// Names are used as `:name:`, so keep them to lowercase ASCII words.
+fn validate_name(raw: &str) -> Result<String, ApiError> {
+    let name = raw.trim().trim_matches(':').to_lowercase();
+    let valid = (2..=32).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(name)
+    } else {
+        Err(ApiError::BadRequest(
+            "Name must be 2–32 characters of a-z, 0-9 and _".into(),
+        ))
+    }
+}
+
+/// Best-effort: a leftover image is harmless once its row is gone.
+async fn remove_images(state: &AppState, paths: &[String]) {
+    if let Err(e) = storage::remove(&state.config.emoji.bucket, paths).await {
+        error!("[emoji] Failed to delete images: {}", e);
+    }
+}
@@ -0,0 +1,349 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::stream;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::db::Order;
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{fetch_message_count, verify_membership};
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{ExportQuery, ExportRow, MessageRow, ProfileRow};
+use crate::storage;
+use crate::store::{MessageFilter, Storage};
+use crate::AppState;
+
+/// Messages fetched per query while writing an export.
+const PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn parse(raw: Option<&str>) -> Result<Self, ApiError> {
+        match raw.unwrap_or("json") {
+            "json" => Ok(ExportFormat::Json),
+            "html" => Ok(ExportFormat::Html),
+            other => Err(ApiError::BadRequest(format!(
+                "Unknown export format '{}'; use 'json' or 'html'",
+                other
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// GET /conversations/{id}/export  –  full history as JSON or HTML
+// ---------------------------------------------------------------------------
+
+/// Small conversations are streamed straight back as a download. Larger
+/// ones are queued (202) for the export job; poll `GET /exports/{id}` or
+/// wait for the `export_ready` notification.
+pub async fn export_conversation_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    cookies: Cookies,
+) -> Result<Response, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+    let format = ExportFormat::parse(query.format.as_deref())?;
+
+    let count = fetch_message_count(&state, conversation_id).await?;
+    if count > state.config.exports.inline_max_messages {
+        let export = ExportRow {
+            id: Uuid::new_v4(),
+            user_id: me,
+            conversation_id,
+            format: format.as_str().to_string(),
+            status: "pending".into(),
+            path: None,
+            error: None,
+            requested_at: None,
+            completed_at: None,
+        };
+        state.store.insert_export(&export).await?;
+        info!(
+            "[export] {} queued export {} of {} ({} messages)",
+            me, export.id, conversation_id, count
+        );
+        return Ok((StatusCode::ACCEPTED, Json(json!({ "export": export }))).into_response());
+    }
+
+    let writer = ExportWriter::new(state.store.clone(), conversation_id, me, format);
+    let body = Body::from_stream(stream::unfold(writer, |mut writer| async move {
+        match writer.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), writer)),
+            Ok(None) => None,
+            // The status line is long gone; cutting the stream short is all we can do.
+            Err(e) => Some((Err(std::io::Error::other(e.to_string())), writer.finish())),
+        }
+    }));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"conversation-{}.{}\"",
+                    conversation_id,
+                    format.as_str()
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// ---------------------------------------------------------------------------
+// GET /exports/{id}  –  status of a background export
+// ---------------------------------------------------------------------------
+
+pub async fn get_export_handler(
+    State(state): State<AppState>,
+    Path(export_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let export = state.store.export(export_id, me).await?;
+
+    let download_url = match (&export.status[..], &export.path) {
+        ("completed", Some(path)) => Some(
+            storage::signed_url(
+                &state.config.exports.bucket,
+                path,
+                state.config.exports.url_ttl,
+            )
+            .await?,
+        ),
+        _ => None,
+    };
+
+    Ok(Json(
+        json!({ "export": export, "download_url": download_url }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Writer
+// ---------------------------------------------------------------------------
+
+/// Produces an export a page of messages at a time, so an inline download
+/// never holds more than one page of rows in memory.
+pub struct ExportWriter {
+    store: Arc<dyn Storage>,
+    conversation_id: Uuid,
+    /// Whose export this is; decides which shadow-hidden messages show.
+    viewer: Uuid,
+    format: ExportFormat,
+    /// Keyset cursor: the id of the last message written.
+    last_id: i64,
+    written: usize,
+    started: bool,
+    done: bool,
+    senders: HashMap<Uuid, ProfileRow>,
+}
+
+impl ExportWriter {
+    pub fn new(
+        store: Arc<dyn Storage>,
+        conversation_id: Uuid,
+        viewer: Uuid,
+        format: ExportFormat,
+    ) -> Self {
+        Self {
+            store,
+            conversation_id,
+            viewer,
+            format,
+            last_id: 0,
+            written: 0,
+            started: false,
+            done: false,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// The next piece of the document, or `None` once it is complete.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, ApiError> {
+        if self.done {
+            return Ok(None);
+        }
+        if !self.started {
+            self.started = true;
+            return Ok(Some(self.header().into_bytes()));
+        }
+
+        let page = self
+            .store
+            .messages(
+                &MessageFilter {
+                    conversation_id: Some(self.conversation_id),
+                    after_id: Some(self.last_id),
+                    live_only: true,
+                    visible_to: Some(self.viewer),
+                    ..Default::default()
+                },
+                Order::Asc,
+                Some(PAGE_SIZE),
+            )
+            .await?;
+        if page.is_empty() {
+            self.done = true;
+            return Ok(Some(self.footer().into_bytes()));
+        }
+
+        self.resolve_senders(&page).await?;
+        let mut out = String::new();
+        for message in &page {
+            self.last_id = message.id.unwrap_or(self.last_id);
+            self.write_message(&mut out, message);
+            self.written += 1;
+        }
+        Ok(Some(out.into_bytes()))
+    }
+
+    /// Stop producing chunks, e.g. after an error.
+    fn finish(mut self) -> Self {
+        self.done = true;
+        self
+    }
+
+    /// Look up profiles for senders not seen on earlier pages.
+    async fn resolve_senders(&mut self, page: &[MessageRow]) -> Result<(), ApiError> {
+        let mut missing: Vec<Uuid> = page
+            .iter()
+            .map(|m| m.sender_id)
+            .filter(|id| !self.senders.contains_key(id))
+            .collect();
+        missing.sort();
+        missing.dedup();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        self.senders.extend(self.store.profiles(&missing).await?);
+        Ok(())
+    }
+
+    fn header(&self) -> String {
+        let exported_at = to_timestamp(chrono::Utc::now());
+        match self.format {
+            ExportFormat::Json => format!(
+                "{{\"conversation_id\":\"{}\",\"exported_at\":\"{}\",\"messages\":[",
+                self.conversation_id, exported_at
+            ),
+            ExportFormat::Html => format!(
+                "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+                 <title>Conversation {id}</title>\
+                 <style>body{{font-family:sans-serif;max-width:50em;margin:auto}}\
+                 .msg{{margin:.6em 0}}.author{{font-weight:bold}}\
+                 time{{color:#777;font-size:.85em;margin-left:.5em}}\
+                 p{{margin:.2em 0;white-space:pre-wrap}}</style></head>\n\
+                 <body><h1>Conversation {id}</h1><p>Exported {at}</p>\n",
+                id = self.conversation_id,
+                at = exported_at
+            ),
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self.format {
+            ExportFormat::Json => "]}".to_string(),
+            ExportFormat::Html => "</body></html>\n".to_string(),
+        }
+    }
+
+    fn write_message(&self, out: &mut String, message: &MessageRow) {
+        let sender = self.senders.get(&message.sender_id);
+        let username = sender.map(|p| p.username.as_str()).unwrap_or("unknown");
+        let display_name = sender
+            .and_then(|p| p.display_name.as_deref())
+            .unwrap_or(username);
+
+        match self.format {
+            ExportFormat::Json => {
+                if self.written > 0 {
+                    out.push(',');
+                }
+                let entry = json!({
+                    "id": message.id,
+                    "sender": {
+                        "id": message.sender_id,
+                        "username": username,
+                        "display_name": display_name,
+                    },
+                    "content": message.content,
+                    "message_type": message.message_type.as_deref().unwrap_or("text"),
+                    "attachment": message.attachment,
+                    "sticker": message.sticker,
+                    "created_at": message.created_at,
+                });
+                out.push_str(&entry.to_string());
+            }
+            ExportFormat::Html => {
+                out.push_str(&format!(
+                    "<div class=\"msg\"><span class=\"author\">{}</span><time>{}</time><p>{}</p>",
+                    escape_html(display_name),
+                    escape_html(message.created_at.as_deref().unwrap_or_default()),
+                    escape_html(&message.content)
+                ));
+                if let Some(a) = &message.attachment {
+                    out.push_str(&format!(
+                        "<p>[attachment: {}]</p>",
+                        escape_html(&a.file_name)
+                    ));
+                }
+                if let Some(s) = &message.sticker {
+                    out.push_str(&format!("<p>[sticker: {}]</p>", escape_html(&s.name)));
+                }
+                out.push_str("</div>\n");
+            }
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
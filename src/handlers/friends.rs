@@ -1,13 +1,36 @@
-use axum::{extract::State, response::IntoResponse, Json};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
 use serde_json::json;
+use std::collections::HashMap;
 use tower_cookies::Cookies;
+use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::error::ApiError;
 use crate::handlers::auth::get_session;
-use crate::models::{AddFriendRequest, FriendInfo, FriendRow, ProfileRow};
+use crate::handlers::blocks::{blocked_either_way, ensure_not_blocked};
+use crate::handlers::notifications::notify_user;
+use crate::handlers::settings::{fetch_notification_settings, fetch_settings_many, in_quiet_hours};
+use crate::models::{
+    AddFriendByCodeRequest, AddFriendByUsernameRequest, AddFriendRequest, FriendInfo,
+    FriendListQuery, FriendRequestNotice, FriendRequestPolicy, FriendRow, FriendSuggestion,
+    PageQuery, ProfileResponse, ProfileRow, UserEvent, CAP_FRIEND_REQUESTS,
+};
 use crate::AppState;
 
+/// Longest note that can accompany a friend request.
+const MAX_NOTE_CHARS: usize = 200;
+
+/// Friend codes leave out 0/O and 1/I so they survive being read aloud.
+/// 32 symbols keep `next_u32() % len` unbiased.
+const FRIEND_CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+const FRIEND_CODE_LEN: usize = 8;
+
 // ---------------------------------------------------------------------------
 // POST /friends
 // ---------------------------------------------------------------------------
@@ -17,65 +40,158 @@ pub async fn add_friend_handler(
     cookies: Cookies,
     Json(body): Json<AddFriendRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let me = get_session(&cookies)?;
+    let me = get_session(&state, &cookies)?;
+    Ok(Json(
+        send_friend_request(&state, me, body.friend_id, body.note).await?,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// POST /friends/by-username
+// ---------------------------------------------------------------------------
+
+/// Same as `POST /friends`, for when all the caller knows is a username.
+pub async fn add_friend_by_username_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<AddFriendByUsernameRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let username = state.config.username.normalize(&body.username);
+    let friend = match state.store.profile_by_username(&username).await? {
+        Some(friend) => friend,
+        None => return Err(ApiError::NotFound("User not found".into())),
+    };
+
+    Ok(Json(
+        send_friend_request(&state, me, friend.id, body.note).await?,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// POST /friends/by-code
+// ---------------------------------------------------------------------------
+
+pub async fn add_friend_by_code_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<AddFriendByCodeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let code = normalize_friend_code(&body.code);
+    if code.len() != FRIEND_CODE_LEN {
+        return Err(ApiError::NotFound("No user has that friend code".into()));
+    }
+    let friend = match state.store.profile_by_friend_code(&code).await? {
+        Some(friend) => friend,
+        None => return Err(ApiError::NotFound("No user has that friend code".into())),
+    };
+
+    Ok(Json(
+        send_friend_request(&state, me, friend.id, body.note).await?,
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// GET /me/friend-code  ·  POST /me/friend-code (regenerate)
+// ---------------------------------------------------------------------------
+
+/// Created on first request, so users who never share a code never get one.
+pub async fn get_friend_code_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let code = match state.store.profile(me).await?.friend_code {
+        Some(code) => code,
+        None => assign_friend_code(&state, me).await?,
+    };
+    Ok(Json(json!({ "friend_code": format_friend_code(&code) })))
+}
+
+/// Replace the caller's code; the old one stops working immediately.
+pub async fn regenerate_friend_code_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let code = assign_friend_code(&state, me).await?;
+    info!("[friends] {} regenerated their friend code", me);
+    Ok(Json(json!({ "friend_code": format_friend_code(&code) })))
+}
 
-    if me == body.friend_id {
+/// Send `me`'s friend request to `friend_id`, or accept theirs if they
+/// already asked (in which case `note` is dropped).
+async fn send_friend_request(
+    state: &AppState,
+    me: Uuid,
+    friend_id: Uuid,
+    note: Option<String>,
+) -> Result<serde_json::Value, ApiError> {
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > MAX_NOTE_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Note must be at most {} characters",
+            MAX_NOTE_CHARS
+        )));
+    }
+
+    if me == friend_id {
         return Err(ApiError::BadRequest(
             "You cannot add yourself as a friend".into(),
         ));
     }
 
+    // Bots may only send friend requests when explicitly allowed.
+    if !state.store.profile(me).await?.can(CAP_FRIEND_REQUESTS) {
+        return Err(ApiError::Forbidden(
+            "This bot is not allowed to send friend requests".into(),
+        ));
+    }
+
     // Check that the friend actually exists.
-    let friend_rows = state
-        .supabase
-        .select("profiles")
-        .eq("id", &body.friend_id.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    if friend_rows.is_empty() {
+    let friend = match state.store.profile(friend_id).await {
+        Ok(p) => p,
+        Err(ApiError::NotFound(_)) => return Err(ApiError::NotFound("User not found".into())),
+        Err(e) => return Err(e),
+    };
+    if friend.is_deactivated() {
         return Err(ApiError::NotFound("User not found".into()));
     }
+    ensure_not_blocked(state, me, friend_id).await?;
 
     // Enforce user_a < user_b so the UNIQUE constraint works.
-    let (user_a, user_b) = if me < body.friend_id {
-        (me, body.friend_id)
+    let (user_a, user_b) = if me < friend_id {
+        (me, friend_id)
     } else {
-        (body.friend_id, me)
+        (friend_id, me)
     };
 
     // Check if a friendship row already exists between these two users.
-    let existing_rows = state
-        .supabase
-        .select("friends")
-        .eq("user_a", &user_a.to_string())
-        .eq("user_b", &user_b.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    if !existing_rows.is_empty() {
+    if let Some(row) = state.store.friendship(user_a, user_b).await? {
         // A row already exists – check its status.
-        let row: FriendRow = serde_json::from_value(existing_rows[0].clone())
-            .map_err(|e| ApiError::Database(e.to_string()))?;
-
         match row.status.as_str() {
             "accepted" => {
                 return Err(ApiError::BadRequest("You are already friends".into()));
             }
+            "pending" if row.requester_id == Some(me) => {
+                return Err(ApiError::BadRequest("Friend request already sent".into()));
+            }
             "pending" => {
-                // If *I* am the one who received the request, accept it.
-                // If I sent it, tell the user it's already pending.
-                // In the simple model we just accept it from either side.
-                let row_id = row.id.map(|i| i.to_string()).unwrap_or_default();
+                // They already asked me, so asking back is the same as accepting.
+                let row_id = row.id.unwrap_or_default();
                 state
-                    .supabase
-                    .update("friends", &row_id, json!({ "status": "accepted" }))
-                    .await
-                    .map_err(|e| ApiError::Database(e.to_string()))?;
+                    .store
+                    .set_friendship_status(row_id, "accepted")
+                    .await?;
+                notify_request(state, friend_id, row_id, me, "accepted").await;
 
-                return Ok(Json(json!({ "status": "accepted" })));
+                return Ok(json!({ "status": "accepted", "request_id": row_id }));
             }
             "blocked" => {
                 return Err(ApiError::BadRequest("This friendship is blocked".into()));
@@ -89,177 +205,454 @@ pub async fn add_friend_handler(
         }
     }
 
-    // No existing row – create a new friendship with status "accepted" immediately.
-    // No need to wait for the other user to accept.
+    // No existing row – send a request, if they take them from me.
+    ensure_accepts_requests(state, &friend, me).await?;
+    state.friend_requests.check(me, friend_id)?;
     let insert_body = json!({
         "user_a": user_a.to_string(),
         "user_b": user_b.to_string(),
-        "status": "accepted",
+        "status": "pending",
+        "requester_id": me.to_string(),
+        "note": note,
     });
 
+    let request_id = match state.store.insert_friendship(insert_body).await {
+        Ok(row) => row.id,
+        // Lost a race with the other user's request; the row is already there.
+        Err(ApiError::UniqueViolation(_)) => None,
+        Err(e) => return Err(e),
+    };
+    if let Some(id) = request_id {
+        notify_request(state, friend_id, id, me, "pending").await;
+    }
+
+    Ok(json!({ "status": "pending", "request_id": request_id }))
+}
+
+// ---------------------------------------------------------------------------
+// POST /friends/requests/{id}/accept
+// ---------------------------------------------------------------------------
+
+pub async fn accept_friend_request_handler(
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let row = fetch_incoming_request(&state, request_id, me).await?;
+
     state
-        .supabase
-        .insert("friends", insert_body)
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+        .store
+        .set_friendship_status(request_id, "accepted")
+        .await?;
+    notify_request(&state, other_member(&row, me), request_id, me, "accepted").await;
+
+    info!("[friends] {} accepted friend request {}", me, request_id);
+    Ok(Json(
+        json!({ "status": "accepted", "request_id": request_id }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// POST /friends/requests/{id}/reject
+// ---------------------------------------------------------------------------
+
+/// The row is removed, so the sender may ask again later.
+pub async fn reject_friend_request_handler(
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let row = fetch_incoming_request(&state, request_id, me).await?;
 
-    Ok(Json(json!({ "status": "accepted" })))
+    state.store.delete_friendship(request_id, None).await?;
+    notify_request(&state, other_member(&row, me), request_id, me, "rejected").await;
+
+    info!("[friends] {} rejected friend request {}", me, request_id);
+    Ok(Json(
+        json!({ "status": "rejected", "request_id": request_id }),
+    ))
 }
 
 // ---------------------------------------------------------------------------
 // GET /friends
 // ---------------------------------------------------------------------------
 
+/// Paged by friendship id, oldest friendship first, so a cursor stays
+/// valid while friends are added or removed.
 pub async fn get_friends_handler(
     State(state): State<AppState>,
+    Query(query): Query<FriendListQuery>,
     cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
-    let me = get_session(&cookies)?;
-    let me_str = me.to_string();
-
-    // Fetch rows where I am user_a.
-    let rows_a = state
-        .supabase
-        .select("friends")
-        .eq("user_a", &me_str)
-        .eq("status", "accepted")
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    // Fetch rows where I am user_b.
-    let rows_b = state
-        .supabase
-        .select("friends")
-        .eq("user_b", &me_str)
-        .eq("status", "accepted")
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    let mut friend_ids: Vec<Uuid> = Vec::new();
-
-    // From rows where I am user_a, the friend is user_b.
-    for row_val in &rows_a {
-        if let Ok(row) = serde_json::from_value::<FriendRow>(row_val.clone()) {
-            friend_ids.push(row.user_b);
-        }
-    }
+    let me = get_session(&state, &cookies)?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
 
-    // From rows where I am user_b, the friend is user_a.
-    for row_val in &rows_b {
-        if let Ok(row) = serde_json::from_value::<FriendRow>(row_val.clone()) {
-            friend_ids.push(row.user_a);
-        }
+    // With `favorites`, only the ones I starred.
+    let rows = state
+        .store
+        .friends_page(me, query.favorites, query.cursor, limit)
+        .await?;
+
+    let next_cursor = if rows.len() == limit {
+        rows.last().and_then(|row| row.id)
+    } else {
+        None
+    };
+    let friends = friend_infos(&state, &rows, me).await?;
+
+    Ok(Json(
+        json!({ "friends": friends, "next_cursor": next_cursor }),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// POST /friends/{id}/favorite  ·  DELETE /friends/{id}/favorite
+// ---------------------------------------------------------------------------
+
+/// Only the caller's side of the friendship changes; the friend isn't told.
+pub async fn add_favorite_handler(
+    State(state): State<AppState>,
+    Path(friend_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    set_favorite(&state, me, friend_id, true).await?;
+    Ok(Json(json!({ "friend_id": friend_id, "is_favorite": true })))
+}
+
+pub async fn remove_favorite_handler(
+    State(state): State<AppState>,
+    Path(friend_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    set_favorite(&state, me, friend_id, false).await?;
+    Ok(Json(
+        json!({ "friend_id": friend_id, "is_favorite": false }),
+    ))
+}
+
+async fn set_favorite(
+    state: &AppState,
+    me: Uuid,
+    friend_id: Uuid,
+    favorite: bool,
+) -> Result<(), ApiError> {
+    if !state.store.set_favorite(me, friend_id, favorite).await? {
+        return Err(ApiError::NotFound(
+            "You are not friends with this user".into(),
+        ));
     }
+    Ok(())
+}
 
-    // Resolve each friend id into a FriendInfo with profile details.
-    let mut friends: Vec<FriendInfo> = Vec::new();
-
-    for fid in &friend_ids {
-        let profile_result = state
-            .supabase
-            .select("profiles")
-            .eq("id", &fid.to_string())
-            .execute()
-            .await;
-
-        if let Ok(profile_rows) = profile_result {
-            if let Some(first) = profile_rows.into_iter().next() {
-                if let Ok(p) = serde_json::from_value::<ProfileRow>(first) {
-                    friends.push(FriendInfo {
-                        friend_id: p.id,
-                        username: p.username,
-                        display_name: p.display_name,
-                        avatar_url: p.avatar_url,
-                        status: "accepted".into(),
-                    });
-                }
-            }
+// ---------------------------------------------------------------------------
+// GET /friends/suggestions  –  friends of friends
+// ---------------------------------------------------------------------------
+
+/// People the caller's friends are friends with, most mutual friends first.
+/// Anyone already connected (friends or a pending request either way) or on
+/// either side of a block is left out.
+pub async fn friend_suggestions_handler(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0);
+
+    // Every row with me in it, whatever its status.
+    let mine = state.store.friendships_of(me, None).await?;
+    let friends: Vec<Uuid> = mine
+        .iter()
+        .filter(|row| row.status == "accepted")
+        .map(|row| other_member(row, me))
+        .collect();
+
+    let mut excluded = blocked_either_way(&state, me).await?;
+    excluded.insert(me);
+    excluded.extend(mine.iter().map(|row| other_member(row, me)));
+
+    let mut mutual_counts: HashMap<Uuid, usize> = HashMap::new();
+    for theirs in state
+        .store
+        .accepted_friends_of(&friends)
+        .await?
+        .into_values()
+    {
+        for candidate in theirs.into_iter().filter(|c| !excluded.contains(c)) {
+            *mutual_counts.entry(candidate).or_default() += 1;
         }
     }
 
-    Ok(Json(json!({ "friends": friends })))
+    let mut ranked: Vec<(Uuid, usize)> = mutual_counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let page: Vec<(Uuid, usize)> = ranked.into_iter().skip(offset).take(limit).collect();
+
+    let ids: Vec<Uuid> = page.iter().map(|(id, _)| *id).collect();
+    let mut profiles = state.store.profiles(&ids).await?;
+    let suggestions: Vec<FriendSuggestion> = page
+        .into_iter()
+        .filter_map(|(id, mutual_count)| {
+            Some(FriendSuggestion {
+                profile: ProfileResponse::from(
+                    profiles.remove(&id).filter(|p| !p.is_deactivated())?,
+                ),
+                mutual_count,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({ "suggestions": suggestions })))
 }
 
 // ---------------------------------------------------------------------------
 // GET /friends/pending
 // ---------------------------------------------------------------------------
 
+/// Split by direction so clients only offer accept/reject on `incoming`.
+/// Requests from before direction was recorded can be answered by either
+/// side, so they are listed as incoming.
 pub async fn get_pending_friends_handler(
     State(state): State<AppState>,
     cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
-    let me = get_session(&cookies)?;
-    let me_str = me.to_string();
-
-    // Pending requests where I am user_a.
-    let rows_a = state
-        .supabase
-        .select("friends")
-        .eq("user_a", &me_str)
-        .eq("status", "pending")
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    // Pending requests where I am user_b.
-    let rows_b = state
-        .supabase
-        .select("friends")
-        .eq("user_b", &me_str)
-        .eq("status", "pending")
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    let mut pending: Vec<FriendInfo> = Vec::new();
-
-    for row_val in &rows_a {
-        if let Ok(row) = serde_json::from_value::<FriendRow>(row_val.clone()) {
-            if let Ok(p) = fetch_profile_brief(&state, row.user_b).await {
-                pending.push(p);
-            }
-        }
+    let me = get_session(&state, &cookies)?;
+
+    let (outgoing, incoming): (Vec<FriendRow>, Vec<FriendRow>) = state
+        .store
+        .friendships_of(me, Some("pending"))
+        .await?
+        .into_iter()
+        .partition(|row| row.requester_id == Some(me));
+    let incoming = friend_infos(&state, &incoming, me).await?;
+    let outgoing = friend_infos(&state, &outgoing, me).await?;
+
+    Ok(Json(json!({ "incoming": incoming, "outgoing": outgoing })))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /friends/requests/{id}  –  withdraw a request I sent
+// ---------------------------------------------------------------------------
+
+pub async fn cancel_friend_request_handler(
+    State(state): State<AppState>,
+    Path(request_id): Path<i64>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let row = match state.store.friend_request(request_id).await? {
+        Some(row) => row,
+        None => return Err(ApiError::NotFound("Friend request not found".into())),
+    };
+    if row.status != "pending" || row.requester_id != Some(me) {
+        return Err(ApiError::NotFound("Friend request not found".into()));
     }
 
-    for row_val in &rows_b {
-        if let Ok(row) = serde_json::from_value::<FriendRow>(row_val.clone()) {
-            if let Ok(p) = fetch_profile_brief(&state, row.user_a).await {
-                pending.push(p);
-            }
-        }
+    // Only delete while still pending, in case it was accepted meanwhile.
+    let deleted = state
+        .store
+        .delete_friendship(request_id, Some("pending"))
+        .await?;
+    if !deleted {
+        return Err(ApiError::BadRequest(
+            "The request has already been answered".into(),
+        ));
     }
 
-    Ok(Json(json!({ "pending": pending })))
+    notify_user(
+        &state,
+        other_member(&row, me),
+        UserEvent::FriendRequestWithdrawn(FriendRequestNotice {
+            request_id,
+            user_id: me,
+            status: "withdrawn".into(),
+            silent: true,
+        }),
+    )
+    .await;
+
+    info!("[friends] {} withdrew friend request {}", me, request_id);
+    Ok(Json(
+        json!({ "status": "withdrawn", "request_id": request_id }),
+    ))
 }
 
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
-/// Fetch minimal profile info for a friend entry.
-async fn fetch_profile_brief(state: &AppState, user_id: Uuid) -> Result<FriendInfo, ApiError> {
-    let rows = state
-        .supabase
-        .select("profiles")
-        .eq("id", &user_id.to_string())
-        .execute()
-        .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
-
-    if rows.is_empty() {
-        return Err(ApiError::NotFound("Profile not found".into()));
+/// Load a pending request that `me` is allowed to answer, i.e. one they
+/// are part of but didn't send.
+async fn fetch_incoming_request(
+    state: &AppState,
+    request_id: i64,
+    me: Uuid,
+) -> Result<FriendRow, ApiError> {
+    let row = match state.store.friend_request(request_id).await? {
+        Some(row) => row,
+        None => return Err(ApiError::NotFound("Friend request not found".into())),
+    };
+
+    if row.status != "pending" || (row.user_a != me && row.user_b != me) {
+        return Err(ApiError::NotFound("Friend request not found".into()));
+    }
+    if row.requester_id == Some(me) {
+        return Err(ApiError::Forbidden(
+            "You can't answer your own friend request".into(),
+        ));
     }
+    Ok(row)
+}
 
-    let p: ProfileRow =
-        serde_json::from_value(rows[0].clone()).map_err(|e| ApiError::Database(e.to_string()))?;
+/// Give `user_id` a fresh random friend code, retrying on the rare clash
+/// with someone else's.
+async fn assign_friend_code(state: &AppState, user_id: Uuid) -> Result<String, ApiError> {
+    const ATTEMPTS: usize = 5;
+
+    for _ in 0..ATTEMPTS {
+        let code: String = (0..FRIEND_CODE_LEN)
+            .map(|_| {
+                let i = OsRng.next_u32() as usize % FRIEND_CODE_ALPHABET.len();
+                FRIEND_CODE_ALPHABET[i] as char
+            })
+            .collect();
+        match state
+            .store
+            .update_profile(user_id, json!({ "friend_code": code }))
+            .await
+        {
+            Ok(_) => return Ok(code),
+            Err(ApiError::UniqueViolation(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(ApiError::Internal(
+        "Could not generate a unique friend code".into(),
+    ))
+}
+
+/// Uppercase and drop the separators people type or copy along with it.
+fn normalize_friend_code(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// `ABCD2345` → `ABCD-2345`, easier to read out loud.
+fn format_friend_code(code: &str) -> String {
+    let (head, tail) = code.split_at(code.len() / 2);
+    format!("{}-{}", head, tail)
+}
+
+/// Apply `target`'s friend request policy to a new request from `from`.
+async fn ensure_accepts_requests(
+    state: &AppState,
+    target: &ProfileRow,
+    from: Uuid,
+) -> Result<(), ApiError> {
+    match target.friend_request_policy.unwrap_or_default() {
+        FriendRequestPolicy::Everyone => Ok(()),
+        FriendRequestPolicy::Nobody => Err(ApiError::Forbidden(
+            "This user isn't accepting friend requests".into(),
+        )),
+        FriendRequestPolicy::FriendsOfFriends => {
+            let friends = state.store.accepted_friends_of(&[target.id, from]).await?;
+            let shared = match (friends.get(&target.id), friends.get(&from)) {
+                (Some(theirs), Some(mine)) => !theirs.is_disjoint(mine),
+                _ => false,
+            };
+            if shared {
+                Ok(())
+            } else {
+                Err(ApiError::Forbidden(
+                    "This user only accepts friend requests from friends of friends".into(),
+                ))
+            }
+        }
+    }
+}
+
+/// The user on the other side of a friends row from `me`.
+fn other_member(row: &FriendRow, me: Uuid) -> Uuid {
+    if row.user_a == me {
+        row.user_b
+    } else {
+        row.user_a
+    }
+}
+
+/// Push a `friend_request` event to `to` so their pending list updates
+/// without polling, unless they turned these notifications off.
+async fn notify_request(state: &AppState, to: Uuid, request_id: i64, from: Uuid, status: &str) {
+    let silent = match fetch_notification_settings(state, to).await {
+        Ok(prefs) if !prefs.friend_requests.ws => return,
+        Ok(prefs) => in_quiet_hours(&prefs.quiet_hours, Utc::now()),
+        Err(e) => {
+            error!(
+                "[friends] Failed to load notification settings of {}: {}",
+                to, e
+            );
+            false
+        }
+    };
+    notify_user(
+        state,
+        to,
+        UserEvent::FriendRequest(FriendRequestNotice {
+            request_id,
+            user_id: from,
+            status: status.into(),
+            silent,
+        }),
+    )
+    .await;
+}
 
-    Ok(FriendInfo {
-        friend_id: p.id,
-        username: p.username,
-        display_name: p.display_name,
-        avatar_url: p.avatar_url,
-        status: "pending".into(),
-    })
+/// Turn `me`'s friends rows into list entries, resolving every other side's
+/// profile in one query. Rows whose profile is gone are skipped.
+async fn friend_infos(
+    state: &AppState,
+    rows: &[FriendRow],
+    me: Uuid,
+) -> Result<Vec<FriendInfo>, ApiError> {
+    let ids: Vec<Uuid> = rows.iter().map(|row| other_member(row, me)).collect();
+    let profiles = state.store.profiles(&ids).await?;
+    let settings = fetch_settings_many(state, &ids).await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let p = profiles
+                .get(&other_member(row, me))
+                .filter(|p| !p.is_deactivated())?
+                .clone();
+            let pending = row.status == "pending";
+            let shows_last_seen = settings
+                .get(&p.id)
+                .cloned()
+                .unwrap_or_default()
+                .last_seen_visibility
+                .allows(row.status == "accepted");
+            Some(FriendInfo {
+                custom_status: p.custom_status(),
+                last_seen_at: p.last_seen_at.clone().filter(|_| shows_last_seen),
+                friend_id: p.id,
+                username: p.username,
+                display_name: p.display_name,
+                avatar_url: p.avatar_url,
+                accent_color: p.accent_color,
+                is_verified: p.is_verified.unwrap_or(false),
+                status: row.status.clone(),
+                is_favorite: row.is_favorite_of(me),
+                request_id: if pending { row.id } else { None },
+                note: if pending { row.note.clone() } else { None },
+            })
+        })
+        .collect())
 }
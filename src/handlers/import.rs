@@ -0,0 +1,250 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, require_admin};
+use crate::handlers::chat::validate_content;
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{ImportConversation, ImportRequest, MemberRow, ProfileRow};
+use crate::AppState;
+
+/// Messages inserted per request.
+const BATCH_SIZE: usize = 500;
+
+// ---------------------------------------------------------------------------
+// POST /admin/import  –  recreate conversations from another platform
+// ---------------------------------------------------------------------------
+
+/// Everything is validated before anything is written: every source user
+/// must map to an existing account by username, and every timestamp must
+/// parse. Messages the content policy refuses are skipped and counted
+/// rather than failing the whole import. Imported DMs are new
+/// conversations even if the two users already have one.
+pub async fn import_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<ImportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let users = resolve_users(&state, &body).await?;
+    for (i, conversation) in body.conversations.iter().enumerate() {
+        validate_conversation(i, conversation, &users)?;
+    }
+
+    let mut results = Vec::with_capacity(body.conversations.len());
+    for (i, conversation) in body.conversations.iter().enumerate() {
+        let (conversation_id, imported, skipped) =
+            import_conversation(&state, conversation, &users).await?;
+
+        info!(
+            "[import] {} imported conversation {} ({} messages, {} skipped)",
+            admin.id, conversation_id, imported, skipped
+        );
+        audit(
+            &state,
+            admin.id,
+            "conversation.import",
+            conversation_id,
+            json!({ "messages": imported, "skipped": skipped }),
+        )
+        .await;
+        results.push(json!({
+            "source_index": i,
+            "conversation_id": conversation_id,
+            "messages_imported": imported,
+            "messages_skipped": skipped,
+        }));
+    }
+
+    Ok(Json(json!({ "conversations": results })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Map source user ids to local profiles.
+async fn resolve_users(
+    state: &AppState,
+    body: &ImportRequest,
+) -> Result<HashMap<String, ProfileRow>, ApiError> {
+    let mut seen = HashSet::new();
+    if let Some(dup) = body.users.iter().find(|u| !seen.insert(u.id.as_str())) {
+        return Err(ApiError::BadRequest(format!(
+            "Duplicate user id '{}'",
+            dup.id
+        )));
+    }
+
+    let usernames: Vec<&str> = body.users.iter().map(|u| u.username.as_str()).collect();
+    let profiles: HashMap<String, ProfileRow> = state
+        .store
+        .profiles_by_username(&usernames)
+        .await?
+        .into_iter()
+        .filter(|p| p.deleted_at.is_none())
+        .map(|p| (p.username.clone(), p))
+        .collect();
+
+    let missing: Vec<&str> = usernames
+        .iter()
+        .filter(|name| !profiles.contains_key(**name))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(ApiError::BadRequest(format!(
+            "No account for {} user(s): {}",
+            missing.len(),
+            missing
+                .iter()
+                .take(10)
+                .copied()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+
+    Ok(body
+        .users
+        .iter()
+        .filter_map(|u| Some((u.id.clone(), profiles.get(&u.username)?.clone())))
+        .collect())
+}
+
+fn validate_conversation(
+    index: usize,
+    conversation: &ImportConversation,
+    users: &HashMap<String, ProfileRow>,
+) -> Result<(), ApiError> {
+    let bad = |msg: String| ApiError::BadRequest(format!("conversations[{}]: {}", index, msg));
+
+    let members: HashSet<&str> = conversation.members.iter().map(String::as_str).collect();
+    if !conversation.is_group && members.len() != 2 {
+        return Err(bad("a direct conversation needs exactly 2 members".into()));
+    }
+    if members.is_empty() {
+        return Err(bad("no members".into()));
+    }
+    if let Some(unknown) = members.iter().find(|m| !users.contains_key(**m)) {
+        return Err(bad(format!("unknown member '{}'", unknown)));
+    }
+
+    for (i, message) in conversation.messages.iter().enumerate() {
+        if !members.contains(message.sender.as_str()) {
+            return Err(bad(format!(
+                "messages[{}]: sender '{}' is not a member",
+                i, message.sender
+            )));
+        }
+        if DateTime::parse_from_rfc3339(&message.created_at).is_err() {
+            return Err(bad(format!(
+                "messages[{}]: created_at must be an RFC 3339 timestamp",
+                i
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Create one conversation with its members and messages. Returns the new
+/// id and how many messages were imported and skipped.
+async fn import_conversation(
+    state: &AppState,
+    conversation: &ImportConversation,
+    users: &HashMap<String, ProfileRow>,
+) -> Result<(Uuid, usize, usize), ApiError> {
+    let mut messages: Vec<(DateTime<Utc>, &ProfileRow, &str)> = conversation
+        .messages
+        .iter()
+        .filter_map(|m| {
+            let at = DateTime::parse_from_rfc3339(&m.created_at).ok()?;
+            Some((
+                at.with_timezone(&Utc),
+                users.get(&m.sender)?,
+                m.content.as_str(),
+            ))
+        })
+        .collect();
+    messages.sort_by_key(|(at, _, _)| *at);
+
+    let conversation_id = Uuid::new_v4();
+    let created_at = messages
+        .first()
+        .map(|(at, _, _)| *at)
+        .unwrap_or_else(Utc::now);
+    state
+        .store
+        .insert_conversation(json!({
+            "id": conversation_id,
+            "is_group": conversation.is_group,
+            "name": conversation.name,
+            "created_at": to_timestamp(created_at),
+        }))
+        .await?;
+
+    let mut member_ids: Vec<Uuid> = Vec::new();
+    for source_id in &conversation.members {
+        if let Some(profile) = users.get(source_id) {
+            if !member_ids.contains(&profile.id) {
+                member_ids.push(profile.id);
+            }
+        }
+    }
+    let member_rows: Vec<MemberRow> = member_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let role = if conversation.is_group && i == 0 {
+                "owner"
+            } else {
+                "member"
+            };
+            MemberRow::new(conversation_id, *id, role)
+        })
+        .collect();
+    state.store.add_members(&member_rows).await?;
+
+    let mut imported = 0;
+    let mut skipped = 0;
+    for batch in messages.chunks(BATCH_SIZE) {
+        let mut rows = Vec::with_capacity(batch.len());
+        for (at, sender, content) in batch {
+            let sanitized = match state.content_policy.sanitize(content) {
+                Ok(s) if !s.content.trim().is_empty() => s,
+                _ => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if validate_content(&state.config.messages, &sanitized.content).is_err() {
+                skipped += 1;
+                continue;
+            }
+            rows.push(json!({
+                "conversation_id": conversation_id,
+                "sender_id": sender.id,
+                "content": sanitized.content,
+                "content_html": sanitized.html,
+                "message_type": "text",
+                "is_bot": sender.is_bot(),
+                "created_at": to_timestamp(*at),
+            }));
+        }
+        state.store.insert_messages(&rows).await?;
+        imported += rows.len();
+    }
+
+    // Bypassed `deliver_message`, so set the stored counter directly.
+    state
+        .store
+        .update_conversation(conversation_id, json!({ "message_count": imported }))
+        .await?;
+
+    Ok((conversation_id, imported, skipped))
+}
@@ -0,0 +1,142 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, Admin};
+use crate::handlers::scheduled::to_timestamp;
+use crate::ip_bans::{ClientIp, IpNet};
+use crate::models::{CreateIpBanRequest, IpBanRow};
+use crate::AppState;
+
+const MAX_REASON_CHARS: usize = 500;
+
+// ---------------------------------------------------------------------------
+// GET /admin/ip-bans  –  every banned address block
+// ---------------------------------------------------------------------------
+
+/// Newest first, expired bans included until they are deleted.
+pub async fn list_ip_bans_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+) -> Result<impl IntoResponse, ApiError> {
+    let bans = state.store.ip_bans().await?;
+
+    Ok(Json(json!({ "ip_bans": bans })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/ip-bans  –  ban an address or CIDR block
+// ---------------------------------------------------------------------------
+
+/// Refused if the block contains the admin's own address, so nobody locks
+/// themselves out by accident.
+pub async fn create_ip_ban_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    client_ip: Option<Extension<ClientIp>>,
+    Json(body): Json<CreateIpBanRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let net: IpNet = body.cidr.parse().map_err(ApiError::BadRequest)?;
+    if let Some(Extension(ClientIp(ip))) = client_ip {
+        if net.contains(ip) {
+            return Err(ApiError::BadRequest(format!(
+                "{} includes your own address ({})",
+                net, ip
+            )));
+        }
+    }
+
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_REASON_CHARS
+        )));
+    }
+
+    let expires_at = match body.expires_in_secs {
+        Some(secs) => {
+            let ttl = chrono::Duration::try_seconds(secs as i64)
+                .filter(|d| *d > chrono::Duration::zero())
+                .ok_or_else(|| ApiError::BadRequest("Invalid expires_in_secs".into()))?;
+            Some(to_timestamp(Utc::now() + ttl))
+        }
+        None => None,
+    };
+
+    let row = IpBanRow {
+        id: Uuid::new_v4(),
+        cidr: net.to_string(),
+        reason,
+        created_by: Some(admin.id),
+        expires_at,
+        created_at: None,
+    };
+    let stored = match state.store.insert_ip_ban(&row).await {
+        Ok(v) => v,
+        Err(ApiError::UniqueViolation(_)) => {
+            return Err(ApiError::BadRequest(format!(
+                "{} is already banned",
+                row.cidr
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+    state.ip_bans.add(&row);
+
+    info!("[ip_bans] admin={} banned {}", admin.id, row.cidr);
+    audit(
+        &state,
+        admin.id,
+        "ip_ban.add",
+        row.id,
+        json!({ "cidr": row.cidr, "reason": row.reason, "expires_at": row.expires_at }),
+    )
+    .await;
+
+    Ok(Json(stored))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/ip-bans/{id}  –  lift a ban
+// ---------------------------------------------------------------------------
+
+pub async fn delete_ip_ban_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(ban_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let cidr = match state.store.delete_ip_ban(ban_id).await? {
+        Some(row) => row.cidr,
+        None => return Err(ApiError::NotFound("IP ban not found".into())),
+    };
+    state.ip_bans.remove(ban_id);
+
+    info!(
+        "[ip_bans] admin={} lifted ban {} ({})",
+        admin.id, ban_id, cidr
+    );
+    audit(
+        &state,
+        admin.id,
+        "ip_ban.remove",
+        ban_id,
+        json!({ "cidr": cidr }),
+    )
+    .await;
+
+    Ok(Json(json!({ "deleted": ban_id })))
+}
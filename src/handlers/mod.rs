@@ -1,4 +1,31 @@
+pub mod activity;
+pub mod admin;
+pub mod announcements;
+pub mod appeals;
+pub mod attachments;
 pub mod auth;
+pub mod blocks;
+pub mod calls;
+pub mod channels;
 pub mod chat;
+pub mod drafts;
+pub mod edits;
+pub mod emoji;
+pub mod export;
 pub mod friends;
+pub mod import;
+pub mod ip_bans;
+pub mod moderation;
+pub mod notifications;
+pub mod polls;
 pub mod profile;
+pub mod purge;
+pub mod rate_limits;
+pub mod reports;
+pub mod scheduled;
+pub mod search;
+pub mod settings;
+pub mod system;
+pub mod usage;
+pub mod users;
+pub mod word_filter;
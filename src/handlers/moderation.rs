@@ -0,0 +1,195 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::admin_events;
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, Admin};
+use crate::handlers::chat::{deliver_message, verify_membership};
+use crate::models::{AdminEvent, HeldMessageRow, PageQuery, WsIncoming};
+use crate::storage;
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /admin/held-messages?limit=50&offset=0  –  messages awaiting review
+// ---------------------------------------------------------------------------
+
+/// Oldest first; senders are waiting on these.
+pub async fn list_held_messages_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<PageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0);
+
+    let held = state.store.held_messages_page(limit, offset).await?;
+
+    Ok(Json(json!({
+        "next_offset": if held.len() == limit { Some(offset + limit) } else { None },
+        "held_messages": held,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/held-messages/{id}/approve  –  deliver a held message
+// ---------------------------------------------------------------------------
+
+/// Sends the message as its author, through the normal pipeline minus the
+/// spam and moderation checks. Refused if the author has left the
+/// conversation since.
+pub async fn approve_held_message_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(held_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let held = fetch_held(&state, held_id).await?;
+    verify_membership(&state, held.conversation_id, held.sender_id).await?;
+
+    let sender_id = held.sender_id;
+    let conversation_id = held.conversation_id;
+    let categories = held.categories.clone();
+    let message = deliver_message(
+        &state,
+        conversation_id,
+        sender_id,
+        false,
+        WsIncoming {
+            content: held.content,
+            components: None,
+            attachment: held.attachment,
+            poll: held.poll,
+            sticker_id: held.sticker_id,
+            client_id: held.client_id,
+            approved: true,
+        },
+    )
+    .await?;
+
+    state
+        .store
+        .review_held_message(held_id, "approved", admin.id)
+        .await?;
+    info!(
+        "[moderation] admin={} approved held message {} in {}",
+        admin.id, held_id, conversation_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "held_message.approve",
+        held_id,
+        json!({
+            "conversation_id": conversation_id,
+            "sender_id": sender_id,
+            "message_id": message.id,
+            "categories": categories,
+        }),
+    )
+    .await;
+
+    Ok(Json(message))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/held-messages/{id}/reject  –  drop a held message
+// ---------------------------------------------------------------------------
+
+pub async fn reject_held_message_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(held_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let held = fetch_held(&state, held_id).await?;
+    state
+        .store
+        .review_held_message(held_id, "rejected", admin.id)
+        .await?;
+
+    if let Some(attachment) = &held.attachment {
+        let paths: Vec<String> = std::iter::once(attachment.path.clone())
+            .chain(attachment.thumbnail.as_ref().map(|t| t.path.clone()))
+            .collect();
+        if let Err(e) = storage::remove(&state.config.attachments.bucket, &paths).await {
+            error!(
+                "[moderation] Failed to delete attachment of held message {}: {}",
+                held_id, e
+            );
+        }
+    }
+
+    info!(
+        "[moderation] admin={} rejected held message {} in {}",
+        admin.id, held_id, held.conversation_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "held_message.reject",
+        held_id,
+        json!({
+            "conversation_id": held.conversation_id,
+            "sender_id": held.sender_id,
+            "content": held.content,
+            "categories": held.categories,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({ "rejected": held_id })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Keep a message automated moderation flagged for a moderator, exactly as
+/// it was sent. Called from `deliver_message` instead of storing it.
+pub async fn hold_message(
+    state: &AppState,
+    conversation_id: Uuid,
+    sender_id: Uuid,
+    incoming: WsIncoming,
+    categories: String,
+) -> Result<(), ApiError> {
+    let row = HeldMessageRow {
+        id: Uuid::new_v4(),
+        conversation_id,
+        sender_id,
+        content: incoming.content,
+        sticker_id: incoming.sticker_id,
+        client_id: incoming.client_id,
+        attachment: incoming.attachment,
+        poll: incoming.poll,
+        categories,
+        status: "held".into(),
+        reviewed_by: None,
+        reviewed_at: None,
+        created_at: None,
+    };
+    state.store.hold_message(&row).await?;
+
+    info!(
+        "[moderation] Held message {} from {} in {} ({})",
+        row.id, sender_id, conversation_id, row.categories
+    );
+    admin_events::publish(state, AdminEvent::MessageHeld(Box::new(row))).await;
+    Ok(())
+}
+
+/// A held message that nobody has reviewed yet.
+async fn fetch_held(state: &AppState, held_id: Uuid) -> Result<HeldMessageRow, ApiError> {
+    let held = state.store.held_message(held_id).await?;
+    if held.status != "held" {
+        return Err(ApiError::BadRequest(format!(
+            "This message was already {}",
+            held.status
+        )));
+    }
+    Ok(held)
+}
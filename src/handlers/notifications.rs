@@ -0,0 +1,330 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Path, State, WebSocketUpgrade,
+    },
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{broadcast, RwLock};
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::blocks::members_blocking;
+use crate::handlers::chat::{publish, verify_membership};
+use crate::handlers::profile::touch_last_seen;
+use crate::handlers::settings::{fetch_notification_settings_many, fetch_settings, in_quiet_hours};
+use crate::models::{
+    MarkReadRequest, NewMessageNotice, ReadMarker, ReadReceipt, UnreadDigest, UserEvent, WsEvent,
+};
+use crate::wire::{self, WireFormat};
+use crate::AppState;
+
+/// Per-user broadcast channels for notification sockets. Several devices
+/// of the same user share one channel.
+pub type UserChannels = Arc<RwLock<HashMap<Uuid, broadcast::Sender<UserEvent>>>>;
+
+/// Create a new empty channel map. Called once at startup.
+pub fn new_user_channel_map() -> UserChannels {
+    Arc::new(RwLock::new(HashMap::new()))
+}
+
+// ---------------------------------------------------------------------------
+// GET /ws/notifications  –  per-user socket for badges across conversations
+// ---------------------------------------------------------------------------
+
+/// Opens with a `digest` of every conversation with unread messages, then
+/// streams `new_message` and `read` events. Clients only need one of these
+/// per device, however many conversations they have.
+pub async fn ws_notifications_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, ApiError> {
+    let user_id = get_session(&state, &cookies)?;
+
+    Ok(ws.protocols(wire::PROTOCOLS).on_upgrade(move |socket| {
+        let format = WireFormat::from_protocol(socket.protocol().and_then(|p| p.to_str().ok()));
+        handle_notification_socket(socket, state, user_id, format)
+    }))
+}
+
+async fn handle_notification_socket(
+    socket: WebSocket,
+    state: AppState,
+    user_id: Uuid,
+    format: WireFormat,
+) {
+    let ws_config = state.config.ws.clone();
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+
+    // Subscribe *before* building the digest so nothing in between is lost.
+    let channels = state.user_channels.clone();
+    let mut rx = subscribe(&channels, user_id).await;
+
+    match fetch_unread_digest(&state, user_id).await {
+        Ok(digest) => {
+            if let Some(frame) = format.encode(&UserEvent::Digest(digest)) {
+                if ws_sender.send(frame).await.is_err() {
+                    release_channel(&channels, user_id).await;
+                    return;
+                }
+            }
+        }
+        Err(e) => error!(
+            "[notifications] Failed to build digest for {}: {}",
+            user_id, e
+        ),
+    }
+
+    let last_pong = Arc::new(Mutex::new(Instant::now()));
+
+    // These sockets are quiet by design, so only the heartbeat closes them.
+    let last_pong_for_send = last_pong.clone();
+    let mut send_task = tokio::spawn(async move {
+        let mut heartbeat = tokio::time::interval(ws_config.heartbeat_interval);
+        heartbeat.tick().await;
+
+        loop {
+            tokio::select! {
+                received = rx.recv() => {
+                    let event = match received {
+                        Ok(e) => e,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    };
+                    let frame = match format.encode(&event) {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    if ws_sender.send(frame).await.is_err() {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    let deadline = ws_config.heartbeat_interval * ws_config.max_missed_heartbeats;
+                    if last_pong_for_send.lock().unwrap().elapsed() > deadline {
+                        break;
+                    }
+                    if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    // Nothing is read from clients; just watch for pongs and close.
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_receiver.next().await {
+            match msg {
+                Message::Pong(_) => *last_pong.lock().unwrap() = Instant::now(),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = &mut send_task => {
+            recv_task.abort();
+            let _ = recv_task.await;
+        }
+        _ = &mut recv_task => {
+            send_task.abort();
+            let _ = send_task.await;
+        }
+    }
+
+    release_channel(&channels, user_id).await;
+    touch_last_seen(&state, user_id).await;
+}
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/read  –  mark messages up to an id as read
+// ---------------------------------------------------------------------------
+
+pub async fn mark_read_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<MarkReadRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    let current = state
+        .store
+        .member(conversation_id, me)
+        .await?
+        .and_then(|m| m.last_read_message_id);
+
+    // Never move the marker backwards, e.g. when an older device catches up.
+    if current.is_some_and(|id| id >= body.message_id) {
+        return Ok(Json(json!({ "last_read_message_id": current })));
+    }
+
+    state
+        .store
+        .update_member(
+            conversation_id,
+            me,
+            json!({ "last_read_message_id": body.message_id }),
+        )
+        .await?;
+
+    notify_user(
+        &state,
+        me,
+        UserEvent::Read(ReadMarker {
+            conversation_id,
+            last_read_message_id: body.message_id,
+        }),
+    )
+    .await;
+
+    if fetch_settings(&state, me).await?.read_receipts {
+        publish(
+            &state,
+            conversation_id,
+            WsEvent::ReadReceipt(ReadReceipt {
+                user_id: me,
+                last_read_message_id: body.message_id,
+            }),
+        )
+        .await;
+    }
+
+    Ok(Json(json!({ "last_read_message_id": body.message_id })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Send an event to every notification socket the user has open.
+pub async fn notify_user(state: &AppState, user_id: Uuid, event: UserEvent) {
+    state
+        .bus
+        .publish_user(&state.user_channels, user_id, event)
+        .await;
+}
+
+/// Tell every other member of a conversation that a message arrived, if
+/// their notification settings want it. Runs after the message has gone
+/// out; a failure only costs a badge update.
+pub async fn notify_new_message(state: AppState, notice: NewMessageNotice, content: String) {
+    let members = match state.store.members(notice.conversation_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!(
+                "[notifications] Failed to load members of {}: {}",
+                notice.conversation_id, e
+            );
+            return;
+        }
+    };
+
+    let members: Vec<Uuid> = members
+        .into_iter()
+        .map(|m| m.user_id)
+        .filter(|id| *id != notice.sender_id)
+        .collect();
+    let blocking = match members_blocking(&state, notice.sender_id, &members).await {
+        Ok(set) => set,
+        Err(e) => {
+            error!(
+                "[notifications] Failed to load blocks on {}: {}",
+                notice.sender_id, e
+            );
+            Default::default()
+        }
+    };
+
+    let members: Vec<Uuid> = members
+        .into_iter()
+        .filter(|id| !blocking.contains(id))
+        .collect();
+    let (usernames, settings) = match tokio::try_join!(
+        state.store.profiles(&members),
+        fetch_notification_settings_many(&state, &members)
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(
+                "[notifications] Failed to load settings for {}: {}",
+                notice.conversation_id, e
+            );
+            return;
+        }
+    };
+
+    for member in members {
+        let mentioned = usernames
+            .get(&member)
+            .is_some_and(|p| mentions(&content, &p.username));
+        let prefs = settings.get(&member).cloned().unwrap_or_default();
+        if !(prefs.messages.ws || (mentioned && prefs.mentions.ws)) {
+            continue;
+        }
+        let mut notice = notice.clone();
+        notice.mentioned = mentioned;
+        notice.silent = in_quiet_hours(&prefs.quiet_hours, Utc::now());
+        notify_user(&state, member, UserEvent::NewMessage(notice)).await;
+    }
+}
+
+/// Whether `content` contains `@username` as a whole word, ignoring case.
+fn mentions(content: &str, username: &str) -> bool {
+    let needle = format!("@{}", username.to_lowercase());
+    let lower = content.to_lowercase();
+    lower.match_indices(&needle).any(|(i, _)| {
+        let before_ok = lower[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric());
+        let after_ok = lower[i + needle.len()..]
+            .chars()
+            .next()
+            .is_none_or(|c| !(c.is_alphanumeric() || c == '_'));
+        before_ok && after_ok
+    })
+}
+
+/// Unread counts for every conversation the user is in, in one query.
+async fn fetch_unread_digest(state: &AppState, user_id: Uuid) -> Result<UnreadDigest, ApiError> {
+    let conversations = state.store.unread_digest(user_id).await?;
+
+    info!(
+        "[notifications] {} has unread messages in {} conversations",
+        user_id,
+        conversations.len()
+    );
+    Ok(UnreadDigest { conversations })
+}
+
+async fn subscribe(channels: &UserChannels, user_id: Uuid) -> broadcast::Receiver<UserEvent> {
+    let mut map = channels.write().await;
+    map.entry(user_id)
+        .or_insert_with(|| {
+            let (tx, _) = broadcast::channel(64);
+            tx
+        })
+        .subscribe()
+}
+
+async fn release_channel(channels: &UserChannels, user_id: Uuid) {
+    let mut map = channels.write().await;
+    if map.get(&user_id).is_some_and(|tx| tx.receiver_count() == 0) {
+        map.remove(&user_id);
+    }
+}
@@ -0,0 +1,253 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::collections::HashSet;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{
+    deliver_message, find_sent_message, publish, validate_client_id, verify_membership,
+};
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{
+    CreatePollRequest, MessageRow, PollOption, PollOptionTally, PollRow, PollState, PollUpdate,
+    PollVoteRow, VotePollRequest, WsEvent, WsIncoming,
+};
+use crate::AppState;
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+const MAX_QUESTION_CHARS: usize = 300;
+const MAX_OPTION_CHARS: usize = 100;
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/polls  –  post a poll as a message
+// ---------------------------------------------------------------------------
+
+pub async fn create_poll_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<CreatePollRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    // Check before creating the poll so a retry doesn't leave a second one.
+    validate_client_id(body.client_id.as_deref())?;
+    if let Some(original) =
+        find_sent_message(&state, conversation_id, me, body.client_id.as_deref()).await?
+    {
+        return Ok(Json(original));
+    }
+
+    let question = state.content_policy.sanitize(&body.question)?.content;
+    let question = question.trim().to_string();
+    if question.is_empty() || question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Question must be between 1 and {} characters",
+            MAX_QUESTION_CHARS
+        )));
+    }
+
+    if !(MIN_OPTIONS..=MAX_OPTIONS).contains(&body.options.len()) {
+        return Err(ApiError::BadRequest(format!(
+            "A poll needs between {} and {} options",
+            MIN_OPTIONS, MAX_OPTIONS
+        )));
+    }
+    let mut options = Vec::with_capacity(body.options.len());
+    for (i, raw) in body.options.iter().enumerate() {
+        let text = state
+            .content_policy
+            .sanitize(raw)?
+            .content
+            .trim()
+            .to_string();
+        if text.is_empty() || text.chars().count() > MAX_OPTION_CHARS {
+            return Err(ApiError::BadRequest(format!(
+                "Each option must be between 1 and {} characters",
+                MAX_OPTION_CHARS
+            )));
+        }
+        if options.iter().any(|o: &PollOption| o.text == text) {
+            return Err(ApiError::BadRequest("Poll options must be unique".into()));
+        }
+        options.push(PollOption {
+            id: (i + 1).to_string(),
+            text,
+        });
+    }
+
+    let row = PollRow {
+        id: Uuid::new_v4(),
+        conversation_id,
+        created_by: me,
+        question: question.clone(),
+        options,
+        allow_multiple: body.allow_multiple,
+        closes_at: body
+            .closes_in_seconds
+            .map(|secs| to_timestamp(Utc::now() + chrono::Duration::seconds(secs as i64))),
+        created_at: None,
+    };
+    state.store.insert_poll(&row).await?;
+
+    info!(
+        "[create_poll] {} created poll {} in conversation {}",
+        me, row.id, conversation_id
+    );
+
+    let is_bot = state.store.profile(me).await?.is_bot();
+    let message = deliver_message(
+        &state,
+        conversation_id,
+        me,
+        is_bot,
+        WsIncoming {
+            content: question,
+            components: None,
+            attachment: None,
+            poll: Some(tally(&row, &[], None)),
+            sticker_id: None,
+            client_id: body.client_id,
+            approved: false,
+        },
+    )
+    .await?;
+
+    Ok(Json(message))
+}
+
+// ---------------------------------------------------------------------------
+// POST /polls/{id}/vote  –  cast or change a vote
+// ---------------------------------------------------------------------------
+
+pub async fn vote_poll_handler(
+    State(state): State<AppState>,
+    Path(poll_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<VotePollRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let poll = state.store.poll(poll_id).await?;
+    verify_membership(&state, poll.conversation_id, me).await?;
+
+    if is_closed(&poll) {
+        return Err(ApiError::BadRequest("This poll is closed".into()));
+    }
+
+    let chosen: HashSet<&str> = body.option_ids.iter().map(String::as_str).collect();
+    if chosen.len() > 1 && !poll.allow_multiple {
+        return Err(ApiError::BadRequest(
+            "This poll allows only one choice".into(),
+        ));
+    }
+    if let Some(unknown) = chosen
+        .iter()
+        .find(|id| !poll.options.iter().any(|o| o.id == **id))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown option '{}'",
+            unknown
+        )));
+    }
+
+    // Replace rather than add, so changing your mind is a single request.
+    let rows: Vec<PollVoteRow> = chosen
+        .iter()
+        .map(|option_id| PollVoteRow {
+            poll_id,
+            user_id: me,
+            option_id: option_id.to_string(),
+        })
+        .collect();
+    state.store.replace_votes(poll_id, me, &rows).await?;
+
+    let votes = state.store.votes(&[poll_id]).await?;
+    publish(
+        &state,
+        poll.conversation_id,
+        WsEvent::PollUpdated(PollUpdate {
+            poll: tally(&poll, &votes, None),
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({ "poll": tally(&poll, &votes, Some(me)) })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Fill in `poll` on every poll message in `messages`, with `viewer`'s own
+/// votes, using one query for the polls and one for their votes.
+pub async fn attach_polls(
+    state: &AppState,
+    messages: &mut [MessageRow],
+    viewer: Uuid,
+) -> Result<(), ApiError> {
+    let ids: Vec<Uuid> = messages.iter().filter_map(|m| m.poll_id).collect();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let polls = state.store.polls(&ids).await?;
+    let votes = state.store.votes(&ids).await?;
+
+    for message in messages.iter_mut() {
+        if let Some(poll) = message.poll_id.and_then(|id| polls.get(&id)) {
+            let poll_votes: Vec<PollVoteRow> = votes
+                .iter()
+                .filter(|v| v.poll_id == poll.id)
+                .cloned()
+                .collect();
+            message.poll = Some(tally(poll, &poll_votes, Some(viewer)));
+        }
+    }
+    Ok(())
+}
+
+/// Count votes per option. `viewer` adds that user's own choices.
+fn tally(poll: &PollRow, votes: &[PollVoteRow], viewer: Option<Uuid>) -> PollState {
+    let voters: HashSet<Uuid> = votes.iter().map(|v| v.user_id).collect();
+    PollState {
+        id: poll.id,
+        question: poll.question.clone(),
+        options: poll
+            .options
+            .iter()
+            .map(|o| PollOptionTally {
+                id: o.id.clone(),
+                text: o.text.clone(),
+                votes: votes.iter().filter(|v| v.option_id == o.id).count(),
+            })
+            .collect(),
+        allow_multiple: poll.allow_multiple,
+        closes_at: poll.closes_at.clone(),
+        total_voters: voters.len(),
+        my_votes: viewer.map(|me| {
+            votes
+                .iter()
+                .filter(|v| v.user_id == me)
+                .map(|v| v.option_id.clone())
+                .collect()
+        }),
+    }
+}
+
+fn is_closed(poll: &PollRow) -> bool {
+    poll.closes_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|closes_at| closes_at <= Utc::now())
+}
@@ -1,28 +1,124 @@
+use axum::extract::multipart::Multipart;
 use axum::{
     extract::{Path, State},
     response::IntoResponse,
     Json,
 };
+use chrono::Utc;
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Cursor;
 use tower_cookies::Cookies;
+use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::error::ApiError;
+use crate::content_policy::normalize_text;
+use crate::error::{ApiError, SUPPORTED_LOCALES};
+use crate::handlers::activity::record_activity;
+use crate::handlers::attachments::{bad_multipart, looks_like_image};
 use crate::handlers::auth::get_session;
-use crate::models::{EditProfileRequest, ProfileResponse, ProfileRow};
+use crate::handlers::notifications::notify_user;
+use crate::handlers::scheduled::to_timestamp;
+use crate::handlers::settings::fetch_settings;
+use crate::locale::is_supported;
+use crate::models::{
+    ActivityKind, AvatarVariants, EditProfileRequest, ProfileResponse, ProfileRow,
+    SetStatusRequest, StatusChanged, UserEvent,
+};
+use crate::storage;
 use crate::AppState;
 
+/// Image types accepted as avatars and banners, all checked by magic bytes.
+const AVATAR_MIME_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Longest a status can be set to expire after: a year.
+const MAX_STATUS_SECS: i64 = 365 * 24 * 60 * 60;
+
+/// Square sizes rendered for every uploaded avatar: small, medium, large.
+const AVATAR_SIZES: [u32; 3] = [64, 128, 512];
+
+/// Size banners are cropped and scaled to, a 3:1 strip.
+const BANNER_SIZE: (u32, u32) = (1500, 500);
+
+const MAX_DISPLAY_NAME_CHARS: usize = 64;
+const MAX_BIO_CHARS: usize = 500;
+const MAX_URL_CHARS: usize = 2048;
+
+/// Schemes accepted for `avatar_url` and `banner_url`; anything else could
+/// load over plain HTTP or run script in the client.
+const IMAGE_URL_SCHEMES: [&str; 1] = ["https"];
+
 // ---------------------------------------------------------------------------
 // GET /profile/{id}
 // ---------------------------------------------------------------------------
 
+/// Public, but a signed-in viewer may also see when the user was last online.
 pub async fn get_profile_handler(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
+    cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
-    let profile = fetch_profile_by_id(&state, id).await?;
-    let response: ProfileResponse = profile.into();
-    Ok(Json(response))
+    let profile = state.store.profile(id).await?;
+    Ok(Json(public_profile(&state, &cookies, profile).await?))
+}
+
+// ---------------------------------------------------------------------------
+// GET /profile/by-username/{username}
+// ---------------------------------------------------------------------------
+
+/// Case-insensitive, and a leading `@` is ignored, so `/@nick` style
+/// pages can pass their path segment straight through.
+pub async fn get_profile_by_username_handler(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let username = username.trim();
+    let username = username.strip_prefix('@').unwrap_or(username);
+    if username.is_empty() {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+
+    let profile = match state.store.find_profile_by_username(username).await? {
+        Some(profile) => profile,
+        None => return Err(ApiError::NotFound("Profile not found".into())),
+    };
+    Ok(Json(public_profile(&state, &cookies, profile).await?))
+}
+
+/// `profile` as shown to whoever is asking, who may be signed out.
+async fn public_profile(
+    state: &AppState,
+    cookies: &Cookies,
+    profile: ProfileRow,
+) -> Result<ProfileResponse, ApiError> {
+    let viewer = get_session(state, cookies).ok();
+    if profile.is_deactivated() && viewer != Some(profile.id) {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+    if viewer == Some(profile.id) {
+        let last_seen_at = profile.last_seen_at.clone();
+        let mut response: ProfileResponse = profile.into();
+        response.last_seen_at = last_seen_at;
+        return Ok(response);
+    }
+
+    let settings = fetch_settings(state, profile.id).await?;
+    let viewer_is_friend = match viewer {
+        Some(viewer) => state.store.are_friends(viewer, profile.id).await?,
+        None => false,
+    };
+    let last_seen_at = profile
+        .last_seen_at
+        .clone()
+        .filter(|_| settings.last_seen_visibility.allows(viewer_is_friend));
+    let mut response: ProfileResponse = profile.into();
+    response.last_seen_at = last_seen_at;
+    if settings.profile_visibility.allows(viewer_is_friend) {
+        Ok(response)
+    } else {
+        Ok(response.restricted())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -33,9 +129,11 @@ pub async fn get_my_profile_handler(
     State(state): State<AppState>,
     cookies: Cookies,
 ) -> Result<impl IntoResponse, ApiError> {
-    let user_id = get_session(&cookies)?;
-    let profile = fetch_profile_by_id(&state, user_id).await?;
-    let response: ProfileResponse = profile.into();
+    let user_id = get_session(&state, &cookies)?;
+    let profile = state.store.profile(user_id).await?;
+    let last_seen_at = profile.last_seen_at.clone();
+    let mut response: ProfileResponse = profile.into();
+    response.last_seen_at = last_seen_at;
     Ok(Json(response))
 }
 
@@ -50,62 +148,600 @@ pub async fn edit_profile_handler(
     Json(body): Json<EditProfileRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
     // Only the owner can edit their own profile.
-    let session_user = get_session(&cookies)?;
+    let session_user = get_session(&state, &cookies)?;
     if session_user != id {
         return Err(ApiError::Unauthorized);
     }
+    let body = validate_profile_edit(&state, body)?;
 
     // Build the update payload with only the fields the client provided.
     let mut update = json!({});
     if let Some(ref display_name) = body.display_name {
         update["display_name"] = json!(display_name);
     }
+    // An external URL replaces any uploaded image, which is then removed.
+    let mut replaced_upload = None;
     if let Some(ref avatar_url) = body.avatar_url {
-        update["avatar_url"] = json!(avatar_url);
+        update["avatar_url"] = json!(non_empty(avatar_url));
+        update["avatar_path"] = json!(null);
+        update["avatar_variants"] = json!(null);
+        replaced_upload = state.store.profile(id).await?.avatar_path;
+    }
+    let mut replaced_banner = None;
+    if let Some(ref banner_url) = body.banner_url {
+        update["banner_url"] = json!(non_empty(banner_url));
+        update["banner_path"] = json!(null);
+        replaced_banner = state.store.profile(id).await?.banner_path;
     }
     if let Some(ref bio) = body.bio {
         update["bio"] = json!(bio);
     }
+    if let Some(ref fields) = body.extra_fields {
+        update["extra_fields"] = json!(fields);
+    }
+    if let Some(ref color) = body.accent_color {
+        update["accent_color"] = json!(non_empty(color));
+    }
+    if let Some(theme) = body.theme {
+        update["theme"] = json!(theme);
+    }
+    if let Some(ref locale) = body.locale {
+        update["locale"] = json!(non_empty(locale));
+    }
+    if let Some(policy) = body.friend_request_policy {
+        update["friend_request_policy"] = json!(policy);
+    }
+    if let Some(share) = body.share_activity {
+        update["share_activity"] = json!(share);
+    }
 
     // If nothing was provided there is nothing to do.
-    if update.as_object().map_or(true, |m| m.is_empty()) {
+    if update.as_object().is_none_or(|m| m.is_empty()) {
         return Err(ApiError::BadRequest(
             "Provide at least one field to update".into(),
         ));
     }
 
-    state
-        .supabase
-        .update("profiles", &id.to_string(), update)
+    // Return the updated profile so the client can refresh its state.
+    let updated = state.store.update_profile(id, update).await?;
+
+    if let Some(path) = replaced_upload {
+        remove_avatar(&state, path).await;
+    }
+    if let Some(path) = replaced_banner {
+        remove_banner(&state, path).await;
+    }
+
+    if let Some(avatar_url) = body.avatar_url.filter(|url| !url.is_empty()) {
+        record_activity(
+            &state,
+            &updated,
+            ActivityKind::AvatarChanged,
+            json!({ "avatar_url": avatar_url }),
+        )
+        .await;
+    }
+    let response: ProfileResponse = updated.into();
+    Ok(Json(response))
+}
+
+/// Normalize every text field of a profile edit and check it, collecting
+/// all problems so the client can mark each bad field at once. Empty
+/// image URLs clear the image.
+fn validate_profile_edit(
+    state: &AppState,
+    mut body: EditProfileRequest,
+) -> Result<EditProfileRequest, ApiError> {
+    let mut errors = BTreeMap::new();
+
+    if let Some(name) = body.display_name.take() {
+        let name = normalize_text(&name).trim().to_string();
+        if name.is_empty() {
+            errors.insert("display_name".into(), "Display name cannot be empty".into());
+        } else if name.chars().count() > MAX_DISPLAY_NAME_CHARS {
+            errors.insert(
+                "display_name".into(),
+                format!(
+                    "Display name must be at most {} characters",
+                    MAX_DISPLAY_NAME_CHARS
+                ),
+            );
+        } else if name.contains(['\n', '\t']) {
+            errors.insert(
+                "display_name".into(),
+                "Display name must be a single line".into(),
+            );
+        }
+        body.display_name = Some(name);
+    }
+
+    if let Some(bio) = body.bio.take() {
+        let bio = normalize_text(&bio).trim().to_string();
+        if bio.chars().count() > MAX_BIO_CHARS {
+            errors.insert(
+                "bio".into(),
+                format!("Bio must be at most {} characters", MAX_BIO_CHARS),
+            );
+        }
+        body.bio = Some(bio);
+    }
+
+    for (field, url) in [
+        ("avatar_url", &mut body.avatar_url),
+        ("banner_url", &mut body.banner_url),
+    ] {
+        if let Some(raw) = url.take() {
+            let raw = raw.trim().to_string();
+            if let Err(msg) = check_image_url(&raw) {
+                errors.insert(field.into(), msg);
+            }
+            *url = Some(raw);
+        }
+    }
+
+    if let Some(color) = body.accent_color.take() {
+        match normalize_hex_color(&color) {
+            Some(color) => body.accent_color = Some(color),
+            None => {
+                errors.insert(
+                    "accent_color".into(),
+                    "Accent color must be a hex color like #3b82f6".into(),
+                );
+            }
+        }
+    }
+
+    if let Some(locale) = body.locale.take() {
+        let locale = locale.trim().to_lowercase();
+        if !locale.is_empty() && !is_supported(&locale) {
+            errors.insert(
+                "locale".into(),
+                format!("Supported locales: {}", SUPPORTED_LOCALES.join(", ")),
+            );
+        }
+        body.locale = Some(locale);
+    }
+
+    if let Some(fields) = body.extra_fields.take() {
+        let fields = fields
+            .into_iter()
+            .map(|(key, value)| (key, normalize_text(&value)))
+            .collect();
+        match state.config.profile_fields.validate(fields) {
+            Ok(fields) => body.extra_fields = Some(fields),
+            Err(e) => {
+                errors.insert("extra_fields".into(), e.to_string());
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(body)
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
+
+/// `#rgb` or `#rrggbb` in any case, as lowercase `#rrggbb`. An empty
+/// string stays empty.
+fn normalize_hex_color(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Some(String::new());
+    }
+    let hex = raw.strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => Some(format!("#{}", hex.to_lowercase())),
+        3 => Some(
+            hex.to_lowercase()
+                .chars()
+                .fold(String::from("#"), |mut out, c| {
+                    out.push(c);
+                    out.push(c);
+                    out
+                }),
+        ),
+        _ => None,
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// An image URL must be empty (no image) or an absolute URL with an
+/// allowed scheme.
+fn check_image_url(raw: &str) -> Result<(), String> {
+    if raw.is_empty() {
+        return Ok(());
+    }
+    if raw.len() > MAX_URL_CHARS {
+        return Err(format!("URL must be at most {} characters", MAX_URL_CHARS));
+    }
+    match reqwest::Url::parse(raw) {
+        Ok(url) if IMAGE_URL_SCHEMES.contains(&url.scheme()) && url.host().is_some() => Ok(()),
+        Ok(_) => Err(format!("URL must use {}", IMAGE_URL_SCHEMES.join(" or "))),
+        Err(_) => Err("Not a valid URL".into()),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PUT /profile/me/status  –  set or clear the custom status
+// ---------------------------------------------------------------------------
+
+/// Friends are told straight away over their notification sockets.
+pub async fn set_status_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<SetStatusRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    const MAX_TEXT_CHARS: usize = 128;
+    const MAX_EMOJI_CHARS: usize = 16;
+
+    let me = get_session(&state, &cookies)?;
+
+    let text = match body
+        .text
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        Some(t) if t.chars().count() > MAX_TEXT_CHARS => {
+            return Err(ApiError::BadRequest(format!(
+                "Status must be at most {} characters",
+                MAX_TEXT_CHARS
+            )))
+        }
+        Some(t) => Some(state.word_filter.apply(t, false)?.content),
+        None => None,
+    };
+    let emoji = match body
+        .emoji
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    {
+        Some(e) if e.chars().count() > MAX_EMOJI_CHARS => {
+            return Err(ApiError::BadRequest("Status emoji is too long".into()))
+        }
+        Some(e) => Some(e.to_string()),
+        None => None,
+    };
+    let cleared = text.is_none() && emoji.is_none();
+    let expires_at = match body.expires_in_secs {
+        Some(secs) if !cleared => {
+            let secs = i64::try_from(secs).unwrap_or(i64::MAX).min(MAX_STATUS_SECS);
+            Some(to_timestamp(Utc::now() + chrono::Duration::seconds(secs)))
+        }
+        _ => None,
+    };
+
+    let updated = state
+        .store
+        .update_profile(
+            me,
+            json!({
+                "status_text": text,
+                "status_emoji": emoji,
+                "status_expires_at": expires_at,
+            }),
+        )
+        .await?;
+    let status = updated.custom_status();
+    if let Some(ref status) = status {
+        record_activity(
+            &state,
+            &updated,
+            ActivityKind::StatusChanged,
+            serde_json::to_value(status)?,
+        )
+        .await;
+    }
+
+    let friends = state
+        .store
+        .accepted_friends_of(&[me])
+        .await?
+        .remove(&me)
+        .unwrap_or_default();
+    for friend in friends {
+        notify_user(
+            &state,
+            friend,
+            UserEvent::StatusChanged(StatusChanged {
+                user_id: me,
+                status: status.clone(),
+            }),
+        )
+        .await;
+    }
+
+    Ok(Json(json!({ "status": status })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /profile/me/avatar  –  upload a profile picture
+// ---------------------------------------------------------------------------
+
+/// Multipart form with a single `file` image part. Replaces `avatar_url`
+/// and removes the previously uploaded image, if any.
+pub async fn upload_avatar_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let config = &state.config.avatars;
+    let data = read_image_upload(&mut multipart, config.max_bytes).await?;
+
+    let processed = tokio::task::spawn_blocking(move || process_avatar(&data))
         .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+        .map_err(|e| ApiError::Internal(format!("Avatar task failed: {}", e)))??;
+
+    let previous = state.store.profile(me).await?.avatar_path;
+
+    // A new folder per upload, so caches never serve the old picture.
+    let folder = format!("{}/{}", me, Uuid::new_v4());
+    let mut urls = Vec::with_capacity(AVATAR_SIZES.len());
+    for (size, bytes) in processed.images {
+        let path = format!("{}/{}.{}", folder, size, processed.extension);
+        if let Err(e) =
+            storage::upload(&config.bucket, &path, processed.mime_type, bytes.into()).await
+        {
+            remove_avatar(&state, folder).await;
+            return Err(e);
+        }
+        urls.push(storage::public_url(&config.bucket, &path)?);
+    }
+    let variants = AvatarVariants {
+        small: urls[0].clone(),
+        medium: urls[1].clone(),
+        large: urls[2].clone(),
+    };
+
+    let updated = match state
+        .store
+        .update_profile(
+            me,
+            json!({
+                "avatar_url": variants.large,
+                "avatar_path": folder,
+                "avatar_variants": variants,
+            }),
+        )
+        .await
+    {
+        Ok(updated) => updated,
+        Err(e) => {
+            // Don't leave orphaned images behind.
+            remove_avatar(&state, folder).await;
+            return Err(e);
+        }
+    };
+    if let Some(previous) = previous {
+        remove_avatar(&state, previous).await;
+    }
+    info!("[profile] {} uploaded a new avatar", me);
+
+    record_activity(
+        &state,
+        &updated,
+        ActivityKind::AvatarChanged,
+        json!({ "avatar_url": variants.large }),
+    )
+    .await;
 
-    // Return the updated profile so the client can refresh its state.
-    let updated = fetch_profile_by_id(&state, id).await?;
     let response: ProfileResponse = updated.into();
     Ok(Json(response))
 }
 
 // ---------------------------------------------------------------------------
-// Internal helper
+// POST /profile/me/banner  –  upload a profile banner
 // ---------------------------------------------------------------------------
 
-/// Fetch a single profile row from Supabase by its UUID.
-async fn fetch_profile_by_id(state: &AppState, id: Uuid) -> Result<ProfileRow, ApiError> {
-    let rows = state
-        .supabase
-        .select("profiles")
-        .eq("id", &id.to_string())
-        .execute()
+/// Same form and limits as avatars. The image is center-cropped to 3:1
+/// and scaled to `BANNER_SIZE`; the previous upload, if any, is removed.
+pub async fn upload_banner_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let config = &state.config.avatars;
+    let data = read_image_upload(&mut multipart, config.max_bytes).await?;
+
+    let (bytes, mime_type, extension) = tokio::task::spawn_blocking(move || process_banner(&data))
         .await
-        .map_err(|e| ApiError::Database(e.to_string()))?;
+        .map_err(|e| ApiError::Internal(format!("Banner task failed: {}", e)))??;
 
-    if rows.is_empty() {
-        return Err(ApiError::NotFound("Profile not found".into()));
+    let previous = state.store.profile(me).await?.banner_path;
+
+    let path = format!("{}/banners/{}.{}", me, Uuid::new_v4(), extension);
+    storage::upload(&config.bucket, &path, mime_type, bytes.into()).await?;
+    let url = storage::public_url(&config.bucket, &path)?;
+
+    let updated = match state
+        .store
+        .update_profile(me, json!({ "banner_url": url, "banner_path": path }))
+        .await
+    {
+        Ok(updated) => updated,
+        Err(e) => {
+            remove_banner(&state, path).await;
+            return Err(e);
+        }
+    };
+    if let Some(previous) = previous {
+        remove_banner(&state, previous).await;
+    }
+    info!("[profile] {} uploaded a new banner", me);
+
+    let response: ProfileResponse = updated.into();
+    Ok(Json(response))
+}
+
+/// Read the `file` part of an image upload form, checking its declared
+/// type, size and magic bytes.
+async fn read_image_upload(
+    multipart: &mut Multipart,
+    max_bytes: usize,
+) -> Result<Vec<u8>, ApiError> {
+    let mut upload: Option<(String, Vec<u8>)> = None;
+    while let Some(mut field) = multipart.next_field().await.map_err(bad_multipart)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let mime_type = field.content_type().unwrap_or_default().to_lowercase();
+        if !AVATAR_MIME_TYPES.contains(&mime_type.as_str()) {
+            return Err(ApiError::UnsupportedMediaType(format!(
+                "'{}' is not allowed. Allowed: {}",
+                mime_type,
+                AVATAR_MIME_TYPES.join(", ")
+            )));
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(bad_multipart)? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(ApiError::AttachmentTooLarge { max_bytes });
+            }
+            data.extend_from_slice(&chunk);
+        }
+        upload = Some((mime_type, data));
     }
 
-    let profile: ProfileRow =
-        serde_json::from_value(rows[0].clone()).map_err(|e| ApiError::Database(e.to_string()))?;
+    let (mime_type, data) = match upload {
+        Some(u) => u,
+        None => return Err(ApiError::BadRequest("Missing 'file' part".into())),
+    };
+    if !looks_like_image(&mime_type, &data) {
+        return Err(ApiError::UnsupportedMediaType(format!(
+            "content does not match '{}'",
+            mime_type
+        )));
+    }
+    Ok(data)
+}
+
+struct ProcessedAvatar {
+    /// Encoded image per entry of `AVATAR_SIZES`, in the same order.
+    images: Vec<(u32, Vec<u8>)>,
+    mime_type: &'static str,
+    extension: &'static str,
+}
+
+/// Center-crop to a square and render every size in `AVATAR_SIZES`.
+/// Re-encoding drops EXIF and any other metadata, including GPS
+/// positions from phone cameras. Animated images keep their first frame.
+fn process_avatar(data: &[u8]) -> Result<ProcessedAvatar, ApiError> {
+    let img = image::load_from_memory(data)
+        .map_err(|e| ApiError::UnsupportedMediaType(format!("could not decode image: {}", e)))?;
+
+    let side = img.width().min(img.height());
+    let square = img.crop_imm(
+        (img.width() - side) / 2,
+        (img.height() - side) / 2,
+        side,
+        side,
+    );
+    let has_alpha = img.color().has_alpha();
+    let (format, mime_type, extension) = if has_alpha {
+        (image::ImageFormat::Png, "image/png", "png")
+    } else {
+        (image::ImageFormat::Jpeg, "image/jpeg", "jpg")
+    };
+
+    let mut images = Vec::with_capacity(AVATAR_SIZES.len());
+    for size in AVATAR_SIZES {
+        let resized = square.resize_exact(size, size, image::imageops::FilterType::Lanczos3);
+        // JPEG has no alpha channel, so flatten to RGB first.
+        let resized = if has_alpha {
+            resized
+        } else {
+            image::DynamicImage::from(resized.to_rgb8())
+        };
+        let mut bytes = Cursor::new(Vec::new());
+        resized
+            .write_to(&mut bytes, format)
+            .map_err(|e| ApiError::Internal(format!("Failed to encode avatar: {}", e)))?;
+        images.push((size, bytes.into_inner()));
+    }
 
-    Ok(profile)
+    Ok(ProcessedAvatar {
+        images,
+        mime_type,
+        extension,
+    })
+}
+
+/// Center-crop to `BANNER_SIZE`'s aspect ratio and scale to it, dropping
+/// metadata like `process_avatar`. Returns the bytes, MIME type and extension.
+fn process_banner(data: &[u8]) -> Result<(Vec<u8>, &'static str, &'static str), ApiError> {
+    let img = image::load_from_memory(data)
+        .map_err(|e| ApiError::UnsupportedMediaType(format!("could not decode image: {}", e)))?;
+
+    let (target_w, target_h) = BANNER_SIZE;
+    // Widest crop with the target ratio that fits inside the image.
+    let (w, h) = if img.width() as u64 * target_h as u64 > img.height() as u64 * target_w as u64 {
+        (img.height() * target_w / target_h, img.height())
+    } else {
+        (img.width(), img.width() * target_h / target_w)
+    };
+    let cropped = img
+        .crop_imm((img.width() - w) / 2, (img.height() - h) / 2, w, h)
+        .resize_exact(target_w, target_h, image::imageops::FilterType::Lanczos3);
+
+    let (cropped, format, mime_type, extension) = if img.color().has_alpha() {
+        (cropped, image::ImageFormat::Png, "image/png", "png")
+    } else {
+        (
+            image::DynamicImage::from(cropped.to_rgb8()),
+            image::ImageFormat::Jpeg,
+            "image/jpeg",
+            "jpg",
+        )
+    };
+    let mut bytes = Cursor::new(Vec::new());
+    cropped
+        .write_to(&mut bytes, format)
+        .map_err(|e| ApiError::Internal(format!("Failed to encode banner: {}", e)))?;
+    Ok((bytes.into_inner(), mime_type, extension))
+}
+
+/// Remove an uploaded banner by its `banner_path`. Best effort, like
+/// `remove_avatar`.
+async fn remove_banner(state: &AppState, banner_path: String) {
+    if let Err(e) = storage::remove(&state.config.avatars.bucket, &[banner_path]).await {
+        error!("[profile] Failed to remove old banner: {}", e);
+    }
+}
+
+/// Remove an uploaded avatar by its `avatar_path`. Best effort: a leftover
+/// image only costs storage.
+async fn remove_avatar(state: &AppState, avatar_path: String) {
+    // Paths that don't exist are ignored, so cover both encodings.
+    let paths: Vec<String> = AVATAR_SIZES
+        .iter()
+        .flat_map(|size| ["png", "jpg"].map(|ext| format!("{}/{}.{}", avatar_path, size, ext)))
+        .collect();
+    if let Err(e) = storage::remove(&state.config.avatars.bucket, &paths).await {
+        error!("[profile] Failed to remove old avatar: {}", e);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Internal helper
+// ---------------------------------------------------------------------------
+
+/// Record that `user_id` was just online. Best effort, since it only feeds
+/// a "last seen" label.
+pub async fn touch_last_seen(state: &AppState, user_id: Uuid) {
+    if let Err(e) = state.store.touch_last_seen(user_id).await {
+        error!(
+            "[profile] Failed to update last_seen_at for {}: {}",
+            user_id, e
+        );
+    }
 }
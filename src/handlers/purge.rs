@@ -0,0 +1,146 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::db::Order;
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, parse_time, Admin};
+use crate::handlers::chat::publish;
+use crate::models::{MessageDeleted, MessageRow, PurgeMessagesRequest, WsEvent};
+use crate::storage;
+use crate::store::MessageFilter;
+use crate::AppState;
+
+/// Messages tombstoned per round trip.
+const BATCH_SIZE: usize = 200;
+/// Messages one request may delete; run it again for the rest.
+const MAX_PURGED: usize = 10_000;
+
+// ---------------------------------------------------------------------------
+// POST /admin/purge  –  delete every message matching a filter
+// ---------------------------------------------------------------------------
+
+/// For cleaning up spam floods. Needs a sender or a conversation so a
+/// missing field can't wipe the whole service. Matching messages are
+/// tombstoned oldest first, their files removed, and each conversation is
+/// told. `complete` is false when `MAX_PURGED` was reached before the
+/// filter ran out.
+pub async fn purge_messages_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Json(body): Json<PurgeMessagesRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.sender_id.is_none() && body.conversation_id.is_none() {
+        return Err(ApiError::BadRequest(
+            "Set sender_id, conversation_id or both".into(),
+        ));
+    }
+    let from = body
+        .from
+        .as_deref()
+        .map(|t| parse_time("from", t))
+        .transpose()?;
+    let to = body
+        .to
+        .as_deref()
+        .map(|t| parse_time("to", t))
+        .transpose()?;
+
+    let mut purged = 0;
+    let mut complete = false;
+    while purged < MAX_PURGED {
+        // Tombstoned rows drop out of the filter, so every batch is the
+        // next one without paging.
+        let batch = state
+            .store
+            .messages(
+                &MessageFilter {
+                    sender_id: body.sender_id,
+                    conversation_id: body.conversation_id,
+                    created_from: from.clone(),
+                    created_before: to.clone(),
+                    live_only: true,
+                    ..Default::default()
+                },
+                Order::Asc,
+                Some(BATCH_SIZE.min(MAX_PURGED - purged)),
+            )
+            .await?;
+        if batch.is_empty() {
+            complete = true;
+            break;
+        }
+
+        purge_batch(&state, &batch).await?;
+        purged += batch.len();
+    }
+
+    info!(
+        "[purge] admin={} purged {} messages (sender={:?}, conversation={:?})",
+        admin.id, purged, body.sender_id, body.conversation_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "message.purge",
+        body.sender_id
+            .or(body.conversation_id)
+            .map(|id| id.to_string())
+            .unwrap_or_default(),
+        json!({
+            "sender_id": body.sender_id,
+            "conversation_id": body.conversation_id,
+            "from": from,
+            "to": to,
+            "purged": purged,
+            "complete": complete,
+        }),
+    )
+    .await;
+
+    Ok(Json(json!({ "purged": purged, "complete": complete })))
+}
+
+/// Tombstone `messages`, remove their files and revisions, and tell each
+/// conversation.
+async fn purge_batch(state: &AppState, messages: &[MessageRow]) -> Result<(), ApiError> {
+    let ids: Vec<i64> = messages.iter().filter_map(|m| m.id).collect();
+
+    // Files first: once the rows are tombstoned nothing points at them.
+    let paths: Vec<String> = messages
+        .iter()
+        .filter_map(|m| m.attachment.as_ref())
+        .flat_map(|a| {
+            std::iter::once(a.path.clone()).chain(a.thumbnail.as_ref().map(|t| t.path.clone()))
+        })
+        .collect();
+    if !paths.is_empty() {
+        if let Err(e) = storage::remove(&state.config.attachments.bucket, &paths).await {
+            error!(
+                "[purge] Failed to delete {} attachments: {}",
+                paths.len(),
+                e
+            );
+        }
+    }
+
+    state
+        .store
+        .tombstone_messages(&MessageFilter {
+            ids: Some(ids),
+            ..Default::default()
+        })
+        .await?;
+
+    for message in messages {
+        if let Some(message_id) = message.id {
+            publish(
+                state,
+                message.conversation_id,
+                WsEvent::MessageDeleted(MessageDeleted { message_id }),
+            )
+            .await;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, Admin};
+use crate::models::{RateLimitOverrideRow, SetRateLimitRequest};
+use crate::AppState;
+
+const MAX_REASON_CHARS: usize = 500;
+const MAX_RATE_PER_SEC: f64 = 1000.0;
+const MAX_BURST: u32 = 10_000;
+const MAX_SEND_LIMIT: usize = 100_000;
+
+// ---------------------------------------------------------------------------
+// GET /admin/rate-limits  –  every user with non-default limits
+// ---------------------------------------------------------------------------
+
+/// Most recently changed first.
+pub async fn list_rate_limits_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+) -> Result<impl IntoResponse, ApiError> {
+    let overrides = state.store.rate_limit_overrides().await?;
+
+    Ok(Json(json!({ "rate_limits": overrides })))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /admin/users/{id}/rate-limit  –  give a user their own limits
+// DELETE /admin/users/{id}/rate-limit  –  back to the defaults
+// ---------------------------------------------------------------------------
+
+/// Replaces any earlier override. The send limit applies to the user's
+/// next message; socket limits to sockets opened from now on.
+pub async fn set_rate_limit_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+    Json(body): Json<SetRateLimitRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.ws_rate_per_sec.is_none() && body.ws_rate_burst.is_none() && body.send_limit.is_none() {
+        return Err(ApiError::BadRequest(
+            "Set at least one of ws_rate_per_sec, ws_rate_burst or send_limit".into(),
+        ));
+    }
+    if body
+        .ws_rate_per_sec
+        .is_some_and(|r| !r.is_finite() || r <= 0.0 || r > MAX_RATE_PER_SEC)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "ws_rate_per_sec must be above 0 and at most {}",
+            MAX_RATE_PER_SEC
+        )));
+    }
+    if body.ws_rate_burst.is_some_and(|b| b == 0 || b > MAX_BURST) {
+        return Err(ApiError::BadRequest(format!(
+            "ws_rate_burst must be 1-{}",
+            MAX_BURST
+        )));
+    }
+    if body.send_limit.is_some_and(|l| l > MAX_SEND_LIMIT) {
+        return Err(ApiError::BadRequest(format!(
+            "send_limit must be at most {} (0 for no limit)",
+            MAX_SEND_LIMIT
+        )));
+    }
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_REASON_CHARS
+        )));
+    }
+    if state.store.profile(user_id).await?.deleted_at.is_some() {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+
+    let row = RateLimitOverrideRow {
+        user_id,
+        ws_rate_per_sec: body.ws_rate_per_sec,
+        ws_rate_burst: body.ws_rate_burst,
+        send_limit: body.send_limit,
+        reason,
+        set_by: Some(admin.id),
+        updated_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    let stored = state.store.save_rate_limit_override(&row).await?;
+    state.rate_limits.set(row.clone());
+
+    info!(
+        "[rate_limits] admin={} set limits for {}",
+        admin.id, user_id
+    );
+    audit(
+        &state,
+        admin.id,
+        "rate_limit.set",
+        user_id,
+        json!({
+            "ws_rate_per_sec": row.ws_rate_per_sec,
+            "ws_rate_burst": row.ws_rate_burst,
+            "send_limit": row.send_limit,
+            "reason": row.reason,
+        }),
+    )
+    .await;
+
+    Ok(Json(stored))
+}
+
+pub async fn clear_rate_limit_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.store.delete_rate_limit_override(user_id).await? {
+        return Err(ApiError::NotFound("User has no rate-limit override".into()));
+    }
+    state.rate_limits.remove(user_id);
+
+    info!(
+        "[rate_limits] admin={} cleared limits for {}",
+        admin.id, user_id
+    );
+    audit(&state, admin.id, "rate_limit.clear", user_id, json!({})).await;
+
+    Ok(Json(json!({ "cleared": user_id })))
+}
@@ -0,0 +1,279 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::admin_events;
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, ban_user, remove_message, Admin};
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::verify_membership;
+use crate::handlers::edits::fetch_live_message;
+use crate::handlers::notifications::notify_user;
+use crate::handlers::scheduled::to_timestamp;
+use crate::models::{
+    AdminEvent, CreateReportRequest, ModerationWarning, ReportAction, ReportQueueQuery,
+    ReportResolved, ReportRow, ReportTarget, ResolveReportRequest, UserEvent, REPORT_STATUSES,
+};
+use crate::AppState;
+
+const MAX_REASON_CHARS: usize = 1000;
+
+/// Reports one user may file per hour.
+const REPORTS_PER_HOUR: usize = 10;
+
+// ---------------------------------------------------------------------------
+// POST /reports  –  report a user or a message
+// ---------------------------------------------------------------------------
+
+/// Messages can only be reported by members of their conversation. Filing
+/// the same report twice while the first is still open returns the first.
+pub async fn create_report_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateReportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let reason = body
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason
+        .as_ref()
+        .is_some_and(|r| r.chars().count() > MAX_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_REASON_CHARS
+        )));
+    }
+
+    let (target_user_id, message) = match body.target {
+        ReportTarget::User { user_id } => {
+            state.store.profile(user_id).await?;
+            (user_id, None)
+        }
+        ReportTarget::Message { message_id } => {
+            let message = fetch_live_message(&state, message_id).await?;
+            verify_membership(&state, message.conversation_id, me).await?;
+            (message.sender_id, Some(message))
+        }
+    };
+    if target_user_id == me {
+        return Err(ApiError::BadRequest("You cannot report yourself".into()));
+    }
+
+    let message_id = message.as_ref().map(|m| m.id.unwrap_or_default());
+    if let Some(existing) = state
+        .store
+        .open_report(me, target_user_id, message_id)
+        .await?
+    {
+        return Ok(Json(confirmation(existing.id)));
+    }
+
+    check_report_rate(&state, me).await?;
+
+    let report = ReportRow {
+        id: Uuid::new_v4(),
+        reporter_id: me,
+        target_user_id,
+        message_id: message.as_ref().and_then(|m| m.id),
+        conversation_id: message.as_ref().map(|m| m.conversation_id),
+        message_content: message.map(|m| m.content),
+        category: body.category,
+        reason,
+        status: "open".into(),
+        created_at: None,
+        resolved_by: None,
+        resolved_at: None,
+        action: None,
+        resolution_note: None,
+    };
+    state.store.insert_report(&report).await?;
+
+    info!(
+        "[reports] {} reported {} ({:?}), report {}",
+        me, target_user_id, report.category, report.id
+    );
+    let report_id = report.id;
+    admin_events::publish(&state, AdminEvent::ReportFiled(report)).await;
+
+    Ok(Json(confirmation(report_id)))
+}
+
+fn confirmation(report_id: Uuid) -> serde_json::Value {
+    json!({
+        "report_id": report_id,
+        "status": "received",
+        "message": "Thanks for letting us know. A moderator will review your report.",
+    })
+}
+
+/// Refuse a new report once the caller has filed `REPORTS_PER_HOUR` in the
+/// last hour. Counted from the table, so it holds across instances.
+async fn check_report_rate(state: &AppState, reporter: Uuid) -> Result<(), ApiError> {
+    let window = Duration::hours(1);
+    let recent = state
+        .store
+        .report_times(reporter, Utc::now() - window)
+        .await?;
+    if recent.len() < REPORTS_PER_HOUR {
+        return Ok(());
+    }
+
+    let wait = recent
+        .first()
+        .map(|at| (*at + window - Utc::now()).to_std().unwrap_or_default())
+        .unwrap_or(std::time::Duration::from_secs(60));
+    Err(ApiError::rate_limited(wait))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/reports?status=open&limit=50&offset=0  –  the review queue
+// ---------------------------------------------------------------------------
+
+/// Oldest first, so reports are worked through in the order they came in.
+pub async fn list_reports_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+    Query(query): Query<ReportQueueQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let status = query.status.as_deref().unwrap_or("open");
+    if !REPORT_STATUSES.contains(&status) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown status '{}'. Allowed: {}",
+            status,
+            REPORT_STATUSES.join(", ")
+        )));
+    }
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+    let offset = query.offset.unwrap_or(0);
+
+    let reports = state.store.reports_page(status, limit, offset).await?;
+
+    Ok(Json(json!({
+        "next_offset": if reports.len() == limit { Some(offset + limit) } else { None },
+        "reports": reports,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/reports/{id}/resolve  –  act on a report and close it
+// ---------------------------------------------------------------------------
+
+/// Carries out the action, closes the report and tells the reporter it was
+/// reviewed. Deleting content that's already gone still closes the report.
+pub async fn resolve_report_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Path(report_id): Path<Uuid>,
+    Json(body): Json<ResolveReportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = state.store.report(report_id).await?;
+    if report.status != "open" {
+        return Err(ApiError::BadRequest(
+            "This report has already been resolved".into(),
+        ));
+    }
+
+    let note = body
+        .note
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty());
+    if note
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > MAX_REASON_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Note must be at most {} characters",
+            MAX_REASON_CHARS
+        )));
+    }
+
+    match body.action {
+        ReportAction::Dismiss => {}
+        ReportAction::Warn => {
+            notify_user(
+                &state,
+                report.target_user_id,
+                UserEvent::ModerationWarning(ModerationWarning {
+                    report_id,
+                    category: report.category,
+                    message_id: report.message_id,
+                }),
+            )
+            .await;
+        }
+        ReportAction::DeleteContent => {
+            let message_id = match report.message_id {
+                Some(id) => id,
+                None => {
+                    return Err(ApiError::BadRequest(
+                        "This report is about a user, not a message".into(),
+                    ))
+                }
+            };
+            match remove_message(&state, admin.id, message_id).await {
+                Ok(()) | Err(ApiError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        ReportAction::Ban => {
+            let reason = note
+                .clone()
+                .unwrap_or_else(|| format!("Report {}", report_id));
+            ban_user(&state, admin.id, report.target_user_id, Some(reason)).await?;
+        }
+    }
+
+    let updated = state
+        .store
+        .update_report(
+            report_id,
+            json!({
+                "status": body.action.status(),
+                "action": body.action,
+                "resolved_by": admin.id,
+                "resolved_at": to_timestamp(Utc::now()),
+                "resolution_note": note,
+            }),
+        )
+        .await?;
+
+    info!(
+        "[reports] admin={} resolved report {} with {:?}",
+        admin.id, report_id, body.action
+    );
+    audit(
+        &state,
+        admin.id,
+        "report.resolve",
+        report_id,
+        json!({
+            "action": body.action,
+            "target_user_id": report.target_user_id,
+            "note": note,
+        }),
+    )
+    .await;
+
+    notify_user(
+        &state,
+        report.reporter_id,
+        UserEvent::ReportResolved(ReportResolved {
+            report_id,
+            action_taken: body.action != ReportAction::Dismiss,
+        }),
+    )
+    .await;
+
+    Ok(Json(updated))
+}
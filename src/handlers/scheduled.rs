@@ -0,0 +1,247 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::json;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::{validate_content, verify_membership};
+use crate::models::{ScheduleMessageRequest, ScheduledMessageRow};
+use crate::AppState;
+
+/// Format used for `send_at_local`.
+pub const LOCAL_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// How often a recurring schedule repeats.
+#[derive(Debug, Clone, Copy)]
+pub enum Recurrence {
+    Daily,
+    Weekly,
+}
+
+impl Recurrence {
+    pub fn parse(raw: &str) -> Result<Self, ApiError> {
+        match raw {
+            "daily" => Ok(Recurrence::Daily),
+            "weekly" => Ok(Recurrence::Weekly),
+            other => Err(ApiError::BadRequest(format!(
+                "Unknown recurrence '{}'. Use 'daily' or 'weekly'",
+                other
+            ))),
+        }
+    }
+
+    fn period(self) -> chrono::Duration {
+        match self {
+            Recurrence::Daily => chrono::Duration::days(1),
+            Recurrence::Weekly => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// POST /conversations/{id}/scheduled  –  schedule a message
+// ---------------------------------------------------------------------------
+
+pub async fn create_scheduled_handler(
+    State(state): State<AppState>,
+    Path(conversation_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<ScheduleMessageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    verify_membership(&state, conversation_id, me).await?;
+
+    let content = state.content_policy.sanitize(&body.content)?.content;
+    if content.trim().is_empty() {
+        return Err(ApiError::BadRequest("Message cannot be empty".into()));
+    }
+    validate_content(&state.config.messages, &content)?;
+
+    let timezone = body.timezone.unwrap_or_else(|| "UTC".into());
+    let tz = parse_timezone(&timezone)?;
+    if let Some(raw) = &body.recurrence {
+        Recurrence::parse(raw)?;
+    }
+
+    let local = parse_local(&body.send_at)?;
+    let next_run = local_to_utc(tz, local)
+        .ok_or_else(|| ApiError::BadRequest("send_at is not a valid local time".into()))?;
+
+    if next_run <= Utc::now() {
+        return Err(ApiError::BadRequest("send_at must be in the future".into()));
+    }
+
+    let row = ScheduledMessageRow {
+        id: Uuid::new_v4(),
+        conversation_id,
+        sender_id: me,
+        content,
+        send_at_local: local.format(LOCAL_FORMAT).to_string(),
+        timezone,
+        recurrence: body.recurrence,
+        next_run_at: to_timestamp(next_run),
+        paused: Some(false),
+        created_at: None,
+    };
+
+    state.store.insert_schedule(&row).await?;
+
+    Ok(Json(row))
+}
+
+// ---------------------------------------------------------------------------
+// GET /scheduled  –  list my scheduled messages
+// ---------------------------------------------------------------------------
+
+pub async fn list_scheduled_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let scheduled = state.store.schedules_of(me).await?;
+
+    Ok(Json(json!({ "scheduled": scheduled })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /scheduled/{id}/pause
+// ---------------------------------------------------------------------------
+
+pub async fn pause_scheduled_handler(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    fetch_owned(&state, id, me).await?;
+
+    state
+        .store
+        .update_schedule(id, json!({ "paused": true }))
+        .await?;
+
+    Ok(Json(json!({ "status": "paused" })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /scheduled/{id}/resume
+// ---------------------------------------------------------------------------
+
+pub async fn resume_scheduled_handler(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let row = fetch_owned(&state, id, me).await?;
+
+    let mut update = json!({ "paused": false });
+
+    // Runs missed while paused are skipped rather than sent in a burst.
+    // One-off messages that came due while paused go out on the next tick.
+    if row.recurrence.is_some() {
+        if let Some((local, next_run)) = next_occurrence(&row, Utc::now())? {
+            update["send_at_local"] = json!(local.format(LOCAL_FORMAT).to_string());
+            update["next_run_at"] = json!(to_timestamp(next_run));
+        }
+    }
+
+    state.store.update_schedule(id, update).await?;
+
+    Ok(Json(json!({ "status": "resumed" })))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /scheduled/{id}
+// ---------------------------------------------------------------------------
+
+pub async fn delete_scheduled_handler(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    fetch_owned(&state, id, me).await?;
+
+    state.store.delete_schedule(id).await?;
+
+    Ok(Json(json!({ "status": "deleted" })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Load a schedule and make sure it belongs to `user_id`.
+async fn fetch_owned(
+    state: &AppState,
+    id: Uuid,
+    user_id: Uuid,
+) -> Result<ScheduledMessageRow, ApiError> {
+    match state.store.schedule(id).await? {
+        Some(row) if row.sender_id == user_id => Ok(row),
+        _ => Err(ApiError::NotFound("Scheduled message not found".into())),
+    }
+}
+
+/// The first occurrence of a recurring schedule strictly after `now`,
+/// as (local wall-clock time, UTC instant). Returns `None` for one-off
+/// schedules. Missed occurrences are skipped, never replayed.
+pub fn next_occurrence(
+    row: &ScheduledMessageRow,
+    now: DateTime<Utc>,
+) -> Result<Option<(NaiveDateTime, DateTime<Utc>)>, ApiError> {
+    let recurrence = match &row.recurrence {
+        Some(raw) => Recurrence::parse(raw)?,
+        None => return Ok(None),
+    };
+    let tz = parse_timezone(&row.timezone)?;
+    let mut local = parse_local(&row.send_at_local)?;
+
+    loop {
+        local += recurrence.period();
+        if let Some(utc) = local_to_utc(tz, local) {
+            if utc > now {
+                return Ok(Some((local, utc)));
+            }
+        }
+    }
+}
+
+/// RFC 3339 in UTC with a `Z` suffix, safe to use in PostgREST filters.
+pub fn to_timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, ApiError> {
+    name.parse::<Tz>()
+        .map_err(|_| ApiError::BadRequest(format!("Unknown timezone '{}'", name)))
+}
+
+fn parse_local(raw: &str) -> Result<NaiveDateTime, ApiError> {
+    NaiveDateTime::parse_from_str(raw, LOCAL_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| {
+            ApiError::BadRequest("send_at must look like 2026-10-20T09:00 (local time)".into())
+        })
+}
+
+/// Resolve a wall-clock time in `tz` to UTC. Ambiguous times (DST fall-back)
+/// use the earlier instant; times skipped by DST spring-forward move an hour later.
+fn local_to_utc(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .map(|dt| dt.with_timezone(&Utc))
+}
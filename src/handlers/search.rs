@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::db::Order;
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::chat::verify_membership;
+use crate::models::{SearchHit, SearchMessagesQuery};
+use crate::store::MessageFilter;
+use crate::AppState;
+
+/// Text search configuration used by the `content_tsv` column:
+///
+/// ```sql
+/// alter table messages add column content_tsv tsvector
+///   generated always as (to_tsvector('english', content)) stored;
+/// create index messages_content_tsv_idx on messages using gin (content_tsv);
+/// ```
+pub const SEARCH_CONFIG: &str = "english";
+
+/// Characters of context kept on each side of the first match.
+const SNIPPET_RADIUS: usize = 60;
+
+// ---------------------------------------------------------------------------
+// GET /search/messages?q=...  –  full-text search across my conversations
+// ---------------------------------------------------------------------------
+
+pub async fn search_messages_handler(
+    State(state): State<AppState>,
+    Query(params): Query<SearchMessagesQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    const DEFAULT_PAGE_SIZE: usize = 20;
+    const MAX_PAGE_SIZE: usize = 100;
+    const MAX_QUERY_LEN: usize = 200;
+
+    let me = get_session(&state, &cookies)?;
+
+    let q = params.q.trim();
+    if q.is_empty() {
+        return Err(ApiError::BadRequest("Search query cannot be empty".into()));
+    }
+    if q.chars().count() > MAX_QUERY_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "Search query must be at most {} characters",
+            MAX_QUERY_LEN
+        )));
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut filter = MessageFilter {
+        search: Some(q.to_string()),
+        live_only: true,
+        visible_to: Some(me),
+        before_id: params.before,
+        ..Default::default()
+    };
+
+    // Only conversations the caller belongs to are searchable.
+    match params.conversation_id {
+        Some(conversation_id) => {
+            verify_membership(&state, conversation_id, me).await?;
+            filter.conversation_id = Some(conversation_id);
+        }
+        None => {
+            let conversation_ids: Vec<Uuid> = state
+                .store
+                .memberships(me)
+                .await?
+                .iter()
+                .map(|m| m.conversation_id)
+                .collect();
+            if conversation_ids.is_empty() {
+                return Ok(Json(json!({ "results": [], "next_before": null })));
+            }
+            filter.conversation_ids = Some(conversation_ids);
+        }
+    }
+
+    let messages = state
+        .store
+        .messages(&filter, Order::Desc, Some(limit))
+        .await?;
+
+    let next_before = if messages.len() == limit {
+        messages.last().and_then(|m| m.id)
+    } else {
+        None
+    };
+
+    let terms = search_terms(q);
+    let results: Vec<SearchHit> = messages
+        .into_iter()
+        .map(|message| {
+            let (snippet, highlights) = snippet(&message.content, &terms);
+            SearchHit {
+                message,
+                snippet,
+                highlights,
+            }
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "results": results,
+        "next_before": next_before,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Words to highlight: the query minus web-search operators and excluded
+/// (`-word`) terms, lowercased.
+fn search_terms(q: &str) -> Vec<Vec<char>> {
+    q.split_whitespace()
+        .filter(|word| !word.starts_with('-') && !word.eq_ignore_ascii_case("or"))
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase().chars().collect())
+        .collect()
+}
+
+/// Cut `content` down to a window around the first matched term and return
+/// it with the character ranges of every term inside that window.
+///
+/// Postgres matches on stems, so this marks words that *start with* a query
+/// term: "run" highlights "running". A hit Postgres found through stemming
+/// alone (e.g. "ran") just gets an unhighlighted excerpt.
+fn snippet(content: &str, terms: &[Vec<char>]) -> (String, Vec<(usize, usize)>) {
+    let text: Vec<char> = content.chars().collect();
+    let hits = find_terms(&text, terms);
+
+    let anchor = hits.first().map(|&(start, _)| start).unwrap_or(0);
+    let start = anchor.saturating_sub(SNIPPET_RADIUS);
+    let end = (anchor + SNIPPET_RADIUS)
+        .max(start + 2 * SNIPPET_RADIUS)
+        .min(text.len());
+
+    let mut snippet = String::new();
+    let mut offset = 0;
+    if start > 0 {
+        snippet.push('…');
+        offset = 1;
+    }
+    snippet.extend(&text[start..end]);
+    if end < text.len() {
+        snippet.push('…');
+    }
+
+    let highlights = hits
+        .into_iter()
+        .filter(|&(s, e)| s >= start && e <= end)
+        .map(|(s, e)| (s - start + offset, e - start + offset))
+        .collect();
+
+    (snippet, highlights)
+}
+
+/// `[start, end)` ranges of word-initial, case-insensitive term matches.
+fn find_terms(text: &[char], terms: &[Vec<char>]) -> Vec<(usize, usize)> {
+    let mut hits = Vec::new();
+    let mut i = 0;
+
+    while i < text.len() {
+        let at_word_start = i == 0 || !text[i - 1].is_alphanumeric();
+        let longest = if at_word_start {
+            terms
+                .iter()
+                .filter(|term| matches_at(text, i, term))
+                .map(|term| term.len())
+                .max()
+        } else {
+            None
+        };
+
+        match longest {
+            Some(len) => {
+                hits.push((i, i + len));
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+
+    hits
+}
+
+fn matches_at(text: &[char], at: usize, term: &[char]) -> bool {
+    term.iter().enumerate().all(|(k, c)| {
+        text.get(at + k)
+            .is_some_and(|t| t.to_lowercase().eq(c.to_lowercase()))
+    })
+}
@@ -0,0 +1,179 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, NaiveTime, Utc};
+use std::collections::HashMap;
+use tower_cookies::Cookies;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::scheduled::parse_timezone;
+use crate::models::{
+    NotificationSettings, QuietHours, UpdateNotificationSettingsRequest, UpdateSettingsRequest,
+    UserSettings,
+};
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /me/settings
+// ---------------------------------------------------------------------------
+
+pub async fn get_settings_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    Ok(Json(fetch_settings(&state, me).await?))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /me/settings  –  change some or all privacy settings
+// ---------------------------------------------------------------------------
+
+pub async fn update_settings_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<UpdateSettingsRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let mut settings = fetch_settings(&state, me).await?;
+    if let Some(audience) = body.profile_visibility {
+        settings.profile_visibility = audience;
+    }
+    if let Some(audience) = body.last_seen_visibility {
+        settings.last_seen_visibility = audience;
+    }
+    if let Some(enabled) = body.read_receipts {
+        settings.read_receipts = enabled;
+    }
+    if let Some(audience) = body.dm_policy {
+        settings.dm_policy = audience;
+    }
+
+    state.store.save_privacy_settings(me, &settings).await?;
+    info!("[settings] {} updated their privacy settings", me);
+
+    Ok(Json(settings))
+}
+
+// ---------------------------------------------------------------------------
+// GET /me/notification-settings
+// ---------------------------------------------------------------------------
+
+pub async fn get_notification_settings_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    Ok(Json(fetch_notification_settings(&state, me).await?))
+}
+
+// ---------------------------------------------------------------------------
+// PUT /me/notification-settings
+// ---------------------------------------------------------------------------
+
+pub async fn update_notification_settings_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<UpdateNotificationSettingsRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let mut settings = fetch_notification_settings(&state, me).await?;
+    if let Some(channels) = body.messages {
+        settings.messages = channels;
+    }
+    if let Some(channels) = body.mentions {
+        settings.mentions = channels;
+    }
+    if let Some(channels) = body.friend_requests {
+        settings.friend_requests = channels;
+    }
+    if let Some(channels) = body.calls {
+        settings.calls = channels;
+    }
+    if let Some(hours) = body.quiet_hours {
+        parse_time(&hours.start)?;
+        parse_time(&hours.end)?;
+        parse_timezone(&hours.timezone)?;
+        settings.quiet_hours = hours;
+    }
+
+    state
+        .store
+        .save_notification_settings(me, &settings)
+        .await?;
+    info!("[settings] {} updated their notification settings", me);
+
+    Ok(Json(settings))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// A user's settings, or the defaults if they never changed any.
+pub async fn fetch_settings(state: &AppState, user_id: Uuid) -> Result<UserSettings, ApiError> {
+    Ok(state
+        .store
+        .privacy_settings(user_id)
+        .await?
+        .unwrap_or_default())
+}
+
+/// Settings for many users in one query. Users without a row are left out;
+/// callers fall back to `UserSettings::default()`.
+pub async fn fetch_settings_many(
+    state: &AppState,
+    user_ids: &[Uuid],
+) -> Result<HashMap<Uuid, UserSettings>, ApiError> {
+    state.store.privacy_settings_of(user_ids).await
+}
+
+/// A user's notification settings, or the defaults.
+pub async fn fetch_notification_settings(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<NotificationSettings, ApiError> {
+    Ok(state
+        .store
+        .notification_settings(user_id)
+        .await?
+        .unwrap_or_default())
+}
+
+/// Notification settings for many users in one query. Users without a row
+/// are left out.
+pub async fn fetch_notification_settings_many(
+    state: &AppState,
+    user_ids: &[Uuid],
+) -> Result<HashMap<Uuid, NotificationSettings>, ApiError> {
+    state.store.notification_settings_of(user_ids).await
+}
+
+/// Whether `at` falls inside the user's quiet hours. Settings that no
+/// longer parse count as outside, so nothing is silenced by mistake.
+pub fn in_quiet_hours(hours: &QuietHours, at: DateTime<Utc>) -> bool {
+    if !hours.enabled {
+        return false;
+    }
+    let (Ok(start), Ok(end), Ok(tz)) = (
+        parse_time(&hours.start),
+        parse_time(&hours.end),
+        parse_timezone(&hours.timezone),
+    ) else {
+        return false;
+    };
+    let now = at.with_timezone(&tz).time();
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+fn parse_time(raw: &str) -> Result<NaiveTime, ApiError> {
+    NaiveTime::parse_from_str(raw, "%H:%M")
+        .map_err(|_| ApiError::BadRequest(format!("Invalid time '{}', expected HH:MM", raw)))
+}
@@ -0,0 +1,44 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_json::json;
+
+use crate::models::WsEndpointInfo;
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /health  –  liveness probe (also used by endpoint discovery)
+// ---------------------------------------------------------------------------
+
+pub async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
+    Json(json!({
+        "status": "ok",
+        "region": state.config.discovery.region,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// GET /ws/endpoints  –  regional WebSocket endpoints, best first
+// ---------------------------------------------------------------------------
+
+pub async fn ws_endpoints_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let mut endpoints = Vec::with_capacity(state.endpoints.endpoints.len());
+
+    for endpoint in &state.endpoints.endpoints {
+        let status = state.endpoints.status(&endpoint.region).await;
+        endpoints.push(WsEndpointInfo {
+            region: endpoint.region.clone(),
+            url: endpoint.url.clone(),
+            // Unprobed endpoints are assumed healthy until the first check.
+            healthy: status.as_ref().is_none_or(|s| s.healthy),
+            latency_ms: status.as_ref().and_then(|s| s.latency_ms),
+            last_checked: status.map(|s| s.last_checked),
+        });
+    }
+
+    // Healthy first, then fastest; unknown latency sorts last.
+    endpoints.sort_by_key(|e| (!e.healthy, e.latency_ms.unwrap_or(u64::MAX)));
+
+    Json(json!({
+        "current_region": state.config.discovery.region,
+        "endpoints": endpoints,
+    }))
+}
@@ -0,0 +1,63 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_json::json;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /me/usage  –  how much of each quota the caller has used
+// ---------------------------------------------------------------------------
+
+/// A `limit` of 0 means the quota is off. The message count resets at
+/// midnight UTC.
+pub async fn my_usage_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    let quotas = &state.config.quotas;
+
+    let messages_today = state.store.messages_sent_today(me).await?;
+    let attachment_bytes = state.store.attachment_bytes_used(me).await?;
+
+    Ok(Json(json!({
+        "messages_per_day": { "used": messages_today, "limit": quotas.messages_per_day },
+        "attachment_bytes": { "used": attachment_bytes, "limit": quotas.attachment_bytes },
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+/// Refuse a send once `user_id` has reached the daily message quota.
+pub async fn check_message_quota(state: &AppState, user_id: Uuid) -> Result<(), ApiError> {
+    let limit = state.config.quotas.messages_per_day;
+    if limit > 0 && state.store.messages_sent_today(user_id).await? >= limit {
+        return Err(ApiError::QuotaExceeded {
+            quota: "messages_per_day",
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Refuse an upload of `size_bytes` that would take `user_id` past the
+/// attachment storage quota.
+pub async fn check_attachment_quota(
+    state: &AppState,
+    user_id: Uuid,
+    size_bytes: usize,
+) -> Result<(), ApiError> {
+    let limit = state.config.quotas.attachment_bytes;
+    if limit > 0 && state.store.attachment_bytes_used(user_id).await? + size_bytes as u64 > limit {
+        return Err(ApiError::QuotaExceeded {
+            quota: "attachment_bytes",
+            limit,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::auth::get_session;
+use crate::handlers::blocks::blocked_either_way;
+use crate::models::{ProfileResponse, UserSearchQuery};
+use crate::AppState;
+
+// ---------------------------------------------------------------------------
+// GET /users/{id}/mutual-friends
+// ---------------------------------------------------------------------------
+
+pub async fn mutual_friends_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+    if me == user_id {
+        return Err(ApiError::BadRequest(
+            "Mutual friends need another user".into(),
+        ));
+    }
+    if state.store.profile(user_id).await?.is_deactivated() {
+        return Err(ApiError::NotFound("Profile not found".into()));
+    }
+
+    let friends = state.store.accepted_friends_of(&[me, user_id]).await?;
+    let mut mutual: Vec<Uuid> = match (friends.get(&me), friends.get(&user_id)) {
+        (Some(mine), Some(theirs)) => mine.intersection(theirs).copied().collect(),
+        _ => Vec::new(),
+    };
+    mutual.sort();
+
+    let mut profiles = state.store.profiles(&mutual).await?;
+    let mutual_friends: Vec<ProfileResponse> = mutual
+        .iter()
+        .filter_map(|id| profiles.remove(id))
+        .filter(|p| !p.is_deactivated())
+        .map(ProfileResponse::from)
+        .collect();
+
+    Ok(Json(json!({
+        "count": mutual_friends.len(),
+        "mutual_friends": mutual_friends,
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /users/search?q=gi&limit=20  –  find people by username
+// ---------------------------------------------------------------------------
+
+/// Prefix match on usernames, in alphabetical order. The caller and
+/// anyone on either side of a block with them are left out.
+pub async fn search_users_handler(
+    State(state): State<AppState>,
+    Query(query): Query<UserSearchQuery>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let me = get_session(&state, &cookies)?;
+
+    let q = state.config.username.normalize(&query.q);
+    if q.is_empty() {
+        return Err(ApiError::BadRequest("Search query cannot be empty".into()));
+    }
+    if q.chars().count() > state.config.username.max_len {
+        return Ok(Json(json!({ "users": [] })));
+    }
+    let limit = query.limit.unwrap_or(20).clamp(1, 50);
+
+    let mut excluded = blocked_either_way(&state, me).await?;
+    excluded.insert(me);
+
+    let users: Vec<ProfileResponse> = state
+        .store
+        .search_profiles(&q, &excluded, limit)
+        .await?
+        .into_iter()
+        .map(ProfileResponse::from)
+        .collect();
+
+    Ok(Json(json!({ "users": users })))
+}
@@ -0,0 +1,258 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use tower_cookies::Cookies;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::handlers::admin::{audit, require_admin, Admin};
+use crate::models::{
+    CreateFilterTermRequest, ReplaceWordFilterRequest, UpdateFilterTermRequest, WordFilterTermRow,
+};
+use crate::AppState;
+
+const MAX_TERM_CHARS: usize = 100;
+const MAX_TERMS: usize = 5000;
+
+// ---------------------------------------------------------------------------
+// GET /admin/word-filter  –  the whole filter
+// PUT /admin/word-filter  –  replace it
+// ---------------------------------------------------------------------------
+
+/// The terms in effect, in the shape `PUT` takes back, so a client can
+/// fetch the list, edit it and save it in one go.
+pub async fn get_word_filter_handler(
+    State(state): State<AppState>,
+    Admin(_): Admin,
+) -> Result<impl IntoResponse, ApiError> {
+    let terms = state.store.filter_terms().await?;
+    Ok(Json(json!({ "terms": terms })))
+}
+
+/// Terms missing from the body are removed, new ones added and changed
+/// ones updated; a term that is already there keeps its id. Takes effect
+/// on this instance at once and on the others at their next reload.
+pub async fn replace_word_filter_handler(
+    State(state): State<AppState>,
+    Admin(admin): Admin,
+    Json(body): Json<ReplaceWordFilterRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if body.terms.len() > MAX_TERMS {
+        return Err(ApiError::BadRequest(format!("At most {} terms", MAX_TERMS)));
+    }
+    let mut wanted: HashMap<String, CreateFilterTermRequest> = HashMap::new();
+    for entry in body.terms {
+        let term = normalize_term(&entry.term)?;
+        if wanted.insert(term.clone(), entry).is_some() {
+            return Err(ApiError::BadRequest(format!(
+                "'{}' is listed more than once",
+                term
+            )));
+        }
+    }
+
+    let existing = state.store.filter_terms().await?;
+    let removed: Vec<Uuid> = existing
+        .iter()
+        .filter(|row| !wanted.contains_key(&row.term))
+        .map(|row| row.id)
+        .collect();
+    let mut updated = 0;
+    for row in &existing {
+        let entry = match wanted.remove(&row.term) {
+            Some(e) => e,
+            None => continue,
+        };
+        if entry.mode == row.mode && entry.enforce_in_adult == row.enforce_in_adult {
+            continue;
+        }
+        state
+            .store
+            .update_filter_term(
+                row.id,
+                json!({ "mode": entry.mode, "enforce_in_adult": entry.enforce_in_adult }),
+            )
+            .await?;
+        updated += 1;
+    }
+    let added: Vec<WordFilterTermRow> = wanted
+        .into_iter()
+        .map(|(term, entry)| WordFilterTermRow {
+            id: Uuid::new_v4(),
+            term,
+            mode: entry.mode,
+            enforce_in_adult: entry.enforce_in_adult,
+            created_by: Some(admin.id),
+            created_at: None,
+        })
+        .collect();
+
+    state.store.delete_filter_terms(&removed).await?;
+    state.store.insert_filter_terms(&added).await?;
+
+    audit(
+        &state,
+        admin.id,
+        "word_filter.replace",
+        "word_filter",
+        json!({ "added": added.len(), "updated": updated, "removed": removed.len() }),
+    )
+    .await;
+    reload(&state).await;
+
+    Ok(Json(json!({
+        "terms": state.store.filter_terms().await?,
+        "added": added.len(),
+        "updated": updated,
+        "removed": removed.len(),
+    })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /admin/word-filter/terms  –  every blocked term
+// ---------------------------------------------------------------------------
+
+pub async fn list_terms_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    require_admin(&state, &cookies).await?;
+
+    let terms = state.store.filter_terms().await?;
+
+    Ok(Json(json!({ "terms": terms })))
+}
+
+// ---------------------------------------------------------------------------
+// POST /admin/word-filter/terms  –  add a term
+// ---------------------------------------------------------------------------
+
+pub async fn create_term_handler(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    Json(body): Json<CreateFilterTermRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+    let term = normalize_term(&body.term)?;
+
+    let row = WordFilterTermRow {
+        id: Uuid::new_v4(),
+        term,
+        mode: body.mode,
+        enforce_in_adult: body.enforce_in_adult,
+        created_by: Some(admin.id),
+        created_at: None,
+    };
+    let stored = match state.store.insert_filter_term(&row).await {
+        Ok(v) => v,
+        Err(ApiError::UniqueViolation(_)) => {
+            return Err(ApiError::BadRequest(format!(
+                "'{}' is already in the word filter",
+                row.term
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+
+    audit(
+        &state,
+        admin.id,
+        "word_filter.add",
+        row.id,
+        json!({ "term": row.term, "mode": row.mode }),
+    )
+    .await;
+    reload(&state).await;
+
+    Ok(Json(stored))
+}
+
+// ---------------------------------------------------------------------------
+// PATCH /admin/word-filter/terms/{id}  –  change a term's mode
+// ---------------------------------------------------------------------------
+
+pub async fn update_term_handler(
+    State(state): State<AppState>,
+    Path(term_id): Path<Uuid>,
+    cookies: Cookies,
+    Json(body): Json<UpdateFilterTermRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let mut changes = serde_json::Map::new();
+    if let Some(mode) = body.mode {
+        changes.insert("mode".into(), json!(mode));
+    }
+    if let Some(enforce) = body.enforce_in_adult {
+        changes.insert("enforce_in_adult".into(), json!(enforce));
+    }
+    if changes.is_empty() {
+        return Err(ApiError::BadRequest("Nothing to update".into()));
+    }
+    let changes = serde_json::Value::Object(changes);
+
+    let updated = state
+        .store
+        .update_filter_term(term_id, changes.clone())
+        .await?;
+
+    audit(&state, admin.id, "word_filter.update", term_id, changes).await;
+    reload(&state).await;
+
+    Ok(Json(updated))
+}
+
+// ---------------------------------------------------------------------------
+// DELETE /admin/word-filter/terms/{id}
+// ---------------------------------------------------------------------------
+
+pub async fn delete_term_handler(
+    State(state): State<AppState>,
+    Path(term_id): Path<Uuid>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, ApiError> {
+    let admin = require_admin(&state, &cookies).await?;
+
+    let term = match state.store.delete_filter_terms(&[term_id]).await?.pop() {
+        Some(row) => row.term,
+        None => return Err(ApiError::NotFound("Term not found".into())),
+    };
+
+    audit(
+        &state,
+        admin.id,
+        "word_filter.remove",
+        term_id,
+        json!({ "term": term }),
+    )
+    .await;
+    reload(&state).await;
+
+    Ok(Json(json!({ "deleted": term_id })))
+}
+
+/// Terms are stored trimmed and lowercased.
+fn normalize_term(raw: &str) -> Result<String, ApiError> {
+    let term = raw.trim().to_lowercase();
+    if term.is_empty() || term.chars().count() > MAX_TERM_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Term must be 1-{} characters",
+            MAX_TERM_CHARS
+        )));
+    }
+    Ok(term)
+}
+
+/// Apply a change on this instance right away. Other instances pick it up
+/// on their next periodic reload.
+async fn reload(state: &AppState) {
+    match state.word_filter.reload(state.store.as_ref()).await {
+        Ok(count) => info!("[word_filter] Reloaded {} terms", count),
+        Err(e) => error!("[word_filter] Reload failed: {}", e),
+    }
+}
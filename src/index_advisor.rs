@@ -0,0 +1,274 @@
+use tracing::{info, warn};
+
+use crate::db::{col, Column, Table};
+use crate::error::ApiError;
+use crate::store::Storage;
+
+/// SQL for the function the advisor reads indexes through, since PostgREST
+/// doesn't expose the `pg_indexes` catalog.
+const LIST_INDEXES_SQL: &str = "\
+create or replace function list_indexes()
+returns table(tablename text, indexname text, indexdef text)
+language sql stable security definer as $$
+  select tablename::text, indexname::text, indexdef
+  from pg_indexes where schemaname = 'public';
+$$;";
+
+/// An index some query path depends on.
+struct ExpectedIndex {
+    table: Table,
+    /// Leading columns, in order. Any index starting with these satisfies it.
+    columns: &'static [Column],
+    /// Index method when it isn't btree, e.g. "gin".
+    method: Option<&'static str>,
+    /// Which queries need it.
+    used_by: &'static str,
+}
+
+const EXPECTED_INDEXES: &[ExpectedIndex] = &[
+    ExpectedIndex {
+        table: Table::ConversationMembers,
+        columns: &[col::CONVERSATION_ID, col::USER_ID],
+        method: None,
+        used_by: "membership checks on every message and socket",
+    },
+    ExpectedIndex {
+        table: Table::ConversationMembers,
+        columns: &[col::USER_ID],
+        method: None,
+        used_by: "listing a user's conversations",
+    },
+    ExpectedIndex {
+        table: Table::Messages,
+        columns: &[col::CONVERSATION_ID, col::CREATED_AT],
+        method: None,
+        used_by: "message history pages and message expiry",
+    },
+    ExpectedIndex {
+        table: Table::Messages,
+        columns: &[col::CONVERSATION_ID, col::ID],
+        method: None,
+        used_by: "reconnect replay, message context and snapshots",
+    },
+    ExpectedIndex {
+        table: Table::Messages,
+        columns: &[col::SENDER_ID],
+        method: None,
+        used_by: "account erasure, duplicate send checks and quotas",
+    },
+    ExpectedIndex {
+        table: Table::Messages,
+        columns: &[col::CONTENT_TSV],
+        method: Some("gin"),
+        used_by: "full-text search",
+    },
+    ExpectedIndex {
+        table: Table::Friends,
+        columns: &[col::USER_A, col::STATUS],
+        method: None,
+        used_by: "friend lists",
+    },
+    ExpectedIndex {
+        table: Table::Friends,
+        columns: &[col::USER_B, col::STATUS],
+        method: None,
+        used_by: "friend lists",
+    },
+    ExpectedIndex {
+        table: Table::PollVotes,
+        columns: &[col::POLL_ID],
+        method: None,
+        used_by: "poll tallies",
+    },
+    ExpectedIndex {
+        table: Table::Profiles,
+        columns: &[col::USERNAME],
+        method: None,
+        used_by: "login and username checks",
+    },
+    ExpectedIndex {
+        table: Table::ScheduledMessages,
+        columns: &[col::NEXT_RUN_AT],
+        method: None,
+        used_by: "the scheduled message job",
+    },
+    ExpectedIndex {
+        table: Table::ScheduledMessages,
+        columns: &[col::SENDER_ID],
+        method: None,
+        used_by: "listing a user's scheduled messages",
+    },
+    ExpectedIndex {
+        table: Table::ErasureRequests,
+        columns: &[col::STATUS, col::REQUESTED_AT],
+        method: None,
+        used_by: "the erasure job",
+    },
+    ExpectedIndex {
+        table: Table::ModerationSnapshots,
+        columns: &[col::CONVERSATION_ID, col::CREATED_AT],
+        method: None,
+        used_by: "listing snapshots",
+    },
+];
+
+impl ExpectedIndex {
+    fn method(&self) -> &'static str {
+        self.method.unwrap_or("btree")
+    }
+
+    fn create_statement(&self) -> String {
+        let names: Vec<&str> = self.columns.iter().map(|c| c.name()).collect();
+        let using = match self.method {
+            Some(method) => format!(" using {}", method),
+            None => String::new(),
+        };
+        format!(
+            "create index if not exists {}_{}_idx on {}{} ({});",
+            self.table.name(),
+            names.join("_"),
+            self.table.name(),
+            using,
+            names.join(", ")
+        )
+    }
+}
+
+/// An existing index, parsed from its `pg_indexes.indexdef`.
+struct ExistingIndex {
+    table: String,
+    method: String,
+    columns: Vec<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Entry points
+// ---------------------------------------------------------------------------
+
+/// `backend check-indexes`: print a report and the CREATE INDEX statements
+/// for anything missing. Returns false if indexes are missing or the
+/// check couldn't run.
+pub async fn run_command(store: &dyn Storage) -> bool {
+    let existing = match fetch_indexes(store).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            println!("Could not read indexes: {}", e);
+            println!(
+                "If list_indexes() doesn't exist yet, create it first:\n\n{}",
+                LIST_INDEXES_SQL
+            );
+            return false;
+        }
+    };
+
+    let missing = missing_indexes(&existing);
+    for expected in EXPECTED_INDEXES {
+        let status = if missing.iter().any(|m| std::ptr::eq(*m, expected)) {
+            "MISSING"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:8} {}  ({})",
+            status,
+            expected.create_statement(),
+            expected.used_by
+        );
+    }
+
+    if missing.is_empty() {
+        println!("\nAll expected indexes are present.");
+        return true;
+    }
+
+    println!("\nRun these to add the missing indexes:\n");
+    for expected in &missing {
+        println!("{}", expected.create_statement());
+    }
+    false
+}
+
+/// Startup check: log a warning per missing index but never block boot.
+pub async fn warn_missing(store: &dyn Storage) {
+    let existing = match fetch_indexes(store).await {
+        Ok(existing) => existing,
+        Err(e) => {
+            warn!(
+                "[index_advisor] Skipping index check ({}). Create list_indexes() to enable it; \
+                 see `backend check-indexes`.",
+                e
+            );
+            return;
+        }
+    };
+
+    let missing = missing_indexes(&existing);
+    if missing.is_empty() {
+        info!(
+            "[index_advisor] All {} expected indexes present",
+            EXPECTED_INDEXES.len()
+        );
+    }
+    for expected in missing {
+        warn!(
+            "[index_advisor] Missing index for {}: {}",
+            expected.used_by,
+            expected.create_statement()
+        );
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Helpers
+// ---------------------------------------------------------------------------
+
+async fn fetch_indexes(store: &dyn Storage) -> Result<Vec<ExistingIndex>, ApiError> {
+    Ok(store
+        .indexes()
+        .await?
+        .iter()
+        .filter_map(|(table, def)| parse_index_def(table, def))
+        .collect())
+}
+
+/// Parse `CREATE [UNIQUE] INDEX name ON public.t USING btree (a, b DESC)`.
+fn parse_index_def(table: &str, def: &str) -> Option<ExistingIndex> {
+    let (_, rest) = def.split_once(" USING ")?;
+    let (method, rest) = rest.split_once(" (")?;
+    let column_list = rest.split(')').next()?;
+
+    let columns = column_list
+        .split(',')
+        .map(|c| {
+            c.split_whitespace()
+                .next()
+                .unwrap_or("")
+                .trim_matches('"')
+                .to_string()
+        })
+        .collect();
+
+    Some(ExistingIndex {
+        table: table.to_string(),
+        method: method.trim().to_lowercase(),
+        columns,
+    })
+}
+
+fn missing_indexes(existing: &[ExistingIndex]) -> Vec<&'static ExpectedIndex> {
+    EXPECTED_INDEXES
+        .iter()
+        .filter(|expected| {
+            !existing.iter().any(|index| {
+                index.table == expected.table.name()
+                    && index.method == expected.method()
+                    && index.columns.len() >= expected.columns.len()
+                    && expected
+                        .columns
+                        .iter()
+                        .zip(&index.columns)
+                        .all(|(want, have)| want.name() == have)
+            })
+        })
+        .collect()
+}
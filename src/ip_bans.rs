@@ -0,0 +1,198 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::models::IpBanRow;
+use crate::store::Storage;
+use crate::AppState;
+
+/// An address block such as `203.0.113.0/24` or `2001:db8::/32`. A bare
+/// address is a block of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.addr.is_ipv4() && network(ip, self.prefix) == self.addr
+    }
+}
+
+/// `addr` with everything past the first `prefix` bits cleared.
+fn network(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(a) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4((u32::from(a) & mask).into())
+        }
+        IpAddr::V6(a) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6((u128::from(a) & mask).into())
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        let (addr, prefix) = match raw.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (raw, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("'{}' is not an IP address or CIDR block", raw))?;
+        let addr = addr.to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Prefix length must be 0-{}", max))?,
+            None => max,
+        };
+        Ok(IpNet {
+            addr: network(addr, prefix),
+            prefix,
+        })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// The caller's address as worked out by `enforce`, for handlers that need
+/// it. Available as `Extension<ClientIp>` on every route.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Banned address blocks, held in memory so every request can be checked
+/// without a query. The source of truth is the `ip_bans` table; `reload`
+/// swaps in a fresh copy, and changes made on this instance apply at once.
+#[derive(Default)]
+pub struct IpBanList {
+    entries: RwLock<Vec<BanEntry>>,
+}
+
+struct BanEntry {
+    id: Uuid,
+    net: IpNet,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl BanEntry {
+    fn from_row(row: &IpBanRow) -> Option<Self> {
+        Some(BanEntry {
+            id: row.id,
+            net: row.cidr.parse().ok()?,
+            expires_at: row
+                .expires_at
+                .as_deref()
+                .and_then(|t| t.parse::<DateTime<Utc>>().ok()),
+        })
+    }
+}
+
+impl IpBanList {
+    /// Replace the in-memory list with what's in the database. Returns the
+    /// number of bans loaded.
+    pub async fn reload(&self, store: &dyn Storage) -> Result<usize, ApiError> {
+        let entries: Vec<BanEntry> = store
+            .ip_bans()
+            .await?
+            .iter()
+            .filter_map(BanEntry::from_row)
+            .collect();
+
+        let count = entries.len();
+        *self.entries.write().unwrap() = entries;
+        Ok(count)
+    }
+
+    /// Whether `ip` falls in a ban that hasn't expired.
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        let now = Utc::now();
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| e.expires_at.is_none_or(|at| at > now) && e.net.contains(ip))
+    }
+
+    pub fn add(&self, row: &IpBanRow) {
+        if let Some(entry) = BanEntry::from_row(row) {
+            self.entries.write().unwrap().push(entry);
+        }
+    }
+
+    pub fn remove(&self, id: Uuid) {
+        self.entries.write().unwrap().retain(|e| e.id != id);
+    }
+}
+
+/// The client's address. With `trusted_hops` proxies in front of us, each
+/// one appends the address it received the request from to
+/// `X-Forwarded-For`, so the client is that many entries from the right of
+/// the chain ending in the peer address. Anything further left was written
+/// by the client and can't be trusted.
+pub fn client_ip(headers: &HeaderMap, peer: IpAddr, trusted_hops: usize) -> IpAddr {
+    if trusted_hops == 0 {
+        return peer.to_canonical();
+    }
+    let mut chain: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|ip| ip.trim().parse().ok())
+        .collect();
+    chain.push(peer);
+    let index = chain.len().saturating_sub(trusted_hops + 1);
+    chain[index].to_canonical()
+}
+
+/// Refuse requests from banned addresses before they reach any route, and
+/// record the client address for the handlers. Wraps the whole router, so
+/// the frontend fallback is covered too.
+pub async fn enforce(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let peer = match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => addr.ip(),
+        None => return next.run(request).await,
+    };
+    let ip = client_ip(request.headers(), peer, state.config.proxy.trusted_hops);
+
+    if state.ip_bans.is_banned(ip) {
+        info!(
+            "[ip_bans] Refused {} {} from {}",
+            request.method(),
+            request.uri().path(),
+            ip
+        );
+        return ApiError::Forbidden("Access from your network has been blocked".into())
+            .into_response();
+    }
+
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
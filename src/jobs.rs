@@ -0,0 +1,486 @@
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::db::Order;
+use crate::error::ApiError;
+use crate::handlers::chat::{deliver_message, publish, verify_membership};
+use crate::handlers::export::{ExportFormat, ExportWriter};
+use crate::handlers::notifications::notify_user;
+use crate::handlers::scheduled::{next_occurrence, to_timestamp, LOCAL_FORMAT};
+use crate::models::{
+    ExportReady, ExportRow, MessagesExpired, ScheduledMessageRow, UserEvent, WsEvent, WsIncoming,
+};
+use crate::storage;
+use crate::store::MessageFilter;
+use crate::AppState;
+
+/// Start every background job. Called once at startup; jobs run until the
+/// process exits.
+pub fn spawn_all(state: AppState) {
+    tokio::spawn(run_scheduled_messages(state.clone()));
+    tokio::spawn(run_erasures(state.clone()));
+    tokio::spawn(run_message_expiry(state.clone()));
+    tokio::spawn(run_exports(state.clone()));
+    tokio::spawn(run_word_filter_reload(state.clone()));
+    tokio::spawn(run_ban_list_reload(state.clone()));
+    tokio::spawn(run_rate_limit_reload(state.clone()));
+    if !state.endpoints.endpoints.is_empty() {
+        tokio::spawn(run_endpoint_probes(state));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Scheduled messages
+// ---------------------------------------------------------------------------
+
+async fn run_scheduled_messages(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.scheduler_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = send_due_messages(&state).await {
+            error!("[jobs] Scheduled message run failed: {}", e);
+        }
+    }
+}
+
+/// How long a claimed schedule stays away from other runs. An instance that
+/// dies mid-send leaves it to be picked up again after this.
+const SCHEDULE_CLAIM_LEASE: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Send every schedule whose `next_run_at` has passed.
+///
+/// Each one is claimed first by moving `next_run_at` past the lease, only
+/// if nobody else has, so several instances never send it twice. After a
+/// successful send, or one that can never succeed, recurring schedules
+/// advance and one-off schedules are deleted; a transient failure puts the
+/// schedule back to be retried on a later run.
+///
+/// Catch-up after downtime: an overdue schedule is sent once, then recurring
+/// ones jump to their next future occurrence instead of replaying each
+/// missed run.
+async fn send_due_messages(state: &AppState) -> Result<(), ApiError> {
+    let now = Utc::now();
+
+    for row in state.store.due_schedules(now, 100).await? {
+        let claimed = state
+            .store
+            .claim_schedule(row.id, &row.next_run_at, now + SCHEDULE_CLAIM_LEASE)
+            .await?;
+        if !claimed {
+            continue;
+        }
+
+        let result = match send_scheduled(state, &row).await {
+            Err(e) if e.is_transient() => {
+                warn!(
+                    "[jobs] Scheduled message {} failed, will retry: {}",
+                    row.id, e
+                );
+                let retry_at =
+                    now + chrono::Duration::seconds(e.retry_after_secs().unwrap_or(0) as i64);
+                state
+                    .store
+                    .update_schedule(row.id, json!({ "next_run_at": to_timestamp(retry_at) }))
+                    .await
+            }
+            sent => {
+                if let Err(e) = sent {
+                    error!("[jobs] Scheduled message {} failed: {}", row.id, e);
+                }
+                advance_schedule(state, &row, now).await
+            }
+        };
+        if let Err(e) = result {
+            error!(
+                "[jobs] Failed to update scheduled message {}: {}",
+                row.id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Move a recurring schedule on to its next occurrence; one-off schedules
+/// are done.
+async fn advance_schedule(
+    state: &AppState,
+    row: &ScheduledMessageRow,
+    now: DateTime<Utc>,
+) -> Result<(), ApiError> {
+    match next_occurrence(row, now) {
+        Ok(Some((local, next_run))) => {
+            state
+                .store
+                .update_schedule(
+                    row.id,
+                    json!({
+                        "send_at_local": local.format(LOCAL_FORMAT).to_string(),
+                        "next_run_at": to_timestamp(next_run),
+                    }),
+                )
+                .await
+        }
+        _ => state.store.delete_schedule(row.id).await,
+    }
+}
+
+async fn send_scheduled(state: &AppState, row: &ScheduledMessageRow) -> Result<(), ApiError> {
+    // The sender may have left the conversation since scheduling.
+    verify_membership(state, row.conversation_id, row.sender_id).await?;
+    let sender = state.store.profile(row.sender_id).await?;
+
+    deliver_message(
+        state,
+        row.conversation_id,
+        row.sender_id,
+        sender.is_bot(),
+        WsIncoming {
+            content: row.content.clone(),
+            components: None,
+            attachment: None,
+            poll: None,
+            sticker_id: None,
+            client_id: None,
+            approved: false,
+        },
+    )
+    .await?;
+
+    info!(
+        "[jobs] Sent scheduled message {} to conversation {}",
+        row.id, row.conversation_id
+    );
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Right-to-erasure
+// ---------------------------------------------------------------------------
+
+async fn run_erasures(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.erasure_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = process_erasures(&state).await {
+            error!("[jobs] Erasure run failed: {}", e);
+        }
+    }
+}
+
+/// Work through pending erasure requests, recording a report on each.
+async fn process_erasures(state: &AppState) -> Result<(), ApiError> {
+    for request in state.store.pending_erasures(10).await? {
+        let (status, report) = match erase_user(state, request.user_id).await {
+            Ok(report) => ("completed", report),
+            Err(e) => ("failed", json!({ "error": e.to_string() })),
+        };
+
+        info!(
+            "[jobs] Erasure {} for user {}: {}",
+            request.id, request.user_id, status
+        );
+
+        state
+            .store
+            .finish_erasure(request.id, status, report)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Scrub everything a user authored or is linked to, returning row counts per step.
+/// Messages become tombstones (row kept, content and files removed) so
+/// conversation history stays consistent for the other participants.
+async fn erase_user(state: &AppState, user_id: Uuid) -> Result<serde_json::Value, ApiError> {
+    // Files first: once the rows are tombstoned nothing points at them.
+    let sent = MessageFilter {
+        sender_id: Some(user_id),
+        ..Default::default()
+    };
+    let held = state.store.held_messages_of(user_id).await?;
+    let paths: Vec<String> = state
+        .store
+        .messages(
+            &MessageFilter {
+                with_attachment: true,
+                ..sent.clone()
+            },
+            Order::Asc,
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter_map(|m| m.attachment)
+        .chain(held.into_iter().filter_map(|h| h.attachment))
+        .flat_map(|a| std::iter::once(a.path).chain(a.thumbnail.map(|t| t.path)))
+        .collect();
+    storage::remove(&state.config.attachments.bucket, &paths).await?;
+
+    let tombstoned = state.store.tombstone_messages(&sent).await?;
+    let friendships = state.store.delete_friendships_of(user_id).await?;
+    let memberships = state.store.remove_memberships(user_id).await?;
+    let held = state.store.delete_held_messages_of(user_id).await?;
+    let scheduled = state.store.delete_schedules_of(user_id).await?;
+    let activity = state.store.delete_activity_of(user_id).await?;
+    // Reports they filed stay for the moderation record, minus their words.
+    let reports = state.store.scrub_report_reasons(user_id).await?;
+    let appeals = state.store.delete_appeals_of(user_id).await?;
+    let drafts = state.store.delete_drafts_of(user_id).await?;
+    state.store.delete_settings(user_id).await?;
+
+    // Backups can't be rewritten; record the user so restores re-apply the erasure.
+    state.store.record_backup_exclusion(user_id).await?;
+
+    Ok(json!({
+        "messages_tombstoned": tombstoned.messages,
+        "attachment_files_removed": paths.len(),
+        "message_revisions_removed": tombstoned.revisions,
+        "friendships_removed": friendships,
+        "memberships_removed": memberships,
+        "held_messages_removed": held,
+        "scheduled_messages_removed": scheduled,
+        "drafts_removed": drafts,
+        "report_reasons_scrubbed": reports,
+        "appeals_removed": appeals,
+        "activity_removed": activity,
+        "backup_exclusion_recorded": true,
+    }))
+}
+
+// ---------------------------------------------------------------------------
+// Disappearing messages
+// ---------------------------------------------------------------------------
+
+async fn run_message_expiry(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.expiry_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = expire_messages(&state).await {
+            error!("[jobs] Message expiry run failed: {}", e);
+        }
+    }
+}
+
+/// Tombstone messages older than their conversation's TTL and tell
+/// connected clients to drop them. Rows are kept (like erasure) so message
+/// counts stay consistent; content and files are removed.
+async fn expire_messages(state: &AppState) -> Result<(), ApiError> {
+    // Large backlogs drain over several runs.
+    const BATCH_SIZE: usize = 500;
+
+    for conversation in state.store.conversations_with_ttl().await? {
+        let ttl = match conversation.message_ttl_seconds {
+            Some(t) if t > 0 => t,
+            _ => continue,
+        };
+        let cutoff = Utc::now() - chrono::Duration::seconds(ttl as i64);
+
+        let expired = state
+            .store
+            .messages(
+                &MessageFilter {
+                    conversation_id: Some(conversation.id),
+                    created_before: Some(to_timestamp(cutoff)),
+                    live_only: true,
+                    ..Default::default()
+                },
+                Order::Asc,
+                Some(BATCH_SIZE),
+            )
+            .await?;
+        let ids: Vec<i64> = expired.iter().filter_map(|m| m.id).collect();
+        if ids.is_empty() {
+            continue;
+        }
+
+        state
+            .store
+            .tombstone_messages(&MessageFilter {
+                ids: Some(ids.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+        // Best-effort: nothing links to the files once the row is tombstoned.
+        let paths: Vec<String> = expired
+            .iter()
+            .filter_map(|m| m.attachment.as_ref())
+            .flat_map(|a| {
+                std::iter::once(a.path.clone()).chain(a.thumbnail.as_ref().map(|t| t.path.clone()))
+            })
+            .collect();
+        if let Err(e) = storage::remove(&state.config.attachments.bucket, &paths).await {
+            error!("[jobs] Failed to delete expired attachments: {}", e);
+        }
+
+        info!(
+            "[jobs] Expired {} messages in conversation {}",
+            ids.len(),
+            conversation.id
+        );
+        publish(
+            state,
+            conversation.id,
+            WsEvent::MessageExpired(MessagesExpired { message_ids: ids }),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Conversation exports
+// ---------------------------------------------------------------------------
+
+async fn run_exports(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.export_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = process_exports(&state).await {
+            error!("[jobs] Export run failed: {}", e);
+        }
+    }
+}
+
+/// Build pending exports, upload them to Storage and tell the requester.
+/// Each export is claimed by flipping it to "processing" first, so two
+/// instances never build the same one.
+async fn process_exports(state: &AppState) -> Result<(), ApiError> {
+    for export in state.store.pending_exports(5).await? {
+        if !state.store.claim_export(export.id).await? {
+            continue;
+        }
+
+        let update = match build_export(state, &export).await {
+            Ok(path) => json!({
+                "status": "completed",
+                "path": path,
+                "completed_at": to_timestamp(Utc::now()),
+            }),
+            Err(e) => {
+                error!("[jobs] Export {} failed: {}", export.id, e);
+                json!({
+                    "status": "failed",
+                    "error": e.to_string(),
+                    "completed_at": to_timestamp(Utc::now()),
+                })
+            }
+        };
+        let status = update["status"].as_str().unwrap_or_default().to_string();
+        state.store.update_export(export.id, update).await?;
+
+        info!(
+            "[jobs] Export {} of conversation {}: {}",
+            export.id, export.conversation_id, status
+        );
+        notify_user(
+            state,
+            export.user_id,
+            UserEvent::ExportReady(ExportReady {
+                export_id: export.id,
+                conversation_id: export.conversation_id,
+                status,
+            }),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Write the whole export and upload it, returning its Storage path.
+async fn build_export(state: &AppState, export: &ExportRow) -> Result<String, ApiError> {
+    // The requester may have left since asking.
+    verify_membership(state, export.conversation_id, export.user_id).await?;
+
+    let format = ExportFormat::parse(Some(&export.format))?;
+    let mut writer = ExportWriter::new(
+        state.store.clone(),
+        export.conversation_id,
+        export.user_id,
+        format,
+    );
+    let mut document = Vec::new();
+    while let Some(chunk) = writer.next_chunk().await? {
+        document.extend_from_slice(&chunk);
+    }
+
+    let path = format!("{}/{}.{}", export.user_id, export.id, format.as_str());
+    storage::upload(
+        &state.config.exports.bucket,
+        &path,
+        format.content_type(),
+        document.into(),
+    )
+    .await?;
+    Ok(path)
+}
+
+// ---------------------------------------------------------------------------
+// Word filter
+// ---------------------------------------------------------------------------
+
+/// The first tick fires immediately, which doubles as the initial load.
+async fn run_word_filter_reload(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.word_filter_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = state.word_filter.reload(state.store.as_ref()).await {
+            error!("[jobs] Word filter reload failed: {}", e);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Banned accounts and revoked sessions
+// ---------------------------------------------------------------------------
+
+/// Pick up bans and revocations made straight in the database. A failed
+/// reload leaves the last good copy in place.
+async fn run_ban_list_reload(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.ban_list_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = state.bans.reload(state.store.as_ref()).await {
+            error!("[jobs] Ban list reload failed: {}", e);
+        }
+        if let Err(e) = state.ip_bans.reload(state.store.as_ref()).await {
+            error!("[jobs] IP ban list reload failed: {}", e);
+        }
+        if let Err(e) = state.sessions.reload(state.store.as_ref()).await {
+            error!("[jobs] Session version reload failed: {}", e);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Rate-limit overrides
+// ---------------------------------------------------------------------------
+
+async fn run_rate_limit_reload(state: AppState) {
+    let mut tick = tokio::time::interval(state.config.jobs.rate_limit_interval);
+    loop {
+        tick.tick().await;
+        if let Err(e) = state.rate_limits.reload(state.store.as_ref()).await {
+            error!("[jobs] Rate-limit override reload failed: {}", e);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Regional endpoint health
+// ---------------------------------------------------------------------------
+
+async fn run_endpoint_probes(state: AppState) {
+    let discovery = &state.config.discovery;
+    let mut tick = tokio::time::interval(discovery.probe_interval);
+    loop {
+        tick.tick().await;
+        state.endpoints.probe_all(discovery.probe_timeout).await;
+    }
+}
@@ -0,0 +1,345 @@
+// Link previews ("unfurling").
+//
+// When a message contains a URL we fetch the page server-side and pull out
+// its OpenGraph metadata. Fetching arbitrary user-supplied URLs from inside
+// our network is an SSRF risk, so every hop is checked before we connect:
+// only http(s), only public addresses, the connection is pinned to the
+// address we checked (no DNS rebinding between check and connect),
+// redirects are followed by hand and re-checked, and both time and body
+// size are capped. Results, including failures, are cached.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use reqwest::{header, redirect, StatusCode, Url};
+use tracing::info;
+
+use crate::config::LinkPreviewConfig;
+use crate::models::LinkPreview;
+
+/// Redirect hops followed before giving up.
+const MAX_REDIRECTS: usize = 3;
+
+/// Longest title/description kept, in characters.
+const MAX_TEXT_CHARS: usize = 300;
+
+pub struct LinkPreviewer {
+    config: LinkPreviewConfig,
+    /// url → (fetched at, preview). `None` caches a failed or empty fetch.
+    cache: Mutex<HashMap<String, (Instant, Option<LinkPreview>)>>,
+}
+
+impl LinkPreviewer {
+    pub fn new(config: &LinkPreviewConfig) -> Self {
+        Self {
+            config: config.clone(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// The cached preview for `url`, without fetching. The outer `None`
+    /// means "not cached"; `Some(None)` means we tried and found nothing.
+    pub fn cached(&self, url: &str) -> Option<Option<LinkPreview>> {
+        let cache = self.cache.lock().unwrap();
+        match cache.get(url) {
+            Some((at, preview)) if at.elapsed() < self.config.cache_ttl => Some(preview.clone()),
+            _ => None,
+        }
+    }
+
+    /// Fetch (or reuse) the preview for `url`.
+    pub async fn preview(&self, url: &str) -> Option<LinkPreview> {
+        if let Some(hit) = self.cached(url) {
+            return hit;
+        }
+
+        let preview = match self.fetch(url).await {
+            Ok(p) => p,
+            Err(reason) => {
+                info!("[link_preview] No preview for {}: {}", url, reason);
+                None
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.config.cache_capacity {
+            let ttl = self.config.cache_ttl;
+            cache.retain(|_, (at, _)| at.elapsed() < ttl);
+            // Still full of live entries: start over rather than track LRU order.
+            if cache.len() >= self.config.cache_capacity {
+                cache.clear();
+            }
+        }
+        cache.insert(url.to_string(), (Instant::now(), preview.clone()));
+        preview
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Option<LinkPreview>, String> {
+        let mut current = Url::parse(url).map_err(|e| e.to_string())?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let addr = resolve_public(&current).await?;
+            let host = current.host_str().unwrap_or_default().to_string();
+
+            let client = reqwest::Client::builder()
+                .redirect(redirect::Policy::none())
+                .timeout(self.config.timeout)
+                .resolve(&host, addr)
+                .user_agent("GigaChatBot/1.0 (link preview)")
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            let mut res = client
+                .get(current.clone())
+                .header(header::ACCEPT, "text/html")
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if res.status().is_redirection() {
+                let location = res
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or("redirect without a Location")?;
+                current = current.join(location).map_err(|e| e.to_string())?;
+                continue;
+            }
+            if res.status() != StatusCode::OK {
+                return Err(format!("HTTP {}", res.status()));
+            }
+
+            let is_html = res
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("text/html"));
+            if !is_html {
+                return Ok(None);
+            }
+
+            // Metadata lives in <head>, so a truncated body is fine.
+            let mut body = Vec::new();
+            while let Some(chunk) = res.chunk().await.map_err(|e| e.to_string())? {
+                body.extend_from_slice(&chunk);
+                if body.len() >= self.config.max_bytes {
+                    body.truncate(self.config.max_bytes);
+                    break;
+                }
+            }
+
+            let html = String::from_utf8_lossy(&body);
+            return Ok(extract_preview(&current, &html));
+        }
+
+        Err("too many redirects".into())
+    }
+}
+
+/// First http(s) URL in a message, if any.
+pub fn first_url(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let candidate = word[start..].trim_end_matches(|c: char| ".,;:!?)]}>'\"".contains(c));
+            Url::parse(candidate).ok().map(|u| u.to_string())
+        })
+        .next()
+}
+
+// ---------------------------------------------------------------------------
+// SSRF checks
+// ---------------------------------------------------------------------------
+
+/// Resolve the URL's host and return an address that is safe to connect to.
+/// Refuses the whole host if any of its addresses is non-public, so a
+/// record mixing public and private addresses can't slip through.
+async fn resolve_public(url: &Url) -> Result<SocketAddr, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("scheme '{}' not allowed", url.scheme()));
+    }
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+
+    // `lookup_host` wants a bare host; IPv6 literals come back bracketed.
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((bare, port))
+        .await
+        .map_err(|e| format!("DNS lookup failed: {}", e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err("host has no addresses".into());
+    }
+    if let Some(bad) = addrs.iter().find(|a| !is_public(a.ip())) {
+        return Err(format!(
+            "{} resolves to non-public address {}",
+            host,
+            bad.ip()
+        ));
+    }
+    Ok(addrs[0])
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (64..128).contains(&b))
+                // "This network", 0.0.0.0/8.
+                || a == 0)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local, fc00::/7.
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10.
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// HTML metadata extraction
+// ---------------------------------------------------------------------------
+
+/// Pull OpenGraph fields out of `html`, falling back to `<title>` and the
+/// plain `description` meta tag. Returns `None` if there is nothing to show.
+fn extract_preview(page_url: &Url, html: &str) -> Option<LinkPreview> {
+    let mut meta: HashMap<String, String> = HashMap::new();
+
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find("<meta") {
+        let start = pos + offset;
+        let end = match lower[start..].find('>') {
+            Some(e) => start + e,
+            None => break,
+        };
+        let attrs = parse_attributes(&html[start + "<meta".len()..end]);
+        let key = attrs
+            .get("property")
+            .or_else(|| attrs.get("name"))
+            .map(|k| k.to_ascii_lowercase());
+        if let (Some(key), Some(content)) = (key, attrs.get("content")) {
+            meta.entry(key).or_insert_with(|| content.clone());
+        }
+        pos = end;
+    }
+
+    let title_tag = lower.find("<title").and_then(|start| {
+        let open_end = start + lower[start..].find('>')? + 1;
+        let close = open_end + lower[open_end..].find("</title")?;
+        Some(html[open_end..close].to_string())
+    });
+
+    let clean = |s: &str| {
+        let text = decode_entities(s.trim());
+        let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+        Some(text).filter(|t| !t.is_empty())
+    };
+
+    let title = meta
+        .get("og:title")
+        .or_else(|| meta.get("twitter:title"))
+        .cloned()
+        .or(title_tag)
+        .and_then(|t| clean(&t));
+    let description = meta
+        .get("og:description")
+        .or_else(|| meta.get("description"))
+        .and_then(|d| clean(d));
+    let site_name = meta.get("og:site_name").and_then(|s| clean(s));
+    // Only absolute http(s) images; relative paths are resolved against the page.
+    let image_url = meta
+        .get("og:image")
+        .or_else(|| meta.get("twitter:image"))
+        .and_then(|src| page_url.join(&decode_entities(src.trim())).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(|u| u.to_string());
+
+    if title.is_none() && description.is_none() && image_url.is_none() {
+        return None;
+    }
+
+    Some(LinkPreview {
+        url: page_url.to_string(),
+        title,
+        description,
+        image_url,
+        site_name,
+    })
+}
+
+/// Parse `key="value" key='value' key=value` pairs from the inside of a tag.
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = tag.trim_start();
+
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (value, remaining) = match after_eq.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let inner = &after_eq[1..];
+                    match inner.find(q) {
+                        Some(close) => (&inner[..close], &inner[close + 1..]),
+                        None => (inner, ""),
+                    }
+                }
+                _ => {
+                    let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                    (&after_eq[..end], &after_eq[end..])
+                }
+            };
+            if !name.is_empty() {
+                attrs.insert(name, value.to_string());
+            }
+            rest = remaining.trim_start();
+        } else if name.is_empty() {
+            // A stray '/' or '='; skip it to make progress.
+            rest = rest.get(1..).unwrap_or("").trim_start();
+        }
+    }
+
+    attrs
+}
+
+/// Decode the handful of entities that show up in titles and descriptions.
+fn decode_entities(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
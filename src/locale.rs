@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use tower_cookies::Cookies;
+
+use crate::error::{ApiError, SUPPORTED_LOCALES};
+use crate::handlers::auth::get_session;
+use crate::AppState;
+
+/// Translate error messages into the caller's language. The locale saved on
+/// a signed-in user's profile wins over `Accept-Language`; codes never
+/// change. The profile is only looked up when a request actually failed.
+pub async fn localize_errors(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> Response {
+    let accept_language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    let mut response = next.run(request).await;
+    let error = match response.extensions_mut().remove::<ApiError>() {
+        Some(e) => e,
+        None => return response,
+    };
+
+    let saved = match get_session(&state, &cookies) {
+        Ok(user_id) => state
+            .store
+            .profile(user_id)
+            .await
+            .ok()
+            .and_then(|p| p.locale),
+        Err(_) => None,
+    };
+    let locale = saved
+        .filter(|l| is_supported(l))
+        .or_else(|| accept_language.as_deref().and_then(negotiate));
+
+    if let Some(locale) = locale {
+        error.localize_response(&mut response, &locale);
+    }
+    response
+}
+
+/// Whether `locale` is in the message catalog.
+pub fn is_supported(locale: &str) -> bool {
+    SUPPORTED_LOCALES.contains(&locale)
+}
+
+/// The best supported language in an `Accept-Language` header, by quality.
+/// Region subtags are ignored, so `de-AT` picks `de`.
+fn negotiate(header: &str) -> Option<String> {
+    let mut candidates: Vec<(f32, String)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let tag = pieces.next()?.trim();
+            let quality = pieces
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            let language = tag.split('-').next()?.to_lowercase();
+            Some((quality, language))
+        })
+        .filter(|(quality, language)| *quality > 0.0 && is_supported(language))
+        .collect();
+    // Stable, so equal qualities keep the header's order.
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidates.into_iter().next().map(|(_, language)| language)
+}
@@ -3,24 +3,68 @@
 // This file sets up the Axum web server with all routes, shared state,
 // CORS policy, cookie middleware, and serves the frontend static files.
 
+mod admin_events;
+mod bans;
+mod bus;
+mod config;
+mod content_policy;
+mod db;
+mod discovery;
 mod error;
+mod friend_limits;
 mod handlers;
+mod index_advisor;
+mod ip_bans;
+mod jobs;
+mod link_preview;
+mod locale;
 mod models;
+mod moderation;
+mod rate_limit;
+mod sender_history;
+mod sessions;
+mod spam;
+mod spam_policy;
+mod storage;
+mod store;
+mod wire;
+mod word_filter;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use axum::http::{HeaderName, HeaderValue, Method};
 use axum::{
-    routing::{get, post},
-    Router,
+    extract::DefaultBodyLimit,
+    routing::{delete, get, patch, post, put},
+    Router, ServiceExt,
 };
 use supabase_rs::SupabaseClient;
+use tower::Layer;
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use tower_http::services::ServeDir;
 
-use handlers::chat::ConversationChannels;
+use admin_events::AdminEvents;
+use bans::BanList;
+use bus::MessageBus;
+use config::Config;
+use content_policy::ContentPolicy;
+use discovery::EndpointRegistry;
+use friend_limits::{FriendRequestLimiter, FriendRequestLimits};
+use handlers::blocks::BlockCache;
+use handlers::calls::CallTracker;
+use handlers::chat::{ConversationChannels, SlowModeSlots};
+use handlers::notifications::UserChannels;
+use ip_bans::IpBanList;
+use link_preview::LinkPreviewer;
+use moderation::Moderator;
+use rate_limit::RateLimitOverrides;
+use sessions::SessionVersions;
+use spam::{SpamScorer, SpamScoring};
+use spam_policy::{SpamGuard, SpamPolicy};
+use store::{Storage, SupabaseStore};
+use word_filter::WordFilter;
 
 // ---------------------------------------------------------------------------
 // Application state shared across all handlers
@@ -28,8 +72,27 @@ use handlers::chat::ConversationChannels;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub supabase: Arc<SupabaseClient>,
+    pub store: Arc<dyn Storage>,
     pub channels: ConversationChannels,
+    pub user_channels: UserChannels,
+    pub calls: CallTracker,
+    pub blocks: BlockCache,
+    pub slow_mode: SlowModeSlots,
+    pub bus: Arc<MessageBus>,
+    pub endpoints: Arc<EndpointRegistry>,
+    pub link_previews: Arc<LinkPreviewer>,
+    pub config: Arc<Config>,
+    pub content_policy: Arc<ContentPolicy>,
+    pub spam: Arc<SpamGuard>,
+    pub spam_scores: Arc<SpamScorer>,
+    pub moderation: Arc<Moderator>,
+    pub friend_requests: Arc<FriendRequestLimiter>,
+    pub word_filter: Arc<WordFilter>,
+    pub bans: Arc<BanList>,
+    pub sessions: Arc<SessionVersions>,
+    pub admin_events: Arc<AdminEvents>,
+    pub ip_bans: Arc<IpBanList>,
+    pub rate_limits: Arc<RateLimitOverrides>,
 }
 
 // ---------------------------------------------------------------------------
@@ -88,12 +151,83 @@ async fn main() {
         )
         .init();
 
+    // Maintenance: `backend check-indexes` reports missing indexes and exits.
+    if std::env::args().nth(1).as_deref() == Some("check-indexes") {
+        let store = SupabaseStore::new(Arc::new(create_supabase_client()));
+        let ok = index_advisor::run_command(&store).await;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     // Build shared state.
+    let config = Config::from_env();
+    let channels = handlers::chat::new_channel_map();
+    let user_channels = handlers::notifications::new_user_channel_map();
+    let admin_events = Arc::new(AdminEvents::default());
+    let bans = Arc::new(BanList::default());
+    let sessions = Arc::new(SessionVersions::default());
+    let bus = MessageBus::connect(
+        &config.bus,
+        channels.clone(),
+        user_channels.clone(),
+        admin_events.sender(),
+        bans.clone(),
+        sessions.clone(),
+    )
+    .await;
+
     let state = AppState {
-        supabase: Arc::new(create_supabase_client()),
-        channels: handlers::chat::new_channel_map(),
+        store: Arc::new(SupabaseStore::new(Arc::new(create_supabase_client()))),
+        channels,
+        user_channels,
+        calls: handlers::calls::new_call_tracker(),
+        blocks: handlers::blocks::new_block_cache(),
+        slow_mode: handlers::chat::new_slow_mode_slots(),
+        bus: Arc::new(bus),
+        endpoints: Arc::new(EndpointRegistry::new(&config.discovery)),
+        link_previews: Arc::new(LinkPreviewer::new(&config.link_previews)),
+        config: Arc::new(config),
+        content_policy: Arc::new(ContentPolicy::from_env()),
+        spam: Arc::new(SpamGuard::new(SpamPolicy::from_env())),
+        spam_scores: Arc::new(SpamScorer::new(SpamScoring::from_env())),
+        moderation: Arc::new(Moderator::from_env()),
+        friend_requests: Arc::new(FriendRequestLimiter::new(FriendRequestLimits::from_env())),
+        word_filter: Arc::new(WordFilter::default()),
+        bans,
+        sessions,
+        admin_events,
+        ip_bans: Arc::new(IpBanList::default()),
+        rate_limits: Arc::new(RateLimitOverrides::default()),
     };
 
+    // Bans and revoked sessions must be known before the first request,
+    // or banned users would get in until the first reload.
+    let banned = state
+        .bans
+        .reload(state.store.as_ref())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load the ban list: {}", e));
+    state
+        .ip_bans
+        .reload(state.store.as_ref())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load the IP ban list: {}", e));
+    let revoked = state
+        .sessions
+        .reload(state.store.as_ref())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load session versions: {}", e));
+    println!(
+        "Access: {} banned accounts, {} with revoked sessions",
+        banned, revoked
+    );
+
+    // Background jobs (scheduled messages, ...).
+    jobs::spawn_all(state.clone());
+
+    // Warn about missing indexes without delaying startup.
+    let store = state.store.clone();
+    tokio::spawn(async move { index_advisor::warn_missing(store.as_ref()).await });
+
     // Resolve the path to the frontend directory.
     // Default: ../frontend (relative to where `cargo run` is executed, i.e. the backend/ folder).
     let frontend_dir = std::env::var("FRONTEND_DIR").unwrap_or_else(|_| "../frontend".into());
@@ -124,6 +258,7 @@ async fn main() {
             Method::GET,
             Method::POST,
             Method::PUT,
+            Method::PATCH,
             Method::DELETE,
             Method::OPTIONS,
         ])
@@ -141,21 +276,60 @@ async fn main() {
     // Build the router with all API routes, then fall back to static files.
     let app = Router::new()
         // ── API routes ────────────────────────────────────────────────
+        .route("/health", get(handlers::system::health_handler))
         // Auth
         .route("/register", post(handlers::auth::register_handler))
         .route("/login", post(handlers::auth::login_handler))
         .route("/logout", post(handlers::auth::logout_handler))
-        .route("/me", get(handlers::auth::me_handler))
+        .route(
+            "/me",
+            get(handlers::auth::me_handler).delete(handlers::auth::delete_account_handler),
+        )
+        .route(
+            "/me/password",
+            post(handlers::auth::change_password_handler),
+        )
+        .route(
+            "/me/deactivate",
+            post(handlers::auth::deactivate_account_handler),
+        )
+        .route("/me/usage", get(handlers::usage::my_usage_handler))
         // Profile
         .route(
             "/profile/me",
             get(handlers::profile::get_my_profile_handler),
         )
+        .route(
+            "/profile/me/status",
+            put(handlers::profile::set_status_handler),
+        )
+        .route(
+            "/profile/me/banner",
+            post(handlers::profile::upload_banner_handler).layer(DefaultBodyLimit::max(
+                state.config.avatars.max_bytes + 64 * 1024,
+            )),
+        )
+        .route(
+            "/profile/me/avatar",
+            post(handlers::profile::upload_avatar_handler).layer(DefaultBodyLimit::max(
+                state.config.avatars.max_bytes + 64 * 1024,
+            )),
+        )
+        .route(
+            "/profile/by-username/:username",
+            get(handlers::profile::get_profile_by_username_handler),
+        )
         .route(
             "/profile/:id",
             get(handlers::profile::get_profile_handler)
                 .put(handlers::profile::edit_profile_handler),
         )
+        .route("/users/search", get(handlers::users::search_users_handler))
+        .route("/feed", get(handlers::activity::feed_handler))
+        .route(
+            "/users/:id/mutual-friends",
+            get(handlers::users::mutual_friends_handler),
+        )
         // Friends
         .route(
             "/friends",
@@ -165,23 +339,357 @@ async fn main() {
             "/friends/pending",
             get(handlers::friends::get_pending_friends_handler),
         )
+        .route(
+            "/blocks",
+            get(handlers::blocks::list_blocks_handler).post(handlers::blocks::block_user_handler),
+        )
+        .route(
+            "/blocks/:user_id",
+            delete(handlers::blocks::unblock_user_handler),
+        )
+        .route(
+            "/friends/:id/favorite",
+            post(handlers::friends::add_favorite_handler)
+                .delete(handlers::friends::remove_favorite_handler),
+        )
+        .route(
+            "/friends/suggestions",
+            get(handlers::friends::friend_suggestions_handler),
+        )
+        .route(
+            "/friends/by-code",
+            post(handlers::friends::add_friend_by_code_handler),
+        )
+        .route("/reports", post(handlers::reports::create_report_handler))
+        .route(
+            "/appeals",
+            get(handlers::appeals::my_appeals_handler)
+                .post(handlers::appeals::create_appeal_handler),
+        )
+        .route(
+            "/me/settings",
+            get(handlers::settings::get_settings_handler)
+                .put(handlers::settings::update_settings_handler),
+        )
+        .route(
+            "/me/notification-settings",
+            get(handlers::settings::get_notification_settings_handler)
+                .put(handlers::settings::update_notification_settings_handler),
+        )
+        .route(
+            "/me/friend-code",
+            get(handlers::friends::get_friend_code_handler)
+                .post(handlers::friends::regenerate_friend_code_handler),
+        )
+        .route(
+            "/friends/by-username",
+            post(handlers::friends::add_friend_by_username_handler),
+        )
+        .route(
+            "/friends/requests/:id",
+            delete(handlers::friends::cancel_friend_request_handler),
+        )
+        .route(
+            "/friends/requests/:id/accept",
+            post(handlers::friends::accept_friend_request_handler),
+        )
+        .route(
+            "/friends/requests/:id/reject",
+            post(handlers::friends::reject_friend_request_handler),
+        )
         // Conversations & messages
         .route(
             "/conversations",
             get(handlers::chat::list_conversations_handler)
                 .post(handlers::chat::start_conversation_handler),
         )
+        .route(
+            "/conversations/:id",
+            patch(handlers::chat::update_conversation_handler)
+                .delete(handlers::chat::delete_conversation_handler),
+        )
         .route(
             "/conversations/:id/messages",
             get(handlers::chat::get_messages_handler),
         )
+        .route(
+            "/conversations/:id/messages/:message_id/context",
+            get(handlers::chat::get_message_context_handler),
+        )
+        .route(
+            "/conversations/:id/attachments",
+            post(handlers::attachments::upload_attachment_handler)
+                // Multipart framing adds a little on top of the file itself.
+                .layer(DefaultBodyLimit::max(
+                    state.config.attachments.max_bytes + 64 * 1024,
+                )),
+        )
+        .route(
+            "/conversations/:id/events",
+            get(handlers::chat::sse_handler),
+        )
+        .route(
+            "/conversations/:id/call",
+            get(handlers::calls::get_call_handler),
+        )
+        .route(
+            "/conversations/:id/ephemeral",
+            post(handlers::chat::send_ephemeral_handler),
+        )
+        .route(
+            "/conversations/:id/polls",
+            post(handlers::polls::create_poll_handler),
+        )
+        .route(
+            "/conversations/:id/export",
+            get(handlers::export::export_conversation_handler),
+        )
+        .route("/exports/:id", get(handlers::export::get_export_handler))
+        .route(
+            "/channels",
+            get(handlers::channels::list_channels_handler)
+                .post(handlers::channels::create_channel_handler),
+        )
+        .route(
+            "/channels/:id/join",
+            post(handlers::channels::join_channel_handler),
+        )
+        .route(
+            "/conversations/:id/slow-mode",
+            put(handlers::chat::set_slow_mode_handler),
+        )
+        .route(
+            "/conversations/:id/lock",
+            post(handlers::chat::lock_conversation_handler)
+                .delete(handlers::chat::unlock_conversation_handler),
+        )
+        .route(
+            "/conversations/:id/adult-content",
+            put(handlers::chat::set_adult_content_handler),
+        )
+        .route(
+            "/conversations/:id/read",
+            post(handlers::notifications::mark_read_handler),
+        )
+        .route(
+            "/conversations/:id/draft",
+            put(handlers::drafts::save_draft_handler),
+        )
+        .route("/drafts", get(handlers::drafts::list_drafts_handler))
+        .route(
+            "/messages/:id",
+            patch(handlers::edits::edit_message_handler),
+        )
+        .route(
+            "/messages/:id/history",
+            get(handlers::edits::message_history_handler),
+        )
+        .route("/polls/:id/vote", post(handlers::polls::vote_poll_handler))
+        .route("/emoji", get(handlers::emoji::list_emoji_handler))
+        .route(
+            "/search/messages",
+            get(handlers::search::search_messages_handler),
+        )
+        // Calls
+        .route(
+            "/calls/turn-credentials",
+            get(handlers::calls::turn_credentials_handler),
+        )
+        // Scheduled messages
+        .route(
+            "/conversations/:id/scheduled",
+            post(handlers::scheduled::create_scheduled_handler),
+        )
+        .route(
+            "/scheduled",
+            get(handlers::scheduled::list_scheduled_handler),
+        )
+        .route(
+            "/scheduled/:id",
+            delete(handlers::scheduled::delete_scheduled_handler),
+        )
+        .route(
+            "/scheduled/:id/pause",
+            post(handlers::scheduled::pause_scheduled_handler),
+        )
+        .route(
+            "/scheduled/:id/resume",
+            post(handlers::scheduled::resume_scheduled_handler),
+        )
         // WebSocket
+        .route("/ws/endpoints", get(handlers::system::ws_endpoints_handler))
+        .route(
+            "/ws/notifications",
+            get(handlers::notifications::ws_notifications_handler),
+        )
+        .route("/ws/admin", get(handlers::admin::admin_events_ws_handler))
         .route("/ws/:conversation_id", get(handlers::chat::ws_handler))
+        // Admin
+        .route("/admin/bots", post(handlers::admin::create_bot_handler))
+        .route(
+            "/admin/import",
+            post(handlers::import::import_handler)
+                .layer(DefaultBodyLimit::max(state.config.exports.import_max_bytes)),
+        )
+        .route(
+            "/admin/conversations/:id/snapshots",
+            get(handlers::admin::list_snapshots_handler)
+                .post(handlers::admin::create_snapshot_handler),
+        )
+        .route(
+            "/admin/conversations/:id/announcement",
+            put(handlers::admin::set_announcement_handler),
+        )
+        .route(
+            "/admin/word-filter",
+            get(handlers::word_filter::get_word_filter_handler)
+                .put(handlers::word_filter::replace_word_filter_handler),
+        )
+        .route(
+            "/admin/word-filter/terms",
+            get(handlers::word_filter::list_terms_handler)
+                .post(handlers::word_filter::create_term_handler),
+        )
+        .route(
+            "/admin/word-filter/terms/:id",
+            patch(handlers::word_filter::update_term_handler)
+                .delete(handlers::word_filter::delete_term_handler),
+        )
+        .route(
+            "/admin/conversations/:id/watch",
+            get(handlers::admin::watch_conversation_handler),
+        )
+        .route(
+            "/admin/emoji-packs",
+            post(handlers::emoji::create_emoji_pack_handler),
+        )
+        .route(
+            "/admin/emoji-packs/:id",
+            delete(handlers::emoji::delete_emoji_pack_handler),
+        )
+        .route(
+            "/admin/emoji-packs/:id/items",
+            post(handlers::emoji::upload_emoji_handler).layer(DefaultBodyLimit::max(
+                state.config.emoji.max_bytes + 64 * 1024,
+            )),
+        )
+        .route(
+            "/admin/emoji/:id",
+            delete(handlers::emoji::delete_emoji_handler),
+        )
+        .route(
+            "/admin/snapshots/:id",
+            get(handlers::admin::get_snapshot_handler),
+        )
+        .route(
+            "/admin/messages/:id",
+            delete(handlers::admin::delete_message_handler),
+        )
+        .route("/admin/audit", get(handlers::admin::list_audit_log_handler))
+        .route("/admin/stats", get(handlers::admin::stats_handler))
+        .route(
+            "/admin/purge",
+            post(handlers::purge::purge_messages_handler),
+        )
+        .route(
+            "/admin/announcements",
+            post(handlers::announcements::create_announcement_handler),
+        )
+        .route(
+            "/admin/ip-bans",
+            get(handlers::ip_bans::list_ip_bans_handler)
+                .post(handlers::ip_bans::create_ip_ban_handler),
+        )
+        .route(
+            "/admin/ip-bans/:id",
+            delete(handlers::ip_bans::delete_ip_ban_handler),
+        )
+        .route(
+            "/admin/held-messages",
+            get(handlers::moderation::list_held_messages_handler),
+        )
+        .route(
+            "/admin/held-messages/:id/approve",
+            post(handlers::moderation::approve_held_message_handler),
+        )
+        .route(
+            "/admin/held-messages/:id/reject",
+            post(handlers::moderation::reject_held_message_handler),
+        )
+        .route(
+            "/admin/reports",
+            get(handlers::reports::list_reports_handler),
+        )
+        .route(
+            "/admin/reports/:id/resolve",
+            post(handlers::reports::resolve_report_handler),
+        )
+        .route(
+            "/admin/appeals",
+            get(handlers::appeals::list_appeals_handler),
+        )
+        .route(
+            "/admin/appeals/:id/resolve",
+            post(handlers::appeals::resolve_appeal_handler),
+        )
+        .route("/admin/users", get(handlers::admin::list_users_handler))
+        .route(
+            "/admin/users/:id",
+            get(handlers::admin::get_user_handler).delete(handlers::admin::delete_user_handler),
+        )
+        .route(
+            "/admin/users/:id/ban",
+            post(handlers::admin::ban_user_handler).delete(handlers::admin::unban_user_handler),
+        )
+        .route(
+            "/admin/users/:id/suspend",
+            post(handlers::admin::suspend_user_handler)
+                .delete(handlers::admin::unsuspend_user_handler),
+        )
+        .route(
+            "/admin/users/:id/shadow-ban",
+            post(handlers::admin::shadow_ban_user_handler)
+                .delete(handlers::admin::lift_shadow_ban_handler),
+        )
+        .route(
+            "/admin/users/:id/rate-limit",
+            put(handlers::rate_limits::set_rate_limit_handler)
+                .delete(handlers::rate_limits::clear_rate_limit_handler),
+        )
+        .route(
+            "/admin/spam/metrics",
+            get(handlers::admin::spam_metrics_handler),
+        )
+        .route(
+            "/admin/rate-limits",
+            get(handlers::rate_limits::list_rate_limits_handler),
+        )
+        .route(
+            "/admin/users/:id/password-reset",
+            post(handlers::admin::reset_password_handler),
+        )
+        .route(
+            "/admin/users/:id/verify",
+            post(handlers::admin::verify_user_handler)
+                .delete(handlers::admin::unverify_user_handler),
+        )
+        .route(
+            "/admin/erasures",
+            get(handlers::admin::list_erasures_handler),
+        )
+        .route(
+            "/admin/erasures/:id",
+            get(handlers::admin::get_erasure_handler),
+        )
         // ── Layers ────────────────────────────────────────────────────
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            locale::localize_errors,
+        ))
         .layer(cors)
         .layer(CookieManagerLayer::new())
         // ── Shared state ──────────────────────────────────────────────
-        .with_state(state)
+        .with_state(state.clone())
         // ── Frontend fallback ─────────────────────────────────────────
         // Any request that does NOT match an API route above will be
         // served as a static file from the frontend directory.
@@ -190,6 +698,10 @@ async fn main() {
         //      GET /css/variables.css → frontend/css/variables.css
         .fallback_service(serve_frontend);
 
+    // IP bans wrap the whole router so they apply before routing, to the
+    // frontend fallback as well as the API.
+    let app = axum::middleware::from_fn_with_state(state, ip_bans::enforce).layer(app);
+
     // Determine the listen address.
     let host = std::env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".into());
     let port: u16 = std::env::var("SERVER_PORT")
@@ -207,7 +719,11 @@ async fn main() {
         .await
         .expect("Failed to bind TCP listener");
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server encountered a fatal error");
+    // Peer addresses are needed to work out the client IP.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .expect("Server encountered a fatal error");
 }
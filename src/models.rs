@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
 // ---------------------------------------------------------------------------
@@ -23,12 +24,103 @@ pub struct LoginRequest {
 pub struct AuthResponse {
     pub user_id: Uuid,
     pub username: String,
+    /// The login brought a deactivated account back.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub reactivated: bool,
+    /// An admin reset the password; the client should send the user to
+    /// `POST /me/password` before anything else.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub must_change_password: bool,
+}
+
+/// Body for `POST /me/password`.
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Body for `POST /me/deactivate`.
+#[derive(Debug, Deserialize)]
+pub struct DeactivateAccountRequest {
+    pub password: String,
+}
+
+/// Body for `DELETE /me`; the password is re-checked before erasing anything.
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+/// Matches the Supabase `erasure_requests` table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErasureRequestRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// "pending", "completed" or "failed".
+    pub status: String,
+    #[serde(default)]
+    pub requested_at: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+    /// What the erasure job did (row counts per step) or why it failed.
+    #[serde(default)]
+    pub report: Option<serde_json::Value>,
 }
 
 // ---------------------------------------------------------------------------
 // Profile
 // ---------------------------------------------------------------------------
 
+/// Bot capability: may send and accept friend requests.
+pub const CAP_FRIEND_REQUESTS: &str = "friend_requests";
+/// Bot capability: may start new conversations.
+pub const CAP_START_CONVERSATIONS: &str = "start_conversations";
+/// Every capability an admin can grant to a bot.
+pub const BOT_CAPABILITIES: &[&str] = &[CAP_FRIEND_REQUESTS, CAP_START_CONVERSATIONS];
+
+/// Who may send a user friend requests
+/// (`alter table profiles add column friend_request_policy text not null default 'everyone'`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FriendRequestPolicy {
+    #[default]
+    Everyone,
+    /// Only people who already share a friend with the user.
+    FriendsOfFriends,
+    Nobody,
+}
+
+/// Colour scheme a user prefers, also shown to others so chats can match
+/// (`alter table profiles add column theme text`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+/// Who a privacy setting lets through.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    Everyone,
+    Friends,
+    Nobody,
+}
+
+impl Audience {
+    pub fn allows(self, viewer_is_friend: bool) -> bool {
+        match self {
+            Audience::Everyone => true,
+            Audience::Friends => viewer_is_friend,
+            Audience::Nobody => false,
+        }
+    }
+}
+
 /// The full profile row as stored in Supabase.
 /// `password_hash` is only used server-side and is never sent to the client.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -45,6 +137,202 @@ pub struct ProfileRow {
     pub bio: Option<String>,
     #[serde(default)]
     pub created_at: Option<String>,
+    /// "admin" for operators; anything else (or NULL) is a regular user.
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub is_bot: Option<bool>,
+    /// Extra permissions granted to a bot, see `BOT_CAPABILITIES`.
+    #[serde(default)]
+    pub bot_capabilities: Option<Vec<String>>,
+    /// Set when the account was deleted; the row stays as an anonymous tombstone.
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    /// Set while the owner has deactivated the account; cleared when they
+    /// log in again (`alter table profiles add column deactivated_at timestamptz`).
+    #[serde(default)]
+    pub deactivated_at: Option<String>,
+    /// Set while an admin has banned the account
+    /// (`alter table profiles add column banned_at timestamptz, add column ban_reason text`).
+    #[serde(default)]
+    pub banned_at: Option<String>,
+    #[serde(default)]
+    pub ban_reason: Option<String>,
+    /// When an admin's suspension of the account ends; in the past once it
+    /// has lifted (`alter table profiles add column suspended_until timestamptz,
+    /// add column suspension_reason text`).
+    #[serde(default)]
+    pub suspended_until: Option<String>,
+    #[serde(default)]
+    pub suspension_reason: Option<String>,
+    /// Set while an admin has shadow banned the account: the user can still
+    /// post, but nobody else sees it
+    /// (`alter table profiles add column shadow_banned_at timestamptz`).
+    #[serde(default)]
+    pub shadow_banned_at: Option<String>,
+    /// Set when an admin reset the password, cleared once the user picks a
+    /// new one (`alter table profiles add column must_change_password boolean not null default false`).
+    #[serde(default)]
+    pub must_change_password: Option<bool>,
+    /// Sessions issued under an older version are refused, see
+    /// `SessionVersions`.
+    #[serde(default)]
+    pub session_version: Option<i64>,
+    #[serde(default)]
+    pub friend_request_policy: Option<FriendRequestPolicy>,
+    /// `false` keeps the user out of friends' activity feeds
+    /// (`alter table profiles add column share_activity boolean not null default true`).
+    #[serde(default)]
+    pub share_activity: Option<bool>,
+    /// Short code others can add this user by; private to the owner
+    /// (`alter table profiles add column friend_code text unique`).
+    #[serde(default)]
+    pub friend_code: Option<String>,
+    /// Storage folder of an uploaded avatar, so it can be removed when replaced
+    /// (`alter table profiles add column avatar_path text`).
+    #[serde(default)]
+    pub avatar_path: Option<String>,
+    /// Square renditions of an uploaded avatar
+    /// (`alter table profiles add column avatar_variants jsonb`).
+    #[serde(default)]
+    pub avatar_variants: Option<AvatarVariants>,
+    /// Wide header image for the profile page
+    /// (`alter table profiles add column banner_url text, add column banner_path text`).
+    #[serde(default)]
+    pub banner_url: Option<String>,
+    /// Storage path of an uploaded banner, so it can be removed when replaced.
+    #[serde(default)]
+    pub banner_path: Option<String>,
+    /// Deployment-defined fields such as pronouns, see `ProfileFieldsConfig`
+    /// (`alter table profiles add column extra_fields jsonb not null default '{}'`).
+    #[serde(default)]
+    pub extra_fields: Option<BTreeMap<String, String>>,
+    /// `#rrggbb`, lowercase
+    /// (`alter table profiles add column accent_color text`).
+    #[serde(default)]
+    pub accent_color: Option<String>,
+    #[serde(default)]
+    pub theme: Option<Theme>,
+    /// Language for API error messages, one of `SUPPORTED_LOCALES`
+    /// (`alter table profiles add column locale text`).
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Badge for official and well-known accounts; only admins set it
+    /// (`alter table profiles add column is_verified boolean not null default false`).
+    #[serde(default)]
+    pub is_verified: Option<bool>,
+    /// Custom status, e.g. "🎮 gaming till 9":
+    ///
+    /// ```sql
+    /// alter table profiles add column status_text text;
+    /// alter table profiles add column status_emoji text;
+    /// alter table profiles add column status_expires_at timestamptz;
+    /// ```
+    #[serde(default)]
+    pub status_text: Option<String>,
+    #[serde(default)]
+    pub status_emoji: Option<String>,
+    #[serde(default)]
+    pub status_expires_at: Option<String>,
+    /// When the user's last socket closed or they logged out
+    /// (`alter table profiles add column last_seen_at timestamptz`).
+    #[serde(default)]
+    pub last_seen_at: Option<String>,
+}
+
+/// A user's custom status as shown to others.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserStatus {
+    pub text: Option<String>,
+    pub emoji: Option<String>,
+    /// When the status clears itself; `None` keeps it until changed.
+    pub expires_at: Option<String>,
+}
+
+/// Body for `PUT /profile/me/status`. Leaving out both `text` and `emoji`
+/// clears the status.
+#[derive(Debug, Deserialize)]
+pub struct SetStatusRequest {
+    pub text: Option<String>,
+    pub emoji: Option<String>,
+    /// Clear the status automatically after this many seconds.
+    pub expires_in_secs: Option<u64>,
+}
+
+/// A friend changed or cleared their custom status.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StatusChanged {
+    pub user_id: Uuid,
+    /// `None` when the status was cleared.
+    pub status: Option<UserStatus>,
+}
+
+/// Public URLs of the square sizes made from an uploaded avatar, stored at
+/// `{user_id}/{version}/{size}.{png|jpg}` in the avatars bucket.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AvatarVariants {
+    /// 64×64
+    pub small: String,
+    /// 128×128
+    pub medium: String,
+    /// 512×512, also used as `avatar_url`.
+    pub large: String,
+}
+
+impl ProfileRow {
+    /// The custom status, unless none is set or it has expired.
+    pub fn custom_status(&self) -> Option<UserStatus> {
+        if self.status_text.is_none() && self.status_emoji.is_none() {
+            return None;
+        }
+        let expired = self
+            .status_expires_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| t <= chrono::Utc::now());
+        if expired {
+            return None;
+        }
+        Some(UserStatus {
+            text: self.status_text.clone(),
+            emoji: self.status_emoji.clone(),
+            expires_at: self.status_expires_at.clone(),
+        })
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role.as_deref() == Some("admin")
+    }
+
+    pub fn is_bot(&self) -> bool {
+        self.is_bot.unwrap_or(false)
+    }
+
+    pub fn is_deactivated(&self) -> bool {
+        self.deactivated_at.is_some()
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.banned_at.is_some()
+    }
+
+    /// When the account's suspension ends, if it hasn't yet.
+    pub fn suspended_until(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.suspended_until
+            .as_deref()
+            .and_then(|t| t.parse::<chrono::DateTime<chrono::Utc>>().ok())
+            .filter(|until| *until > chrono::Utc::now())
+    }
+
+    /// Whether this account may perform an action gated by `capability`.
+    /// Regular users can do everything; bots only what they were granted.
+    pub fn can(&self, capability: &str) -> bool {
+        !self.is_bot()
+            || self
+                .bot_capabilities
+                .as_ref()
+                .is_some_and(|caps| caps.iter().any(|c| c == capability))
+    }
 }
 
 /// The public-facing profile returned to clients (no password hash).
@@ -54,18 +342,43 @@ pub struct ProfileResponse {
     pub username: String,
     pub display_name: Option<String>,
     pub avatar_url: Option<String>,
+    pub banner_url: Option<String>,
     pub bio: Option<String>,
+    pub extra_fields: BTreeMap<String, String>,
+    pub accent_color: Option<String>,
+    pub theme: Theme,
+    pub locale: Option<String>,
     pub created_at: Option<String>,
+    pub is_bot: bool,
+    pub is_verified: bool,
+    /// Lets clients hide "add friend" for users who don't take requests.
+    pub friend_request_policy: FriendRequestPolicy,
+    /// Present when the avatar was uploaded rather than set as a URL.
+    pub avatar_variants: Option<AvatarVariants>,
+    pub custom_status: Option<UserStatus>,
+    /// Only filled in where the viewer is allowed to see it.
+    pub last_seen_at: Option<String>,
 }
 
 impl From<ProfileRow> for ProfileResponse {
     fn from(row: ProfileRow) -> Self {
         Self {
+            is_bot: row.is_bot(),
+            is_verified: row.is_verified.unwrap_or(false),
+            friend_request_policy: row.friend_request_policy.unwrap_or_default(),
+            custom_status: row.custom_status(),
+            last_seen_at: None,
+            avatar_variants: row.avatar_variants,
             id: row.id,
             username: row.username,
             display_name: row.display_name,
             avatar_url: row.avatar_url,
+            banner_url: row.banner_url,
             bio: row.bio,
+            extra_fields: row.extra_fields.unwrap_or_default(),
+            accent_color: row.accent_color,
+            theme: row.theme.unwrap_or_default(),
+            locale: row.locale,
             created_at: row.created_at,
         }
     }
@@ -75,84 +388,2330 @@ impl From<ProfileRow> for ProfileResponse {
 pub struct EditProfileRequest {
     pub display_name: Option<String>,
     pub avatar_url: Option<String>,
+    pub banner_url: Option<String>,
     pub bio: Option<String>,
+    /// Replaces every extra field at once; an empty value clears that key.
+    pub extra_fields: Option<BTreeMap<String, String>>,
+    /// `#rgb` or `#rrggbb`; an empty string clears it.
+    pub accent_color: Option<String>,
+    pub theme: Option<Theme>,
+    /// An empty string clears it, falling back to `Accept-Language`.
+    pub locale: Option<String>,
+    pub friend_request_policy: Option<FriendRequestPolicy>,
+    pub share_activity: Option<bool>,
+}
+
+impl ProfileResponse {
+    /// Strip everything but what identifies the user, for viewers their
+    /// profile visibility setting shuts out.
+    pub fn restricted(mut self) -> Self {
+        self.bio = None;
+        self.banner_url = None;
+        self.extra_fields.clear();
+        self.custom_status = None;
+        self.last_seen_at = None;
+        self.locale = None;
+        self
+    }
 }
 
 // ---------------------------------------------------------------------------
-// Friends
+// Privacy settings
 // ---------------------------------------------------------------------------
 
+/// A user's privacy settings. Users without a row get `Default`.
+///
+/// ```sql
+/// create table user_settings (
+///   user_id uuid primary key references profiles(id) on delete cascade,
+///   profile_visibility text not null default 'everyone',
+///   last_seen_visibility text not null default 'friends',
+///   read_receipts boolean not null default true,
+///   dm_policy text not null default 'everyone',
+///   updated_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserSettings {
+    /// Who sees the full profile; everyone else gets name and avatar only.
+    pub profile_visibility: Audience,
+    pub last_seen_visibility: Audience,
+    /// Share how far the user has read with the other members.
+    pub read_receipts: bool,
+    /// Who can start a direct conversation with the user.
+    pub dm_policy: Audience,
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self {
+            profile_visibility: Audience::Everyone,
+            last_seen_visibility: Audience::Friends,
+            read_receipts: true,
+            dm_policy: Audience::Everyone,
+        }
+    }
+}
+
+/// Where one kind of event is delivered.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct NotificationChannels {
+    /// The notification socket.
+    pub ws: bool,
+    pub push: bool,
+    pub email: bool,
+}
+
+impl NotificationChannels {
+    const fn new(ws: bool, push: bool, email: bool) -> Self {
+        Self { ws, push, email }
+    }
+}
+
+/// Daily do-not-disturb window. `start` and `end` are `HH:MM` local time in
+/// `timezone`; a window past midnight (22:00–07:00) wraps.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct QuietHours {
+    pub enabled: bool,
+    pub start: String,
+    pub end: String,
+    pub timezone: String,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".into(),
+            end: "07:00".into(),
+            timezone: "UTC".into(),
+        }
+    }
+}
+
+/// Which events notify a user, and on which channels. Turning `messages`
+/// off while leaving `mentions` on gives "mentions only". The server
+/// delivers `ws` itself; push and email senders read the same row.
+///
+/// During quiet hours, message and friend request notices still reach the
+/// notification socket but are marked `silent`; only calls ring.
+///
+/// ```sql
+/// create table notification_settings (
+///   user_id uuid primary key references profiles(id) on delete cascade,
+///   messages jsonb not null,
+///   mentions jsonb not null,
+///   friend_requests jsonb not null,
+///   calls jsonb not null,
+///   quiet_hours jsonb,
+///   updated_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationSettings {
+    /// Every message in the user's conversations.
+    pub messages: NotificationChannels,
+    /// Messages that mention the user by `@username`.
+    pub mentions: NotificationChannels,
+    pub friend_requests: NotificationChannels,
+    pub calls: NotificationChannels,
+    #[serde(default)]
+    pub quiet_hours: QuietHours,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            messages: NotificationChannels::new(true, true, false),
+            mentions: NotificationChannels::new(true, true, false),
+            friend_requests: NotificationChannels::new(true, true, false),
+            calls: NotificationChannels::new(true, true, false),
+            quiet_hours: QuietHours::default(),
+        }
+    }
+}
+
+/// Body for `PUT /me/notification-settings`; each event given replaces its
+/// channels, the rest stay as they are.
 #[derive(Debug, Deserialize)]
-pub struct AddFriendRequest {
-    /// The UUID of the user to add as a friend.
-    pub friend_id: Uuid,
+pub struct UpdateNotificationSettingsRequest {
+    pub messages: Option<NotificationChannels>,
+    pub mentions: Option<NotificationChannels>,
+    pub friend_requests: Option<NotificationChannels>,
+    pub calls: Option<NotificationChannels>,
+    pub quiet_hours: Option<QuietHours>,
 }
 
-/// Matches the actual Supabase `friends` table.
-/// `id` is int8 (auto-increment bigint), not UUID.
+/// Body for `PUT /me/settings`; only the fields given change.
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub profile_visibility: Option<Audience>,
+    pub last_seen_visibility: Option<Audience>,
+    pub read_receipts: Option<bool>,
+    pub dm_policy: Option<Audience>,
+}
+
+// ---------------------------------------------------------------------------
+// Reports
+// ---------------------------------------------------------------------------
+
+/// What a report is about.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReportTarget {
+    User { user_id: Uuid },
+    Message { message_id: i64 },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportCategory {
+    Spam,
+    Harassment,
+    HateSpeech,
+    Nudity,
+    Violence,
+    SelfHarm,
+    Impersonation,
+    Other,
+}
+
+/// Body for `POST /reports`.
+#[derive(Debug, Deserialize)]
+pub struct CreateReportRequest {
+    pub target: ReportTarget,
+    pub category: ReportCategory,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Matches the Supabase `reports` table. A reported message's content is
+/// copied in, so edits or deletion don't hide what was reported.
+///
+/// ```sql
+/// create table reports (
+///   id uuid primary key,
+///   reporter_id uuid not null references profiles(id) on delete cascade,
+///   target_user_id uuid not null references profiles(id) on delete cascade,
+///   message_id bigint,
+///   conversation_id uuid,
+///   message_content text,
+///   category text not null,
+///   reason text,
+///   status text not null default 'open',
+///   created_at timestamptz not null default now()
+/// );
+/// create index on reports (reporter_id, created_at);
+/// create index on reports (status, created_at);
+/// alter table reports
+///   add column resolved_by uuid references profiles(id),
+///   add column resolved_at timestamptz,
+///   add column action text,
+///   add column resolution_note text;
+/// ```
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct FriendRow {
+pub struct ReportRow {
+    pub id: Uuid,
+    pub reporter_id: Uuid,
+    /// The reported user, or the sender of the reported message.
+    pub target_user_id: Uuid,
     #[serde(default)]
-    pub id: Option<i64>,
-    pub user_a: Uuid,
-    pub user_b: Uuid,
-    pub status: String,
+    pub message_id: Option<i64>,
+    #[serde(default)]
+    pub conversation_id: Option<Uuid>,
     #[serde(default)]
+    pub message_content: Option<String>,
+    pub category: ReportCategory,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// One of `REPORT_STATUSES`; `open` until a moderator looks at it.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    #[serde(default)]
+    pub resolved_by: Option<Uuid>,
+    #[serde(default)]
+    pub resolved_at: Option<String>,
+    #[serde(default)]
+    pub action: Option<ReportAction>,
+    /// The moderator's note; only admins see it.
+    #[serde(default)]
+    pub resolution_note: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct FriendInfo {
-    pub friend_id: Uuid,
-    pub username: String,
-    pub display_name: Option<String>,
-    pub avatar_url: Option<String>,
+/// Matches the Supabase `appeals` table: a banned or suspended user asking
+/// for the restriction to be lifted. `restriction_at` is the profile's
+/// `banned_at` or `suspended_until` when the appeal was filed, so each ban
+/// or suspension can be appealed once.
+///
+/// ```sql
+/// create table appeals (
+///   id uuid primary key,
+///   user_id uuid not null references profiles(id) on delete cascade,
+///   kind text not null check (kind in ('ban', 'suspension')),
+///   restriction_at timestamptz not null,
+///   message text not null,
+///   status text not null default 'open',
+///   created_at timestamptz not null default now(),
+///   reviewed_by uuid references profiles(id),
+///   reviewed_at timestamptz,
+///   response text
+/// );
+/// create index on appeals (user_id);
+/// create index on appeals (status, created_at);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppealRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: AppealKind,
+    pub restriction_at: String,
+    pub message: String,
+    /// One of `APPEAL_STATUSES`; `open` until a moderator decides.
     pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub reviewed_by: Option<Uuid>,
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+    /// The moderator's answer, shown to the user.
+    #[serde(default)]
+    pub response: Option<String>,
 }
 
-// ---------------------------------------------------------------------------
-// Conversations & Messages
-// ---------------------------------------------------------------------------
+/// What is being appealed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AppealKind {
+    Ban,
+    Suspension,
+}
+
+/// Every value `appeals.status` takes.
+pub const APPEAL_STATUSES: [&str; 3] = ["open", "approved", "rejected"];
 
+/// Body for `POST /appeals`.
 #[derive(Debug, Deserialize)]
-pub struct StartConversationRequest {
-    /// The user to start a conversation with.
-    pub friend_id: Uuid,
+pub struct CreateAppealRequest {
+    pub message: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ConversationResponse {
-    pub conversation_id: Uuid,
+/// Query for `GET /admin/appeals`.
+#[derive(Debug, Deserialize)]
+pub struct AppealQueueQuery {
+    /// One of `APPEAL_STATUSES`; defaults to `open`.
+    pub status: Option<String>,
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
 }
 
-/// Matches the actual Supabase `messages` table.
-/// `id` is int8 (auto-increment bigint), not UUID.
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct MessageRow {
+/// Body for `POST /admin/appeals/{id}/resolve`.
+#[derive(Debug, Deserialize)]
+pub struct ResolveAppealRequest {
+    /// `true` lifts the ban or suspension.
+    pub approve: bool,
     #[serde(default)]
-    pub id: Option<i64>,
+    pub response: Option<String>,
+}
+
+/// An appeal the user filed has been decided.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AppealResolved {
+    pub appeal_id: Uuid,
+    pub approved: bool,
+    pub response: Option<String>,
+}
+
+/// Matches the Supabase `held_messages` table: messages automated
+/// moderation kept back, as they were sent, until a moderator decides.
+///
+/// ```sql
+/// create table held_messages (
+///   id uuid primary key,
+///   conversation_id uuid not null references conversations(id) on delete cascade,
+///   sender_id uuid not null references profiles(id) on delete cascade,
+///   content text not null,
+///   sticker_id uuid,
+///   client_id text,
+///   attachment jsonb,
+///   poll jsonb,
+///   categories text not null,
+///   status text not null default 'held',
+///   reviewed_by uuid references profiles(id),
+///   reviewed_at timestamptz,
+///   created_at timestamptz not null default now()
+/// );
+/// create index on held_messages (status, created_at);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HeldMessageRow {
+    pub id: Uuid,
     pub conversation_id: Uuid,
     pub sender_id: Uuid,
+    /// The text as sent, before sanitization; it goes through the whole
+    /// pipeline again on approval.
     pub content: String,
     #[serde(default)]
-    pub message_type: Option<String>,
+    pub sticker_id: Option<Uuid>,
     #[serde(default)]
-    pub is_deleted: Option<bool>,
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub poll: Option<PollState>,
+    /// Comma-separated categories the provider flagged.
+    pub categories: String,
+    /// `held`, `approved` or `rejected`.
+    pub status: String,
+    #[serde(default)]
+    pub reviewed_by: Option<Uuid>,
     #[serde(default)]
+    pub reviewed_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
 }
 
-/// What the WebSocket client sends.
+/// Every value `reports.status` takes.
+pub const REPORT_STATUSES: [&str; 3] = ["open", "resolved", "dismissed"];
+
+/// What a moderator did about a report.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    /// Nothing wrong; the report is closed as `dismissed`.
+    Dismiss,
+    /// Send the reported user a warning.
+    Warn,
+    /// Remove the reported message.
+    DeleteContent,
+    /// Ban the reported user.
+    Ban,
+}
+
+impl ReportAction {
+    /// The report's status once this action has been taken.
+    pub fn status(self) -> &'static str {
+        match self {
+            ReportAction::Dismiss => "dismissed",
+            _ => "resolved",
+        }
+    }
+}
+
+/// Query for `GET /admin/reports`.
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct WsIncoming {
+pub struct ReportQueueQuery {
+    /// One of `REPORT_STATUSES`; defaults to `open`.
+    pub status: Option<String>,
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Body for `POST /admin/reports/{id}/resolve`.
+#[derive(Debug, Deserialize)]
+pub struct ResolveReportRequest {
+    pub action: ReportAction,
+    /// Kept on the report; also the ban reason when banning.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// A moderator upheld a report against the user.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModerationWarning {
+    pub report_id: Uuid,
+    pub category: ReportCategory,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+}
+
+/// A report the user filed has been reviewed. Which action was taken is
+/// not shared with the reporter.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReportResolved {
+    pub report_id: Uuid,
+    /// `false` when the report was dismissed.
+    pub action_taken: bool,
+}
+
+/// What a server-wide announcement is about, so clients can style it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementKind {
+    #[default]
+    Info,
+    Maintenance,
+    Release,
+}
+
+/// A notice from the operators pushed to everyone connected.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SystemAnnouncement {
+    pub id: Uuid,
+    pub kind: AnnouncementKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
     pub content: String,
+    /// Where the announcement was also posted, if anywhere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+    pub sent_at: String,
 }
 
-/// What the server broadcasts to everyone in the conversation.
-#[derive(Debug, Serialize, Clone)]
-pub struct WsBroadcast {
+// ---------------------------------------------------------------------------
+// Admin
+// ---------------------------------------------------------------------------
+
+/// Everything that travels over the moderators' channel (`/ws/admin`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminEvent {
+    ReportFiled(ReportRow),
+    /// Delivered, but marked for review by the spam guard, word filter or
+    /// automated moderation.
+    MessageFlagged(FlaggedMessage),
+    /// Kept out of the conversation until a moderator decides.
+    MessageHeld(Box<HeldMessageRow>),
+    RateLimited(RateLimitViolation),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlaggedMessage {
+    pub message_id: Option<i64>,
+    pub conversation_id: Uuid,
     pub sender_id: Uuid,
     pub content: String,
-    pub created_at: String,
+    /// `spam`, `word_filter` or `moderation`.
+    pub source: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitViolation {
+    pub user_id: Uuid,
+    /// `socket` for the per-socket token bucket, `spam` for the per-sender
+    /// spam guard.
+    pub limiter: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<Uuid>,
+    pub at: String,
+}
+
+/// Body for `POST /admin/announcements`.
+#[derive(Debug, Deserialize)]
+pub struct CreateAnnouncementRequest {
+    #[serde(default)]
+    pub kind: AnnouncementKind,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub content: String,
+    /// An announcement conversation to post it in as well, so it stays in
+    /// history for people who weren't connected.
+    #[serde(default)]
+    pub conversation_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBotRequest {
+    pub username: String,
+    /// Optional display name; defaults to username if omitted.
+    pub display_name: Option<String>,
+    /// Capabilities to grant, see `BOT_CAPABILITIES`.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateBotResponse {
+    pub user_id: Uuid,
+    pub username: String,
+    /// Shown only once. The bot logs in via `/login` using this as its password.
+    pub token: String,
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSnapshotRequest {
+    /// How many of the most recent messages to capture (default 500).
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Free-text context, e.g. what the reporter said.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Matches the Supabase `moderation_snapshots` table. Rows are write-once:
+/// there is deliberately no update or delete endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotRow {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    /// The admin who took the snapshot.
+    pub created_by: Uuid,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Raw message rows (all columns, including soft-deleted messages), oldest first.
+    pub messages: serde_json::Value,
+    /// Raw conversation_members rows at the time of the snapshot.
+    pub members: serde_json::Value,
+    /// Raw message_revisions rows of the captured messages, so edits survive
+    /// as evidence (`alter table moderation_snapshots add column revisions jsonb`).
+    #[serde(default)]
+    pub revisions: serde_json::Value,
+    /// Raw reports rows filed in the conversation or against its members,
+    /// reporter notes included (`alter table moderation_snapshots add column reports jsonb`).
+    #[serde(default)]
+    pub reports: serde_json::Value,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Matches the Supabase `admin_audit_log` table. Append-only record of
+/// privileged actions; `target_id` is whatever the action touched.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditLogRow {
+    pub id: Uuid,
+    pub admin_id: Uuid,
+    /// Dotted action name, e.g. "conversation.watch_start".
+    pub action: String,
+    pub target_id: String,
+    #[serde(default)]
+    pub details: serde_json::Value,
+    /// Left unset on insert so the database default applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Query for `GET /admin/audit`. Times are RFC 3339.
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    /// The admin who acted.
+    pub actor: Option<Uuid>,
+    /// An exact action name, or a prefix ending in `*` such as `user.*`.
+    pub action: Option<String>,
+    pub target: Option<String>,
+    /// Inclusive lower bound on `created_at`.
+    pub from: Option<String>,
+    /// Exclusive upper bound on `created_at`.
+    pub to: Option<String>,
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Body for `POST /admin/purge`. Times are RFC 3339.
+#[derive(Debug, Deserialize)]
+pub struct PurgeMessagesRequest {
+    pub sender_id: Option<Uuid>,
+    pub conversation_id: Option<Uuid>,
+    /// Inclusive lower bound on `created_at`.
+    pub from: Option<String>,
+    /// Exclusive upper bound on `created_at`.
+    pub to: Option<String>,
+}
+
+/// Query for `GET /admin/stats`.
+#[derive(Debug, Deserialize)]
+pub struct AdminStatsQuery {
+    /// Days of history, today included; defaults to 14 and is capped at 90.
+    pub days: Option<u32>,
+}
+
+/// One UTC day of activity, from the `admin_daily_stats` function.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyStats {
+    pub day: String,
+    pub messages: i64,
+    /// People (not bots) who sent at least one message that day.
+    pub active_users: i64,
+    pub new_users: i64,
+    pub new_conversations: i64,
+}
+
+/// Account counts, from the `admin_user_totals` function.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserTotals {
+    pub registered: i64,
+    pub bots: i64,
+    pub banned: i64,
+    /// Seen in the last 24 hours.
+    pub seen_last_day: i64,
+}
+
+/// Query for `GET /admin/users`.
+#[derive(Debug, Deserialize)]
+pub struct AdminUserQuery {
+    /// Username prefix, matched case-insensitively; omit to list everyone.
+    pub q: Option<String>,
+    /// Only banned (`true`) or only unbanned (`false`) accounts.
+    pub banned: Option<bool>,
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// An account as admins see it: everything in the profile except the
+/// password hash.
+#[derive(Debug, Serialize)]
+pub struct AdminUserView {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role: Option<String>,
+    pub is_bot: bool,
+    pub is_verified: bool,
+    pub created_at: Option<String>,
+    pub last_seen_at: Option<String>,
+    pub deactivated_at: Option<String>,
+    pub deleted_at: Option<String>,
+    pub banned_at: Option<String>,
+    pub ban_reason: Option<String>,
+    pub suspended_until: Option<String>,
+    pub suspension_reason: Option<String>,
+    pub shadow_banned_at: Option<String>,
+    pub must_change_password: bool,
+}
+
+impl From<ProfileRow> for AdminUserView {
+    fn from(p: ProfileRow) -> Self {
+        AdminUserView {
+            is_bot: p.is_bot(),
+            is_verified: p.is_verified.unwrap_or(false),
+            must_change_password: p.must_change_password.unwrap_or(false),
+            id: p.id,
+            username: p.username,
+            display_name: p.display_name,
+            avatar_url: p.avatar_url,
+            role: p.role,
+            created_at: p.created_at,
+            last_seen_at: p.last_seen_at,
+            deactivated_at: p.deactivated_at,
+            deleted_at: p.deleted_at,
+            banned_at: p.banned_at,
+            ban_reason: p.ban_reason,
+            suspended_until: p.suspended_until,
+            suspension_reason: p.suspension_reason,
+            shadow_banned_at: p.shadow_banned_at,
+        }
+    }
+}
+
+/// Body for `POST /admin/users/{id}/ban`.
+#[derive(Debug, Deserialize)]
+pub struct BanUserRequest {
+    /// Kept on the profile and in the audit log; not shown to the user.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Body for `POST /admin/users/{id}/suspend`.
+#[derive(Debug, Deserialize)]
+pub struct SuspendUserRequest {
+    /// How long from now the suspension lasts.
+    pub duration_secs: u64,
+    /// Kept on the profile and in the audit log; not shown to the user.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetResponse {
+    pub user_id: Uuid,
+    /// Shown only once. Hand it to the user out of band; they log in with
+    /// it and are asked to choose a new password.
+    pub temporary_password: String,
+}
+
+/// Matches the Supabase `ip_bans` table:
+///
+/// ```sql
+/// create table ip_bans (
+///   id uuid primary key,
+///   cidr text not null unique,
+///   reason text,
+///   created_by uuid references profiles(id),
+///   expires_at timestamptz,
+///   created_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IpBanRow {
+    pub id: Uuid,
+    /// Network address and prefix length, e.g. `203.0.113.0/24`; single
+    /// addresses are stored as `/32` or `/128`.
+    pub cidr: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+    /// `None` bans until the row is deleted.
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Body for `POST /admin/ip-bans`.
+#[derive(Debug, Deserialize)]
+pub struct CreateIpBanRequest {
+    /// An address or CIDR block, IPv4 or IPv6.
+    pub cidr: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Lift the ban automatically after this many seconds.
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Matches the Supabase `rate_limit_overrides` table. A `None` limit means
+/// the default applies.
+///
+/// ```sql
+/// create table rate_limit_overrides (
+///   user_id uuid primary key references profiles(id) on delete cascade,
+///   ws_rate_per_sec double precision,
+///   ws_rate_burst integer,
+///   send_limit integer,
+///   reason text,
+///   set_by uuid references profiles(id),
+///   updated_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitOverrideRow {
+    pub user_id: Uuid,
+    /// Sustained messages per second on each of the user's sockets.
+    #[serde(default)]
+    pub ws_rate_per_sec: Option<f64>,
+    /// Burst allowance on each of the user's sockets.
+    #[serde(default)]
+    pub ws_rate_burst: Option<u32>,
+    /// Messages per `SPAM_RATE_WINDOW_SECS` across every send path; 0 for
+    /// no limit. Bots skip this check whatever it says.
+    #[serde(default)]
+    pub send_limit: Option<usize>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub set_by: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+/// Body for `PUT /admin/users/{id}/rate-limit`. Omitted limits go back to
+/// the defaults.
+#[derive(Debug, Deserialize)]
+pub struct SetRateLimitRequest {
+    #[serde(default)]
+    pub ws_rate_per_sec: Option<f64>,
+    #[serde(default)]
+    pub ws_rate_burst: Option<u32>,
+    #[serde(default)]
+    pub send_limit: Option<usize>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// What the word filter does with a message containing a term.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterMode {
+    /// Refuse the message.
+    Reject,
+    /// Replace the term with asterisks and deliver the rest.
+    Mask,
+    /// Deliver unchanged but record the term in `messages.filter_flag`.
+    Flag,
+}
+
+/// Matches the Supabase `word_filter_terms` table:
+///
+/// ```sql
+/// create table word_filter_terms (
+///   id uuid primary key,
+///   term text not null unique,
+///   mode text not null check (mode in ('reject', 'mask', 'flag')),
+///   enforce_in_adult boolean not null default false,
+///   created_by uuid references profiles(id),
+///   created_at timestamptz not null default now()
+/// );
+/// alter table messages add column filter_flag text;
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WordFilterTermRow {
+    pub id: Uuid,
+    /// Matched case-insensitively on word boundaries; may span several words.
+    pub term: String,
+    pub mode: FilterMode,
+    /// Also applies in conversations that allow adult content.
+    #[serde(default)]
+    pub enforce_in_adult: bool,
+    #[serde(default)]
+    pub created_by: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Body for `POST /admin/word-filter/terms`.
+#[derive(Debug, Deserialize)]
+pub struct CreateFilterTermRequest {
+    pub term: String,
+    pub mode: FilterMode,
+    #[serde(default)]
+    pub enforce_in_adult: bool,
+}
+
+/// Body for `PUT /admin/word-filter`: the complete new list.
+#[derive(Debug, Deserialize)]
+pub struct ReplaceWordFilterRequest {
+    pub terms: Vec<CreateFilterTermRequest>,
+}
+
+/// Body for `PATCH /admin/word-filter/terms/{id}`; omitted fields are kept.
+#[derive(Debug, Deserialize)]
+pub struct UpdateFilterTermRequest {
+    pub mode: Option<FilterMode>,
+    pub enforce_in_adult: Option<bool>,
+}
+
+// ---------------------------------------------------------------------------
+// Friends
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct AddFriendRequest {
+    /// The UUID of the user to add as a friend.
+    pub friend_id: Uuid,
+    /// Optional short message shown to the recipient with the request.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Body for `POST /friends/by-username`.
+#[derive(Debug, Deserialize)]
+pub struct AddFriendByUsernameRequest {
+    pub username: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Body for `POST /friends/by-code`.
+#[derive(Debug, Deserialize)]
+pub struct AddFriendByCodeRequest {
+    /// As shown by `GET /me/friend-code`; case and dashes don't matter.
+    pub code: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Matches the actual Supabase `friends` table.
+/// `id` is int8 (auto-increment bigint), not UUID.
+///
+/// `requester_id` records who sent a request, so only the other side can
+/// accept it (`alter table friends add column requester_id uuid references profiles(id)`).
+///
+/// Each side can mark the other as a favorite independently:
+///
+/// ```sql
+/// alter table friends add column favorite_a boolean not null default false;
+/// alter table friends add column favorite_b boolean not null default false;
+/// ```
+///
+/// A request may carry a note from the requester
+/// (`alter table friends add column note text`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FriendRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub user_a: Uuid,
+    pub user_b: Uuid,
+    pub status: String,
+    /// `None` on rows from before requests had a direction; either side
+    /// may answer those.
+    #[serde(default)]
+    pub requester_id: Option<Uuid>,
+    /// `user_a` has marked `user_b` as a favorite.
+    #[serde(default)]
+    pub favorite_a: Option<bool>,
+    /// `user_b` has marked `user_a` as a favorite.
+    #[serde(default)]
+    pub favorite_b: Option<bool>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl FriendRow {
+    /// Whether `user_id` has marked the other side as a favorite.
+    pub fn is_favorite_of(&self, user_id: Uuid) -> bool {
+        if user_id == self.user_a {
+            self.favorite_a.unwrap_or(false)
+        } else {
+            self.favorite_b.unwrap_or(false)
+        }
+    }
+}
+
+/// Query string for `GET /friends?favorites=true&limit=50&cursor=123`.
+#[derive(Debug, Deserialize)]
+pub struct FriendListQuery {
+    /// Only return friends the caller has marked as favorites.
+    #[serde(default)]
+    pub favorites: bool,
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendInfo {
+    pub friend_id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub accent_color: Option<String>,
+    pub is_verified: bool,
+    pub status: String,
+    pub is_favorite: bool,
+    pub custom_status: Option<UserStatus>,
+    pub last_seen_at: Option<String>,
+    /// The `friends` row id, used to accept or reject a pending request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<i64>,
+    /// The note sent with a pending request, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// One entry in `GET /friends/suggestions`.
+#[derive(Debug, Serialize)]
+pub struct FriendSuggestion {
+    #[serde(flatten)]
+    pub profile: ProfileResponse,
+    /// How many of the caller's friends are friends with this user.
+    pub mutual_count: usize,
+}
+
+/// Something a user did that their friends see in `GET /feed`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    AvatarChanged,
+    StatusChanged,
+    JoinedChannel,
+}
+
+/// Matches the Supabase `activity` table:
+///
+/// ```sql
+/// create table activity (
+///   id bigserial primary key,
+///   user_id uuid not null references profiles(id) on delete cascade,
+///   kind text not null,
+///   data jsonb not null default '{}',
+///   created_at timestamptz not null default now()
+/// );
+/// create index on activity (user_id, id desc);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityRow {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub user_id: Uuid,
+    pub kind: ActivityKind,
+    /// Kind-specific details, e.g. `{ "conversation_id", "name" }` for
+    /// `joined_channel`.
+    #[serde(default)]
+    pub data: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Query string for `GET /feed?limit=30&before=123`.
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    /// Defaults to 30 and is capped at 100.
+    pub limit: Option<usize>,
+    /// Only entries older than this activity id, for the next page.
+    pub before: Option<i64>,
+}
+
+/// One entry in `GET /feed`.
+#[derive(Debug, Serialize)]
+pub struct FeedEntry {
+    pub id: i64,
+    pub user: ProfileResponse,
+    pub kind: ActivityKind,
+    pub data: serde_json::Value,
+    pub created_at: Option<String>,
+}
+
+/// Query string for `GET /users/search?q=gi&limit=20`.
+#[derive(Debug, Deserialize)]
+pub struct UserSearchQuery {
+    /// Username prefix, matched case-insensitively.
+    pub q: String,
+    /// Defaults to 20 and is capped at 50.
+    pub limit: Option<usize>,
+}
+
+/// Body for `POST /blocks`.
+#[derive(Debug, Deserialize)]
+pub struct BlockUserRequest {
+    pub user_id: Uuid,
+}
+
+/// Matches the Supabase `user_blocks` table:
+///
+/// ```sql
+/// create table user_blocks (
+///   blocker_id uuid not null references profiles(id) on delete cascade,
+///   blocked_id uuid not null references profiles(id) on delete cascade,
+///   created_at timestamptz not null default now(),
+///   primary key (blocker_id, blocked_id)
+/// );
+/// create index on user_blocks (blocked_id);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockRow {
+    pub blocker_id: Uuid,
+    pub blocked_id: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Query string for paginated lists, e.g. `GET /blocks?limit=50&offset=0`.
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    /// Page size; defaults to 50 and is capped at 100.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// One entry in `GET /blocks`.
+#[derive(Debug, Serialize)]
+pub struct BlockedUser {
+    #[serde(flatten)]
+    pub profile: ProfileResponse,
+    pub blocked_at: Option<String>,
+}
+
+/// A change to a friend request, sent to the other side of it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FriendRequestNotice {
+    pub request_id: i64,
+    /// The user who made the change.
+    pub user_id: Uuid,
+    /// `pending` for a new request, then `accepted`, `rejected` or `withdrawn`.
+    pub status: String,
+    /// Don't alert, e.g. because the recipient is in quiet hours.
+    #[serde(default)]
+    pub silent: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Conversations & Messages
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct StartConversationRequest {
+    /// The user to start a conversation with.
+    pub friend_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversationResponse {
+    pub conversation_id: Uuid,
+}
+
+/// Matches the Supabase `conversations` table.
+///
+/// `message_ttl_seconds` turns on disappearing messages; `name` and
+/// `avatar_url` label group conversations; `deleted_at` marks a deleted
+/// conversation, kept so moderation snapshots still resolve;
+/// `is_announcement` makes it read-only for everyone but admins;
+/// `visibility` is `public` for channels anyone can find and join;
+/// `allow_adult_content` relaxes the word filter for that group;
+/// `locked_at` makes it read-only for everyone while set;
+/// `slow_mode_seconds` is the least time between two messages from the
+/// same member:
+///
+/// ```sql
+/// alter table conversations add column message_ttl_seconds integer;
+/// alter table conversations add column name text;
+/// alter table conversations add column avatar_url text;
+/// alter table conversations add column deleted_at timestamptz;
+/// alter table conversations add column is_announcement boolean not null default false;
+/// alter table conversations add column visibility text not null default 'private'
+///   check (visibility in ('private', 'public'));
+/// alter table conversations add column allow_adult_content boolean not null default false;
+/// alter table conversations add column locked_at timestamptz,
+///   add column locked_by uuid references profiles(id);
+/// alter table conversations add column slow_mode_seconds integer;
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationRow {
+    pub id: Uuid,
+    #[serde(default)]
+    pub is_group: Option<bool>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    /// Messages older than this are expired; `None` keeps them forever.
+    #[serde(default)]
+    pub message_ttl_seconds: Option<u64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub deleted_at: Option<String>,
+    #[serde(default)]
+    pub is_announcement: Option<bool>,
+    #[serde(default)]
+    pub visibility: Option<String>,
+    #[serde(default)]
+    pub allow_adult_content: Option<bool>,
+    #[serde(default)]
+    pub locked_at: Option<String>,
+    #[serde(default)]
+    pub locked_by: Option<Uuid>,
+    #[serde(default)]
+    pub slow_mode_seconds: Option<u64>,
+    /// Kept up to date by `increment_message_count`.
+    #[serde(default)]
+    pub message_count: Option<i64>,
+}
+
+impl ConversationRow {
+    pub fn is_public(&self) -> bool {
+        self.visibility.as_deref() == Some("public")
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked_at.is_some()
+    }
+}
+
+/// Matches the Supabase `conversation_members` table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MemberRow {
+    pub conversation_id: Uuid,
+    pub user_id: Uuid,
+    /// "owner" or "member"; `None` on rows from before roles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_read_message_id: Option<i64>,
+}
+
+impl MemberRow {
+    pub fn new(conversation_id: Uuid, user_id: Uuid, role: &str) -> Self {
+        Self {
+            conversation_id,
+            user_id,
+            role: Some(role.into()),
+            last_read_message_id: None,
+        }
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.role.as_deref() == Some("owner")
+    }
+}
+
+/// One of the caller's conversations with its latest message and unread
+/// count, as returned by the `conversation_previews` function.
+#[derive(Debug, Deserialize)]
+pub struct ConversationPreviewRow {
+    pub conversation_id: Uuid,
+    #[serde(default)]
+    pub last_message_id: Option<i64>,
+    #[serde(default)]
+    pub last_sender_id: Option<Uuid>,
+    #[serde(default)]
+    pub last_content: Option<String>,
+    #[serde(default)]
+    pub last_message_type: Option<String>,
+    #[serde(default)]
+    pub last_message_at: Option<String>,
+    pub unread_count: i64,
+}
+
+/// The latest message in a conversation, cut down for the list view.
+#[derive(Debug, Serialize)]
+pub struct LastMessagePreview {
+    pub id: i64,
+    pub sender_id: Uuid,
+    pub content: String,
+    pub message_type: String,
+    pub created_at: String,
+}
+
+/// One entry in `GET /conversations`.
+#[derive(Debug, Serialize)]
+pub struct ConversationSummary {
+    pub conversation_id: Uuid,
+    pub is_group: bool,
+    /// Only admins can post; clients should hide the composer.
+    pub is_announcement: bool,
+    /// Nobody can post until it is unlocked.
+    pub is_locked: bool,
+    /// Members must wait this long between messages.
+    pub slow_mode_seconds: Option<u64>,
+    /// The group's name, or the other participant's display name.
+    pub name: String,
+    pub avatar_url: Option<String>,
+    /// Everyone else in the conversation.
+    pub member_ids: Vec<Uuid>,
+    pub last_message: Option<LastMessagePreview>,
+    /// Time of the latest message, or of creation for empty conversations.
+    pub last_activity_at: Option<String>,
+    pub unread_count: i64,
+    /// A direct conversation with someone the caller marked as a favorite.
+    pub is_favorite: bool,
+}
+
+/// Body for `PATCH /conversations/{id}`.
+#[derive(Debug, Deserialize)]
+pub struct UpdateConversationRequest {
+    /// Seconds a message lives before it disappears; `null` or 0 turns
+    /// disappearing messages off, and leaving it out keeps the current TTL.
+    #[serde(default, deserialize_with = "present")]
+    pub message_ttl_seconds: Option<Option<u64>>,
+}
+
+/// For `Option<Option<T>>` fields: `Some(None)` when the field was sent as
+/// `null`, `None` (via `#[serde(default)]`) when it was left out.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Body for `PUT /conversations/{id}/adult-content`.
+#[derive(Debug, Deserialize)]
+pub struct SetAdultContentRequest {
+    pub enabled: bool,
+}
+
+/// Body for `PUT /conversations/{id}/slow-mode`.
+#[derive(Debug, Deserialize)]
+pub struct SetSlowModeRequest {
+    /// Seconds between two messages from the same member; `null` or 0
+    /// turns slow mode off.
+    pub seconds: Option<u64>,
+}
+
+/// Body for `PUT /admin/conversations/{id}/announcement`.
+#[derive(Debug, Deserialize)]
+pub struct SetAnnouncementRequest {
+    pub is_announcement: bool,
+}
+
+/// Matches the actual Supabase `messages` table.
+/// `id` is int8 (auto-increment bigint), not UUID.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    /// Sanitized HTML rendering of `content`, when Markdown rendering is on.
+    #[serde(default)]
+    pub content_html: Option<String>,
+    #[serde(default)]
+    pub message_type: Option<String>,
+    #[serde(default)]
+    pub is_deleted: Option<bool>,
+    /// Copied from the sender's profile at send time so history can label bots.
+    #[serde(default)]
+    pub is_bot: Option<bool>,
+    /// Interactive buttons/menus attached by a bot (jsonb column).
+    #[serde(default)]
+    pub components: Option<Vec<MessageComponent>>,
+    /// Uploaded file, for `message_type` "image" or "file" (jsonb column).
+    #[serde(default)]
+    pub attachment: Option<Attachment>,
+    /// Unfurled metadata for the first URL in the message (jsonb column).
+    #[serde(default)]
+    pub link_preview: Option<LinkPreview>,
+    /// Set on `message_type` "poll" messages.
+    #[serde(default)]
+    pub poll_id: Option<Uuid>,
+    /// The sticker sent, for `message_type` "sticker" (jsonb column).
+    #[serde(default)]
+    pub sticker: Option<Sticker>,
+    /// Current tallies, filled in when history is loaded; not a column.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<PollState>,
+    /// The sender's temporary id for the message, used to drop retried
+    /// sends (`alter table messages add column client_id text`).
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Set by the first edit; earlier versions are in `message_revisions`
+    /// (`alter table messages add column edited_at timestamptz`).
+    #[serde(default)]
+    pub edited_at: Option<String>,
+    /// Sent by a shadow banned user; only they see it. Never serialized,
+    /// so the sender can't find out.
+    #[serde(default, skip_serializing)]
+    pub shadow_hidden: Option<bool>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// Body for `PATCH /messages/{id}`.
+#[derive(Debug, Deserialize)]
+pub struct EditMessageRequest {
+    pub content: String,
+}
+
+/// A message's content before one of its edits. Matches the Supabase
+/// `message_revisions` table:
+///
+/// ```sql
+/// create table message_revisions (
+///   id bigint generated always as identity primary key,
+///   message_id bigint not null references messages(id) on delete cascade,
+///   conversation_id uuid not null,
+///   content text not null,
+///   content_html text,
+///   edited_by uuid not null references profiles(id),
+///   created_at timestamptz not null default now()
+/// );
+/// create index on message_revisions (message_id, id);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageRevisionRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub message_id: i64,
+    pub conversation_id: Uuid,
+    pub content: String,
+    #[serde(default)]
+    pub content_html: Option<String>,
+    pub edited_by: Uuid,
+    /// When this version was replaced.
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// OpenGraph metadata for a URL posted in a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkPreview {
+    /// The page the metadata came from, after redirects.
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// A file stored in Supabase Storage and attached to a message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    /// "image" or "file"; doubles as the message's `message_type`.
+    pub kind: String,
+    /// Object path inside the attachments bucket.
+    pub path: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: usize,
+    /// Signed download URL, valid until `url_expires_at`.
+    pub url: String,
+    pub url_expires_at: String,
+    /// Downscaled preview for images, so chat lists don't pull the full file.
+    #[serde(default)]
+    pub thumbnail: Option<Thumbnail>,
+}
+
+/// A generated image preview, stored next to the original.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Thumbnail {
+    pub path: String,
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// An interactive element a bot can attach to its message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageComponent {
+    Button {
+        /// Identifier echoed back to the bot when clicked; unique per message.
+        id: String,
+        label: String,
+        /// "primary", "secondary" or "danger". Defaults to secondary.
+        #[serde(default)]
+        style: Option<String>,
+    },
+    Select {
+        id: String,
+        #[serde(default)]
+        placeholder: Option<String>,
+        options: Vec<SelectOption>,
+    },
+}
+
+impl MessageComponent {
+    pub fn id(&self) -> &str {
+        match self {
+            MessageComponent::Button { id, .. } | MessageComponent::Select { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SelectOption {
+    pub label: String,
+    pub value: String,
+}
+
+// ---------------------------------------------------------------------------
+// Channels
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /channels`.
+#[derive(Debug, Deserialize)]
+pub struct CreateChannelRequest {
+    pub name: String,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+}
+
+/// Query string for `GET /channels?q=rust&limit=20&offset=0`.
+#[derive(Debug, Deserialize)]
+pub struct ChannelListQuery {
+    /// Case-insensitive match on the channel name.
+    pub q: Option<String>,
+    /// Page size; defaults to 20 and is capped at 100.
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// A public channel as returned by the `public_channels` function.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChannelSummary {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub is_announcement: Option<bool>,
+    pub member_count: i64,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// Whether the caller has already joined.
+    #[serde(default)]
+    pub is_member: bool,
+}
+
+// ---------------------------------------------------------------------------
+// Scheduled messages
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleMessageRequest {
+    pub content: String,
+    /// Wall-clock time in `timezone`, e.g. "2026-10-20T09:00".
+    pub send_at: String,
+    /// IANA timezone name, e.g. "Europe/Berlin". Defaults to UTC.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// "daily" or "weekly"; omit for a one-off send.
+    #[serde(default)]
+    pub recurrence: Option<String>,
+}
+
+/// Matches the Supabase `scheduled_messages` table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduledMessageRow {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Uuid,
+    pub content: String,
+    /// Wall-clock time of the next send in `timezone`. Recurrences advance
+    /// this value so "every day at 09:00" survives DST changes.
+    pub send_at_local: String,
+    pub timezone: String,
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// UTC instant of the next send; this is what the job runner polls on.
+    pub next_run_at: String,
+    #[serde(default)]
+    pub paused: Option<bool>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Polls
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /conversations/{id}/polls`.
+#[derive(Debug, Deserialize)]
+pub struct CreatePollRequest {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub allow_multiple: bool,
+    /// Stop accepting votes after this long; omit to keep the poll open.
+    #[serde(default)]
+    pub closes_in_seconds: Option<u64>,
+    /// See `WsIncoming::client_id`.
+    #[serde(default)]
+    pub client_id: Option<String>,
+}
+
+/// Body for `POST /polls/{id}/vote`. Replaces the caller's previous votes;
+/// an empty list retracts them.
+#[derive(Debug, Deserialize)]
+pub struct VotePollRequest {
+    pub option_ids: Vec<String>,
+}
+
+/// Matches the Supabase `polls` table:
+///
+/// ```sql
+/// create table polls (
+///   id uuid primary key,
+///   conversation_id uuid not null references conversations(id) on delete cascade,
+///   created_by uuid not null references profiles(id),
+///   question text not null,
+///   options jsonb not null,
+///   allow_multiple boolean not null default false,
+///   closes_at timestamptz,
+///   created_at timestamptz not null default now()
+/// );
+/// alter table messages add column poll_id uuid references polls(id);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollRow {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub created_by: Uuid,
+    pub question: String,
+    pub options: Vec<PollOption>,
+    pub allow_multiple: bool,
+    #[serde(default)]
+    pub closes_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollOption {
+    pub id: String,
+    pub text: String,
+}
+
+/// Matches the Supabase `poll_votes` table:
+///
+/// ```sql
+/// create table poll_votes (
+///   poll_id uuid not null references polls(id) on delete cascade,
+///   user_id uuid not null references profiles(id) on delete cascade,
+///   option_id text not null,
+///   created_at timestamptz not null default now(),
+///   primary key (poll_id, user_id, option_id)
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollVoteRow {
+    pub poll_id: Uuid,
+    pub user_id: Uuid,
+    pub option_id: String,
+}
+
+/// A poll with its current tallies, as shown to clients.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollState {
+    pub id: Uuid,
+    pub question: String,
+    pub options: Vec<PollOptionTally>,
+    pub allow_multiple: bool,
+    pub closes_at: Option<String>,
+    /// Distinct members who have voted.
+    pub total_voters: usize,
+    /// The viewer's own choices; absent in broadcasts, which go to everyone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub my_votes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollOptionTally {
+    pub id: String,
+    pub text: String,
+    pub votes: usize,
+}
+
+// ---------------------------------------------------------------------------
+// Custom emoji & stickers
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /admin/emoji-packs`.
+#[derive(Debug, Deserialize)]
+pub struct CreateEmojiPackRequest {
+    pub name: String,
+    /// "emoji" (used inline as `:name:`) or "sticker"; defaults to "emoji".
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+/// Matches the Supabase `emoji_packs` table:
+///
+/// ```sql
+/// create table emoji_packs (
+///   id uuid primary key,
+///   name text not null,
+///   kind text not null check (kind in ('emoji', 'sticker')),
+///   created_by uuid references profiles(id),
+///   created_at timestamptz not null default now()
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmojiPackRow {
+    pub id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub created_by: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// Matches the Supabase `emoji` table. Names are unique across all packs
+/// so `:name:` is never ambiguous:
+///
+/// ```sql
+/// create table emoji (
+///   id uuid primary key,
+///   pack_id uuid not null references emoji_packs(id) on delete cascade,
+///   name text not null unique,
+///   path text not null,
+///   url text not null,
+///   mime_type text not null,
+///   created_at timestamptz not null default now()
+/// );
+/// alter table messages add column sticker jsonb;
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmojiRow {
+    pub id: Uuid,
+    pub pack_id: Uuid,
+    pub name: String,
+    /// Object path inside the emoji bucket.
+    pub path: String,
+    pub url: String,
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+/// A sticker as embedded in a message. A copy, so history still renders
+/// if the sticker is later renamed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Sticker {
+    pub id: Uuid,
+    pub pack_id: Uuid,
+    pub name: String,
+    pub url: String,
+}
+
+// ---------------------------------------------------------------------------
+// Exports
+// ---------------------------------------------------------------------------
+
+/// Query for `GET /conversations/{id}/export`.
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// "json" (default) or "html".
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// Matches the Supabase `conversation_exports` table, used for exports too
+/// big to stream inline:
+///
+/// ```sql
+/// create table conversation_exports (
+///   id uuid primary key,
+///   user_id uuid not null references profiles(id) on delete cascade,
+///   conversation_id uuid not null references conversations(id),
+///   format text not null,
+///   status text not null default 'pending',
+///   path text,
+///   error text,
+///   requested_at timestamptz not null default now(),
+///   completed_at timestamptz
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportRow {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub conversation_id: Uuid,
+    pub format: String,
+    /// "pending", "processing", "completed" or "failed".
+    pub status: String,
+    /// Object path in the exports bucket once completed.
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub requested_at: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<String>,
+}
+
+/// A background export finished; fetch `GET /exports/{id}` for the link.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportReady {
+    pub export_id: Uuid,
+    pub conversation_id: Uuid,
+    pub status: String,
+}
+
+// ---------------------------------------------------------------------------
+// Imports
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /admin/import`: history from another server or platform,
+/// already converted to this shape. Ids are the source system's own and
+/// only link the pieces together.
+#[derive(Debug, Deserialize)]
+pub struct ImportRequest {
+    pub users: Vec<ImportUser>,
+    pub conversations: Vec<ImportConversation>,
+}
+
+/// Maps a source user onto an account here, by username.
+#[derive(Debug, Deserialize)]
+pub struct ImportUser {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportConversation {
+    #[serde(default)]
+    pub is_group: bool,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Source user ids; the first member owns a group.
+    pub members: Vec<String>,
+    pub messages: Vec<ImportMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportMessage {
+    /// Source user id of the author.
+    pub sender: String,
+    pub content: String,
+    /// Original send time (RFC 3339), kept as the message's `created_at`.
+    pub created_at: String,
+}
+
+// ---------------------------------------------------------------------------
+// Drafts
+// ---------------------------------------------------------------------------
+
+/// Body for `PUT /conversations/{id}/draft`. Empty content clears the draft.
+#[derive(Debug, Deserialize)]
+pub struct SaveDraftRequest {
+    pub content: String,
+    /// When the client last edited the draft (RFC 3339). A save older than
+    /// the stored draft is ignored, so a device coming back online can't
+    /// clobber newer text typed elsewhere. Defaults to now.
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+/// Matches the Supabase `drafts` table:
+///
+/// ```sql
+/// create table drafts (
+///   user_id uuid not null references profiles(id) on delete cascade,
+///   conversation_id uuid not null references conversations(id) on delete cascade,
+///   content text not null,
+///   updated_at timestamptz not null,
+///   primary key (user_id, conversation_id)
+/// );
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DraftRow {
+    pub user_id: Uuid,
+    pub conversation_id: Uuid,
+    pub content: String,
+    pub updated_at: String,
+}
+
+/// One entry in `GET /ws/endpoints`.
+#[derive(Debug, Serialize)]
+pub struct WsEndpointInfo {
+    pub region: String,
+    pub url: String,
+    pub healthy: bool,
+    /// Probe round-trip from the answering instance; clients should still
+    /// measure their own latency before picking.
+    pub latency_ms: Option<u64>,
+    pub last_checked: Option<String>,
+}
+
+/// Query string for `GET /conversations/{id}/messages?limit=50&before=123`.
+#[derive(Debug, Deserialize)]
+pub struct MessagePageQuery {
+    /// Page size; defaults to 50 and is capped at 200.
+    pub limit: Option<usize>,
+    /// Only return messages with an id lower than this (the `next_before`
+    /// cursor from the previous page).
+    pub before: Option<i64>,
+}
+
+/// Query string for `GET /conversations/{id}/messages/{message_id}/context?around=25`.
+#[derive(Debug, Deserialize)]
+pub struct MessageContextQuery {
+    /// Messages to return on each side of the target; defaults to 25, capped at 100.
+    pub around: Option<usize>,
+}
+
+/// Query string for `GET /search/messages?q=...&conversation_id=...`.
+#[derive(Debug, Deserialize)]
+pub struct SearchMessagesQuery {
+    /// Web-search syntax: words, `"exact phrase"`, `or`, `-exclude`.
+    pub q: String,
+    /// Search one conversation instead of all of the caller's conversations.
+    pub conversation_id: Option<Uuid>,
+    /// Page size; defaults to 20 and is capped at 100.
+    pub limit: Option<usize>,
+    /// Only return hits with a message id lower than this (`next_before`).
+    pub before: Option<i64>,
+}
+
+/// One search result.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub message: MessageRow,
+    /// Excerpt around the first match, with `…` where content was cut.
+    pub snippet: String,
+    /// `[start, end)` character offsets of matched terms within `snippet`.
+    pub highlights: Vec<(usize, usize)>,
+}
+
+/// Query string accepted on the WebSocket upgrade, e.g. `/ws/{id}?since=42`.
+#[derive(Debug, Deserialize)]
+pub struct WsConnectQuery {
+    /// Id of the last message the client saw; anything newer is replayed
+    /// before live streaming starts.
+    pub since: Option<i64>,
+}
+
+/// A chat message sent by the WebSocket client.
+#[derive(Debug, Deserialize)]
+pub struct WsIncoming {
+    pub content: String,
+    /// Only accepted from bot accounts.
+    #[serde(default)]
+    pub components: Option<Vec<MessageComponent>>,
+    /// Set by the upload endpoint only; never read from client frames.
+    #[serde(skip)]
+    pub attachment: Option<Attachment>,
+    /// Set by the poll endpoint only; never read from client frames.
+    #[serde(skip)]
+    pub poll: Option<PollState>,
+    /// Send a sticker (an `emoji` row in a sticker pack) instead of text.
+    #[serde(default)]
+    pub sticker_id: Option<Uuid>,
+    /// Client-generated id for an optimistic message. A repeat send with
+    /// the same id is answered with the original message instead.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Set when a moderator releases a held message, which then skips the
+    /// spam and moderation checks; never read from client frames.
+    #[serde(skip)]
+    pub approved: bool,
+}
+
+/// Sent by a client (`"type": "component_interaction"`) when a user clicks
+/// a button or picks a menu option on a bot message.
+#[derive(Debug, Deserialize)]
+pub struct ComponentInteractionRequest {
+    pub message_id: i64,
+    pub component_id: String,
+    /// The chosen option for select menus.
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// A chat message as broadcast to everyone in the conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WsBroadcast {
+    /// Database id; `None` if persisting the message failed.
+    pub id: Option<i64>,
+    pub sender_id: Uuid,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    pub created_at: String,
+    pub is_bot: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub components: Option<Vec<MessageComponent>>,
+    /// Boxed to keep `WsEvent` small; most messages have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Box<Attachment>>,
+    /// Present when the preview was already cached at send time; otherwise
+    /// it follows in a `link_preview` event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_preview: Option<Box<LinkPreview>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Box<PollState>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sticker: Option<Box<Sticker>>,
+    /// Echoed back so the sender can swap out its optimistic copy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<String>,
+}
+
+/// A message from a shadow banned user. Only its sender's sockets get it,
+/// as a plain `message`; admins watching the conversation see it as is.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShadowMessage {
+    pub message: WsBroadcast,
+}
+
+/// An edit to a shadow banned user's message. Only its sender's sockets
+/// get it, as a plain `message_edited`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShadowEdit {
+    pub sender_id: Uuid,
+    pub edit: MessageEdited,
+}
+
+/// New tallies after someone voted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PollUpdate {
+    pub poll: PollState,
+}
+
+/// Conversation settings changed by a member.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationUpdate {
+    pub conversation_id: Uuid,
+    pub message_ttl_seconds: Option<u64>,
+    pub updated_by: Uuid,
+}
+
+/// A conversation was locked or unlocked.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationLockChanged {
+    pub conversation_id: Uuid,
+    pub locked: bool,
+    pub changed_by: Uuid,
+}
+
+/// Slow mode was changed; `slow_mode_seconds` is `None` when it was
+/// turned off.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlowModeChanged {
+    pub conversation_id: Uuid,
+    pub slow_mode_seconds: Option<u64>,
+    pub changed_by: Uuid,
+}
+
+/// A conversation was deleted. Sockets on it are closed right after.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConversationDeleted {
+    pub conversation_id: Uuid,
+    pub deleted_by: Uuid,
+}
+
+/// New content for a message after its sender edited it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageEdited {
+    pub message_id: i64,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    pub edited_at: String,
+}
+
+/// A moderator removed a message; clients drop it from view. Who did it
+/// is only recorded in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessageDeleted {
+    pub message_id: i64,
+}
+
+/// Messages removed by the retention job; clients drop them from view.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MessagesExpired {
+    pub message_ids: Vec<i64>,
+}
+
+/// Sent once a message's link preview has been fetched.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkPreviewUpdate {
+    pub message_id: i64,
+    pub link_preview: LinkPreview,
+}
+
+/// Delivered to the bot that owns the message when a user interacts with it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComponentInteraction {
+    pub message_id: i64,
+    pub component_id: String,
+    pub value: Option<String>,
+    /// The user who clicked.
+    pub user_id: Uuid,
+    /// The bot that sent the message; the only recipient of this event.
+    pub bot_id: Uuid,
+    pub created_at: String,
+}
+
+/// Body for `POST /conversations/{id}/ephemeral`.
+#[derive(Debug, Deserialize)]
+pub struct SendEphemeralRequest {
+    /// The single member who will see the message.
+    pub recipient_id: Uuid,
+    pub content: String,
+    /// How long the client should keep showing it; defaults to 60 seconds.
+    #[serde(default)]
+    pub expires_in_seconds: Option<u32>,
+}
+
+/// A one-off notice shown to a single member and never stored.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EphemeralMessage {
+    pub sender_id: Uuid,
+    pub recipient_id: Uuid,
+    pub content: String,
+    pub is_bot: bool,
+    pub created_at: String,
+    /// Hint for the client to remove the message after this many seconds.
+    pub expires_in_seconds: u32,
+}
+
+/// Sent by a client to drive a WebRTC call; the frame's `"type"` is one of
+/// `call_offer`, `call_answer`, `ice_candidate` or `call_end`. `data` (SDP,
+/// ICE candidate, end reason) is relayed as-is; the server never reads it.
+#[derive(Debug, Deserialize)]
+pub struct CallSignalRequest {
+    pub call_id: Uuid,
+    /// Address a single member; omitted means every other member.
+    #[serde(default)]
+    pub to: Option<Uuid>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// A call signal relayed to the other members. Never persisted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CallSignal {
+    pub call_id: Uuid,
+    pub from: Uuid,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Uuid>,
+    pub data: serde_json::Value,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CallState {
+    /// Offered, not yet answered.
+    Ringing,
+    Active,
+}
+
+/// The call currently running in a conversation, as returned by
+/// `GET /conversations/{id}/call`.
+#[derive(Debug, Serialize, Clone)]
+pub struct CallInfo {
+    pub call_id: Uuid,
+    pub conversation_id: Uuid,
+    pub caller_id: Uuid,
+    /// The member who answered, or the one the offer was addressed to.
+    pub callee_id: Option<Uuid>,
+    pub state: CallState,
+    pub started_at: String,
+}
+
+/// Everything that travels over a conversation's broadcast channel.
+/// Serialized with a `"type"` tag so clients can tell events apart.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsEvent {
+    Message(WsBroadcast),
+    /// A message in an announcement conversation, so clients can style it.
+    Announcement(WsBroadcast),
+    MessageEdited(MessageEdited),
+    ComponentInteraction(ComponentInteraction),
+    Ephemeral(EphemeralMessage),
+    LinkPreview(LinkPreviewUpdate),
+    PollUpdated(PollUpdate),
+    ConversationUpdated(ConversationUpdate),
+    ConversationLockChanged(ConversationLockChanged),
+    SlowModeChanged(SlowModeChanged),
+    ConversationDeleted(ConversationDeleted),
+    MessageDeleted(MessageDeleted),
+    MessageExpired(MessagesExpired),
+    /// A shadow banned user's message, delivered to them alone.
+    ShadowMessage(ShadowMessage),
+    /// An edit to a shadow banned user's message, delivered to them alone.
+    ShadowEdit(ShadowEdit),
+    ReadReceipt(ReadReceipt),
+    CallOffer(CallSignal),
+    CallAnswer(CallSignal),
+    IceCandidate(CallSignal),
+    CallEnd(CallSignal),
+}
+
+impl WsEvent {
+    /// The member who caused this event, for events a blocker shouldn't see.
+    pub fn sender_id(&self) -> Option<Uuid> {
+        match self {
+            WsEvent::Message(m) | WsEvent::Announcement(m) => Some(m.sender_id),
+            WsEvent::ReadReceipt(r) => Some(r.user_id),
+            WsEvent::CallOffer(s)
+            | WsEvent::CallAnswer(s)
+            | WsEvent::IceCandidate(s)
+            | WsEvent::CallEnd(s) => Some(s.from),
+            _ => None,
+        }
+    }
+
+    /// Whether the socket belonging to `user_id` should receive this event.
+    pub fn is_visible_to(&self, user_id: Uuid) -> bool {
+        match self {
+            WsEvent::Message(_)
+            | WsEvent::Announcement(_)
+            | WsEvent::MessageEdited(_)
+            | WsEvent::LinkPreview(_)
+            | WsEvent::PollUpdated(_)
+            | WsEvent::ConversationUpdated(_)
+            | WsEvent::ConversationLockChanged(_)
+            | WsEvent::SlowModeChanged(_)
+            | WsEvent::ConversationDeleted(_)
+            | WsEvent::MessageDeleted(_)
+            | WsEvent::MessageExpired(_) => true,
+            WsEvent::ComponentInteraction(i) => i.bot_id == user_id,
+            WsEvent::Ephemeral(m) => m.recipient_id == user_id,
+            WsEvent::ShadowMessage(m) => m.message.sender_id == user_id,
+            WsEvent::ShadowEdit(e) => e.sender_id == user_id,
+            WsEvent::ReadReceipt(r) => r.user_id != user_id,
+            WsEvent::CallOffer(s)
+            | WsEvent::CallAnswer(s)
+            | WsEvent::IceCandidate(s)
+            | WsEvent::CallEnd(s) => s.from != user_id && s.to.is_none_or(|to| to == user_id),
+        }
+    }
+
+    /// The event as a member's client receives it. A shadow message or edit
+    /// goes out as an ordinary one so its sender can't tell the difference.
+    pub fn for_client(self) -> WsEvent {
+        match self {
+            WsEvent::ShadowMessage(m) => WsEvent::Message(m.message),
+            WsEvent::ShadowEdit(e) => WsEvent::MessageEdited(e.edit),
+            event => event,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Notifications (per-user channel)
+// ---------------------------------------------------------------------------
+
+/// Body for `POST /conversations/{id}/read`.
+#[derive(Debug, Deserialize)]
+pub struct MarkReadRequest {
+    /// The newest message the user has seen.
+    pub message_id: i64,
+}
+
+/// Unread state of one conversation, as computed by `unread_digest`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnreadSummary {
+    pub conversation_id: Uuid,
+    pub unread_count: i64,
+    pub last_message_id: i64,
+    pub last_message_at: String,
+}
+
+/// Sent once when a notification socket connects: every conversation with
+/// messages the user hasn't read, so badges are right straight away.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnreadDigest {
+    pub conversations: Vec<UnreadSummary>,
+}
+
+/// A message arrived in one of the user's conversations.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewMessageNotice {
+    pub conversation_id: Uuid,
+    pub message_id: i64,
+    pub sender_id: Uuid,
+    pub created_at: String,
+    /// The message mentions the recipient.
+    #[serde(default)]
+    pub mentioned: bool,
+    /// Update badges but don't alert; the recipient is in quiet hours.
+    #[serde(default)]
+    pub silent: bool,
+}
+
+/// Someone is calling the user, for ringing devices that aren't looking at
+/// the conversation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IncomingCall {
+    pub call_id: Uuid,
+    pub conversation_id: Uuid,
+    pub caller_id: Uuid,
+}
+
+/// The user read a conversation on some device; other devices clear the badge.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReadMarker {
+    pub conversation_id: Uuid,
+    pub last_read_message_id: i64,
+}
+
+/// A member read up to a message, sent to the conversation when their
+/// settings share read receipts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReadReceipt {
+    pub user_id: Uuid,
+    pub last_read_message_id: i64,
+}
+
+/// Everything that travels over a user's notification channel.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UserEvent {
+    Digest(UnreadDigest),
+    NewMessage(NewMessageNotice),
+    Read(ReadMarker),
+    ConversationDeleted(ConversationDeleted),
+    ExportReady(ExportReady),
+    FriendRequest(FriendRequestNotice),
+    FriendRequestWithdrawn(FriendRequestNotice),
+    StatusChanged(StatusChanged),
+    IncomingCall(IncomingCall),
+    ModerationWarning(ModerationWarning),
+    ReportResolved(ReportResolved),
+    AppealResolved(AppealResolved),
+    SystemAnnouncement(SystemAnnouncement),
 }
@@ -0,0 +1,295 @@
+// Automated content moderation.
+//
+// Message text that got past sanitization and the word filter is handed to
+// an external classifier before it's stored. Providers implement
+// `ModerationProvider`; `Moderator` picks one from env vars, bounds every
+// call with a timeout and turns a flag into the configured action. A
+// provider that errors or times out lets the message through: a slow API
+// must never stop people from chatting.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::config::env_or;
+use crate::error::ApiError;
+
+/// What a provider made of a piece of text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Classification {
+    Clean,
+    /// Objectionable, with the provider's category names.
+    Flagged(Vec<String>),
+}
+
+/// An external classifier such as the OpenAI moderation endpoint or
+/// Perspective. Errors are plain strings: they are only logged.
+#[async_trait]
+pub trait ModerationProvider: Send + Sync {
+    /// Short name for logs.
+    fn name(&self) -> &'static str;
+
+    async fn classify(&self, text: &str) -> Result<Classification, String>;
+}
+
+/// What to do with a message the provider flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlaggedAction {
+    /// Deliver it, but record the categories for moderators
+    /// (`alter table messages add column moderation_flag text`).
+    Allow,
+    /// Keep it out of the conversation until a moderator approves it.
+    Hold,
+    /// Refuse it.
+    Block,
+}
+
+impl FlaggedAction {
+    fn parse(raw: &str, default: FlaggedAction) -> FlaggedAction {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "allow" => FlaggedAction::Allow,
+            "hold" => FlaggedAction::Hold,
+            "block" => FlaggedAction::Block,
+            _ => default,
+        }
+    }
+}
+
+/// Outcome of a check that didn't refuse the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationVerdict {
+    Allow,
+    /// Deliver, but record the comma-separated categories.
+    Flag(String),
+    /// Store for review instead of delivering.
+    Hold(String),
+}
+
+/// Runs the configured provider over outgoing messages. Checked in
+/// `deliver_message`, so every send path goes through it; bots are exempt.
+pub struct Moderator {
+    provider: Option<Box<dyn ModerationProvider>>,
+    timeout: Duration,
+    action: FlaggedAction,
+}
+
+impl Moderator {
+    /// Build the moderator from env vars. `MODERATION_PROVIDER` is `none`
+    /// (the default), `openai` or `perspective`; both need
+    /// `MODERATION_API_KEY`.
+    pub fn from_env() -> Self {
+        let timeout = Duration::from_millis(env_or("MODERATION_TIMEOUT_MS", 1500));
+        let action = FlaggedAction::parse(
+            &env_or("MODERATION_ACTION", "hold".to_string()),
+            FlaggedAction::Hold,
+        );
+        let api_key = env_or("MODERATION_API_KEY", String::new());
+
+        let kind = env_or("MODERATION_PROVIDER", "none".to_string()).to_ascii_lowercase();
+        let provider: Option<Box<dyn ModerationProvider>> = match kind.as_str() {
+            "none" | "" => None,
+            _ if api_key.is_empty() => {
+                eprintln!(
+                    "WARNING: MODERATION_PROVIDER={} but MODERATION_API_KEY is not set; automated moderation is off",
+                    kind
+                );
+                None
+            }
+            "openai" => Some(Box::new(OpenAiModeration {
+                client: reqwest::Client::new(),
+                api_key,
+                model: env_or(
+                    "OPENAI_MODERATION_MODEL",
+                    "omni-moderation-latest".to_string(),
+                ),
+            })),
+            "perspective" => Some(Box::new(PerspectiveModeration {
+                client: reqwest::Client::new(),
+                api_key,
+                threshold: env_or("PERSPECTIVE_THRESHOLD", 0.8),
+            })),
+            other => {
+                eprintln!(
+                    "WARNING: unknown MODERATION_PROVIDER '{}'; automated moderation is off",
+                    other
+                );
+                None
+            }
+        };
+
+        Self {
+            provider,
+            timeout,
+            action,
+        }
+    }
+
+    /// Classify `text` and apply the configured action. Fails only when the
+    /// action is `block` and the text was flagged.
+    pub async fn check(&self, text: &str) -> Result<ModerationVerdict, ApiError> {
+        let provider = match &self.provider {
+            Some(p) if !text.trim().is_empty() => p,
+            _ => return Ok(ModerationVerdict::Allow),
+        };
+
+        let categories = match tokio::time::timeout(self.timeout, provider.classify(text)).await {
+            Ok(Ok(Classification::Clean)) => return Ok(ModerationVerdict::Allow),
+            Ok(Ok(Classification::Flagged(categories))) => categories.join(","),
+            Ok(Err(e)) => {
+                warn!("[moderation] {} failed, allowing: {}", provider.name(), e);
+                return Ok(ModerationVerdict::Allow);
+            }
+            Err(_) => {
+                warn!(
+                    "[moderation] {} took longer than {:?}, allowing",
+                    provider.name(),
+                    self.timeout
+                );
+                return Ok(ModerationVerdict::Allow);
+            }
+        };
+
+        match self.action {
+            FlaggedAction::Allow => Ok(ModerationVerdict::Flag(categories)),
+            FlaggedAction::Hold => Ok(ModerationVerdict::Hold(categories)),
+            FlaggedAction::Block => Err(ApiError::BadRequest(
+                "Message was blocked by automated moderation".into(),
+            )),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// OpenAI moderation endpoint
+// ---------------------------------------------------------------------------
+
+struct OpenAiModeration {
+    client: reqwest::Client,
+    api_key: String,
+    model: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResponse {
+    results: Vec<OpenAiResult>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiResult {
+    flagged: bool,
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+#[async_trait]
+impl ModerationProvider for OpenAiModeration {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn classify(&self, text: &str) -> Result<Classification, String> {
+        let res = self
+            .client
+            .post("https://api.openai.com/v1/moderations")
+            .bearer_auth(&self.api_key)
+            .json(&json!({ "model": self.model, "input": text }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()));
+        }
+        let body: OpenAiResponse = res.json().await.map_err(|e| e.to_string())?;
+
+        let result = match body.results.into_iter().next() {
+            Some(r) if r.flagged => r,
+            _ => return Ok(Classification::Clean),
+        };
+        let mut categories: Vec<String> = result
+            .categories
+            .into_iter()
+            .filter(|(_, hit)| *hit)
+            .map(|(name, _)| name)
+            .collect();
+        categories.sort();
+        Ok(Classification::Flagged(categories))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Perspective API
+// ---------------------------------------------------------------------------
+
+/// Attributes requested from Perspective; any scoring at or above the
+/// threshold flags the text.
+const PERSPECTIVE_ATTRIBUTES: [&str; 4] = ["TOXICITY", "SEVERE_TOXICITY", "THREAT", "INSULT"];
+
+struct PerspectiveModeration {
+    client: reqwest::Client,
+    api_key: String,
+    threshold: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PerspectiveResponse {
+    #[serde(default)]
+    attribute_scores: HashMap<String, PerspectiveScore>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PerspectiveScore {
+    summary_score: PerspectiveValue,
+}
+
+#[derive(Deserialize)]
+struct PerspectiveValue {
+    value: f64,
+}
+
+#[async_trait]
+impl ModerationProvider for PerspectiveModeration {
+    fn name(&self) -> &'static str {
+        "perspective"
+    }
+
+    async fn classify(&self, text: &str) -> Result<Classification, String> {
+        let requested: serde_json::Map<String, serde_json::Value> = PERSPECTIVE_ATTRIBUTES
+            .iter()
+            .map(|a| (a.to_string(), json!({})))
+            .collect();
+        let res = self
+            .client
+            .post("https://commentanalyzer.googleapis.com/v1alpha1/comments:analyze")
+            .query(&[("key", &self.api_key)])
+            .json(&json!({
+                "comment": { "text": text },
+                "requestedAttributes": requested,
+                "doNotStore": true,
+            }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !res.status().is_success() {
+            return Err(format!("HTTP {}", res.status()));
+        }
+        let body: PerspectiveResponse = res.json().await.map_err(|e| e.to_string())?;
+
+        let mut categories: Vec<String> = body
+            .attribute_scores
+            .into_iter()
+            .filter(|(_, score)| score.summary_score.value >= self.threshold)
+            .map(|(name, _)| name.to_ascii_lowercase())
+            .collect();
+        if categories.is_empty() {
+            return Ok(Classification::Clean);
+        }
+        categories.sort();
+        Ok(Classification::Flagged(categories))
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::config::WsConfig;
+use crate::error::ApiError;
+use crate::models::RateLimitOverrideRow;
+use crate::store::Storage;
+
+/// Classic token bucket: holds up to `capacity` tokens, refilled continuously
+/// at `refill_per_sec`. Each allowed action spends one token.
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Start with a full bucket so short bursts right after connecting are fine.
+    pub fn new(refill_per_sec: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Spend one token if available. Returns false when the caller is over the limit.
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Per-user exceptions to the default send limits, e.g. more headroom for a
+/// trusted bot or less for an account under watch. Held in memory so the
+/// limiters never query; the source of truth is `rate_limit_overrides`,
+/// `reload` swaps in a fresh copy and changes made on this instance apply
+/// at once.
+#[derive(Default)]
+pub struct RateLimitOverrides {
+    users: RwLock<HashMap<Uuid, RateLimitOverrideRow>>,
+}
+
+impl RateLimitOverrides {
+    /// Replace the in-memory overrides with what's in the database. Returns
+    /// the number loaded.
+    pub async fn reload(&self, store: &dyn Storage) -> Result<usize, ApiError> {
+        let users: HashMap<Uuid, RateLimitOverrideRow> = store
+            .rate_limit_overrides()
+            .await?
+            .into_iter()
+            .map(|row| (row.user_id, row))
+            .collect();
+
+        let count = users.len();
+        *self.users.write().unwrap() = users;
+        Ok(count)
+    }
+
+    pub fn get(&self, user_id: Uuid) -> Option<RateLimitOverrideRow> {
+        self.users.read().unwrap().get(&user_id).cloned()
+    }
+
+    pub fn set(&self, row: RateLimitOverrideRow) {
+        self.users.write().unwrap().insert(row.user_id, row);
+    }
+
+    pub fn remove(&self, user_id: Uuid) {
+        self.users.write().unwrap().remove(&user_id);
+    }
+
+    /// The token bucket for a new socket of `user_id`'s. Sockets already
+    /// open keep the limits they started with.
+    pub fn socket_bucket(&self, user_id: Uuid, ws: &WsConfig) -> TokenBucket {
+        let row = self.get(user_id);
+        let row = row.as_ref();
+        TokenBucket::new(
+            row.and_then(|r| r.ws_rate_per_sec)
+                .unwrap_or(ws.rate_per_sec),
+            row.and_then(|r| r.ws_rate_burst).unwrap_or(ws.rate_burst),
+        )
+    }
+
+    /// `user_id`'s cap on messages per spam-policy window, if overridden.
+    pub fn send_limit(&self, user_id: Uuid) -> Option<usize> {
+        self.users.read().unwrap().get(&user_id)?.send_limit
+    }
+}
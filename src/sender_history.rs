@@ -0,0 +1,105 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+/// Senders tracked before idle ones are swept out.
+const SWEEP_THRESHOLD: usize = 4096;
+
+/// What one sender sent recently, for `SpamGuard` and `SpamScorer`.
+/// In-memory and per instance, like the WebSocket rate limiter.
+#[derive(Debug, Default)]
+pub struct SenderHistory {
+    /// (sent at, fingerprint of the normalized text), oldest first.
+    recent: VecDeque<(Instant, u64)>,
+    /// Every message from the sender is refused until then.
+    pub blocked_until: Option<Instant>,
+}
+
+impl SenderHistory {
+    /// How much longer the sender is refused, if they are.
+    pub fn blocked_for(&self, now: Instant) -> Option<Duration> {
+        self.blocked_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+
+    /// Forget messages older than `window`.
+    pub fn forget_older_than(&mut self, now: Instant, window: Duration) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > window)
+        {
+            self.recent.pop_front();
+        }
+    }
+
+    /// How many messages were sent within `window`, and when the oldest of
+    /// them was.
+    pub fn sent_within(&self, now: Instant, window: Duration) -> (usize, Option<Instant>) {
+        let mut within = self
+            .recent
+            .iter()
+            .map(|(t, _)| *t)
+            .filter(|t| now.duration_since(*t) <= window);
+        let oldest = within.next();
+        (oldest.map_or(0, |_| 1 + within.count()), oldest)
+    }
+
+    /// How many messages within `window` had this fingerprint.
+    pub fn duplicates(&self, now: Instant, window: Duration, fingerprint: u64) -> usize {
+        self.recent
+            .iter()
+            .filter(|(t, f)| *f == fingerprint && now.duration_since(*t) <= window)
+            .count()
+    }
+
+    pub fn record(&mut self, now: Instant, fingerprint: u64) {
+        self.recent.push_back((now, fingerprint));
+    }
+
+    fn is_active(&self, now: Instant, horizon: Duration) -> bool {
+        self.blocked_for(now).is_some()
+            || self
+                .recent
+                .back()
+                .is_some_and(|(t, _)| now.duration_since(*t) <= horizon)
+    }
+}
+
+/// Once `senders` has grown large, drop everyone who is neither blocked
+/// nor sent anything within `horizon`. `history` picks out each entry's
+/// history.
+pub fn sweep_idle<T>(
+    senders: &mut HashMap<Uuid, T>,
+    now: Instant,
+    horizon: Duration,
+    history: impl Fn(&T) -> &SenderHistory,
+) {
+    if senders.len() > SWEEP_THRESHOLD {
+        senders.retain(|_, entry| history(entry).is_active(now, horizon));
+    }
+}
+
+/// Hash of the text with case and whitespace folded, so trivial variations
+/// still count as the same message.
+pub fn fingerprint(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for word in content.split_whitespace() {
+        word.to_lowercase().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+pub fn count_links(content: &str) -> usize {
+    content
+        .split_whitespace()
+        .filter(|w| {
+            let w = w.to_ascii_lowercase();
+            w.contains("http://") || w.contains("https://") || w.starts_with("www.")
+        })
+        .count()
+}
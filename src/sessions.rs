@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use uuid::Uuid;
+
+use crate::bans::{self, AccessChange};
+use crate::error::ApiError;
+use crate::store::Storage;
+use crate::AppState;
+
+/// The session version of every account that has had its sessions revoked,
+/// held in memory so `get_session` can check it without a query per
+/// request. A session cookie carries the version it was issued under and
+/// stops working once the account's version moves past it. The source of
+/// truth is `profiles.session_version`
+/// (`alter table profiles add column session_version bigint not null default 0`).
+/// It is loaded before the server starts listening and `reload` swaps in a
+/// fresh copy (a failed reload keeps the old one); revocations made on any
+/// instance reach every other one over the bus straight away.
+#[derive(Default)]
+pub struct SessionVersions {
+    versions: RwLock<HashMap<Uuid, i64>>,
+}
+
+impl SessionVersions {
+    /// Replace the in-memory map with what's in the database. Returns the
+    /// number of accounts with revoked sessions. Nothing changes on error.
+    pub async fn reload(&self, store: &dyn Storage) -> Result<usize, ApiError> {
+        let versions = store.session_versions().await?;
+
+        let count = versions.len();
+        *self.versions.write().unwrap() = versions;
+        Ok(count)
+    }
+
+    /// The version a session of `user_id` needs to be valid.
+    pub fn current(&self, user_id: Uuid) -> i64 {
+        self.versions
+            .read()
+            .unwrap()
+            .get(&user_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Make sessions of `user_id` older than `version` invalid here. A
+    /// version older than the one already known is ignored.
+    pub fn raise(&self, user_id: Uuid, version: i64) {
+        let mut versions = self.versions.write().unwrap();
+        let current = versions.entry(user_id).or_default();
+        *current = (*current).max(version);
+    }
+
+    /// Bump the stored version of `user_id` and apply it on this instance.
+    /// Use [`revoke`] to reach the other instances too.
+    pub async fn revoke(&self, store: &dyn Storage, user_id: Uuid) -> Result<i64, ApiError> {
+        let version = store.bump_session_version(user_id).await?;
+
+        self.raise(user_id, version);
+        Ok(version)
+    }
+}
+
+/// Log `user_id` out everywhere, on every instance. Returns the new
+/// version, for a session the caller wants to keep.
+pub async fn revoke(state: &AppState, user_id: Uuid) -> Result<i64, ApiError> {
+    let version = state.sessions.revoke(state.store.as_ref(), user_id).await?;
+    bans::apply(state, AccessChange::SessionsRevoked { user_id, version }).await;
+    Ok(version)
+}
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::config::env_or;
+use crate::error::ApiError;
+use crate::sender_history::{count_links, fingerprint, sweep_idle, SenderHistory};
+use crate::AppState;
+/// How far back a sender's messages count towards velocity and repetition.
+const HISTORY_WINDOW: Duration = Duration::from_secs(60);
+/// Buckets in the score histogram, each 0.1 wide.
+const HISTOGRAM_BUCKETS: usize = 10;
+
+/// Weights of each signal in the final score; they add up to 1.
+const LINK_WEIGHT: f64 = 0.35;
+const REPETITION_WEIGHT: f64 = 0.3;
+const VELOCITY_WEIGHT: f64 = 0.2;
+const ACCOUNT_AGE_WEIGHT: f64 = 0.15;
+
+/// Settings for heuristic spam scoring. Where `SpamPolicy` enforces hard
+/// limits, this weighs several soft signals into one score from 0 to 1 and
+/// acts when it crosses a threshold. Bots and approved messages are not
+/// scored.
+#[derive(Debug, Clone)]
+pub struct SpamScoring {
+    pub enabled: bool,
+    /// Scores at or above this are held for a moderator.
+    pub hold_threshold: f64,
+    /// Scores at or above this (but below `hold_threshold`) refuse the
+    /// message and make the sender wait `slow_down`.
+    pub slow_down_threshold: f64,
+    pub slow_down: Duration,
+    /// Accounts younger than this look more suspicious the newer they are.
+    pub new_account_age: Duration,
+    /// Messages a minute that count as the fastest plausible human pace.
+    pub velocity_limit: usize,
+}
+
+impl SpamScoring {
+    /// Build the settings from env vars, falling back to sensible defaults.
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env_or("SPAM_SCORING_ENABLED", true),
+            hold_threshold: env_or("SPAM_SCORE_HOLD", 0.85),
+            slow_down_threshold: env_or("SPAM_SCORE_SLOW_DOWN", 0.65),
+            slow_down: Duration::from_secs(env_or("SPAM_SCORE_SLOW_DOWN_SECS", 30)),
+            new_account_age: Duration::from_secs(
+                env_or("SPAM_SCORE_NEW_ACCOUNT_HOURS", 72) * 60 * 60,
+            ),
+            velocity_limit: env_or("SPAM_SCORE_VELOCITY_PER_MIN", 20),
+        }
+    }
+}
+
+/// The individual signals, each from 0 to 1, and their weighted total.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SpamScore {
+    pub score: f64,
+    pub link_density: f64,
+    pub repetition: f64,
+    pub velocity: f64,
+    pub account_age: f64,
+}
+
+impl SpamScore {
+    /// A short summary for moderators, e.g. `spam_score 0.91 (links 1.00, ...)`.
+    pub fn describe(&self) -> String {
+        format!(
+            "spam_score {:.2} (links {:.2}, repetition {:.2}, velocity {:.2}, account_age {:.2})",
+            self.score, self.link_density, self.repetition, self.velocity, self.account_age
+        )
+    }
+}
+
+/// What to do with a scored message.
+#[derive(Debug, Clone, Copy)]
+pub enum ScoreAction {
+    Allow,
+    Hold,
+    /// Refuse it and everything else from the sender for this long.
+    SlowDown(Duration),
+}
+
+#[derive(Debug, Default)]
+struct ScoredSender {
+    /// Blocked while a slow-down is in force.
+    history: SenderHistory,
+    /// Cached so the profile is fetched once per sender, not per message.
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// Scores messages against [`SpamScoring`] and keeps counters on the
+/// results. In-memory and per instance, like the spam guard.
+pub struct SpamScorer {
+    config: SpamScoring,
+    senders: Mutex<HashMap<Uuid, ScoredSender>>,
+    metrics: ScoreMetrics,
+}
+
+#[derive(Default)]
+struct ScoreMetrics {
+    allowed: AtomicU64,
+    held: AtomicU64,
+    slowed: AtomicU64,
+    /// Sum of every score in thousandths, for the mean.
+    score_sum: AtomicU64,
+    histogram: [AtomicU64; HISTOGRAM_BUCKETS],
+}
+
+/// Counters since this instance started, for `GET /admin/spam/metrics`.
+#[derive(Debug, Serialize)]
+pub struct SpamMetrics {
+    pub enabled: bool,
+    pub hold_threshold: f64,
+    pub slow_down_threshold: f64,
+    pub scored: u64,
+    pub allowed: u64,
+    pub held: u64,
+    pub slowed: u64,
+    pub mean_score: f64,
+    /// Messages per 0.1-wide score range, lowest first.
+    pub histogram: Vec<u64>,
+}
+
+impl SpamScorer {
+    pub fn new(config: SpamScoring) -> Self {
+        Self {
+            config,
+            senders: Mutex::new(HashMap::new()),
+            metrics: ScoreMetrics::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Refuse `sender_id` while a slow-down is in force.
+    pub fn check_slowed(&self, sender_id: Uuid) -> Result<(), ApiError> {
+        let senders = self.senders.lock().unwrap();
+        match senders
+            .get(&sender_id)
+            .and_then(|s| s.history.blocked_for(Instant::now()))
+        {
+            Some(wait) => Err(ApiError::rate_limited(wait)),
+            None => Ok(()),
+        }
+    }
+
+    /// Score one message and decide what to do with it. The message counts
+    /// towards the sender's later scores unless they were told to slow down.
+    pub fn score(
+        &self,
+        sender_id: Uuid,
+        content: &str,
+        created_at: Option<DateTime<Utc>>,
+    ) -> (SpamScore, ScoreAction) {
+        let config = &self.config;
+        let now = Instant::now();
+        let mut senders = self.senders.lock().unwrap();
+        sweep_idle(&mut senders, now, HISTORY_WINDOW, |s| &s.history);
+        let history = &mut senders.entry(sender_id).or_default().history;
+        history.forget_older_than(now, HISTORY_WINDOW);
+
+        let fingerprint = fingerprint(content);
+        let duplicates = history.duplicates(now, HISTORY_WINDOW, fingerprint);
+        let (sent, _) = history.sent_within(now, HISTORY_WINDOW);
+
+        let link_density = link_density(content);
+        let repetition = repetition(content).max((duplicates as f64 / 3.0).min(1.0));
+        let velocity = if config.velocity_limit == 0 {
+            0.0
+        } else {
+            (sent as f64 / config.velocity_limit as f64).min(1.0)
+        };
+        let account_age = created_at
+            .map(|t| {
+                let age = (Utc::now() - t).to_std().unwrap_or_default();
+                1.0 - (age.as_secs_f64() / config.new_account_age.as_secs_f64().max(1.0)).min(1.0)
+            })
+            .unwrap_or(0.0);
+
+        let score = SpamScore {
+            score: LINK_WEIGHT * link_density
+                + REPETITION_WEIGHT * repetition
+                + VELOCITY_WEIGHT * velocity
+                + ACCOUNT_AGE_WEIGHT * account_age,
+            link_density,
+            repetition,
+            velocity,
+            account_age,
+        };
+
+        let action = if score.score >= config.hold_threshold {
+            ScoreAction::Hold
+        } else if score.score >= config.slow_down_threshold {
+            ScoreAction::SlowDown(config.slow_down)
+        } else {
+            ScoreAction::Allow
+        };
+        match action {
+            ScoreAction::SlowDown(wait) => history.blocked_until = Some(now + wait),
+            _ => history.record(now, fingerprint),
+        }
+        drop(senders);
+
+        self.metrics.record(score.score, action);
+        (score, action)
+    }
+
+    /// When `sender_id`'s account was created, if it has been looked up.
+    fn cached_created_at(&self, sender_id: Uuid) -> Option<DateTime<Utc>> {
+        self.senders
+            .lock()
+            .unwrap()
+            .get(&sender_id)
+            .and_then(|s| s.created_at)
+    }
+
+    fn remember_created_at(&self, sender_id: Uuid, created_at: DateTime<Utc>) {
+        self.senders
+            .lock()
+            .unwrap()
+            .entry(sender_id)
+            .or_default()
+            .created_at = Some(created_at);
+    }
+
+    pub fn metrics(&self) -> SpamMetrics {
+        let m = &self.metrics;
+        let allowed = m.allowed.load(Ordering::Relaxed);
+        let held = m.held.load(Ordering::Relaxed);
+        let slowed = m.slowed.load(Ordering::Relaxed);
+        let scored = allowed + held + slowed;
+        SpamMetrics {
+            enabled: self.config.enabled,
+            hold_threshold: self.config.hold_threshold,
+            slow_down_threshold: self.config.slow_down_threshold,
+            scored,
+            allowed,
+            held,
+            slowed,
+            mean_score: if scored == 0 {
+                0.0
+            } else {
+                m.score_sum.load(Ordering::Relaxed) as f64 / 1000.0 / scored as f64
+            },
+            histogram: m
+                .histogram
+                .iter()
+                .map(|b| b.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+}
+
+impl ScoreMetrics {
+    fn record(&self, score: f64, action: ScoreAction) {
+        let counter = match action {
+            ScoreAction::Allow => &self.allowed,
+            ScoreAction::Hold => &self.held,
+            ScoreAction::SlowDown(_) => &self.slowed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.score_sum
+            .fetch_add((score * 1000.0).round() as u64, Ordering::Relaxed);
+        let bucket = ((score * HISTOGRAM_BUCKETS as f64) as usize).min(HISTOGRAM_BUCKETS - 1);
+        self.histogram[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Score a message from `sender_id`, looking up their account age the first
+/// time they are seen.
+pub async fn score_message(
+    state: &AppState,
+    sender_id: Uuid,
+    content: &str,
+) -> Result<(SpamScore, ScoreAction), ApiError> {
+    let scorer = &state.spam_scores;
+    let created_at = match scorer.cached_created_at(sender_id) {
+        Some(t) => Some(t),
+        None => {
+            let created_at = state
+                .store
+                .profile(sender_id)
+                .await?
+                .created_at
+                .and_then(|t| t.parse::<DateTime<Utc>>().ok());
+            if let Some(t) = created_at {
+                scorer.remember_created_at(sender_id, t);
+            }
+            created_at
+        }
+    };
+    Ok(scorer.score(sender_id, content, created_at))
+}
+
+/// Share of words that are links, doubled so one link in two words is
+/// already as bad as it gets.
+fn link_density(content: &str) -> f64 {
+    let words = content.split_whitespace().count();
+    if words == 0 {
+        return 0.0;
+    }
+    (2.0 * count_links(content) as f64 / words as f64).min(1.0)
+}
+
+/// How much the message repeats itself: the share of words that are
+/// repeats, or of characters in long runs of one character, whichever is
+/// higher. Short messages don't have enough to go on.
+fn repetition(content: &str) -> f64 {
+    let words: Vec<String> = content
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect();
+    let repeated_words = if words.len() < 6 {
+        0.0
+    } else {
+        let unique = words.iter().collect::<std::collections::HashSet<_>>().len();
+        1.0 - unique as f64 / words.len() as f64
+    };
+
+    let chars: Vec<char> = content.chars().filter(|c| !c.is_whitespace()).collect();
+    let repeated_chars = if chars.len() < 12 {
+        0.0
+    } else {
+        let mut in_runs = 0;
+        let mut run = 1;
+        for i in 1..=chars.len() {
+            if i < chars.len() && chars[i] == chars[i - 1] {
+                run += 1;
+                continue;
+            }
+            if run >= 4 {
+                in_runs += run;
+            }
+            run = 1;
+        }
+        in_runs as f64 / chars.len() as f64
+    };
+
+    repeated_words.max(repeated_chars)
+}
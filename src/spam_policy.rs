@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::env_or;
+use crate::error::ApiError;
+use crate::sender_history::{count_links, fingerprint, sweep_idle, SenderHistory};
+
+/// What to do with a message that trips the duplicate or link checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamAction {
+    /// Refuse this message only.
+    Throttle,
+    /// Refuse this message and everything else from the sender for `cooldown`.
+    Cooldown,
+    /// Deliver it, but mark it for moderators
+    /// (`alter table messages add column spam_flag text`).
+    Flag,
+}
+
+impl SpamAction {
+    fn parse(raw: &str, default: SpamAction) -> SpamAction {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "throttle" => SpamAction::Throttle,
+            "cooldown" => SpamAction::Cooldown,
+            "flag" => SpamAction::Flag,
+            _ => default,
+        }
+    }
+}
+
+/// Thresholds for per-sender spam detection. Checked in `deliver_message`
+/// after sanitization, so every send path (WS, uploads, polls, scheduled)
+/// counts towards the same limits; bots are exempt.
+#[derive(Debug, Clone)]
+pub struct SpamPolicy {
+    /// Messages a sender may send across all conversations per `rate_window`.
+    pub rate_limit: usize,
+    pub rate_window: Duration,
+    /// How many times the same text may be sent within `duplicate_window`.
+    pub max_duplicates: usize,
+    pub duplicate_window: Duration,
+    pub duplicate_action: SpamAction,
+    /// Links allowed in one message.
+    pub max_links: usize,
+    pub link_action: SpamAction,
+    /// How long a sender is refused after a `Cooldown` action.
+    pub cooldown: Duration,
+}
+
+/// Outcome of a check that didn't refuse the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpamVerdict {
+    Allow,
+    /// Deliver, but record why it looked like spam.
+    Flag(&'static str),
+}
+
+impl SpamPolicy {
+    /// Build the policy from env vars, falling back to sensible defaults.
+    pub fn from_env() -> Self {
+        Self {
+            rate_limit: env_or("SPAM_RATE_LIMIT", 30),
+            rate_window: Duration::from_secs(env_or("SPAM_RATE_WINDOW_SECS", 10)),
+            max_duplicates: env_or("SPAM_MAX_DUPLICATES", 3),
+            duplicate_window: Duration::from_secs(env_or("SPAM_DUPLICATE_WINDOW_SECS", 60)),
+            duplicate_action: SpamAction::parse(
+                &env_or("SPAM_DUPLICATE_ACTION", "throttle".to_string()),
+                SpamAction::Throttle,
+            ),
+            max_links: env_or("SPAM_MAX_LINKS", 5),
+            link_action: SpamAction::parse(
+                &env_or("SPAM_LINK_ACTION", "flag".to_string()),
+                SpamAction::Flag,
+            ),
+            cooldown: Duration::from_secs(env_or("SPAM_COOLDOWN_SECS", 60)),
+        }
+    }
+}
+
+/// Remembers what each sender sent recently and applies a [`SpamPolicy`].
+pub struct SpamGuard {
+    policy: SpamPolicy,
+    senders: Mutex<HashMap<Uuid, SenderHistory>>,
+}
+
+impl SpamGuard {
+    pub fn new(policy: SpamPolicy) -> Self {
+        Self {
+            policy,
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check one message about to be sent. Refusals come back as
+    /// `ApiError::RateLimited` with the seconds until the sender may retry;
+    /// a message that is let through counts towards later checks.
+    /// `rate_limit` replaces the policy's own for this sender.
+    pub fn check(
+        &self,
+        sender_id: Uuid,
+        content: &str,
+        rate_limit: Option<usize>,
+    ) -> Result<SpamVerdict, ApiError> {
+        let policy = &self.policy;
+        let rate_limit = rate_limit.unwrap_or(policy.rate_limit);
+        let now = Instant::now();
+        let horizon = policy.rate_window.max(policy.duplicate_window);
+        let mut senders = self.senders.lock().unwrap();
+        sweep_idle(&mut senders, now, horizon, |h| h);
+        let history = senders.entry(sender_id).or_default();
+
+        if let Some(wait) = history.blocked_for(now) {
+            return Err(ApiError::rate_limited(wait));
+        }
+        history.forget_older_than(now, horizon);
+
+        let (sent, oldest) = history.sent_within(now, policy.rate_window);
+        if let Some(oldest) = oldest.filter(|_| rate_limit > 0 && sent >= rate_limit) {
+            return Err(ApiError::rate_limited(
+                policy.rate_window - now.duration_since(oldest),
+            ));
+        }
+
+        let fingerprint = fingerprint(content);
+        let duplicates = history.duplicates(now, policy.duplicate_window, fingerprint);
+
+        let mut verdict = SpamVerdict::Allow;
+        let violations = [
+            (
+                // Captionless uploads all look alike; only compare text.
+                policy.max_duplicates > 0
+                    && !content.trim().is_empty()
+                    && duplicates >= policy.max_duplicates,
+                policy.duplicate_action,
+                "duplicate",
+                policy.duplicate_window,
+            ),
+            (
+                policy.max_links > 0 && count_links(content) > policy.max_links,
+                policy.link_action,
+                "links",
+                Duration::from_secs(1),
+            ),
+        ];
+        for (tripped, action, reason, retry_after) in violations {
+            if !tripped {
+                continue;
+            }
+            match action {
+                SpamAction::Throttle => return Err(ApiError::rate_limited(retry_after)),
+                SpamAction::Cooldown => {
+                    history.blocked_until = Some(now + policy.cooldown);
+                    return Err(ApiError::rate_limited(policy.cooldown));
+                }
+                SpamAction::Flag => verdict = SpamVerdict::Flag(reason),
+            }
+        }
+
+        history.record(now, fingerprint);
+        Ok(verdict)
+    }
+}
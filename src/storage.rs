@@ -0,0 +1,135 @@
+use axum::body::Bytes;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::error::ApiError;
+
+/// Upload an object to Supabase Storage at `{bucket}/{path}`.
+/// Fails instead of overwriting if the path is already taken.
+pub async fn upload(
+    bucket: &str,
+    path: &str,
+    content_type: &str,
+    body: Bytes,
+) -> Result<(), ApiError> {
+    let (base, key) = storage_base()?;
+    let url = format!("{}/object/{}/{}", base, bucket, path);
+
+    let res = reqwest::Client::new()
+        .post(&url)
+        .header("apikey", &key)
+        .header("Authorization", format!("Bearer {}", key))
+        .header("Content-Type", content_type)
+        .header("x-upsert", "false")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| ApiError::Database(format!("Network error talking to Storage: {}", e)))?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().await.unwrap_or_default();
+        eprintln!(
+            "[storage::upload] {} on '{}/{}': {}",
+            status, bucket, path, body
+        );
+        return Err(ApiError::Database(format!(
+            "Storage error {} uploading to '{}': {}",
+            status.as_u16(),
+            bucket,
+            body
+        )));
+    }
+
+    Ok(())
+}
+
+/// Delete objects from a bucket. Paths that don't exist are ignored.
+pub async fn remove(bucket: &str, paths: &[String]) -> Result<(), ApiError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let (base, key) = storage_base()?;
+    let url = format!("{}/object/{}", base, bucket);
+
+    let res = reqwest::Client::new()
+        .delete(&url)
+        .header("apikey", &key)
+        .header("Authorization", format!("Bearer {}", key))
+        .json(&json!({ "prefixes": paths }))
+        .send()
+        .await
+        .map_err(|e| ApiError::Database(format!("Network error talking to Storage: {}", e)))?;
+
+    let status = res.status();
+    if !status.is_success() {
+        let body = res.text().await.unwrap_or_default();
+        eprintln!("[storage::remove] {} on '{}': {}", status, bucket, body);
+        return Err(ApiError::Database(format!(
+            "Storage error {} deleting from '{}': {}",
+            status.as_u16(),
+            bucket,
+            body
+        )));
+    }
+
+    Ok(())
+}
+
+/// Create a time-limited download URL for a private object.
+pub async fn signed_url(bucket: &str, path: &str, ttl: Duration) -> Result<String, ApiError> {
+    let (base, key) = storage_base()?;
+    let url = format!("{}/object/sign/{}/{}", base, bucket, path);
+
+    let res = reqwest::Client::new()
+        .post(&url)
+        .header("apikey", &key)
+        .header("Authorization", format!("Bearer {}", key))
+        .json(&json!({ "expiresIn": ttl.as_secs() }))
+        .send()
+        .await
+        .map_err(|e| ApiError::Database(format!("Network error talking to Storage: {}", e)))?;
+
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    if !status.is_success() {
+        eprintln!(
+            "[storage::signed_url] {} on '{}/{}': {}",
+            status, bucket, path, body
+        );
+        return Err(ApiError::Database(format!(
+            "Storage error {} signing '{}': {}",
+            status.as_u16(),
+            path,
+            body
+        )));
+    }
+
+    // The response holds a path relative to /storage/v1, e.g.
+    // {"signedURL": "/object/sign/bucket/path?token=..."}.
+    let signed = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|v| v.get("signedURL")?.as_str().map(String::from))
+        .ok_or_else(|| ApiError::Database(format!("Unexpected Storage response: {}", body)))?;
+
+    Ok(format!("{}{}", base, signed))
+}
+
+/// Permanent URL of an object in a public bucket.
+pub fn public_url(bucket: &str, path: &str) -> Result<String, ApiError> {
+    let (base, _) = storage_base()?;
+    Ok(format!("{}/object/public/{}/{}", base, bucket, path))
+}
+
+/// `{SUPABASE_URL}/storage/v1` and the service key.
+fn storage_base() -> Result<(String, String), ApiError> {
+    let supabase_url = std::env::var("SUPABASE_URL")
+        .map_err(|_| ApiError::Internal("SUPABASE_URL not set".into()))?;
+    let supabase_key = std::env::var("SUPABASE_KEY")
+        .map_err(|_| ApiError::Internal("SUPABASE_KEY not set".into()))?;
+
+    Ok((
+        format!("{}/storage/v1", supabase_url.trim_end_matches('/')),
+        supabase_key,
+    ))
+}
@@ -0,0 +1,2759 @@
+// Storage behind traits.
+//
+// Handlers, jobs and the in-memory caches reach every table through
+// `AppState::store` rather than calling Supabase themselves, so another
+// backend only has to implement these traits. `SupabaseStore` is the
+// production implementation on top of the PostgREST wrapper in `db` and
+// the supabase_rs client; nothing outside this file and `db` talks to
+// Supabase's tables directly.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use supabase_rs::SupabaseClient;
+use uuid::Uuid;
+
+use crate::db::{self, col, Order, Table};
+use crate::error::ApiError;
+use crate::handlers::scheduled::to_timestamp;
+use crate::handlers::search::SEARCH_CONFIG;
+use crate::models::{
+    ActivityRow, AppealRow, AuditLogRow, BlockRow, ChannelSummary, ConversationPreviewRow,
+    ConversationRow, DailyStats, DraftRow, EmojiPackRow, EmojiRow, ErasureRequestRow, ExportRow,
+    FriendRow, HeldMessageRow, IpBanRow, MemberRow, MessageRevisionRow, MessageRow,
+    NotificationSettings, PollRow, PollVoteRow, ProfileRow, RateLimitOverrideRow, ReportRow,
+    ScheduledMessageRow, SnapshotRow, UnreadSummary, UserSettings, UserTotals, WordFilterTermRow,
+};
+
+/// Which messages a `MessageStore` call covers. Every field that is set
+/// narrows it further; the default matches every message.
+#[derive(Debug, Default, Clone)]
+pub struct MessageFilter {
+    pub ids: Option<Vec<i64>>,
+    pub conversation_id: Option<Uuid>,
+    pub conversation_ids: Option<Vec<Uuid>>,
+    pub sender_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    /// Only ids below this.
+    pub before_id: Option<i64>,
+    /// Only ids above this.
+    pub after_id: Option<i64>,
+    /// Only messages created at or after this RFC 3339 time.
+    pub created_from: Option<String>,
+    /// Only messages created before this RFC 3339 time.
+    pub created_before: Option<String>,
+    /// Leave out tombstones.
+    pub live_only: bool,
+    pub with_attachment: bool,
+    /// Leave out messages this user mustn't see: a shadow banned user's
+    /// messages are shown to nobody but them.
+    pub visible_to: Option<Uuid>,
+    /// Full-text search over the content, in web-search syntax.
+    pub search: Option<String>,
+}
+
+/// Which entries an `AdminStore::audit_log` call covers. Every field that
+/// is set narrows it further.
+#[derive(Debug, Default, Clone)]
+pub struct AuditLogFilter {
+    pub admin_id: Option<Uuid>,
+    /// An exact action, ignoring case.
+    pub action: Option<String>,
+    /// Actions starting with this, ignoring case.
+    pub action_prefix: Option<String>,
+    /// An exact target, ignoring case.
+    pub target_id: Option<String>,
+    /// Only entries created at or after this RFC 3339 time.
+    pub created_from: Option<String>,
+    /// Only entries created before this RFC 3339 time.
+    pub created_before: Option<String>,
+}
+
+/// What `tombstone_messages` changed.
+#[derive(Debug, Clone, Copy)]
+pub struct Tombstoned {
+    pub messages: usize,
+    pub revisions: usize,
+}
+
+#[async_trait]
+pub trait ProfileStore: Send + Sync {
+    /// `NotFound` when there is no such profile.
+    async fn profile(&self, id: Uuid) -> Result<ProfileRow, ApiError>;
+
+    /// Many profiles keyed by id. Unknown ids are left out.
+    async fn profiles(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, ProfileRow>, ApiError>;
+
+    /// Exact match, as stored after normalization.
+    async fn profile_by_username(&self, username: &str) -> Result<Option<ProfileRow>, ApiError>;
+
+    /// Ignores case, for names typed by people.
+    async fn find_profile_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<ProfileRow>, ApiError>;
+
+    async fn profile_by_friend_code(&self, code: &str) -> Result<Option<ProfileRow>, ApiError>;
+
+    /// Many profiles by exact username. Unknown names are left out.
+    async fn profiles_by_username(&self, usernames: &[&str]) -> Result<Vec<ProfileRow>, ApiError>;
+
+    /// Active profiles whose username starts with `prefix`, ignoring case,
+    /// in username order. `excluded` ids are left out.
+    async fn search_profiles(
+        &self,
+        prefix: &str,
+        excluded: &HashSet<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<ProfileRow>, ApiError>;
+
+    /// Every profile, deactivated and deleted ones included, in username
+    /// order. `prefix` narrows by username ignoring case; `banned` to
+    /// banned or not banned accounts.
+    async fn profiles_page(
+        &self,
+        prefix: Option<&str>,
+        banned: Option<bool>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ProfileRow>, ApiError>;
+
+    /// Profiles under a ban, a shadow ban or a suspension that hasn't ended.
+    async fn restricted_profiles(&self) -> Result<Vec<ProfileRow>, ApiError>;
+
+    /// `fields` must include the id. `UniqueViolation` when the username is
+    /// taken.
+    async fn insert_profile(&self, fields: Value) -> Result<(), ApiError>;
+
+    /// Apply `changes`, a JSON object of column values, and return the
+    /// updated profile. `NotFound` when there is no such profile.
+    async fn update_profile(&self, id: Uuid, changes: Value) -> Result<ProfileRow, ApiError>;
+
+    async fn touch_last_seen(&self, id: Uuid) -> Result<(), ApiError>;
+
+    /// Session versions above zero, keyed by user.
+    async fn session_versions(&self) -> Result<HashMap<Uuid, i64>, ApiError>;
+
+    /// Add one to the session version of `id` and return the new one.
+    async fn bump_session_version(&self, id: Uuid) -> Result<i64, ApiError>;
+}
+
+#[async_trait]
+pub trait FriendStore: Send + Sync {
+    async fn are_friends(&self, a: Uuid, b: Uuid) -> Result<bool, ApiError>;
+
+    /// The row between `a` and `b`, whatever its status.
+    async fn friendship(&self, a: Uuid, b: Uuid) -> Result<Option<FriendRow>, ApiError>;
+
+    async fn friend_request(&self, id: i64) -> Result<Option<FriendRow>, ApiError>;
+
+    /// Rows with `user_id` on either side, only those in `status` if given.
+    async fn friendships_of(
+        &self,
+        user_id: Uuid,
+        status: Option<&str>,
+    ) -> Result<Vec<FriendRow>, ApiError>;
+
+    /// Accepted friendships of `user_id` by ascending id, starting after
+    /// `after`. With `favorites_only`, just those `user_id` has starred.
+    async fn friends_page(
+        &self,
+        user_id: Uuid,
+        favorites_only: bool,
+        after: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<FriendRow>, ApiError>;
+
+    /// Accepted friends of each of `users`. Users without friends are
+    /// missing from the map.
+    async fn accepted_friends_of(
+        &self,
+        users: &[Uuid],
+    ) -> Result<HashMap<Uuid, HashSet<Uuid>>, ApiError>;
+
+    /// Friends `user_id` has marked as favorites.
+    async fn favorite_friends(&self, user_id: Uuid) -> Result<HashSet<Uuid>, ApiError>;
+
+    /// Insert a row from `fields`, a JSON object of column values.
+    /// `UniqueViolation` when the pair already has one.
+    async fn insert_friendship(&self, fields: Value) -> Result<FriendRow, ApiError>;
+
+    async fn set_friendship_status(&self, id: i64, status: &str) -> Result<(), ApiError>;
+
+    /// Star or unstar `friend_id` on `user_id`'s side. False when they
+    /// aren't friends.
+    async fn set_favorite(
+        &self,
+        user_id: Uuid,
+        friend_id: Uuid,
+        favorite: bool,
+    ) -> Result<bool, ApiError>;
+
+    /// Delete the row, only while it is in `status` if given. False when
+    /// nothing was deleted.
+    async fn delete_friendship(&self, id: i64, status: Option<&str>) -> Result<bool, ApiError>;
+
+    async fn delete_friendship_between(&self, a: Uuid, b: Uuid) -> Result<(), ApiError>;
+
+    /// Delete every row with `user_id` on either side. Returns how many.
+    async fn delete_friendships_of(&self, user_id: Uuid) -> Result<usize, ApiError>;
+}
+
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    /// `NotFound` when there is no such conversation.
+    async fn conversation(&self, id: Uuid) -> Result<ConversationRow, ApiError>;
+
+    /// Many conversations keyed by id. Unknown ids are left out.
+    async fn conversations(&self, ids: &[Uuid])
+        -> Result<HashMap<Uuid, ConversationRow>, ApiError>;
+
+    /// Conversations whose messages expire.
+    async fn conversations_with_ttl(&self) -> Result<Vec<ConversationRow>, ApiError>;
+
+    /// Insert a conversation from `fields`, a JSON object of column values.
+    async fn insert_conversation(&self, fields: Value) -> Result<(), ApiError>;
+
+    /// Apply `changes`, a JSON object of column values. `NotFound` when
+    /// there is no such conversation.
+    async fn update_conversation(
+        &self,
+        id: Uuid,
+        changes: Value,
+    ) -> Result<ConversationRow, ApiError>;
+
+    /// Bump the stored message counter by one, without losing increments
+    /// to concurrent senders.
+    async fn increment_message_count(&self, id: Uuid) -> Result<(), ApiError>;
+
+    /// Each of `user_id`'s conversations with its latest message they can
+    /// see and their unread count.
+    async fn conversation_previews(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<ConversationPreviewRow>, ApiError>;
+
+    /// Conversations where `user_id` has unread messages from others.
+    async fn unread_digest(&self, user_id: Uuid) -> Result<Vec<UnreadSummary>, ApiError>;
+
+    /// Public channels, most members first, optionally only those whose
+    /// name contains `search` (a `LIKE` pattern).
+    async fn public_channels(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ChannelSummary>, ApiError>;
+
+    async fn members(&self, conversation_id: Uuid) -> Result<Vec<MemberRow>, ApiError>;
+
+    /// Members of all of `conversation_ids`.
+    async fn members_of(&self, conversation_ids: &[Uuid]) -> Result<Vec<MemberRow>, ApiError>;
+
+    /// Every conversation `user_id` belongs to.
+    async fn memberships(&self, user_id: Uuid) -> Result<Vec<MemberRow>, ApiError>;
+
+    async fn member(
+        &self,
+        conversation_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<MemberRow>, ApiError>;
+
+    async fn add_members(&self, members: &[MemberRow]) -> Result<(), ApiError>;
+
+    /// Apply `changes` to one membership, a JSON object of column values.
+    async fn update_member(
+        &self,
+        conversation_id: Uuid,
+        user_id: Uuid,
+        changes: Value,
+    ) -> Result<(), ApiError>;
+
+    /// Remove everyone from the conversation. Returns how many.
+    async fn remove_members(&self, conversation_id: Uuid) -> Result<usize, ApiError>;
+
+    /// Remove `user_id` from every conversation. Returns how many.
+    async fn remove_memberships(&self, user_id: Uuid) -> Result<usize, ApiError>;
+}
+
+#[async_trait]
+pub trait MessageStore: Send + Sync {
+    /// The message, deleted or not.
+    async fn message(&self, id: i64) -> Result<Option<MessageRow>, ApiError>;
+
+    /// Messages matching `filter` ordered by id, at most `limit` of them.
+    async fn messages(
+        &self,
+        filter: &MessageFilter,
+        order: Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<MessageRow>, ApiError>;
+
+    /// `messages` with every stored column, as the backend has them.
+    async fn raw_messages(
+        &self,
+        filter: &MessageFilter,
+        order: Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<Value>, ApiError>;
+
+    /// Insert a message from `fields`, a JSON object of column values, and
+    /// return it as stored.
+    async fn insert_message(&self, fields: Value) -> Result<MessageRow, ApiError>;
+
+    async fn insert_messages(&self, rows: &[Value]) -> Result<(), ApiError>;
+
+    /// Apply `changes`, a JSON object of column values. `None` when there
+    /// is no such message.
+    async fn update_message(&self, id: i64, changes: Value)
+        -> Result<Option<MessageRow>, ApiError>;
+
+    /// Clear the content, files and previews of every message matching
+    /// `filter` and mark it deleted, keeping the row so history stays
+    /// consistent. Their revisions go too.
+    async fn tombstone_messages(&self, filter: &MessageFilter) -> Result<Tombstoned, ApiError>;
+
+    /// Messages `user_id` has sent since midnight UTC, deleted ones included.
+    async fn messages_sent_today(&self, user_id: Uuid) -> Result<u64, ApiError>;
+
+    /// Bytes of attachments `user_id` has in messages that still exist.
+    /// Thumbnails aren't counted.
+    async fn attachment_bytes_used(&self, user_id: Uuid) -> Result<u64, ApiError>;
+
+    async fn add_revision(&self, revision: &MessageRevisionRow) -> Result<(), ApiError>;
+
+    /// Earlier versions of a message, oldest first.
+    async fn revisions(&self, message_id: i64) -> Result<Vec<MessageRevisionRow>, ApiError>;
+
+    /// Revisions in a conversation of messages from `from_message_id` on,
+    /// oldest first.
+    async fn conversation_revisions(
+        &self,
+        conversation_id: Uuid,
+        from_message_id: i64,
+    ) -> Result<Vec<MessageRevisionRow>, ApiError>;
+}
+
+#[async_trait]
+pub trait SettingsStore: Send + Sync {
+    /// `None` when the user never changed their privacy settings.
+    async fn privacy_settings(&self, user_id: Uuid) -> Result<Option<UserSettings>, ApiError>;
+
+    /// Privacy settings of many users. Users without a row are left out.
+    async fn privacy_settings_of(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, UserSettings>, ApiError>;
+
+    async fn save_privacy_settings(
+        &self,
+        user_id: Uuid,
+        settings: &UserSettings,
+    ) -> Result<(), ApiError>;
+
+    /// `None` when the user never changed their notification settings.
+    async fn notification_settings(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<NotificationSettings>, ApiError>;
+
+    /// Notification settings of many users. Users without a row are left out.
+    async fn notification_settings_of(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, NotificationSettings>, ApiError>;
+
+    async fn save_notification_settings(
+        &self,
+        user_id: Uuid,
+        settings: &NotificationSettings,
+    ) -> Result<(), ApiError>;
+
+    /// Drop both kinds of settings, back to the defaults.
+    async fn delete_settings(&self, user_id: Uuid) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait BlockStore: Send + Sync {
+    /// Blocks `blocker_id` has made, most recent first.
+    async fn blocks_page(
+        &self,
+        blocker_id: Uuid,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<BlockRow>, ApiError>;
+
+    /// Blocking someone twice is not an error.
+    async fn add_block(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), ApiError>;
+
+    /// Whether there was a block to remove.
+    async fn remove_block(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<bool, ApiError>;
+
+    /// Whether either of `a` and `b` has blocked the other.
+    async fn blocked_between(&self, a: Uuid, b: Uuid) -> Result<bool, ApiError>;
+
+    /// Everyone `blocker_id` has blocked.
+    async fn blocked_ids(&self, blocker_id: Uuid) -> Result<HashSet<Uuid>, ApiError>;
+
+    /// Everyone who has blocked `blocked_id`, only among `among` if given.
+    async fn blocker_ids(
+        &self,
+        blocked_id: Uuid,
+        among: Option<&[Uuid]>,
+    ) -> Result<HashSet<Uuid>, ApiError>;
+}
+
+/// Server-side policy an admin manages: word filter terms, IP bans and
+/// per-user rate limits.
+#[async_trait]
+pub trait PolicyStore: Send + Sync {
+    /// Oldest first.
+    async fn filter_terms(&self) -> Result<Vec<WordFilterTermRow>, ApiError>;
+
+    /// `UniqueViolation` when the term is already there.
+    async fn insert_filter_term(
+        &self,
+        term: &WordFilterTermRow,
+    ) -> Result<WordFilterTermRow, ApiError>;
+
+    async fn insert_filter_terms(&self, terms: &[WordFilterTermRow]) -> Result<(), ApiError>;
+
+    /// `NotFound` when there is no such term.
+    async fn update_filter_term(
+        &self,
+        id: Uuid,
+        changes: Value,
+    ) -> Result<WordFilterTermRow, ApiError>;
+
+    /// Returns the terms that were removed.
+    async fn delete_filter_terms(&self, ids: &[Uuid]) -> Result<Vec<WordFilterTermRow>, ApiError>;
+
+    /// Newest first, expired ones included.
+    async fn ip_bans(&self) -> Result<Vec<IpBanRow>, ApiError>;
+
+    /// `UniqueViolation` when the block is already banned.
+    async fn insert_ip_ban(&self, ban: &IpBanRow) -> Result<IpBanRow, ApiError>;
+
+    /// The removed ban, if there was one.
+    async fn delete_ip_ban(&self, id: Uuid) -> Result<Option<IpBanRow>, ApiError>;
+
+    /// Most recently changed first.
+    async fn rate_limit_overrides(&self) -> Result<Vec<RateLimitOverrideRow>, ApiError>;
+
+    /// Replaces any earlier override for the same user.
+    async fn save_rate_limit_override(
+        &self,
+        row: &RateLimitOverrideRow,
+    ) -> Result<RateLimitOverrideRow, ApiError>;
+
+    /// Whether there was an override to remove.
+    async fn delete_rate_limit_override(&self, user_id: Uuid) -> Result<bool, ApiError>;
+}
+
+#[async_trait]
+pub trait ReportStore: Send + Sync {
+    /// An open report by `reporter_id` about the same user and message
+    /// (`None` for the user themselves), if there is one.
+    async fn open_report(
+        &self,
+        reporter_id: Uuid,
+        target_user_id: Uuid,
+        message_id: Option<i64>,
+    ) -> Result<Option<ReportRow>, ApiError>;
+
+    async fn insert_report(&self, report: &ReportRow) -> Result<(), ApiError>;
+
+    /// When each report by `reporter_id` since `since` was filed, oldest
+    /// first.
+    async fn report_times(
+        &self,
+        reporter_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>, ApiError>;
+
+    /// Reports in `status`, oldest first.
+    async fn reports_page(
+        &self,
+        status: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ReportRow>, ApiError>;
+
+    /// The latest reports about `user_id`, newest first.
+    async fn reports_about(&self, user_id: Uuid, limit: usize) -> Result<Vec<ReportRow>, ApiError>;
+
+    /// Raw rows of every report filed in `conversation_id` or about one of
+    /// `member_ids`, oldest first, so every column survives as evidence.
+    async fn conversation_reports(
+        &self,
+        conversation_id: Uuid,
+        member_ids: &[Uuid],
+    ) -> Result<Vec<Value>, ApiError>;
+
+    /// `NotFound` when there is no such report.
+    async fn report(&self, id: Uuid) -> Result<ReportRow, ApiError>;
+
+    /// `NotFound` when there is no such report.
+    async fn update_report(&self, id: Uuid, changes: Value) -> Result<ReportRow, ApiError>;
+
+    /// Clear the reason on every report `reporter_id` filed. Returns how
+    /// many there were.
+    async fn scrub_report_reasons(&self, reporter_id: Uuid) -> Result<usize, ApiError>;
+}
+
+#[async_trait]
+pub trait AppealStore: Send + Sync {
+    async fn insert_appeal(&self, appeal: &AppealRow) -> Result<(), ApiError>;
+
+    /// Every appeal `user_id` has filed, newest first.
+    async fn appeals_of(&self, user_id: Uuid) -> Result<Vec<AppealRow>, ApiError>;
+
+    /// Appeals in `status`, oldest first.
+    async fn appeals_page(
+        &self,
+        status: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AppealRow>, ApiError>;
+
+    /// `NotFound` when there is no such appeal.
+    async fn appeal(&self, id: Uuid) -> Result<AppealRow, ApiError>;
+
+    /// `NotFound` when there is no such appeal.
+    async fn update_appeal(&self, id: Uuid, changes: Value) -> Result<AppealRow, ApiError>;
+
+    /// Returns how many were removed.
+    async fn delete_appeals_of(&self, user_id: Uuid) -> Result<usize, ApiError>;
+}
+
+/// Messages held for review and the snapshots admins take as evidence.
+#[async_trait]
+pub trait ModerationStore: Send + Sync {
+    async fn hold_message(&self, held: &HeldMessageRow) -> Result<(), ApiError>;
+
+    /// Messages still waiting for review, oldest first.
+    async fn held_messages_page(
+        &self,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<HeldMessageRow>, ApiError>;
+
+    /// `NotFound` when there is no such held message.
+    async fn held_message(&self, id: Uuid) -> Result<HeldMessageRow, ApiError>;
+
+    /// Record the review of a held message.
+    async fn review_held_message(
+        &self,
+        id: Uuid,
+        status: &str,
+        reviewer_id: Uuid,
+    ) -> Result<(), ApiError>;
+
+    /// Everything `sender_id` has had held, whatever its status.
+    async fn held_messages_of(&self, sender_id: Uuid) -> Result<Vec<HeldMessageRow>, ApiError>;
+
+    /// Returns how many were removed.
+    async fn delete_held_messages_of(&self, sender_id: Uuid) -> Result<usize, ApiError>;
+
+    async fn insert_snapshot(&self, snapshot: &SnapshotRow) -> Result<(), ApiError>;
+
+    /// Metadata of every snapshot of `conversation_id`, newest first,
+    /// without the captured contents.
+    async fn snapshots(&self, conversation_id: Uuid) -> Result<Vec<Value>, ApiError>;
+
+    /// `NotFound` when there is no such snapshot.
+    async fn snapshot(&self, id: Uuid) -> Result<SnapshotRow, ApiError>;
+}
+
+/// The admin audit log and dashboard numbers.
+#[async_trait]
+pub trait AdminStore: Send + Sync {
+    async fn record_audit(&self, entry: &AuditLogRow) -> Result<(), ApiError>;
+
+    /// Newest first.
+    async fn audit_log(
+        &self,
+        filter: &AuditLogFilter,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditLogRow>, ApiError>;
+
+    /// One row per UTC day over the last `days` days, oldest first.
+    async fn daily_stats(&self, days: u32) -> Result<Vec<DailyStats>, ApiError>;
+
+    async fn user_totals(&self) -> Result<UserTotals, ApiError>;
+
+    /// `(table, definition)` of every index, for the index advisor.
+    async fn indexes(&self) -> Result<Vec<(String, String)>, ApiError>;
+}
+
+/// Right-to-erasure requests and the record that keeps backups honest.
+#[async_trait]
+pub trait ErasureStore: Send + Sync {
+    /// Queue `user_id` for the erasure job.
+    async fn queue_erasure(&self, id: Uuid, user_id: Uuid) -> Result<(), ApiError>;
+
+    /// The latest requests, newest first.
+    async fn erasure_requests(&self, limit: usize) -> Result<Vec<ErasureRequestRow>, ApiError>;
+
+    /// `NotFound` when there is no such request.
+    async fn erasure_request(&self, id: Uuid) -> Result<ErasureRequestRow, ApiError>;
+
+    /// Requests the job hasn't run yet, oldest first.
+    async fn pending_erasures(&self, limit: usize) -> Result<Vec<ErasureRequestRow>, ApiError>;
+
+    async fn finish_erasure(&self, id: Uuid, status: &str, report: Value) -> Result<(), ApiError>;
+
+    /// Remember that `user_id` was erased, so restoring a backup can erase
+    /// them again.
+    async fn record_backup_exclusion(&self, user_id: Uuid) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait ScheduleStore: Send + Sync {
+    async fn insert_schedule(&self, row: &ScheduledMessageRow) -> Result<(), ApiError>;
+
+    /// Soonest first.
+    async fn schedules_of(&self, sender_id: Uuid) -> Result<Vec<ScheduledMessageRow>, ApiError>;
+
+    async fn schedule(&self, id: Uuid) -> Result<Option<ScheduledMessageRow>, ApiError>;
+
+    async fn update_schedule(&self, id: Uuid, changes: Value) -> Result<(), ApiError>;
+
+    async fn delete_schedule(&self, id: Uuid) -> Result<(), ApiError>;
+
+    /// Unpaused schedules whose `next_run_at` is at or before `now`,
+    /// earliest first.
+    async fn due_schedules(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<ScheduledMessageRow>, ApiError>;
+
+    /// Move `next_run_at` on to `until`, only if it is still
+    /// `seen_next_run_at`. `false` when another run got there first.
+    async fn claim_schedule(
+        &self,
+        id: Uuid,
+        seen_next_run_at: &str,
+        until: DateTime<Utc>,
+    ) -> Result<bool, ApiError>;
+
+    /// How many were deleted.
+    async fn delete_schedules_of(&self, sender_id: Uuid) -> Result<usize, ApiError>;
+
+    async fn delete_conversation_schedules(&self, conversation_id: Uuid) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait DraftStore: Send + Sync {
+    async fn draft(
+        &self,
+        user_id: Uuid,
+        conversation_id: Uuid,
+    ) -> Result<Option<DraftRow>, ApiError>;
+
+    /// Most recently edited first.
+    async fn drafts_of(&self, user_id: Uuid) -> Result<Vec<DraftRow>, ApiError>;
+
+    /// Insert or replace the draft for its user and conversation.
+    async fn save_draft(&self, draft: &DraftRow) -> Result<DraftRow, ApiError>;
+
+    async fn delete_draft(&self, user_id: Uuid, conversation_id: Uuid) -> Result<(), ApiError>;
+
+    /// How many were deleted.
+    async fn delete_drafts_of(&self, user_id: Uuid) -> Result<usize, ApiError>;
+
+    async fn delete_conversation_drafts(&self, conversation_id: Uuid) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait PollStore: Send + Sync {
+    async fn insert_poll(&self, poll: &PollRow) -> Result<(), ApiError>;
+
+    /// `NotFound` when there is no such poll.
+    async fn poll(&self, id: Uuid) -> Result<PollRow, ApiError>;
+
+    async fn polls(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, PollRow>, ApiError>;
+
+    /// Every vote cast on any of `poll_ids`.
+    async fn votes(&self, poll_ids: &[Uuid]) -> Result<Vec<PollVoteRow>, ApiError>;
+
+    /// Replace `user_id`'s votes on a poll with `votes`, which may be empty.
+    async fn replace_votes(
+        &self,
+        poll_id: Uuid,
+        user_id: Uuid,
+        votes: &[PollVoteRow],
+    ) -> Result<(), ApiError>;
+}
+
+#[async_trait]
+pub trait EmojiStore: Send + Sync {
+    /// By name.
+    async fn emoji_packs(&self) -> Result<Vec<EmojiPackRow>, ApiError>;
+
+    /// Every emoji and sticker in every pack, by name.
+    async fn all_emoji(&self) -> Result<Vec<EmojiRow>, ApiError>;
+
+    /// `NotFound` when there is no such pack.
+    async fn emoji_pack(&self, id: Uuid) -> Result<EmojiPackRow, ApiError>;
+
+    async fn insert_emoji_pack(&self, pack: &EmojiPackRow) -> Result<(), ApiError>;
+
+    /// Delete a pack with everything in it, returning what was in it.
+    /// `NotFound` when there is no such pack.
+    async fn delete_emoji_pack(&self, id: Uuid) -> Result<Vec<EmojiRow>, ApiError>;
+
+    async fn emoji(&self, id: Uuid) -> Result<Option<EmojiRow>, ApiError>;
+
+    /// `UniqueViolation` when the name is taken.
+    async fn insert_emoji(&self, emoji: &EmojiRow) -> Result<(), ApiError>;
+
+    /// The deleted emoji, or `None` if there was none.
+    async fn delete_emoji(&self, id: Uuid) -> Result<Option<EmojiRow>, ApiError>;
+}
+
+/// The friends feed.
+#[async_trait]
+pub trait ActivityStore: Send + Sync {
+    async fn insert_activity(&self, row: &ActivityRow) -> Result<(), ApiError>;
+
+    /// Newest first, only entries with an id below `before` when set.
+    async fn activity_page(
+        &self,
+        user_ids: &[Uuid],
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<ActivityRow>, ApiError>;
+
+    /// How many were deleted.
+    async fn delete_activity_of(&self, user_id: Uuid) -> Result<usize, ApiError>;
+}
+
+/// Background conversation exports.
+#[async_trait]
+pub trait ExportStore: Send + Sync {
+    async fn insert_export(&self, export: &ExportRow) -> Result<(), ApiError>;
+
+    /// `NotFound` unless `user_id` requested it.
+    async fn export(&self, id: Uuid, user_id: Uuid) -> Result<ExportRow, ApiError>;
+
+    /// Oldest first.
+    async fn pending_exports(&self, limit: usize) -> Result<Vec<ExportRow>, ApiError>;
+
+    /// Flip a pending export to "processing". `false` when another run got
+    /// there first.
+    async fn claim_export(&self, id: Uuid) -> Result<bool, ApiError>;
+
+    async fn update_export(&self, id: Uuid, changes: Value) -> Result<(), ApiError>;
+}
+
+/// Everything the handlers need from a backend.
+pub trait Storage:
+    ProfileStore
+    + FriendStore
+    + ConversationStore
+    + MessageStore
+    + SettingsStore
+    + BlockStore
+    + PolicyStore
+    + ReportStore
+    + AppealStore
+    + ModerationStore
+    + AdminStore
+    + ErasureStore
+    + ScheduleStore
+    + DraftStore
+    + PollStore
+    + EmojiStore
+    + ActivityStore
+    + ExportStore
+{
+}
+
+impl<
+        T: ProfileStore
+            + FriendStore
+            + ConversationStore
+            + MessageStore
+            + SettingsStore
+            + BlockStore
+            + PolicyStore
+            + ReportStore
+            + AppealStore
+            + ModerationStore
+            + AdminStore
+            + ErasureStore
+            + ScheduleStore
+            + DraftStore
+            + PollStore
+            + EmojiStore
+            + ActivityStore
+            + ExportStore,
+    > Storage for T
+{
+}
+
+// ---------------------------------------------------------------------------
+// Supabase
+// ---------------------------------------------------------------------------
+
+pub struct SupabaseStore {
+    client: Arc<SupabaseClient>,
+}
+
+impl SupabaseStore {
+    pub fn new(client: Arc<SupabaseClient>) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ProfileStore for SupabaseStore {
+    async fn profile(&self, id: Uuid) -> Result<ProfileRow, ApiError> {
+        match db::from(Table::Profiles)
+            .eq(col::ID, id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => serde_json::from_value(v).map_err(|e| ApiError::Database(e.to_string())),
+            None => Err(ApiError::NotFound("Profile not found".into())),
+        }
+    }
+
+    async fn profiles(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, ProfileRow>, ApiError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(db::from(Table::Profiles)
+            .in_list(col::ID, ids)
+            .fetch()
+            .await?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value::<ProfileRow>(v).ok())
+            .map(|p| (p.id, p))
+            .collect())
+    }
+
+    async fn profile_by_username(&self, username: &str) -> Result<Option<ProfileRow>, ApiError> {
+        first_row(
+            db::from(Table::Profiles)
+                .eq(col::USERNAME, username)
+                .fetch()
+                .await?,
+        )
+    }
+
+    async fn find_profile_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Option<ProfileRow>, ApiError> {
+        first_row(
+            db::from(Table::Profiles)
+                .eq_ci(col::USERNAME, username)
+                .limit(1)
+                .fetch()
+                .await?,
+        )
+    }
+
+    async fn profile_by_friend_code(&self, code: &str) -> Result<Option<ProfileRow>, ApiError> {
+        first_row(
+            db::from(Table::Profiles)
+                .eq(col::FRIEND_CODE, code)
+                .fetch()
+                .await?,
+        )
+    }
+
+    async fn profiles_by_username(&self, usernames: &[&str]) -> Result<Vec<ProfileRow>, ApiError> {
+        if usernames.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(parse_rows(
+            db::from(Table::Profiles)
+                .in_list(col::USERNAME, usernames)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn search_profiles(
+        &self,
+        prefix: &str,
+        excluded: &HashSet<Uuid>,
+        limit: usize,
+    ) -> Result<Vec<ProfileRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Profiles)
+                .starts_with_ci(col::USERNAME, prefix)
+                .not_in(col::ID, excluded)
+                .null(col::DEACTIVATED_AT)
+                .order(col::USERNAME, Order::Asc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn profiles_page(
+        &self,
+        prefix: Option<&str>,
+        banned: Option<bool>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ProfileRow>, ApiError> {
+        let mut query = db::from(Table::Profiles);
+        if let Some(prefix) = prefix {
+            query = query.starts_with_ci(col::USERNAME, prefix);
+        }
+        query = match banned {
+            Some(true) => query.not_null(col::BANNED_AT),
+            Some(false) => query.null(col::BANNED_AT),
+            None => query,
+        };
+        Ok(parse_rows(
+            query
+                .order(col::USERNAME, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn restricted_profiles(&self) -> Result<Vec<ProfileRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Profiles)
+                .or(&[
+                    format!("{}.not.is.null", col::BANNED_AT.name()),
+                    format!(
+                        "{}.gt.{}",
+                        col::SUSPENDED_UNTIL.name(),
+                        to_timestamp(Utc::now())
+                    ),
+                    format!("{}.not.is.null", col::SHADOW_BANNED_AT.name()),
+                ])
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn insert_profile(&self, fields: Value) -> Result<(), ApiError> {
+        db::insert(Table::Profiles, fields).await?;
+        Ok(())
+    }
+
+    async fn update_profile(&self, id: Uuid, changes: Value) -> Result<ProfileRow, ApiError> {
+        found(
+            db::from(Table::Profiles)
+                .eq(col::ID, id)
+                .update(changes)
+                .await?,
+            "Profile",
+        )
+    }
+
+    async fn touch_last_seen(&self, id: Uuid) -> Result<(), ApiError> {
+        db::from(Table::Profiles)
+            .eq(col::ID, id)
+            .update(json!({ "last_seen_at": to_timestamp(Utc::now()) }))
+            .await?;
+        Ok(())
+    }
+
+    async fn session_versions(&self) -> Result<HashMap<Uuid, i64>, ApiError> {
+        Ok(db::from(Table::Profiles)
+            .gt(col::SESSION_VERSION, 0)
+            .columns(&[col::ID, col::SESSION_VERSION])
+            .fetch()
+            .await?
+            .iter()
+            .filter_map(|row| {
+                let id = row.get("id")?.as_str()?.parse().ok()?;
+                let version = row.get("session_version")?.as_i64()?;
+                Some((id, version))
+            })
+            .collect())
+    }
+
+    /// ```sql
+    /// create function bump_session_version(uid uuid) returns bigint
+    /// language sql as $$
+    ///   update profiles set session_version = session_version + 1
+    ///   where id = uid
+    ///   returning session_version;
+    /// $$;
+    /// ```
+    async fn bump_session_version(&self, id: Uuid) -> Result<i64, ApiError> {
+        db::rpc("bump_session_version", json!({ "uid": id.to_string() }))
+            .await?
+            .as_i64()
+            .ok_or_else(|| ApiError::NotFound("Profile not found".into()))
+    }
+}
+
+/// Ids per `in.(...)` filter, to keep request URLs short.
+const ID_BATCH: usize = 200;
+
+fn parse_rows<T: serde::de::DeserializeOwned>(rows: Vec<Value>) -> Vec<T> {
+    rows.into_iter()
+        .filter_map(|v| serde_json::from_value(v).ok())
+        .collect()
+}
+
+/// The first row, if any.
+fn first_row<T: serde::de::DeserializeOwned>(rows: Vec<Value>) -> Result<Option<T>, ApiError> {
+    match rows.into_iter().next() {
+        Some(v) => Ok(Some(serde_json::from_value(v)?)),
+        None => Ok(None),
+    }
+}
+
+/// The first row, or `NotFound` naming `what`.
+fn found<T: serde::de::DeserializeOwned>(rows: Vec<Value>, what: &str) -> Result<T, ApiError> {
+    first_row(rows)?.ok_or_else(|| ApiError::NotFound(format!("{} not found", what)))
+}
+
+/// `user_a` is always the smaller id so the pair's UNIQUE constraint works.
+fn ordered(a: Uuid, b: Uuid) -> (Uuid, Uuid) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[async_trait]
+impl FriendStore for SupabaseStore {
+    async fn are_friends(&self, a: Uuid, b: Uuid) -> Result<bool, ApiError> {
+        Ok(self
+            .friendship(a, b)
+            .await?
+            .is_some_and(|row| row.status == "accepted"))
+    }
+
+    async fn friendship(&self, a: Uuid, b: Uuid) -> Result<Option<FriendRow>, ApiError> {
+        let (user_a, user_b) = ordered(a, b);
+        match db::from(Table::Friends)
+            .eq(col::USER_A, user_a)
+            .eq(col::USER_B, user_b)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn friend_request(&self, id: i64) -> Result<Option<FriendRow>, ApiError> {
+        match db::from(Table::Friends)
+            .eq(col::ID, id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn friendships_of(
+        &self,
+        user_id: Uuid,
+        status: Option<&str>,
+    ) -> Result<Vec<FriendRow>, ApiError> {
+        let mut query = db::from(Table::Friends).or(&[
+            format!("user_a.eq.{}", user_id),
+            format!("user_b.eq.{}", user_id),
+        ]);
+        if let Some(status) = status {
+            query = query.eq(col::STATUS, status);
+        }
+        Ok(parse_rows(query.fetch().await?))
+    }
+
+    async fn friends_page(
+        &self,
+        user_id: Uuid,
+        favorites_only: bool,
+        after: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<FriendRow>, ApiError> {
+        let conditions = if favorites_only {
+            vec![
+                format!("and(user_a.eq.{},favorite_a.is.true)", user_id),
+                format!("and(user_b.eq.{},favorite_b.is.true)", user_id),
+            ]
+        } else {
+            vec![
+                format!("user_a.eq.{}", user_id),
+                format!("user_b.eq.{}", user_id),
+            ]
+        };
+        let mut query = db::from(Table::Friends)
+            .or(&conditions)
+            .eq(col::STATUS, "accepted");
+        if let Some(after) = after {
+            query = query.gt(col::ID, after);
+        }
+        Ok(parse_rows(
+            query
+                .order(col::ID, Order::Asc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    /// Two queries however many users there are.
+    async fn accepted_friends_of(
+        &self,
+        users: &[Uuid],
+    ) -> Result<HashMap<Uuid, HashSet<Uuid>>, ApiError> {
+        let mut friends: HashMap<Uuid, HashSet<Uuid>> = HashMap::new();
+        if users.is_empty() {
+            return Ok(friends);
+        }
+        let wanted: HashSet<Uuid> = users.iter().copied().collect();
+
+        for column in [col::USER_A, col::USER_B] {
+            let rows: Vec<FriendRow> = parse_rows(
+                db::from(Table::Friends)
+                    .in_list(column, users)
+                    .eq(col::STATUS, "accepted")
+                    .fetch()
+                    .await?,
+            );
+            for row in rows {
+                if wanted.contains(&row.user_a) {
+                    friends.entry(row.user_a).or_default().insert(row.user_b);
+                }
+                if wanted.contains(&row.user_b) {
+                    friends.entry(row.user_b).or_default().insert(row.user_a);
+                }
+            }
+        }
+        Ok(friends)
+    }
+
+    async fn favorite_friends(&self, user_id: Uuid) -> Result<HashSet<Uuid>, ApiError> {
+        let mut favorites = HashSet::new();
+        for (mine, flag, theirs) in [
+            (col::USER_A, col::FAVORITE_A, "user_b"),
+            (col::USER_B, col::FAVORITE_B, "user_a"),
+        ] {
+            favorites.extend(
+                db::from(Table::Friends)
+                    .eq(mine, user_id)
+                    .eq(col::STATUS, "accepted")
+                    .eq(flag, true)
+                    .fetch()
+                    .await?
+                    .iter()
+                    .filter_map(|row| row.get(theirs)?.as_str()?.parse::<Uuid>().ok()),
+            );
+        }
+        Ok(favorites)
+    }
+
+    async fn insert_friendship(&self, fields: Value) -> Result<FriendRow, ApiError> {
+        Ok(serde_json::from_value(
+            db::insert_returning(Table::Friends, fields).await?,
+        )?)
+    }
+
+    async fn set_friendship_status(&self, id: i64, status: &str) -> Result<(), ApiError> {
+        db::from(Table::Friends)
+            .eq(col::ID, id)
+            .update(json!({ "status": status }))
+            .await?;
+        Ok(())
+    }
+
+    async fn set_favorite(
+        &self,
+        user_id: Uuid,
+        friend_id: Uuid,
+        favorite: bool,
+    ) -> Result<bool, ApiError> {
+        let (user_a, user_b) = ordered(user_id, friend_id);
+        let column = if user_a == user_id {
+            "favorite_a"
+        } else {
+            "favorite_b"
+        };
+        Ok(!db::from(Table::Friends)
+            .eq(col::USER_A, user_a)
+            .eq(col::USER_B, user_b)
+            .eq(col::STATUS, "accepted")
+            .update(json!({ column: favorite }))
+            .await?
+            .is_empty())
+    }
+
+    async fn delete_friendship(&self, id: i64, status: Option<&str>) -> Result<bool, ApiError> {
+        let mut query = db::from(Table::Friends).eq(col::ID, id);
+        if let Some(status) = status {
+            query = query.eq(col::STATUS, status);
+        }
+        Ok(!query.delete().await?.is_empty())
+    }
+
+    async fn delete_friendship_between(&self, a: Uuid, b: Uuid) -> Result<(), ApiError> {
+        let (user_a, user_b) = ordered(a, b);
+        db::from(Table::Friends)
+            .eq(col::USER_A, user_a)
+            .eq(col::USER_B, user_b)
+            .delete()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_friendships_of(&self, user_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::Friends)
+            .or(&[
+                format!("user_a.eq.{}", user_id),
+                format!("user_b.eq.{}", user_id),
+            ])
+            .delete()
+            .await?
+            .len())
+    }
+}
+
+#[async_trait]
+impl ConversationStore for SupabaseStore {
+    async fn conversation(&self, id: Uuid) -> Result<ConversationRow, ApiError> {
+        match db::from(Table::Conversations)
+            .eq(col::ID, id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(serde_json::from_value(v)?),
+            None => Err(ApiError::NotFound("Conversation not found".into())),
+        }
+    }
+
+    async fn conversations(
+        &self,
+        ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, ConversationRow>, ApiError> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(parse_rows::<ConversationRow>(
+            db::from(Table::Conversations)
+                .in_list(col::ID, ids)
+                .fetch()
+                .await?,
+        )
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect())
+    }
+
+    async fn conversations_with_ttl(&self) -> Result<Vec<ConversationRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Conversations)
+                .not_null(col::MESSAGE_TTL_SECONDS)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn insert_conversation(&self, fields: Value) -> Result<(), ApiError> {
+        self.client
+            .insert(Table::Conversations.name(), fields)
+            .await
+            .map_err(|e| ApiError::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn update_conversation(
+        &self,
+        id: Uuid,
+        changes: Value,
+    ) -> Result<ConversationRow, ApiError> {
+        match db::from(Table::Conversations)
+            .eq(col::ID, id)
+            .update(changes)
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(serde_json::from_value(v)?),
+            None => Err(ApiError::NotFound("Conversation not found".into())),
+        }
+    }
+
+    /// A single atomic UPDATE through this Postgres function:
+    ///
+    /// ```sql
+    /// alter table conversations add column message_count bigint not null default 0;
+    /// create function increment_message_count(cid uuid) returns bigint
+    /// language sql as $$
+    ///   update conversations set message_count = message_count + 1
+    ///   where id = cid returning message_count;
+    /// $$;
+    /// ```
+    async fn increment_message_count(&self, id: Uuid) -> Result<(), ApiError> {
+        db::rpc("increment_message_count", json!({ "cid": id.to_string() })).await?;
+        Ok(())
+    }
+
+    /// One round trip through this Postgres function:
+    ///
+    /// ```sql
+    /// create function conversation_previews(uid uuid)
+    /// returns table(conversation_id uuid, last_message_id bigint,
+    ///               last_sender_id uuid, last_content text,
+    ///               last_message_type text, last_message_at timestamptz,
+    ///               unread_count bigint)
+    /// language sql stable as $$
+    ///   select m.conversation_id, last.id, last.sender_id, last.content,
+    ///          last.message_type, last.created_at,
+    ///          (select count(*) from messages u
+    ///           where u.conversation_id = m.conversation_id
+    ///             and u.id > coalesce(m.last_read_message_id, 0)
+    ///             and u.sender_id <> uid
+    ///             and not coalesce(u.is_deleted, false)
+    ///             and not coalesce(u.shadow_hidden, false))
+    ///   from conversation_members m
+    ///   left join lateral (
+    ///     select id, sender_id, content, message_type, created_at
+    ///     from messages
+    ///     where conversation_id = m.conversation_id
+    ///       and not coalesce(is_deleted, false)
+    ///       and (not coalesce(shadow_hidden, false) or sender_id = uid)
+    ///     order by id desc limit 1
+    ///   ) last on true
+    ///   where m.user_id = uid;
+    /// $$;
+    /// ```
+    async fn conversation_previews(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<ConversationPreviewRow>, ApiError> {
+        let rows = db::rpc(
+            "conversation_previews",
+            json!({ "uid": user_id.to_string() }),
+        )
+        .await?;
+        Ok(serde_json::from_value(rows)?)
+    }
+
+    /// ```sql
+    /// alter table conversation_members add column last_read_message_id bigint;
+    /// create function unread_digest(uid uuid)
+    /// returns table(conversation_id uuid, unread_count bigint,
+    ///               last_message_id bigint, last_message_at timestamptz)
+    /// language sql stable as $$
+    ///   select m.conversation_id, count(msg.id), max(msg.id), max(msg.created_at)
+    ///   from conversation_members m
+    ///   join messages msg on msg.conversation_id = m.conversation_id
+    ///     and msg.id > coalesce(m.last_read_message_id, 0)
+    ///     and msg.sender_id <> uid
+    ///     and not coalesce(msg.is_deleted, false)
+    ///     and not coalesce(msg.shadow_hidden, false)
+    ///   where m.user_id = uid
+    ///   group by m.conversation_id;
+    /// $$;
+    /// ```
+    async fn unread_digest(&self, user_id: Uuid) -> Result<Vec<UnreadSummary>, ApiError> {
+        let rows = db::rpc("unread_digest", json!({ "uid": user_id.to_string() })).await?;
+        Ok(serde_json::from_value(rows)?)
+    }
+
+    /// ```sql
+    /// create function public_channels(search text, lim int, off int)
+    /// returns table(id uuid, name text, avatar_url text, is_announcement boolean,
+    ///               member_count bigint, created_at timestamptz)
+    /// language sql stable as $$
+    ///   select c.id, c.name, c.avatar_url, c.is_announcement,
+    ///          count(m.user_id), c.created_at
+    ///   from conversations c
+    ///   left join conversation_members m on m.conversation_id = c.id
+    ///   where c.visibility = 'public' and c.deleted_at is null
+    ///     and (search is null or c.name ilike '%' || search || '%')
+    ///   group by c.id
+    ///   order by count(m.user_id) desc, c.created_at desc
+    ///   limit lim offset off;
+    /// $$;
+    /// ```
+    async fn public_channels(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ChannelSummary>, ApiError> {
+        let rows = db::rpc(
+            "public_channels",
+            json!({ "search": search, "lim": limit, "off": offset }),
+        )
+        .await?;
+        Ok(serde_json::from_value(rows)?)
+    }
+
+    async fn members(&self, conversation_id: Uuid) -> Result<Vec<MemberRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ConversationMembers)
+                .eq(col::CONVERSATION_ID, conversation_id)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn members_of(&self, conversation_ids: &[Uuid]) -> Result<Vec<MemberRow>, ApiError> {
+        if conversation_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(parse_rows(
+            db::from(Table::ConversationMembers)
+                .in_list(col::CONVERSATION_ID, conversation_ids)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn memberships(&self, user_id: Uuid) -> Result<Vec<MemberRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ConversationMembers)
+                .eq(col::USER_ID, user_id)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn member(
+        &self,
+        conversation_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<MemberRow>, ApiError> {
+        match db::from(Table::ConversationMembers)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .eq(col::USER_ID, user_id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn add_members(&self, members: &[MemberRow]) -> Result<(), ApiError> {
+        let rows = members
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        db::insert_many(Table::ConversationMembers, &rows).await
+    }
+
+    async fn update_member(
+        &self,
+        conversation_id: Uuid,
+        user_id: Uuid,
+        changes: Value,
+    ) -> Result<(), ApiError> {
+        db::from(Table::ConversationMembers)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .eq(col::USER_ID, user_id)
+            .update(changes)
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_members(&self, conversation_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::ConversationMembers)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .delete()
+            .await?
+            .len())
+    }
+
+    async fn remove_memberships(&self, user_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::ConversationMembers)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?
+            .len())
+    }
+}
+
+/// `filter` as PostgREST filters on `messages`.
+fn message_query(filter: &MessageFilter) -> db::Query {
+    let mut query = db::from(Table::Messages);
+    if let Some(ids) = &filter.ids {
+        query = query.in_list(col::ID, ids);
+    }
+    if let Some(conversation_id) = filter.conversation_id {
+        query = query.eq(col::CONVERSATION_ID, conversation_id);
+    }
+    if let Some(conversation_ids) = &filter.conversation_ids {
+        query = query.in_list(col::CONVERSATION_ID, conversation_ids);
+    }
+    if let Some(sender_id) = filter.sender_id {
+        query = query.eq(col::SENDER_ID, sender_id);
+    }
+    if let Some(client_id) = &filter.client_id {
+        query = query.eq(col::CLIENT_ID, client_id);
+    }
+    if let Some(before) = filter.before_id {
+        query = query.lt(col::ID, before);
+    }
+    if let Some(after) = filter.after_id {
+        query = query.gt(col::ID, after);
+    }
+    if let Some(from) = &filter.created_from {
+        query = query.gte(col::CREATED_AT, from);
+    }
+    if let Some(before) = &filter.created_before {
+        query = query.lt(col::CREATED_AT, before);
+    }
+    if filter.live_only {
+        query = query.not_true(col::IS_DELETED);
+    }
+    if filter.with_attachment {
+        query = query.not_null(col::ATTACHMENT);
+    }
+    // Shadow hidden messages are stored with `shadow_hidden` set
+    // (`alter table messages add column shadow_hidden boolean`).
+    if let Some(viewer) = filter.visible_to {
+        query = query.or(&[
+            "shadow_hidden.not.is.true".into(),
+            format!("sender_id.eq.{}", viewer),
+        ]);
+    }
+    if let Some(search) = &filter.search {
+        query = query.full_text(col::CONTENT_TSV, SEARCH_CONFIG, search);
+    }
+    query
+}
+
+#[async_trait]
+impl MessageStore for SupabaseStore {
+    async fn message(&self, id: i64) -> Result<Option<MessageRow>, ApiError> {
+        match db::from(Table::Messages)
+            .eq(col::ID, id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn messages(
+        &self,
+        filter: &MessageFilter,
+        order: Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<MessageRow>, ApiError> {
+        Ok(parse_rows(self.raw_messages(filter, order, limit).await?))
+    }
+
+    async fn raw_messages(
+        &self,
+        filter: &MessageFilter,
+        order: Order,
+        limit: Option<usize>,
+    ) -> Result<Vec<Value>, ApiError> {
+        let mut query = message_query(filter).order(col::ID, order);
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+        query.fetch().await
+    }
+
+    async fn insert_message(&self, fields: Value) -> Result<MessageRow, ApiError> {
+        Ok(serde_json::from_value(
+            db::insert_returning(Table::Messages, fields).await?,
+        )?)
+    }
+
+    async fn insert_messages(&self, rows: &[Value]) -> Result<(), ApiError> {
+        db::insert_many(Table::Messages, rows).await
+    }
+
+    async fn update_message(
+        &self,
+        id: i64,
+        changes: Value,
+    ) -> Result<Option<MessageRow>, ApiError> {
+        match db::from(Table::Messages)
+            .eq(col::ID, id)
+            .update(changes)
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn tombstone_messages(&self, filter: &MessageFilter) -> Result<Tombstoned, ApiError> {
+        let ids: Vec<i64> = message_query(filter)
+            .update(json!({
+                "content": "",
+                "content_html": null,
+                "components": null,
+                "attachment": null,
+                "link_preview": null,
+                "sticker": null,
+                "is_deleted": true,
+            }))
+            .await?
+            .iter()
+            .filter_map(|row| row.get("id")?.as_i64())
+            .collect();
+
+        let mut revisions = 0;
+        for batch in ids.chunks(ID_BATCH) {
+            revisions += db::from(Table::MessageRevisions)
+                .in_list(col::MESSAGE_ID, batch)
+                .delete()
+                .await?
+                .len();
+        }
+        Ok(Tombstoned {
+            messages: ids.len(),
+            revisions,
+        })
+    }
+
+    /// ```sql
+    /// create function messages_sent_today(uid uuid) returns bigint
+    /// language sql stable as $$
+    ///   select count(*) from messages
+    ///   where sender_id = uid
+    ///     and created_at >= date_trunc('day', now() at time zone 'utc') at time zone 'utc';
+    /// $$;
+    /// ```
+    async fn messages_sent_today(&self, user_id: Uuid) -> Result<u64, ApiError> {
+        let count = db::rpc("messages_sent_today", json!({ "uid": user_id.to_string() })).await?;
+        Ok(count.as_u64().unwrap_or(0))
+    }
+
+    /// ```sql
+    /// create function attachment_bytes_used(uid uuid) returns bigint
+    /// language sql stable as $$
+    ///   select coalesce(sum((attachment->>'size_bytes')::bigint), 0) from messages
+    ///   where sender_id = uid
+    ///     and attachment is not null
+    ///     and not coalesce(is_deleted, false);
+    /// $$;
+    /// ```
+    async fn attachment_bytes_used(&self, user_id: Uuid) -> Result<u64, ApiError> {
+        let bytes = db::rpc(
+            "attachment_bytes_used",
+            json!({ "uid": user_id.to_string() }),
+        )
+        .await?;
+        Ok(bytes.as_u64().unwrap_or(0))
+    }
+
+    async fn add_revision(&self, revision: &MessageRevisionRow) -> Result<(), ApiError> {
+        db::insert_returning(Table::MessageRevisions, serde_json::to_value(revision)?).await?;
+        Ok(())
+    }
+
+    async fn revisions(&self, message_id: i64) -> Result<Vec<MessageRevisionRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::MessageRevisions)
+                .eq(col::MESSAGE_ID, message_id)
+                .order(col::ID, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn conversation_revisions(
+        &self,
+        conversation_id: Uuid,
+        from_message_id: i64,
+    ) -> Result<Vec<MessageRevisionRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::MessageRevisions)
+                .eq(col::CONVERSATION_ID, conversation_id)
+                .gte(col::MESSAGE_ID, from_message_id)
+                .order(col::ID, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+}
+
+/// Settings rows keyed by their `user_id`.
+fn rows_by_user<T: serde::de::DeserializeOwned>(rows: Vec<Value>) -> HashMap<Uuid, T> {
+    rows.into_iter()
+        .filter_map(|v| {
+            let user_id = v.get("user_id")?.as_str()?.parse().ok()?;
+            Some((user_id, serde_json::from_value(v).ok()?))
+        })
+        .collect()
+}
+
+/// Upsert a settings row for `user_id`, stamping `updated_at`.
+async fn save_settings_row<T: serde::Serialize + Sync>(
+    table: Table,
+    user_id: Uuid,
+    settings: &T,
+) -> Result<(), ApiError> {
+    let mut row = serde_json::to_value(settings)?;
+    row["user_id"] = json!(user_id);
+    row["updated_at"] = json!(Utc::now().to_rfc3339());
+    db::upsert(table, &[col::USER_ID], row).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl SettingsStore for SupabaseStore {
+    async fn privacy_settings(&self, user_id: Uuid) -> Result<Option<UserSettings>, ApiError> {
+        match db::from(Table::UserSettings)
+            .eq(col::USER_ID, user_id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn privacy_settings_of(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, UserSettings>, ApiError> {
+        if user_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(rows_by_user(
+            db::from(Table::UserSettings)
+                .in_list(col::USER_ID, user_ids)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn save_privacy_settings(
+        &self,
+        user_id: Uuid,
+        settings: &UserSettings,
+    ) -> Result<(), ApiError> {
+        save_settings_row(Table::UserSettings, user_id, settings).await
+    }
+
+    async fn notification_settings(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<NotificationSettings>, ApiError> {
+        match db::from(Table::NotificationSettings)
+            .eq(col::USER_ID, user_id)
+            .fetch()
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(v) => Ok(Some(serde_json::from_value(v)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn notification_settings_of(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, NotificationSettings>, ApiError> {
+        if user_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(rows_by_user(
+            db::from(Table::NotificationSettings)
+                .in_list(col::USER_ID, user_ids)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn save_notification_settings(
+        &self,
+        user_id: Uuid,
+        settings: &NotificationSettings,
+    ) -> Result<(), ApiError> {
+        save_settings_row(Table::NotificationSettings, user_id, settings).await
+    }
+
+    async fn delete_settings(&self, user_id: Uuid) -> Result<(), ApiError> {
+        for table in [Table::UserSettings, Table::NotificationSettings] {
+            db::from(table).eq(col::USER_ID, user_id).delete().await?;
+        }
+        Ok(())
+    }
+}
+
+/// The `column` uuid of every row.
+fn uuid_column(rows: &[Value], column: &str) -> HashSet<Uuid> {
+    rows.iter()
+        .filter_map(|row| row.get(column)?.as_str()?.parse().ok())
+        .collect()
+}
+
+#[async_trait]
+impl BlockStore for SupabaseStore {
+    async fn blocks_page(
+        &self,
+        blocker_id: Uuid,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<BlockRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::UserBlocks)
+                .eq(col::BLOCKER_ID, blocker_id)
+                .order(col::CREATED_AT, Order::Desc)
+                .order(col::BLOCKED_ID, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn add_block(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<(), ApiError> {
+        db::upsert(
+            Table::UserBlocks,
+            &[col::BLOCKER_ID, col::BLOCKED_ID],
+            serde_json::to_value(BlockRow {
+                blocker_id,
+                blocked_id,
+                created_at: None,
+            })?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_block(&self, blocker_id: Uuid, blocked_id: Uuid) -> Result<bool, ApiError> {
+        Ok(!db::from(Table::UserBlocks)
+            .eq(col::BLOCKER_ID, blocker_id)
+            .eq(col::BLOCKED_ID, blocked_id)
+            .delete()
+            .await?
+            .is_empty())
+    }
+
+    async fn blocked_between(&self, a: Uuid, b: Uuid) -> Result<bool, ApiError> {
+        Ok(!db::from(Table::UserBlocks)
+            .in_list(col::BLOCKER_ID, [a, b])
+            .in_list(col::BLOCKED_ID, [a, b])
+            .fetch()
+            .await?
+            .is_empty())
+    }
+
+    async fn blocked_ids(&self, blocker_id: Uuid) -> Result<HashSet<Uuid>, ApiError> {
+        let rows = db::from(Table::UserBlocks)
+            .eq(col::BLOCKER_ID, blocker_id)
+            .fetch()
+            .await?;
+        Ok(uuid_column(&rows, "blocked_id"))
+    }
+
+    async fn blocker_ids(
+        &self,
+        blocked_id: Uuid,
+        among: Option<&[Uuid]>,
+    ) -> Result<HashSet<Uuid>, ApiError> {
+        let mut query = db::from(Table::UserBlocks).eq(col::BLOCKED_ID, blocked_id);
+        if let Some(among) = among {
+            if among.is_empty() {
+                return Ok(HashSet::new());
+            }
+            query = query.in_list(col::BLOCKER_ID, among);
+        }
+        Ok(uuid_column(&query.fetch().await?, "blocker_id"))
+    }
+}
+
+#[async_trait]
+impl PolicyStore for SupabaseStore {
+    async fn filter_terms(&self) -> Result<Vec<WordFilterTermRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::WordFilterTerms)
+                .order(col::CREATED_AT, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn insert_filter_term(
+        &self,
+        term: &WordFilterTermRow,
+    ) -> Result<WordFilterTermRow, ApiError> {
+        let row = db::insert_returning(Table::WordFilterTerms, serde_json::to_value(term)?).await?;
+        Ok(serde_json::from_value(row)?)
+    }
+
+    async fn insert_filter_terms(&self, terms: &[WordFilterTermRow]) -> Result<(), ApiError> {
+        let rows = terms
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        db::insert_many(Table::WordFilterTerms, &rows).await
+    }
+
+    async fn update_filter_term(
+        &self,
+        id: Uuid,
+        changes: Value,
+    ) -> Result<WordFilterTermRow, ApiError> {
+        found(
+            db::from(Table::WordFilterTerms)
+                .eq(col::ID, id)
+                .update(changes)
+                .await?,
+            "Term",
+        )
+    }
+
+    async fn delete_filter_terms(&self, ids: &[Uuid]) -> Result<Vec<WordFilterTermRow>, ApiError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(parse_rows(
+            db::from(Table::WordFilterTerms)
+                .in_list(col::ID, ids)
+                .delete()
+                .await?,
+        ))
+    }
+
+    async fn ip_bans(&self) -> Result<Vec<IpBanRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::IpBans)
+                .order(col::CREATED_AT, Order::Desc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn insert_ip_ban(&self, ban: &IpBanRow) -> Result<IpBanRow, ApiError> {
+        let row = db::insert_returning(Table::IpBans, serde_json::to_value(ban)?).await?;
+        Ok(serde_json::from_value(row)?)
+    }
+
+    async fn delete_ip_ban(&self, id: Uuid) -> Result<Option<IpBanRow>, ApiError> {
+        first_row(db::from(Table::IpBans).eq(col::ID, id).delete().await?)
+    }
+
+    async fn rate_limit_overrides(&self) -> Result<Vec<RateLimitOverrideRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::RateLimitOverrides)
+                .order(col::UPDATED_AT, Order::Desc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn save_rate_limit_override(
+        &self,
+        row: &RateLimitOverrideRow,
+    ) -> Result<RateLimitOverrideRow, ApiError> {
+        let stored = db::upsert(
+            Table::RateLimitOverrides,
+            &[col::USER_ID],
+            serde_json::to_value(row)?,
+        )
+        .await?;
+        Ok(serde_json::from_value(stored)?)
+    }
+
+    async fn delete_rate_limit_override(&self, user_id: Uuid) -> Result<bool, ApiError> {
+        Ok(!db::from(Table::RateLimitOverrides)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?
+            .is_empty())
+    }
+}
+
+#[async_trait]
+impl ReportStore for SupabaseStore {
+    async fn open_report(
+        &self,
+        reporter_id: Uuid,
+        target_user_id: Uuid,
+        message_id: Option<i64>,
+    ) -> Result<Option<ReportRow>, ApiError> {
+        let query = db::from(Table::Reports)
+            .eq(col::REPORTER_ID, reporter_id)
+            .eq(col::TARGET_USER_ID, target_user_id)
+            .eq(col::STATUS, "open");
+        let query = match message_id {
+            Some(id) => query.eq(col::MESSAGE_ID, id),
+            None => query.null(col::MESSAGE_ID),
+        };
+        first_row(query.limit(1).fetch().await?)
+    }
+
+    async fn insert_report(&self, report: &ReportRow) -> Result<(), ApiError> {
+        db::insert(Table::Reports, serde_json::to_value(report)?).await?;
+        Ok(())
+    }
+
+    async fn report_times(
+        &self,
+        reporter_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>, ApiError> {
+        Ok(db::from(Table::Reports)
+            .eq(col::REPORTER_ID, reporter_id)
+            .gt(col::CREATED_AT, to_timestamp(since))
+            .order(col::CREATED_AT, Order::Asc)
+            .columns(&[col::CREATED_AT])
+            .fetch()
+            .await?
+            .iter()
+            .filter_map(|row| row.get("created_at")?.as_str()?.parse().ok())
+            .collect())
+    }
+
+    async fn reports_page(
+        &self,
+        status: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<ReportRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Reports)
+                .eq(col::STATUS, status)
+                .order(col::CREATED_AT, Order::Asc)
+                .order(col::ID, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn reports_about(&self, user_id: Uuid, limit: usize) -> Result<Vec<ReportRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Reports)
+                .eq(col::TARGET_USER_ID, user_id)
+                .order(col::CREATED_AT, Order::Desc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn conversation_reports(
+        &self,
+        conversation_id: Uuid,
+        member_ids: &[Uuid],
+    ) -> Result<Vec<Value>, ApiError> {
+        let mut filter = vec![format!("conversation_id.eq.{}", conversation_id)];
+        if !member_ids.is_empty() {
+            let ids: Vec<String> = member_ids.iter().map(Uuid::to_string).collect();
+            filter.push(format!("target_user_id.in.({})", ids.join(",")));
+        }
+        db::from(Table::Reports)
+            .or(&filter)
+            .order(col::CREATED_AT, Order::Asc)
+            .fetch()
+            .await
+    }
+
+    async fn report(&self, id: Uuid) -> Result<ReportRow, ApiError> {
+        found(
+            db::from(Table::Reports).eq(col::ID, id).fetch().await?,
+            "Report",
+        )
+    }
+
+    async fn update_report(&self, id: Uuid, changes: Value) -> Result<ReportRow, ApiError> {
+        found(
+            db::from(Table::Reports)
+                .eq(col::ID, id)
+                .update(changes)
+                .await?,
+            "Report",
+        )
+    }
+
+    async fn scrub_report_reasons(&self, reporter_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::Reports)
+            .eq(col::REPORTER_ID, reporter_id)
+            .update(json!({ "reason": null }))
+            .await?
+            .len())
+    }
+}
+
+#[async_trait]
+impl AppealStore for SupabaseStore {
+    async fn insert_appeal(&self, appeal: &AppealRow) -> Result<(), ApiError> {
+        db::insert(Table::Appeals, serde_json::to_value(appeal)?).await?;
+        Ok(())
+    }
+
+    async fn appeals_of(&self, user_id: Uuid) -> Result<Vec<AppealRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Appeals)
+                .eq(col::USER_ID, user_id)
+                .order(col::CREATED_AT, Order::Desc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn appeals_page(
+        &self,
+        status: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AppealRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Appeals)
+                .eq(col::STATUS, status)
+                .order(col::CREATED_AT, Order::Asc)
+                .order(col::ID, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn appeal(&self, id: Uuid) -> Result<AppealRow, ApiError> {
+        found(
+            db::from(Table::Appeals).eq(col::ID, id).fetch().await?,
+            "Appeal",
+        )
+    }
+
+    async fn update_appeal(&self, id: Uuid, changes: Value) -> Result<AppealRow, ApiError> {
+        found(
+            db::from(Table::Appeals)
+                .eq(col::ID, id)
+                .update(changes)
+                .await?,
+            "Appeal",
+        )
+    }
+
+    async fn delete_appeals_of(&self, user_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::Appeals)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?
+            .len())
+    }
+}
+
+#[async_trait]
+impl ModerationStore for SupabaseStore {
+    async fn hold_message(&self, held: &HeldMessageRow) -> Result<(), ApiError> {
+        db::insert(Table::HeldMessages, serde_json::to_value(held)?).await?;
+        Ok(())
+    }
+
+    async fn held_messages_page(
+        &self,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<HeldMessageRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::HeldMessages)
+                .eq(col::STATUS, "held")
+                .order(col::CREATED_AT, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn held_message(&self, id: Uuid) -> Result<HeldMessageRow, ApiError> {
+        found(
+            db::from(Table::HeldMessages)
+                .eq(col::ID, id)
+                .fetch()
+                .await?,
+            "Held message",
+        )
+    }
+
+    async fn review_held_message(
+        &self,
+        id: Uuid,
+        status: &str,
+        reviewer_id: Uuid,
+    ) -> Result<(), ApiError> {
+        db::from(Table::HeldMessages)
+            .eq(col::ID, id)
+            .update(json!({
+                "status": status,
+                "reviewed_by": reviewer_id,
+                "reviewed_at": to_timestamp(Utc::now()),
+            }))
+            .await?;
+        Ok(())
+    }
+
+    async fn held_messages_of(&self, sender_id: Uuid) -> Result<Vec<HeldMessageRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::HeldMessages)
+                .eq(col::SENDER_ID, sender_id)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn delete_held_messages_of(&self, sender_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::HeldMessages)
+            .eq(col::SENDER_ID, sender_id)
+            .delete()
+            .await?
+            .len())
+    }
+
+    async fn insert_snapshot(&self, snapshot: &SnapshotRow) -> Result<(), ApiError> {
+        db::insert(Table::ModerationSnapshots, serde_json::to_value(snapshot)?).await?;
+        Ok(())
+    }
+
+    async fn snapshots(&self, conversation_id: Uuid) -> Result<Vec<Value>, ApiError> {
+        db::from(Table::ModerationSnapshots)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .columns(&[
+                col::ID,
+                col::CONVERSATION_ID,
+                col::CREATED_BY,
+                col::NOTE,
+                col::CREATED_AT,
+            ])
+            .order(col::CREATED_AT, Order::Desc)
+            .fetch()
+            .await
+    }
+
+    async fn snapshot(&self, id: Uuid) -> Result<SnapshotRow, ApiError> {
+        found(
+            db::from(Table::ModerationSnapshots)
+                .eq(col::ID, id)
+                .fetch()
+                .await?,
+            "Snapshot",
+        )
+    }
+}
+
+#[async_trait]
+impl AdminStore for SupabaseStore {
+    async fn record_audit(&self, entry: &AuditLogRow) -> Result<(), ApiError> {
+        db::insert(Table::AdminAuditLog, serde_json::to_value(entry)?).await?;
+        Ok(())
+    }
+
+    async fn audit_log(
+        &self,
+        filter: &AuditLogFilter,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<AuditLogRow>, ApiError> {
+        let mut query = db::from(Table::AdminAuditLog);
+        if let Some(admin_id) = filter.admin_id {
+            query = query.eq(col::ADMIN_ID, admin_id);
+        }
+        if let Some(action) = &filter.action {
+            query = query.eq_ci(col::ACTION, action);
+        }
+        if let Some(prefix) = &filter.action_prefix {
+            query = query.starts_with_ci(col::ACTION, prefix);
+        }
+        if let Some(target_id) = &filter.target_id {
+            query = query.eq_ci(col::TARGET_ID, target_id);
+        }
+        if let Some(from) = &filter.created_from {
+            query = query.gte(col::CREATED_AT, from);
+        }
+        if let Some(before) = &filter.created_before {
+            query = query.lt(col::CREATED_AT, before);
+        }
+        Ok(parse_rows(
+            query
+                .order(col::CREATED_AT, Order::Desc)
+                .order(col::ID, Order::Asc)
+                .limit(limit)
+                .offset(offset)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn daily_stats(&self, days: u32) -> Result<Vec<DailyStats>, ApiError> {
+        Ok(serde_json::from_value(
+            db::rpc("admin_daily_stats", json!({ "days": days })).await?,
+        )?)
+    }
+
+    async fn user_totals(&self) -> Result<UserTotals, ApiError> {
+        serde_json::from_value::<Vec<UserTotals>>(db::rpc("admin_user_totals", json!({})).await?)?
+            .pop()
+            .ok_or_else(|| ApiError::Database("admin_user_totals returned no row".into()))
+    }
+
+    /// Through `list_indexes()`, since PostgREST doesn't expose the
+    /// `pg_indexes` catalog; see `index_advisor` for its SQL.
+    async fn indexes(&self) -> Result<Vec<(String, String)>, ApiError> {
+        Ok(db::rpc("list_indexes", json!({}))
+            .await?
+            .as_array()
+            .map(|rows| {
+                rows.iter()
+                    .filter_map(|row| {
+                        let table = row.get("tablename")?.as_str()?;
+                        let def = row.get("indexdef")?.as_str()?;
+                        Some((table.to_string(), def.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[async_trait]
+impl ErasureStore for SupabaseStore {
+    async fn queue_erasure(&self, id: Uuid, user_id: Uuid) -> Result<(), ApiError> {
+        db::insert(
+            Table::ErasureRequests,
+            json!({
+                "id": id.to_string(),
+                "user_id": user_id.to_string(),
+                "status": "pending",
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn erasure_requests(&self, limit: usize) -> Result<Vec<ErasureRequestRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ErasureRequests)
+                .order(col::REQUESTED_AT, Order::Desc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn erasure_request(&self, id: Uuid) -> Result<ErasureRequestRow, ApiError> {
+        found(
+            db::from(Table::ErasureRequests)
+                .eq(col::ID, id)
+                .fetch()
+                .await?,
+            "Erasure request",
+        )
+    }
+
+    async fn pending_erasures(&self, limit: usize) -> Result<Vec<ErasureRequestRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ErasureRequests)
+                .eq(col::STATUS, "pending")
+                .order(col::REQUESTED_AT, Order::Asc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn finish_erasure(&self, id: Uuid, status: &str, report: Value) -> Result<(), ApiError> {
+        db::from(Table::ErasureRequests)
+            .eq(col::ID, id)
+            .update(json!({
+                "status": status,
+                "completed_at": Utc::now().to_rfc3339(),
+                "report": report,
+            }))
+            .await?;
+        Ok(())
+    }
+
+    async fn record_backup_exclusion(&self, user_id: Uuid) -> Result<(), ApiError> {
+        db::insert_returning(
+            Table::ErasureBackupExclusions,
+            json!({ "user_id": user_id.to_string(), "erased_at": Utc::now().to_rfc3339() }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ScheduleStore for SupabaseStore {
+    async fn insert_schedule(&self, row: &ScheduledMessageRow) -> Result<(), ApiError> {
+        db::insert(
+            Table::ScheduledMessages,
+            json!({
+                "id": row.id.to_string(),
+                "conversation_id": row.conversation_id.to_string(),
+                "sender_id": row.sender_id.to_string(),
+                "content": row.content,
+                "send_at_local": row.send_at_local,
+                "timezone": row.timezone,
+                "recurrence": row.recurrence,
+                "next_run_at": row.next_run_at,
+                "paused": row.paused.unwrap_or(false),
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn schedules_of(&self, sender_id: Uuid) -> Result<Vec<ScheduledMessageRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ScheduledMessages)
+                .eq(col::SENDER_ID, sender_id)
+                .order(col::NEXT_RUN_AT, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn schedule(&self, id: Uuid) -> Result<Option<ScheduledMessageRow>, ApiError> {
+        first_row(
+            db::from(Table::ScheduledMessages)
+                .eq(col::ID, id)
+                .fetch()
+                .await?,
+        )
+    }
+
+    async fn update_schedule(&self, id: Uuid, changes: Value) -> Result<(), ApiError> {
+        db::from(Table::ScheduledMessages)
+            .eq(col::ID, id)
+            .update(changes)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_schedule(&self, id: Uuid) -> Result<(), ApiError> {
+        db::from(Table::ScheduledMessages)
+            .eq(col::ID, id)
+            .delete()
+            .await?;
+        Ok(())
+    }
+
+    async fn due_schedules(
+        &self,
+        now: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<ScheduledMessageRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ScheduledMessages)
+                .lte(col::NEXT_RUN_AT, to_timestamp(now))
+                .not_true(col::PAUSED)
+                .order(col::NEXT_RUN_AT, Order::Asc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn claim_schedule(
+        &self,
+        id: Uuid,
+        seen_next_run_at: &str,
+        until: DateTime<Utc>,
+    ) -> Result<bool, ApiError> {
+        let claimed = db::from(Table::ScheduledMessages)
+            .eq(col::ID, id)
+            .eq(col::NEXT_RUN_AT, seen_next_run_at)
+            .update(json!({ "next_run_at": to_timestamp(until) }))
+            .await?;
+        Ok(!claimed.is_empty())
+    }
+
+    async fn delete_schedules_of(&self, sender_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::ScheduledMessages)
+            .eq(col::SENDER_ID, sender_id)
+            .delete()
+            .await?
+            .len())
+    }
+
+    async fn delete_conversation_schedules(&self, conversation_id: Uuid) -> Result<(), ApiError> {
+        db::from(Table::ScheduledMessages)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .delete()
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DraftStore for SupabaseStore {
+    async fn draft(
+        &self,
+        user_id: Uuid,
+        conversation_id: Uuid,
+    ) -> Result<Option<DraftRow>, ApiError> {
+        first_row(
+            db::from(Table::Drafts)
+                .eq(col::USER_ID, user_id)
+                .eq(col::CONVERSATION_ID, conversation_id)
+                .fetch()
+                .await?,
+        )
+    }
+
+    async fn drafts_of(&self, user_id: Uuid) -> Result<Vec<DraftRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Drafts)
+                .eq(col::USER_ID, user_id)
+                .order(col::UPDATED_AT, Order::Desc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn save_draft(&self, draft: &DraftRow) -> Result<DraftRow, ApiError> {
+        Ok(serde_json::from_value(
+            db::upsert(
+                Table::Drafts,
+                &[col::USER_ID, col::CONVERSATION_ID],
+                serde_json::to_value(draft)?,
+            )
+            .await?,
+        )?)
+    }
+
+    async fn delete_draft(&self, user_id: Uuid, conversation_id: Uuid) -> Result<(), ApiError> {
+        db::from(Table::Drafts)
+            .eq(col::USER_ID, user_id)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .delete()
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_drafts_of(&self, user_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::Drafts)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?
+            .len())
+    }
+
+    async fn delete_conversation_drafts(&self, conversation_id: Uuid) -> Result<(), ApiError> {
+        db::from(Table::Drafts)
+            .eq(col::CONVERSATION_ID, conversation_id)
+            .delete()
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PollStore for SupabaseStore {
+    async fn insert_poll(&self, poll: &PollRow) -> Result<(), ApiError> {
+        db::insert_returning(Table::Polls, serde_json::to_value(poll)?).await?;
+        Ok(())
+    }
+
+    async fn poll(&self, id: Uuid) -> Result<PollRow, ApiError> {
+        found(
+            db::from(Table::Polls).eq(col::ID, id).fetch().await?,
+            "Poll",
+        )
+    }
+
+    async fn polls(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, PollRow>, ApiError> {
+        Ok(
+            parse_rows::<PollRow>(db::from(Table::Polls).in_list(col::ID, ids).fetch().await?)
+                .into_iter()
+                .map(|p| (p.id, p))
+                .collect(),
+        )
+    }
+
+    async fn votes(&self, poll_ids: &[Uuid]) -> Result<Vec<PollVoteRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::PollVotes)
+                .in_list(col::POLL_ID, poll_ids)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn replace_votes(
+        &self,
+        poll_id: Uuid,
+        user_id: Uuid,
+        votes: &[PollVoteRow],
+    ) -> Result<(), ApiError> {
+        db::from(Table::PollVotes)
+            .eq(col::POLL_ID, poll_id)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?;
+        if !votes.is_empty() {
+            db::insert_returning(Table::PollVotes, serde_json::to_value(votes)?).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmojiStore for SupabaseStore {
+    async fn emoji_packs(&self) -> Result<Vec<EmojiPackRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::EmojiPacks)
+                .order(col::NAME, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn all_emoji(&self) -> Result<Vec<EmojiRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::Emoji)
+                .order(col::NAME, Order::Asc)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn emoji_pack(&self, id: Uuid) -> Result<EmojiPackRow, ApiError> {
+        found(
+            db::from(Table::EmojiPacks).eq(col::ID, id).fetch().await?,
+            "Emoji pack",
+        )
+    }
+
+    async fn insert_emoji_pack(&self, pack: &EmojiPackRow) -> Result<(), ApiError> {
+        db::insert_returning(Table::EmojiPacks, serde_json::to_value(pack)?).await?;
+        Ok(())
+    }
+
+    async fn delete_emoji_pack(&self, id: Uuid) -> Result<Vec<EmojiRow>, ApiError> {
+        let items = db::from(Table::Emoji).eq(col::PACK_ID, id).delete().await?;
+        let deleted = db::from(Table::EmojiPacks).eq(col::ID, id).delete().await?;
+        if deleted.is_empty() {
+            return Err(ApiError::NotFound("Emoji pack not found".into()));
+        }
+        Ok(parse_rows(items))
+    }
+
+    async fn emoji(&self, id: Uuid) -> Result<Option<EmojiRow>, ApiError> {
+        first_row(db::from(Table::Emoji).eq(col::ID, id).fetch().await?)
+    }
+
+    async fn insert_emoji(&self, emoji: &EmojiRow) -> Result<(), ApiError> {
+        db::insert_returning(Table::Emoji, serde_json::to_value(emoji)?).await?;
+        Ok(())
+    }
+
+    async fn delete_emoji(&self, id: Uuid) -> Result<Option<EmojiRow>, ApiError> {
+        first_row(db::from(Table::Emoji).eq(col::ID, id).delete().await?)
+    }
+}
+
+#[async_trait]
+impl ActivityStore for SupabaseStore {
+    async fn insert_activity(&self, row: &ActivityRow) -> Result<(), ApiError> {
+        db::insert(Table::Activity, serde_json::to_value(row)?).await?;
+        Ok(())
+    }
+
+    async fn activity_page(
+        &self,
+        user_ids: &[Uuid],
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<ActivityRow>, ApiError> {
+        let mut page = db::from(Table::Activity).in_list(col::USER_ID, user_ids);
+        if let Some(before) = before {
+            page = page.lt(col::ID, before);
+        }
+        Ok(parse_rows(
+            page.order(col::ID, Order::Desc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn delete_activity_of(&self, user_id: Uuid) -> Result<usize, ApiError> {
+        Ok(db::from(Table::Activity)
+            .eq(col::USER_ID, user_id)
+            .delete()
+            .await?
+            .len())
+    }
+}
+
+#[async_trait]
+impl ExportStore for SupabaseStore {
+    async fn insert_export(&self, export: &ExportRow) -> Result<(), ApiError> {
+        db::insert_returning(Table::ConversationExports, serde_json::to_value(export)?).await?;
+        Ok(())
+    }
+
+    async fn export(&self, id: Uuid, user_id: Uuid) -> Result<ExportRow, ApiError> {
+        found(
+            db::from(Table::ConversationExports)
+                .eq(col::ID, id)
+                .eq(col::USER_ID, user_id)
+                .fetch()
+                .await?,
+            "Export",
+        )
+    }
+
+    async fn pending_exports(&self, limit: usize) -> Result<Vec<ExportRow>, ApiError> {
+        Ok(parse_rows(
+            db::from(Table::ConversationExports)
+                .eq(col::STATUS, "pending")
+                .order(col::REQUESTED_AT, Order::Asc)
+                .limit(limit)
+                .fetch()
+                .await?,
+        ))
+    }
+
+    async fn claim_export(&self, id: Uuid) -> Result<bool, ApiError> {
+        let claimed = db::from(Table::ConversationExports)
+            .eq(col::ID, id)
+            .eq(col::STATUS, "pending")
+            .update(json!({ "status": "processing" }))
+            .await?;
+        Ok(!claimed.is_empty())
+    }
+
+    async fn update_export(&self, id: Uuid, changes: Value) -> Result<(), ApiError> {
+        db::from(Table::ConversationExports)
+            .eq(col::ID, id)
+            .update(changes)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::RwLock;
+
+use crate::error::ApiError;
+use crate::models::FilterMode;
+use crate::store::Storage;
+
+/// Server-managed blocklist applied to every message after sanitization.
+/// Terms live in `word_filter_terms`; `reload` swaps in a fresh copy, so
+/// edits through the admin API take effect without a restart.
+#[derive(Default)]
+pub struct WordFilter {
+    terms: RwLock<Vec<CompiledTerm>>,
+}
+
+struct CompiledTerm {
+    term: String,
+    /// Lowercased, one char per char of the original.
+    chars: Vec<char>,
+    mode: FilterMode,
+    enforce_in_adult: bool,
+}
+
+/// Message text after the filter has run.
+#[derive(Debug)]
+pub struct Filtered {
+    pub content: String,
+    /// Some of the text was replaced with asterisks.
+    pub masked: bool,
+    /// Comma-separated `flag` terms found, for `messages.filter_flag`.
+    pub flag: Option<String>,
+}
+
+impl WordFilter {
+    /// Replace the in-memory terms with what's in the database. Returns the
+    /// number of terms loaded.
+    pub async fn reload(&self, store: &dyn Storage) -> Result<usize, ApiError> {
+        let rows = store.filter_terms().await?;
+
+        let compiled: Vec<CompiledTerm> = rows
+            .into_iter()
+            .filter(|r| !r.term.trim().is_empty())
+            .map(|r| CompiledTerm {
+                chars: r.term.trim().chars().map(fold).collect(),
+                term: r.term,
+                mode: r.mode,
+                enforce_in_adult: r.enforce_in_adult,
+            })
+            .collect();
+
+        let count = compiled.len();
+        *self.terms.write().unwrap() = compiled;
+        Ok(count)
+    }
+
+    /// Run `content` through every term. Conversations that allow adult
+    /// content only get the terms marked `enforce_in_adult`.
+    pub fn apply(&self, content: &str, adult_allowed: bool) -> Result<Filtered, ApiError> {
+        let terms = self.terms.read().unwrap();
+        let lower: Vec<char> = content.chars().map(fold).collect();
+        let mut out: Vec<char> = content.chars().collect();
+        let mut masked = false;
+        let mut flagged: Vec<&str> = Vec::new();
+
+        for term in terms
+            .iter()
+            .filter(|t| !adult_allowed || t.enforce_in_adult)
+        {
+            let matches = find_words(&lower, &term.chars);
+            if matches.is_empty() {
+                continue;
+            }
+            match term.mode {
+                FilterMode::Reject => {
+                    return Err(ApiError::BadRequest(
+                        "Message contains a blocked word".into(),
+                    ))
+                }
+                FilterMode::Mask => {
+                    for start in matches {
+                        for c in &mut out[start..start + term.chars.len()] {
+                            if !c.is_whitespace() {
+                                *c = '*';
+                            }
+                        }
+                    }
+                    masked = true;
+                }
+                FilterMode::Flag => flagged.push(&term.term),
+            }
+        }
+
+        Ok(Filtered {
+            content: out.into_iter().collect(),
+            masked,
+            flag: (!flagged.is_empty()).then(|| flagged.join(",")),
+        })
+    }
+}
+
+/// Case-fold one char without changing the char count, so match offsets in
+/// the folded text line up with the original.
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Start offsets of every occurrence of `needle` in `haystack` that isn't
+/// part of a longer word.
+fn find_words(haystack: &[char], needle: &[char]) -> Vec<usize> {
+    if needle.is_empty() || needle.len() > haystack.len() {
+        return Vec::new();
+    }
+    (0..=haystack.len() - needle.len())
+        .filter(|&i| haystack[i..i + needle.len()] == *needle)
+        .filter(|&i| i == 0 || !haystack[i - 1].is_alphanumeric())
+        .filter(|&i| {
+            let end = i + needle.len();
+            end == haystack.len() || !haystack[end].is_alphanumeric()
+        })
+        .collect()
+}